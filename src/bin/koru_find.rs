@@ -1,21 +1,142 @@
-use std::{env, io, path::PathBuf, process};
+use std::{
+    env,
+    fs::File,
+    io::{self, Write},
+    os::fd::{FromRawFd, RawFd},
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
 
-use clap::Parser;
-use koru_find::server;
+use clap::{Parser, Subcommand};
+use koru_find::{
+    cache,
+    config::Config,
+    doctor, fd,
+    hooks::{Event, Hooks},
+    locate, server,
+};
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+
     /// Base Directory
     dir: Option<PathBuf>,
 
     /// Server
     #[arg(long)]
     server: bool,
+
+    /// Language server answering workspace/symbol and workspace/files
+    #[arg(long, conflicts_with = "server")]
+    lsp: bool,
+
+    /// Write messages and warnings to this open file descriptor, e.g. 2 for stderr, instead of
+    /// mixing them with results
+    #[arg(long, global = true, value_name = "FD")]
+    diag_fd: Option<RawFd>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Search like fd: koru_find fd [PATTERN] [PATH]
+    Fd(fd::Args),
+
+    /// Record every path under ROOTS (default: the previous roots) in the locate database
+    Updatedb {
+        /// Database file [default: $XDG_CACHE_HOME/koru_find/locate.db]
+        #[arg(long)]
+        db: Option<PathBuf>,
+
+        /// Print each path added (+) or removed (-) since the last update
+        #[arg(long)]
+        diff: bool,
+
+        roots: Vec<PathBuf>,
+    },
+
+    /// List the paths in the locate database matching QUERY
+    Locate {
+        /// Database file [default: $XDG_CACHE_HOME/koru_find/locate.db]
+        #[arg(long)]
+        db: Option<PathBuf>,
+
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+
+    /// Check the environment, exiting with 1 if anything needs fixing
+    Doctor,
+
+    /// Manage $XDG_CACHE_HOME/koru_find
+    #[command(subcommand)]
+    Cache(CacheCmd),
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCmd {
+    /// List entries with their sizes and roots
+    List,
+
+    /// Remove entries whose roots are gone or which break their roots' limits
+    Prune,
+
+    /// Limit the cache used by ROOT; no limits removes them
+    Limit {
+        root: PathBuf,
+
+        /// Largest entry in bytes
+        #[arg(long)]
+        max_size: Option<u64>,
+
+        /// Oldest entry in seconds
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
 }
 
 fn main() {
     let args = Args::parse();
+    server::set_panic_hook();
+    let diag = match args.diag_fd.map(diag_file).transpose() {
+        Ok(diag) => diag,
+        Err(err) => {
+            eprintln!("--diag-fd: {err}");
+            process::exit(1);
+        }
+    };
+    let config = Config::load();
+
+    if let Some(cmd) = args.command {
+        let config = config_for(config, matches!(cmd, Cmd::Updatedb { .. }));
+        let result = match cmd {
+            Cmd::Fd(args) => match diag {
+                Some(diag) => fd::run(&args, io::stdout(), diag),
+                None => fd::run(&args, io::stdout(), io::stderr()),
+            },
+            Cmd::Updatedb { db, diff, roots } => {
+                locate_db(db).and_then(|db| updatedb(&db, &roots, diff, &config.hooks))
+            }
+            Cmd::Locate { db, query } => locate_db(db).and_then(|db| {
+                locate::locate(&db, &query.join(" "), io::stdout()).map(|n| (n == 0) as i32)
+            }),
+            Cmd::Cache(cmd) => cache_command(cmd),
+            Cmd::Doctor => Ok(doctor::check().iter().fold(0, |code, finding| {
+                println!("{finding}");
+                code.max(finding.fix.is_some() as i32)
+            })),
+        };
+        match result {
+            Ok(code) => process::exit(code),
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        }
+    }
 
     let _dir = if let Some(dir) = args.dir {
         match env::set_current_dir(&dir) {
@@ -30,18 +151,126 @@ fn main() {
     };
 
     if args.server {
-        match server::run(num_cpus::get(), io::stdin(), io::stdout()) {
+        let config = config_for(config, true);
+        let mut builder = server::ServerBuilder::new()
+            .hooks(config.hooks)
+            .weights(config.weights)
+            .quotas(config.quotas)
+            .macros(config.macros);
+        if let Some(file) = cache::frecency_file() {
+            builder = builder.frecency(file);
+        }
+        if let Some(diag) = diag {
+            builder = builder.diagnostics(diag);
+        }
+        match builder.run(io::stdin(), io::stdout()) {
             Ok(_) => process::exit(0),
+            // the panic hook has already reported it
+            Err(server::walker::Error::Panic(_)) => process::exit(1),
             Err(err) => {
                 eprintln!("{err}");
                 process::exit(1);
             }
         }
+    } else if args.lsp {
+        if let Err(err) = koru_find::lsp::run(io::stdin().lock(), io::stdout()) {
+            eprintln!("{err}");
+            process::exit(1);
+        }
     } else {
         todo!()
     }
 }
 
+/// The loaded config, or if it failed to load: exit when the command `needs` it, else warn and
+/// use the defaults.
+fn config_for(config: io::Result<Config>, needs: bool) -> Config {
+    config.unwrap_or_else(|err| {
+        if needs {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+        eprintln!("warning: {err}; using the default config");
+        Config::default()
+    })
+}
+
+/// A file writing to a close-on-exec copy of the already open descriptor `fd`, which stays open.
+fn diag_file(fd: RawFd) -> io::Result<File> {
+    let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if copy == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(copy) })
+}
+
+fn locate_db(db: Option<PathBuf>) -> io::Result<PathBuf> {
+    db.or_else(locate::default_db)
+        .ok_or_else(|| io::Error::other("no cache directory; use --db"))
+}
+
+fn updatedb(db: &Path, roots: &[PathBuf], diff: bool, hooks: &Hooks) -> io::Result<i32> {
+    let mut out = io::BufWriter::new(io::stdout());
+    let mut result = Ok(());
+    let count = locate::refresh_db(db, roots, |change, path| {
+        if diff && result.is_ok() {
+            let sign = if change == locate::Change::Added {
+                b"+"
+            } else {
+                b"-"
+            };
+            result = [&sign[..], path, b"\n"]
+                .iter()
+                .try_for_each(|part| out.write_all(part));
+        }
+    })?
+    .paths;
+    result.and_then(|()| out.flush())?;
+    if let Some(hook) = hooks.run(Event::IndexRefreshed, &db.to_string_lossy(), count) {
+        let _ = hook.join();
+    }
+    Ok(0)
+}
+
+fn cache_command(cmd: CacheCmd) -> io::Result<i32> {
+    let dir = cache::cache_dir().ok_or_else(|| io::Error::other("no cache directory"))?;
+    match cmd {
+        CacheCmd::List => {
+            for entry in cache::list(&dir)? {
+                let roots: Vec<_> = entry
+                    .roots
+                    .iter()
+                    .map(|r| r.display().to_string())
+                    .collect();
+                println!(
+                    "{}\t{}\t{}",
+                    entry.size,
+                    entry.path.display(),
+                    roots.join(" ")
+                );
+            }
+        }
+        CacheCmd::Prune => {
+            for entry in cache::prune(&dir)? {
+                println!("removed {}", entry.path.display());
+            }
+        }
+        CacheCmd::Limit {
+            root,
+            max_size,
+            ttl,
+        } => cache::set_limit(
+            &dir,
+            &root,
+            cache::Limit {
+                max_size,
+                ttl: ttl.map(Duration::from_secs),
+            },
+        )?,
+    }
+    Ok(0)
+}
+
 // use std::io::{stdout, Write};
 // use std::{
 //     io::{self},
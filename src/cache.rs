@@ -0,0 +1,150 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::locate::Database;
+
+const LIMITS: &str = "limits";
+const FRECENCY: &str = "frecency";
+
+/// `$XDG_CACHE_HOME/koru_find`, or `~/.cache/koru_find`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("koru_find"))
+}
+
+/// Where the server keeps the visits that rank its results: `$XDG_CACHE_HOME/koru_find/frecency`.
+pub fn frecency_file() -> Option<PathBuf> {
+    Some(cache_dir()?.join(FRECENCY))
+}
+
+/// A file in the cache directory along with the roots it was built from, if known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub roots: Vec<PathBuf>,
+}
+impl Entry {
+    fn is_stale(&self) -> bool {
+        self.roots.iter().any(|r| !r.is_dir())
+    }
+}
+
+/// How much cache a root may use. An entry breaking the limits of any of its roots is pruned.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limit {
+    pub max_size: Option<u64>,
+    pub ttl: Option<Duration>,
+}
+impl Limit {
+    fn allows(&self, entry: &Entry, now: SystemTime) -> bool {
+        self.max_size.is_none_or(|max| entry.size <= max)
+            && self.ttl.is_none_or(|ttl| {
+                now.duration_since(entry.modified)
+                    .is_ok_and(|age| age <= ttl)
+            })
+    }
+}
+
+/// The entries in `dir` in path order.
+pub fn list(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    let files = match fs::read_dir(dir) {
+        Ok(files) => files,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(entries),
+        Err(err) => return Err(err),
+    };
+    for file in files {
+        let file = file?;
+        let meta = file.metadata()?;
+        if !meta.is_file() || file.file_name() == LIMITS || file.file_name() == FRECENCY {
+            continue;
+        }
+        let path = file.path();
+        let roots = Database::open(&path).map(|db| db.roots).unwrap_or_default();
+        entries.push(Entry {
+            path,
+            size: meta.len(),
+            modified: meta.modified()?,
+            roots,
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Remove entries built from roots which no longer exist or which break their roots' limits.
+/// Returns the entries removed.
+pub fn prune(dir: &Path) -> io::Result<Vec<Entry>> {
+    let limits = limits(dir)?;
+    let now = SystemTime::now();
+    let mut removed = vec![];
+    for entry in list(dir)? {
+        let over_limit = limits
+            .iter()
+            .any(|(root, limit)| entry.roots.contains(root) && !limit.allows(&entry, now));
+        if entry.is_stale() || over_limit {
+            fs::remove_file(&entry.path)?;
+            removed.push(entry);
+        }
+    }
+    Ok(removed)
+}
+
+/// The limits set for each root.
+pub fn limits(dir: &Path) -> io::Result<Vec<(PathBuf, Limit)>> {
+    let text = match fs::read_to_string(dir.join(LIMITS)) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    let number = |v: Option<&str>| v.and_then(|v| v.parse::<u64>().ok());
+    Ok(text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let root = fields.next().filter(|r| !r.is_empty())?;
+            let limit = Limit {
+                max_size: number(fields.next()),
+                ttl: number(fields.next()).map(Duration::from_secs),
+            };
+            Some((PathBuf::from(root), limit))
+        })
+        .collect())
+}
+
+/// Set the limits for `root`; a limit with neither field set removes them.
+pub fn set_limit(dir: &Path, root: &Path, limit: Limit) -> io::Result<()> {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut limits = limits(dir)?;
+    limits.retain(|(r, _)| *r != root);
+    if limit != Limit::default() {
+        limits.push((root, limit));
+        limits.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    let field = |v: Option<u64>| v.map_or("-".to_string(), |v| v.to_string());
+    let text: String = limits
+        .iter()
+        .map(|(root, limit)| {
+            format!(
+                "{}\t{}\t{}\n",
+                root.display(),
+                field(limit.max_size),
+                field(limit.ttl.map(|t| t.as_secs()))
+            )
+        })
+        .collect();
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(LIMITS), text)
+}
+
+#[cfg(test)]
+#[path = "cache_test.rs"]
+mod test;
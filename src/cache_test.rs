@@ -0,0 +1,80 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::{locate::update_db, test_util::TempDir};
+
+fn names(entries: &[Entry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|e| e.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn list_and_prune_stale() {
+    let dir = TempDir::new("cache_stale");
+    let cache = dir.join("cache");
+    let gone = dir.join("gone");
+    fs::create_dir_all(&gone).unwrap();
+    update_db(&cache.join("a.db"), &[PathBuf::from("test")]).unwrap();
+    update_db(&cache.join("b.db"), std::slice::from_ref(&gone)).unwrap();
+    fs::write(cache.join("other"), "x").unwrap();
+    fs::write(cache.join(FRECENCY), "").unwrap();
+
+    let entries = list(&cache).unwrap();
+    assert_eq!(names(&entries), ["a.db", "b.db", "other"]);
+    assert_eq!(entries[0].roots, [fs::canonicalize("test").unwrap()]);
+    assert_eq!(entries[2].size, 1);
+    assert!(entries[2].roots.is_empty());
+
+    assert_eq!(names(&prune(&cache).unwrap()), Vec::<String>::new());
+    fs::remove_dir(&gone).unwrap();
+    assert_eq!(names(&prune(&cache).unwrap()), ["b.db"]);
+    assert_eq!(names(&list(&cache).unwrap()), ["a.db", "other"]);
+
+    assert_eq!(list(&dir.join("missing")).unwrap(), vec![]);
+}
+
+#[test]
+fn limits_prune() {
+    let dir = TempDir::new("cache_limits");
+    update_db(&dir.join("a.db"), &[PathBuf::from("test")]).unwrap();
+    let size = list(&dir).unwrap()[0].size;
+
+    set_limit(
+        &dir,
+        Path::new("test"),
+        Limit {
+            max_size: Some(size),
+            ttl: Some(Duration::from_secs(60)),
+        },
+    )
+    .unwrap();
+    set_limit(&dir, Path::new("/tmp"), Limit::default()).unwrap();
+    assert_eq!(
+        limits(&dir).unwrap(),
+        [(
+            fs::canonicalize("test").unwrap(),
+            Limit {
+                max_size: Some(size),
+                ttl: Some(Duration::from_secs(60)),
+            }
+        )]
+    );
+    assert_eq!(names(&prune(&dir).unwrap()), Vec::<String>::new());
+
+    set_limit(
+        &dir,
+        Path::new("test"),
+        Limit {
+            max_size: Some(size - 1),
+            ttl: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(limits(&dir).unwrap().len(), 1);
+    assert_eq!(names(&prune(&dir).unwrap()), ["a.db"]);
+
+    set_limit(&dir, Path::new("test"), Limit::default()).unwrap();
+    assert_eq!(limits(&dir).unwrap(), vec![]);
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::BTreeSet,
+    ffi::OsStr,
+    io::{self, Read, Write},
+    ops::Deref,
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex, MutexGuard, mpsc},
+    thread,
+};
+
+use bytes::Bytes;
+
+use crate::server::{FrameReader, order::FIELD_SEP, walker::Msg};
+
+type Callback = Box<dyn FnMut(&Msg) + Send>;
+
+#[derive(Default)]
+struct Shared {
+    results: Mutex<BTreeSet<Bytes>>,
+    candidates: Mutex<Vec<String>>,
+    callback: Mutex<Option<Callback>>,
+}
+
+enum Out {
+    Cmd(Vec<u8>),
+    Resync,
+}
+
+/// Speaks the server protocol over any pair of streams, mirroring the server's window in
+/// `results` and answering `resync` requests by resending the lines given to `match_lines`.
+/// Writing happens on its own thread so reading the server's output never waits for the server
+/// to read its input.
+pub struct Client {
+    tx: mpsc::Sender<Out>,
+    shared: Arc<Shared>,
+}
+impl Client {
+    pub fn new(
+        reader: impl Read + Send + 'static,
+        mut writer: impl Write + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let _ = shared.write(rx, &mut writer);
+            });
+        }
+        {
+            let shared = shared.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut reader = FrameReader::new(reader);
+                while let Ok(Some(frame)) = reader.next_frame() {
+                    if let Some(msg) = Msg::parse(frame) {
+                        shared.receive(&msg, &tx);
+                    }
+                }
+            });
+        }
+        Self { tx, shared }
+    }
+
+    pub fn send(&self, cmd: &str, arg: &str) -> io::Result<()> {
+        self.tx
+            .send(Out::Cmd(frame(cmd, arg)))
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+
+    /// Walk `dir` and match the files found against the query.
+    pub fn walk(&self, dir: &str) -> io::Result<()> {
+        lock(&self.shared.candidates).clear();
+        self.send("walk", dir)
+    }
+
+    /// Replace the whole query with `text`.
+    pub fn set_query(&self, text: &str) -> io::Result<()> {
+        self.send("set", &format!("0 {text}"))
+    }
+
+    pub fn ignore(&self, text: &str) -> io::Result<()> {
+        self.send("ignore", text)
+    }
+
+    pub fn window_size(&self, size: usize) -> io::Result<()> {
+        self.send("window_size", &size.to_string())
+    }
+
+    /// Match `lines` instead of walking. The lines are remembered so they can be resent when the
+    /// server asks for a resync.
+    pub fn match_lines<S: Into<String>>(
+        &self,
+        lines: impl IntoIterator<Item = S>,
+    ) -> io::Result<()> {
+        let mut candidates = lock(&self.shared.candidates);
+        for line in lines {
+            let line = line.into();
+            self.send("match", &line)?;
+            candidates.push(line);
+        }
+        Ok(())
+    }
+
+    pub fn stop(&self) -> io::Result<()> {
+        lock(&self.shared.candidates).clear();
+        self.send("stop", "")
+    }
+
+    /// Call `f` with each message after the results have been updated.
+    pub fn on_result(&self, f: impl FnMut(&Msg) + Send + 'static) {
+        *lock(&self.shared.callback) = Some(Box::new(f));
+    }
+
+    /// The paths currently in the server's window, in window order.
+    pub fn results(&self) -> Vec<Bytes> {
+        lock(&self.shared.results).iter().cloned().collect()
+    }
+}
+
+impl Shared {
+    fn receive(&self, msg: &Msg, tx: &mpsc::Sender<Out>) {
+        match msg {
+            Msg::Clear => lock(&self.results).clear(),
+            Msg::AddFile(data) => {
+                let path = match data.iter().position(|&b| b == FIELD_SEP) {
+                    Some(i) => data.slice(..i),
+                    None => data.clone(),
+                };
+                lock(&self.results).insert(path);
+            }
+            Msg::RmFile(path) => {
+                lock(&self.results).remove(path);
+            }
+            Msg::Resync => {
+                let _ = tx.send(Out::Resync);
+            }
+            _ => {}
+        }
+        if let Some(f) = lock(&self.callback).as_mut() {
+            f(msg);
+        }
+    }
+
+    fn write(&self, rx: mpsc::Receiver<Out>, out: &mut impl Write) -> io::Result<()> {
+        while let Ok(mut next) = rx.recv() {
+            loop {
+                match next {
+                    Out::Cmd(data) => out.write_all(&data)?,
+                    Out::Resync => {
+                        let candidates = lock(&self.candidates).clone();
+                        for line in candidates {
+                            out.write_all(&frame("match", &line))?;
+                        }
+                    }
+                }
+                match rx.try_recv() {
+                    Ok(n) => next = n,
+                    Err(_) => break,
+                }
+            }
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A `Client` for a `koru_find --server` child process, which is killed on drop.
+pub struct Spawned {
+    child: Child,
+    client: Client,
+}
+impl Spawned {
+    /// Start `program` (normally the `koru_find` binary) with `--server`.
+    pub fn spawn(program: impl AsRef<OsStr>) -> io::Result<Self> {
+        let mut child = Command::new(program)
+            .arg("--server")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("child has no stdio"));
+        };
+        Ok(Self {
+            child,
+            client: Client::new(stdout, stdin),
+        })
+    }
+}
+impl Deref for Spawned {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+impl Drop for Spawned {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn frame(cmd: &str, arg: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(cmd.len() + arg.len() + 2);
+    data.extend_from_slice(cmd.as_bytes());
+    if !arg.is_empty() {
+        data.push(b' ');
+        data.extend_from_slice(arg.as_bytes());
+    }
+    data.push(0);
+    data
+}
+
+#[inline(always)]
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().expect(crate::LOCK_SHOULD_BE_OK)
+}
+
+#[cfg(test)]
+#[path = "client_test.rs"]
+mod test;
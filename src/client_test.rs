@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+const WT: Duration = Duration::from_millis(500);
+
+fn start() -> (Client, mpsc::Receiver<Msg>) {
+    let (in_reader, in_writer) = io::pipe().unwrap();
+    let (out_reader, out_writer) = io::pipe().unwrap();
+    thread::spawn(move || crate::server::run(4, in_reader, out_writer));
+
+    let client = Client::new(out_reader, in_writer);
+    let (tx, rx) = mpsc::channel();
+    client.on_result(move |msg| {
+        let _ = tx.send(msg.clone());
+    });
+    (client, rx)
+}
+
+fn results(client: &Client) -> Vec<String> {
+    client
+        .results()
+        .iter()
+        .map(|p| String::from_utf8_lossy(p).to_string())
+        .collect()
+}
+
+#[test]
+fn walk() {
+    let (client, rx) = start();
+
+    client.walk("test").unwrap();
+    client.set_query("txt").unwrap();
+
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkDone {}
+    assert_eq!(results(&client), ["a/1/2.txt", "a/1/3.txt"]);
+
+    client.set_query("3").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::RmFile(Bytes::from_static(b"a/1/2.txt"))
+    );
+    assert_eq!(results(&client), ["a/1/3.txt"]);
+
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    client.stop().unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(results(&client), Vec::<String>::new());
+}
+
+#[test]
+fn resync() {
+    let (client, rx) = start();
+
+    client.set_query("ab").unwrap();
+    client.match_lines(["abc", "xyz", "xaxb"]).unwrap();
+
+    for _ in 0..2 {
+        assert!(matches!(rx.recv_timeout(WT).unwrap(), Msg::AddFile(_)));
+    }
+    assert_eq!(results(&client), ["abc", "xaxb"]);
+
+    client.set_query("x").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Resync);
+    // the client resends the lines so the server can match them against the new query
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"xyz"))
+    );
+    assert_eq!(results(&client), ["abc", "xaxb", "xyz"]);
+}
@@ -0,0 +1,92 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+/// Copy `paths`, one per line, to the system clipboard. A clipboard tool (`wl-copy`, `xclip`,
+/// `xsel` or `pbcopy`) is used when one runs; otherwise, or over ssh, the text is sent to the
+/// terminal as an OSC 52 sequence.
+pub fn copy<P: AsRef<[u8]>>(paths: &[P]) -> io::Result<()> {
+    let text = paths.iter().fold(Vec::new(), |mut text, p| {
+        if !text.is_empty() {
+            text.push(b'\n');
+        }
+        text.extend_from_slice(p.as_ref());
+        text
+    });
+    if env::var_os("SSH_TTY").is_none() {
+        for tool in tools() {
+            if pipe_to(tool, &text).is_ok() {
+                return Ok(());
+            }
+        }
+    }
+    let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    tty.write_all(&osc52(&text))?;
+    tty.flush()
+}
+
+fn tools() -> Vec<&'static [&'static str]> {
+    let mut tools: Vec<&[&str]> = vec![];
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(&["wl-copy"]);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        tools.push(&["xclip", "-selection", "clipboard"]);
+        tools.push(&["xsel", "--clipboard", "--input"]);
+    }
+    if cfg!(target_os = "macos") {
+        tools.push(&["pbcopy"]);
+    }
+    tools
+}
+
+fn pipe_to(tool: &[&str], text: &[u8]) -> io::Result<()> {
+    let mut child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text)?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed", tool[0])))
+    }
+}
+
+/// The escape sequence asking the terminal to set its clipboard to `text`.
+pub fn osc52(text: &[u8]) -> Vec<u8> {
+    let mut seq = b"\x1b]52;c;".to_vec();
+    seq.extend_from_slice(&base64(text));
+    seq.push(0x07);
+    seq
+}
+
+fn base64(data: &[u8]) -> Vec<u8> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize]);
+            } else {
+                result.push(b'=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+#[path = "clipboard_test.rs"]
+mod test;
@@ -0,0 +1,17 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn base64_padding() {
+    assert_eq!(base64(b""), b"");
+    assert_eq!(base64(b"f"), b"Zg==");
+    assert_eq!(base64(b"fo"), b"Zm8=");
+    assert_eq!(base64(b"foo"), b"Zm9v");
+    assert_eq!(base64(b"foob"), b"Zm9vYg==");
+}
+
+#[test]
+fn osc52_sequence() {
+    assert_eq!(osc52(b"a/b\nc"), b"\x1b]52;c;YS9iCmM=\x07");
+}
@@ -0,0 +1,164 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{hooks::Hooks, macros::Macros, scorer::Weights, server::quota::Quotas};
+
+/// Settings from `$XDG_CONFIG_HOME/koru_find/config.toml`. Only a TOML subset is understood:
+/// `[section]` headers and `key = value` lines where the value may be a quoted string.
+///
+/// ```toml
+/// [hooks]
+/// walk-done = "notify-send 'found {count} in {path}'"
+///
+/// [weights]
+/// lock = 0.1
+/// "tests/**" = 0.5
+///
+/// [quotas]
+/// window-size = 1000
+///
+/// [macros]
+/// rust = "walk {1}\nadd :rs"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub hooks: Hooks,
+    pub weights: Weights,
+    pub quotas: Quotas,
+    pub macros: Macros,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub reason: String,
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("koru_find/config.toml"))
+    }
+
+    /// Load the config file, if there is one.
+    pub fn load() -> io::Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {err}", path.display()),
+                )
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        let mut section = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |reason: String| ConfigError {
+                line: i + 1,
+                reason,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                if !["hooks", "weights", "quotas", "macros"].contains(&section.as_str()) {
+                    return Err(error(format!("unknown section [{section}]")));
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected key = value".to_string()));
+            };
+            let key = unquote(key.trim()).map_err(error)?;
+            let key = key.as_str();
+            let value = unquote(value.trim()).map_err(error)?;
+            match section.as_str() {
+                "hooks" => {
+                    let event = key
+                        .parse()
+                        .map_err(|_| error(format!("unknown hook {key:?}")))?;
+                    config.hooks.set(event, value);
+                }
+                "weights" => {
+                    let multiplier = value
+                        .parse()
+                        .ok()
+                        .filter(|m: &f64| m.is_finite() && *m >= 0.0)
+                        .ok_or_else(|| {
+                            error(format!(
+                                "expected a finite number of at least 0; got {value:?}"
+                            ))
+                        })?;
+                    config
+                        .weights
+                        .set(key, multiplier)
+                        .map_err(|err| error(err.to_string()))?;
+                }
+                "quotas" => {
+                    let quota = value.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+                        error(format!("expected a positive number; got {value:?}"))
+                    })?;
+                    config.quotas.set(key, quota).map_err(error)?;
+                }
+                "macros" => config.macros.set(key, value),
+                _ => return Err(error(format!("{key} outside a section"))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn unquote(value: &str) -> Result<String, String> {
+    let Some(inner) = value.strip_prefix('"') else {
+        return Ok(value.to_string());
+    };
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.as_str().trim_start().is_empty()
+                || chars.as_str().trim_start().starts_with('#') =>
+            {
+                return Ok(result);
+            }
+            '"' => return Err("text after closing quote".to_string()),
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(c @ ('"' | '\\')) => result.push(c),
+                c => return Err(format!("bad escape \\{}", c.unwrap_or(' '))),
+            },
+            c => result.push(c),
+        }
+    }
+    Err("missing closing quote".to_string())
+}
+
+#[cfg(test)]
+#[path = "config_test.rs"]
+mod test;
@@ -0,0 +1,102 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::hooks::Event;
+
+#[test]
+fn parse() {
+    let config = Config::parse(
+        r#"
+# comment
+[hooks]
+walk-done = "notify-send \"found {count}\"" # trailing
+index-refreshed = echo done
+"#,
+    )
+    .unwrap();
+    let mut hooks = Hooks::default();
+    hooks.set(Event::WalkDone, "notify-send \"found {count}\"");
+    hooks.set(Event::IndexRefreshed, "echo done");
+    assert_eq!(
+        config,
+        Config {
+            hooks,
+            ..Default::default()
+        }
+    );
+
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+}
+
+#[test]
+fn weights() {
+    let config = Config::parse("[weights]\n.lock = 0.1\n\"tests/**\" = 2\n").unwrap();
+    let mut weights = Weights::default();
+    weights.set("lock", 0.1).unwrap();
+    weights.set("tests/**", 2.0).unwrap();
+    assert_eq!(config.weights, weights);
+
+    let err = Config::parse("[weights]\nlock = low").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"line 2: expected a finite number of at least 0; got "low""#
+    );
+    for bad in ["-1", "NaN", "inf"] {
+        assert!(Config::parse(&format!("[weights]\nlock = {bad}")).is_err());
+    }
+}
+
+#[test]
+fn quotas() {
+    let config = Config::parse("[quotas]\nwindow-size = 500\nwalker-threads = 2\n").unwrap();
+    assert_eq!(config.quotas.window_size, Some(500));
+    assert_eq!(config.quotas.walker_threads, Some(2));
+    assert_eq!(config.quotas.watchers, None);
+
+    let err = |text| Config::parse(text).unwrap_err().to_string();
+    assert_eq!(
+        err("[quotas]\nwatchers = 0"),
+        r#"line 2: expected a positive number; got "0""#
+    );
+    assert_eq!(
+        err("[quotas]\nmemory = 1"),
+        r#"line 2: unknown quota "memory""#
+    );
+}
+
+#[test]
+fn macros() {
+    let config = Config::parse("[macros]\nrust = \"walk {1}\\nadd :rs\"\n").unwrap();
+    assert_eq!(
+        config.macros.expand("rust", "src"),
+        Ok(vec!["walk src".to_string(), "add :rs".to_string()])
+    );
+}
+
+#[test]
+fn errors() {
+    let err = |text| Config::parse(text).unwrap_err().to_string();
+    assert_eq!(err("[hooks]\nwalk-done"), "line 2: expected key = value");
+    assert_eq!(err("[hook]"), "line 1: unknown section [hook]");
+    assert_eq!(
+        err("\n\n[hooks]\ndone = x"),
+        r#"line 4: unknown hook "done""#
+    );
+    assert_eq!(err("walk-done = x"), "line 1: walk-done outside a section");
+    assert_eq!(
+        err("[hooks]\nwalk-done = \"x"),
+        "line 2: missing closing quote"
+    );
+    assert_eq!(
+        err("[hooks]\nwalk-done = \"x\" y"),
+        "line 2: text after closing quote"
+    );
+}
+
+#[test]
+fn load_missing() {
+    assert_eq!(
+        Config::load_from(Path::new("/nonexistent/config.toml")).unwrap(),
+        Config::default()
+    );
+}
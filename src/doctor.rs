@@ -0,0 +1,133 @@
+use std::{
+    env, fmt, fs,
+    path::Path,
+    process::{self, Command, Stdio},
+};
+
+use crate::{cache, server::fd_budget};
+
+/// Fewer inotify watches than this won't cover a large tree.
+const MIN_INOTIFY_WATCHES: usize = 65536;
+
+/// What one check found; `fix` says what to do if something is wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub check: &'static str,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+impl Finding {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            check,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.fix {
+            None => write!(f, "{}: ok: {}", self.check, self.detail),
+            Some(fix) => write!(f, "{}: {}; {fix}", self.check, self.detail),
+        }
+    }
+}
+
+/// Check the environment koru_find runs in.
+pub fn check() -> Vec<Finding> {
+    vec![
+        check_cache(cache::cache_dir().as_deref()),
+        check_inotify(fd_budget::max_user_watches()),
+        check_git("git"),
+        check_locale(|name| env::var(name).ok()),
+    ]
+}
+
+fn check_cache(dir: Option<&Path>) -> Finding {
+    const CHECK: &str = "cache";
+    let Some(dir) = dir else {
+        return Finding::problem(CHECK, "no cache directory", "set $XDG_CACHE_HOME or $HOME");
+    };
+    let probe = dir.join(format!(".doctor-{}", process::id()));
+    match fs::create_dir_all(dir).and_then(|()| fs::write(&probe, b"")) {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Finding::ok(CHECK, format!("{} is writable", dir.display()))
+        }
+        Err(err) => Finding::problem(
+            CHECK,
+            format!("can't write to {}: {err}", dir.display()),
+            "fix its permissions or set $XDG_CACHE_HOME; updatedb needs it",
+        ),
+    }
+}
+
+fn check_inotify(max_watches: Option<usize>) -> Finding {
+    const CHECK: &str = "inotify";
+    match max_watches {
+        None => Finding::ok(CHECK, "no inotify; watchers poll"),
+        Some(n) if n < MIN_INOTIFY_WATCHES => Finding::problem(
+            CHECK,
+            format!("max_user_watches is {n}"),
+            format!("raise fs.inotify.max_user_watches to at least {MIN_INOTIFY_WATCHES}"),
+        ),
+        Some(n) => Finding::ok(CHECK, format!("max_user_watches is {n}")),
+    }
+}
+
+fn check_git(program: &str) -> Finding {
+    const CHECK: &str = "git";
+    let output = Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            Finding::ok(CHECK, String::from_utf8_lossy(&output.stdout).trim())
+        }
+        _ => Finding::problem(
+            CHECK,
+            format!("{program} can't be run"),
+            "install git or add it to PATH; core.excludesFile is only read from ~/.gitconfig",
+        ),
+    }
+}
+
+/// `var` looks up an environment variable.
+fn check_locale(var: impl Fn(&str) -> Option<String>) -> Finding {
+    const CHECK: &str = "locale";
+    let Some((name, value)) = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|name| var(name).filter(|v| !v.is_empty()).map(|v| (name, v)))
+    else {
+        return Finding::problem(
+            CHECK,
+            "no locale set",
+            "set LANG to a UTF-8 locale such as C.UTF-8",
+        );
+    };
+    let lower = value.to_ascii_lowercase();
+    if lower.contains("utf-8") || lower.contains("utf8") {
+        Finding::ok(CHECK, format!("{name}={value}"))
+    } else {
+        Finding::problem(
+            CHECK,
+            format!("{name}={value} is not UTF-8"),
+            "use a UTF-8 locale so clients show non-ASCII paths correctly",
+        )
+    }
+}
+
+#[cfg(test)]
+#[path = "doctor_test.rs"]
+mod test;
@@ -0,0 +1,64 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::test_util::TempDir;
+
+#[test]
+fn cache() {
+    let temp = TempDir::new("doctor");
+    let dir = temp.join("cache");
+    let finding = check_cache(Some(&dir));
+    assert_eq!(finding.fix, None);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+    assert_eq!(
+        check_cache(None).to_string(),
+        "cache: no cache directory; set $XDG_CACHE_HOME or $HOME"
+    );
+    let file = env::temp_dir().join(format!("koru_find_doctor_file_{}", process::id()));
+    fs::write(&file, b"").unwrap();
+    assert!(check_cache(Some(&file)).fix.is_some());
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn inotify() {
+    assert_eq!(check_inotify(None).fix, None);
+    assert_eq!(
+        check_inotify(Some(524288)).to_string(),
+        "inotify: ok: max_user_watches is 524288"
+    );
+    assert_eq!(
+        check_inotify(Some(8192)).to_string(),
+        "inotify: max_user_watches is 8192; raise fs.inotify.max_user_watches to at least 65536"
+    );
+}
+
+#[test]
+fn git() {
+    assert!(check_git("git").detail.starts_with("git version"));
+    assert_eq!(
+        check_git("koru_find_no_such_git").detail,
+        "koru_find_no_such_git can't be run"
+    );
+}
+
+#[test]
+fn locale() {
+    let env = |vars: &'static [(&str, &str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        }
+    };
+    assert_eq!(
+        check_locale(env(&[("LANG", "en_NZ.UTF-8"), ("LC_ALL", "")])).to_string(),
+        "locale: ok: LANG=en_NZ.UTF-8"
+    );
+    assert_eq!(
+        check_locale(env(&[("LANG", "en_NZ.utf8"), ("LC_CTYPE", "C")])).to_string(),
+        "locale: LC_CTYPE=C is not UTF-8; use a UTF-8 locale so clients show non-ASCII paths correctly"
+    );
+    assert!(check_locale(env(&[])).fix.is_some());
+}
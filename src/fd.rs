@@ -0,0 +1,173 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
+    sync::mpsc,
+};
+
+use bytes::Bytes;
+
+use crate::server::{
+    walker::{Kinds, Msg, WalkOptions, Walker},
+    window::Window,
+};
+
+/// The subset of fd's command line understood by `koru_find fd`.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct Args {
+    /// Regular expression matched against the path relative to PATH (like fd --full-path)
+    pub pattern: Option<String>,
+
+    /// Directory to search
+    pub path: Option<PathBuf>,
+
+    /// Only show files with this extension
+    #[arg(short = 'e', long = "extension")]
+    pub extensions: Vec<String>,
+
+    /// Include hidden files and directories
+    #[arg(short = 'H', long)]
+    pub hidden: bool,
+
+    /// Include files ignored by .gitignore, .ignore and git excludes
+    #[arg(short = 'I', long)]
+    pub no_ignore: bool,
+
+    /// Only show entries of this type
+    #[arg(short = 't', long = "type")]
+    pub types: Vec<Type>,
+
+    /// Don't descend more than this many directories
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<usize>,
+
+    /// Run a command for each result; `{}` is replaced by the path or else it is appended
+    #[arg(
+        short = 'x',
+        long,
+        num_args = 1..,
+        value_terminator = ";",
+        allow_hyphen_values = true
+    )]
+    pub exec: Vec<String>,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    #[value(alias = "f")]
+    File,
+    #[value(alias = "d", alias = "dir")]
+    Directory,
+    #[value(alias = "l")]
+    Symlink,
+    #[value(alias = "x")]
+    Executable,
+}
+
+impl Args {
+    pub fn options(&self) -> WalkOptions {
+        WalkOptions {
+            hidden: self.hidden,
+            no_ignore: self.no_ignore,
+            max_depth: self.max_depth,
+            kinds: (!self.types.is_empty()).then(|| Kinds {
+                file: self.types.contains(&Type::File),
+                dir: self.types.contains(&Type::Directory),
+                symlink: self.types.contains(&Type::Symlink),
+                executable: self.types.contains(&Type::Executable),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The pattern and extensions as a query for the `add` command.
+    pub fn query(&self) -> String {
+        let mut terms = vec![];
+        if let Some(pattern) = self.pattern.as_ref().filter(|p| !p.is_empty()) {
+            terms.push(format!("*{pattern}"));
+        }
+        if !self.extensions.is_empty() {
+            let exts: Vec<_> = self
+                .extensions
+                .iter()
+                .map(|e| regex::escape(e.trim_start_matches('.')))
+                .collect();
+            terms.push(format!("*\\.(?:{})$", exts.join("|")));
+        }
+        // the query separates terms with spaces
+        terms
+            .iter()
+            .map(|t| t.replace(' ', "\\x20"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Print, or run `--exec` on, each entry found, writing warnings to `diag`. Returns the exit
+/// code: 1 if any command failed.
+pub fn run(args: &Args, out: impl Write, mut diag: impl Write) -> io::Result<i32> {
+    match search(args, &mut BufWriter::new(out), &mut diag) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+        result => result,
+    }
+}
+
+fn search(args: &Args, out: &mut impl Write, diag: &mut impl Write) -> io::Result<i32> {
+    let root = args.path.clone().unwrap_or_else(|| ".".into());
+    let Some(dir) = root.to_str() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path is not UTF-8",
+        ));
+    };
+    let (tx, rx) = mpsc::sync_channel(1024);
+    let mut walker = Walker::new(Window::new(usize::MAX, tx));
+    walker.set_options(args.options());
+    for (cmd, arg) in [("add", args.query().as_str()), ("walk", dir)] {
+        walker.command(cmd, arg).map_err(io::Error::other)?;
+    }
+
+    let mut code = 0;
+    while let Ok(msg) = rx.recv() {
+        match msg {
+            Msg::AddFile(path) => {
+                let path = display_path(&args.path, &path);
+                if args.exec.is_empty() {
+                    writeln!(out, "{path}")?;
+                } else if !exec(&args.exec, &path)? {
+                    code = 1;
+                }
+            }
+            Msg::WalkDone | Msg::WalkTruncated => break,
+            Msg::Message(msg) | Msg::Fatal(msg) => return Err(io::Error::other(msg)),
+            Msg::Warning { kind, detail } => writeln!(diag, "warning {kind} {detail}")?,
+            _ => {}
+        }
+    }
+    out.flush()?;
+    Ok(code)
+}
+
+/// Paths are shown under PATH when given, as fd does.
+fn display_path(root: &Option<PathBuf>, path: &Bytes) -> String {
+    let path = String::from_utf8_lossy(path);
+    match root {
+        Some(root) => root.join(path.as_ref()).to_string_lossy().into_owned(),
+        None => path.into_owned(),
+    }
+}
+
+fn exec(command: &[String], path: &str) -> io::Result<bool> {
+    let mut args: Vec<_> = command[1..].iter().map(|a| a.replace("{}", path)).collect();
+    if !command[1..].iter().any(|a| a.contains("{}")) {
+        args.push(path.to_string());
+    }
+    Ok(process::Command::new(&command[0])
+        .args(args)
+        .status()?
+        .success())
+}
+
+#[cfg(test)]
+#[path = "fd_test.rs"]
+mod test;
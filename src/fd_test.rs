@@ -0,0 +1,89 @@
+use clap::Parser;
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    args: Args,
+}
+
+fn parse(argv: &[&str]) -> Args {
+    Cli::parse_from(["fd"].iter().chain(argv)).args
+}
+
+fn fd(argv: &[&str]) -> (i32, Vec<String>) {
+    let mut out = vec![];
+    let code = run(&parse(argv), &mut out, io::sink()).unwrap();
+    let mut lines: Vec<_> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    lines.sort();
+    (code, lines)
+}
+
+#[test]
+fn query() {
+    assert_eq!(parse(&[]).query(), "");
+    assert_eq!(parse(&["a b$"]).query(), "*a\\x20b$");
+    assert_eq!(
+        parse(&["-e", "rs", "-e", ".c++", "main"]).query(),
+        "*main *\\.(?:rs|c\\+\\+)$"
+    );
+}
+
+#[test]
+fn options() {
+    let args = parse(&["-HI", "-d", "3", "-t", "f", "-t", "x", "pat", "dir"]);
+    assert_eq!(args.pattern.as_deref(), Some("pat"));
+    assert_eq!(args.path, Some(PathBuf::from("dir")));
+    assert_eq!(
+        args.options(),
+        WalkOptions {
+            hidden: true,
+            no_ignore: true,
+            max_depth: Some(3),
+            kinds: Some(Kinds {
+                file: true,
+                executable: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    );
+    assert_eq!(parse(&[]).options(), WalkOptions::default());
+}
+
+#[test]
+fn exec_args() {
+    let args = parse(&["-x", "cmd", "-v", "{}.bak", ";", "x", "dir"]);
+    assert_eq!(args.exec, ["cmd", "-v", "{}.bak"]);
+    assert_eq!(args.pattern.as_deref(), Some("x"));
+}
+
+#[test]
+fn search() {
+    assert_eq!(
+        fd(&["", "test"]),
+        (0, vec!["test/a/1/2.txt".into(), "test/a/1/3.txt".into()])
+    );
+    assert_eq!(fd(&["3", "test"]), (0, vec!["test/a/1/3.txt".into()]));
+    assert_eq!(fd(&["-e", "md", "", "test"]), (0, vec![]));
+    assert_eq!(
+        fd(&["-t", "d", "", "test"]),
+        (0, vec!["test/a".into(), "test/a/1".into()])
+    );
+    assert_eq!(fd(&["-d", "2", "", "test"]), (0, vec![]));
+}
+
+#[test]
+fn exec_command() {
+    assert_eq!(fd(&["-x", "true", ";", "2", "test"]), (0, vec![]));
+    assert_eq!(
+        fd(&["-x", "test", "-d", "{}", ";", "2", "test"]),
+        (1, vec![])
+    );
+}
@@ -0,0 +1,86 @@
+use std::{
+    collections::HashMap,
+    io,
+    process::{Command, ExitStatus, Stdio},
+    str::FromStr,
+    thread,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// A walk finished; `{path}` is the directory walked and `{count}` the results found.
+    WalkDone,
+    /// The client accepted `{path}`.
+    SelectionAccepted,
+    /// `updatedb` wrote `{count}` paths to the database `{path}`.
+    IndexRefreshed,
+}
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::WalkDone => "walk-done",
+            Event::SelectionAccepted => "selection-accepted",
+            Event::IndexRefreshed => "index-refreshed",
+        }
+    }
+}
+impl FromStr for Event {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "walk-done" => Ok(Event::WalkDone),
+            "selection-accepted" => Ok(Event::SelectionAccepted),
+            "index-refreshed" => Ok(Event::IndexRefreshed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Shell commands run on lifecycle events. `{path}` and `{count}` in a command are replaced
+/// by the event's values; they are also in `$KORU_FIND_PATH` and `$KORU_FIND_COUNT`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hooks {
+    commands: HashMap<Event, String>,
+}
+impl Hooks {
+    pub fn set(&mut self, event: Event, command: impl Into<String>) {
+        self.commands.insert(event, command.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Start the hook for `event`, if any, without waiting for it; the returned thread reaps it.
+    pub fn run(
+        &self,
+        event: Event,
+        path: &str,
+        count: usize,
+    ) -> Option<thread::JoinHandle<io::Result<ExitStatus>>> {
+        let command = self
+            .commands
+            .get(&event)?
+            .replace("{path}", &shell_quote(path))
+            .replace("{count}", &count.to_string());
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("KORU_FIND_EVENT", event.name())
+            .env("KORU_FIND_PATH", path)
+            .env("KORU_FIND_COUNT", count.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn();
+        Some(thread::spawn(move || child?.wait()))
+    }
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+#[path = "hooks_test.rs"]
+mod test;
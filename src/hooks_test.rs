@@ -0,0 +1,45 @@
+use std::{env, fs, process};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn run() {
+    let out = env::temp_dir().join(format!("koru_find_hooks_{}", process::id()));
+    let mut hooks = Hooks::default();
+    assert!(hooks.is_empty());
+    assert!(hooks.run(Event::WalkDone, "x", 1).is_none());
+
+    hooks.set(
+        Event::WalkDone,
+        format!(
+            "echo {{path}} {{count}} $KORU_FIND_EVENT \"$KORU_FIND_PATH\" > {}",
+            out.display()
+        ),
+    );
+    let status = hooks
+        .run(Event::WalkDone, "it's a dir", 3)
+        .unwrap()
+        .join()
+        .unwrap()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        fs::read_to_string(&out).unwrap(),
+        "it's a dir 3 walk-done it's a dir\n"
+    );
+    let _ = fs::remove_file(&out);
+}
+
+#[test]
+fn event_names() {
+    for event in [
+        Event::WalkDone,
+        Event::SelectionAccepted,
+        Event::IndexRefreshed,
+    ] {
+        assert_eq!(event.name().parse(), Ok(event));
+    }
+    assert_eq!("walk_done".parse::<Event>(), Err(()));
+}
@@ -1,7 +1,22 @@
 pub(crate) const LOCK_SHOULD_BE_OK: &str = "Lock should be ok";
 
+pub mod cache;
+pub mod client;
+pub mod clipboard;
+pub mod config;
+pub mod doctor;
+pub mod fd;
+pub mod hooks;
+pub mod locate;
+pub mod lsp;
+pub mod macros;
+#[cfg(feature = "nfc")]
+pub mod nfc;
 pub mod pattern;
+pub mod scorer;
 pub mod server;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 #[macro_export]
 macro_rules! fixme {
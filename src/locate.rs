@@ -0,0 +1,420 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::MetadataExt,
+    },
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::pattern::Pattern;
+
+/// Version 1 databases have no directory stamps.
+const MAGIC_V1: &[u8] = b"koru_find locate 1\n";
+const MAGIC: &[u8] = b"koru_find locate 2\n";
+/// No path or part of one is longer, so a corrupt length can't allocate without bound.
+const MAX_BYTES: usize = 1 << 16;
+
+/// A directory's modification time and inode, which change when entries are added to, removed
+/// from or renamed within it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stamp {
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+    pub ino: u64,
+}
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::symlink_metadata(path).ok()?;
+        meta.is_dir().then(|| Self {
+            mtime: meta.mtime(),
+            mtime_nsec: meta.mtime_nsec(),
+            ino: meta.ino(),
+        })
+    }
+}
+
+/// Whether a path was added to or removed from the database by `refresh_db`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+}
+
+/// What `refresh_db` did.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Refresh {
+    /// Paths now in the database.
+    pub paths: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// Directories whose entries were read rather than taken from the old database.
+    pub dirs_read: usize,
+}
+
+/// Where `updatedb` and `locate` keep the database unless told otherwise:
+/// `$XDG_CACHE_HOME/koru_find/locate.db`.
+pub fn default_db() -> Option<PathBuf> {
+    Some(crate::cache::cache_dir()?.join("locate.db"))
+}
+
+/// Record every path under `roots` in `db`. With no roots the roots of the existing database
+/// are reused; it is an error if there is none. Returns the number of paths written.
+pub fn update_db(db: &Path, roots: &[PathBuf]) -> io::Result<usize> {
+    refresh_db(db, roots, |_, _| {}).map(|r| r.paths)
+}
+
+/// Like `update_db`, but only directories whose stamp differs from the one in the existing
+/// database, or which were modified no earlier than the second it was started, are read again;
+/// the entries of the rest are copied. Each path added or removed is passed to `changed`.
+pub fn refresh_db(
+    db: &Path,
+    roots: &[PathBuf],
+    mut changed: impl FnMut(Change, &[u8]),
+) -> io::Result<Refresh> {
+    let old = Old::load(db);
+    let mut roots = match roots {
+        [] => match &old {
+            Some(old) => old.roots.clone(),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no roots given and no existing database",
+                ));
+            }
+        },
+        roots => roots
+            .iter()
+            .map(fs::canonicalize)
+            .collect::<io::Result<_>>()?,
+    };
+    roots.sort();
+    roots.dedup();
+    let old = old.unwrap_or_default();
+
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let mut refresh = Refresh::default();
+    let mut entries = vec![];
+    let mut root_stamps = vec![];
+    for root in &roots {
+        let stamp = Stamp::of(root);
+        root_stamps.push(stamp);
+        if let Some(stamp) = stamp {
+            let dir = root.as_os_str().as_bytes();
+            scan(dir, stamp, &old, &mut entries, &mut refresh.dirs_read);
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+
+    // the old paths are read again rather than held, in the same sorted order as `entries`
+    let mut old_db = Database::open(db).ok();
+    let mut next_old = || {
+        let db = old_db.as_mut()?;
+        db.next_path().ok().flatten().map(<[u8]>::to_vec)
+    };
+    let mut new = entries.iter().map(|e| e.0.as_slice()).peekable();
+    let mut gone = next_old();
+    loop {
+        let order = match (new.peek(), &gone) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => a.cmp(&b.as_slice()),
+        };
+        match order {
+            Ordering::Equal => {
+                new.next();
+                gone = next_old();
+            }
+            Ordering::Less => {
+                let path = new.next().expect("Peeked path should exist");
+                refresh.added += 1;
+                changed(Change::Added, path);
+            }
+            Ordering::Greater => {
+                let path = gone.take().expect("Peeked path should exist");
+                refresh.removed += 1;
+                changed(Change::Removed, &path);
+                gone = next_old();
+            }
+        }
+    }
+    refresh.paths = entries.len();
+
+    if let Some(dir) = db.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // write to the side so concurrent lookups never see a partial database
+    let tmp = db.with_extension("tmp");
+    {
+        let mut out = BufWriter::new(fs::File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        write_varint(&mut out, started as u64 as usize)?;
+        write_varint(&mut out, roots.len())?;
+        for (root, stamp) in roots.iter().zip(&root_stamps) {
+            write_bytes(&mut out, root.as_os_str().as_bytes())?;
+            write_stamp(&mut out, *stamp)?;
+        }
+        let mut prev: &[u8] = &[];
+        for (path, stamp) in &entries {
+            let shared = prev.iter().zip(path).take_while(|(a, b)| a == b).count();
+            write_varint(&mut out, shared)?;
+            write_bytes(&mut out, &path[shared..])?;
+            write_stamp(&mut out, *stamp)?;
+            prev = path;
+        }
+        out.flush()?;
+    }
+    fs::rename(&tmp, db)?;
+    Ok(refresh)
+}
+
+/// A path with its stamp if it is a directory.
+type Entry = (Vec<u8>, Option<Stamp>);
+
+/// What `refresh_db` needs from the existing database.
+#[derive(Default)]
+struct Old {
+    roots: Vec<PathBuf>,
+    /// The second the existing database was started in.
+    started: i64,
+    stamps: HashMap<Vec<u8>, Stamp>,
+    /// The names of each directory's entries, and whether each is a directory.
+    children: HashMap<Vec<u8>, Vec<(Vec<u8>, bool)>>,
+}
+impl Old {
+    fn load(db: &Path) -> Option<Self> {
+        let mut db = Database::open(db).ok()?;
+        let mut old = Self {
+            roots: db.roots.clone(),
+            started: db.started,
+            ..Default::default()
+        };
+        for (root, stamp) in db.roots.iter().zip(&db.root_stamps) {
+            if let Some(stamp) = stamp {
+                old.stamps
+                    .insert(root.as_os_str().as_bytes().to_vec(), *stamp);
+            }
+        }
+        while let Ok(Some((path, stamp))) = db.next_entry() {
+            let split = path.iter().rposition(|c| *c == b'/').unwrap_or(0);
+            let parent = if split == 0 {
+                &b"/"[..]
+            } else {
+                &path[..split]
+            };
+            old.children
+                .entry(parent.to_vec())
+                .or_default()
+                .push((path[split + 1..].to_vec(), stamp.is_some()));
+            if let Some(stamp) = stamp {
+                old.stamps.insert(path.to_vec(), stamp);
+            }
+        }
+        Some(old)
+    }
+
+    /// Whether `dir` is known to be unchanged since the existing database was written. A
+    /// directory modified in the second it was read may have changed again since without its
+    /// stamp changing.
+    fn is_unchanged(&self, dir: &[u8], stamp: Stamp) -> bool {
+        self.stamps.get(dir) == Some(&stamp) && stamp.mtime < self.started
+    }
+}
+
+/// Add the entries below `dir` to `entries`, reading only directories that have changed.
+fn scan(dir: &[u8], stamp: Stamp, old: &Old, entries: &mut Vec<Entry>, dirs_read: &mut usize) {
+    let children: Vec<(Vec<u8>, bool)> = if old.is_unchanged(dir, stamp) {
+        old.children.get(dir).cloned().unwrap_or_default()
+    } else {
+        *dirs_read += 1;
+        let Ok(read) = fs::read_dir(Path::new(OsStr::from_bytes(dir))) else {
+            return;
+        };
+        read.flatten()
+            .map(|e| {
+                let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+                (e.file_name().into_vec(), is_dir)
+            })
+            .collect()
+    };
+    for (name, is_dir) in children {
+        let mut path = dir.to_vec();
+        if !path.ends_with(b"/") {
+            path.push(b'/');
+        }
+        path.extend(name);
+        if !is_dir {
+            entries.push((path, None));
+        } else if let Some(stamp) = Stamp::of(Path::new(OsStr::from_bytes(&path))) {
+            entries.push((path.clone(), Some(stamp)));
+            scan(&path, stamp, old, entries, dirs_read);
+        }
+    }
+}
+
+/// Write each path in `db` which matches `query` to `out`, one per line. Returns the number
+/// found.
+pub fn locate(db: &Path, query: &str, out: impl Write) -> io::Result<usize> {
+    let mut out = BufWriter::new(out);
+    let pattern = Pattern::default();
+    pattern.add(query);
+    let mut found = 0;
+    let mut db = Database::open(db)?;
+    while let Some(path) = db.next_path()? {
+        if pattern.all_matches(path) {
+            found += 1;
+            out.write_all(path)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(found)
+}
+
+/// A reader for the front coded list of paths: each path is stored as the length of the prefix
+/// it shares with the previous path followed by the rest of it.
+pub struct Database {
+    pub roots: Vec<PathBuf>,
+    /// The second the database was started in; 0 for version 1.
+    started: i64,
+    root_stamps: Vec<Option<Stamp>>,
+    stamped: bool,
+    input: BufReader<fs::File>,
+    path: Vec<u8>,
+}
+impl Database {
+    pub fn open(db: &Path) -> io::Result<Self> {
+        let mut input = BufReader::new(fs::File::open(db)?);
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic).map_err(|_| invalid())?;
+        let stamped = match &magic[..] {
+            MAGIC => true,
+            MAGIC_V1 => false,
+            _ => return Err(invalid()),
+        };
+        let started = match stamped {
+            true => read_varint(&mut input)?.ok_or_else(invalid)? as u64 as i64,
+            false => 0,
+        };
+        let mut roots = vec![];
+        let mut root_stamps = vec![];
+        for _ in 0..read_varint(&mut input)?.ok_or_else(invalid)? {
+            roots.push(PathBuf::from(OsString::from_vec(read_bytes(&mut input)?)));
+            root_stamps.push(match stamped {
+                true => read_stamp(&mut input)?,
+                false => None,
+            });
+        }
+        Ok(Self {
+            roots,
+            started,
+            root_stamps,
+            stamped,
+            input,
+            path: vec![],
+        })
+    }
+
+    pub fn next_path(&mut self) -> io::Result<Option<&[u8]>> {
+        Ok(self.next_entry()?.map(|(path, _)| path))
+    }
+
+    /// The next path with its stamp if it is a directory.
+    pub fn next_entry(&mut self) -> io::Result<Option<(&[u8], Option<Stamp>)>> {
+        let Some(shared) = read_varint(&mut self.input)? else {
+            return Ok(None);
+        };
+        if shared > self.path.len() {
+            return Err(invalid());
+        }
+        self.path.truncate(shared);
+        self.path.extend(read_bytes(&mut self.input)?);
+        let stamp = match self.stamped {
+            true => read_stamp(&mut self.input)?,
+            false => None,
+        };
+        Ok(Some((&self.path, stamp)))
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "not a koru_find locate database",
+    )
+}
+
+fn write_varint(out: &mut impl Write, mut n: usize) -> io::Result<()> {
+    while n >= 0x80 {
+        out.write_all(&[n as u8 | 0x80])?;
+        n >>= 7;
+    }
+    out.write_all(&[n as u8])
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_varint(out, bytes.len())?;
+    out.write_all(bytes)
+}
+
+/// A 0 for no stamp, otherwise 1 followed by the stamp's fields.
+fn write_stamp(out: &mut impl Write, stamp: Option<Stamp>) -> io::Result<()> {
+    let Some(stamp) = stamp else {
+        return write_varint(out, 0);
+    };
+    write_varint(out, 1)?;
+    write_varint(out, stamp.mtime as u64 as usize)?;
+    write_varint(out, stamp.mtime_nsec as u64 as usize)?;
+    write_varint(out, stamp.ino as usize)
+}
+
+fn read_stamp(input: &mut impl Read) -> io::Result<Option<Stamp>> {
+    let mut next = || read_varint(input)?.ok_or_else(invalid);
+    Ok(match next()? {
+        0 => None,
+        _ => Some(Stamp {
+            mtime: next()? as u64 as i64,
+            mtime_nsec: next()? as u64 as i64,
+            ino: next()? as u64,
+        }),
+    })
+}
+
+/// `None` at a clean end of input.
+fn read_varint(input: &mut impl Read) -> io::Result<Option<usize>> {
+    let mut n = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let mut byte = [0];
+        if input.read(&mut byte)? == 0 {
+            return if shift == 0 { Ok(None) } else { Err(invalid()) };
+        }
+        n |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] < 0x80 {
+            return Ok(Some(n));
+        }
+    }
+    Err(invalid())
+}
+
+fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_varint(input)?.ok_or_else(invalid)?;
+    if len > MAX_BYTES {
+        return Err(invalid());
+    }
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+#[path = "locate_test.rs"]
+mod test;
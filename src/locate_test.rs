@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::test_util::TempDir;
+
+/// Move the modification time of `dir` by `secs` from now.
+fn set_mtime(dir: &Path, secs: i64) {
+    let now = SystemTime::now();
+    let by = Duration::from_secs(secs.unsigned_abs());
+    let time = if secs < 0 { now - by } else { now + by };
+    fs::File::open(dir).unwrap().set_modified(time).unwrap();
+}
+
+fn locate_lines(db: &Path, query: &str) -> Vec<String> {
+    let mut out = vec![];
+    let n = locate(db, query, &mut out).unwrap();
+    let lines: Vec<_> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(n, lines.len());
+    lines
+}
+
+#[test]
+fn update_and_locate() {
+    let dir = TempDir::new("locate");
+    let db = dir.join("cache/locate.db");
+    let root = fs::canonicalize("test").unwrap();
+    let r = root.to_str().unwrap();
+
+    assert_eq!(update_db(&db, &[PathBuf::from("test")]).unwrap(), 4);
+    assert_eq!(
+        Database::open(&db).unwrap().roots,
+        [fs::canonicalize("test").unwrap()]
+    );
+
+    assert_eq!(
+        locate_lines(&db, "txt"),
+        [format!("{r}/a/1/2.txt"), format!("{r}/a/1/3.txt")]
+    );
+    assert_eq!(locate_lines(&db, "a/1 >3.txt"), [format!("{r}/a/1/3.txt")]);
+    assert_eq!(
+        locate_lines(&db, ""),
+        [
+            format!("{r}/a"),
+            format!("{r}/a/1"),
+            format!("{r}/a/1/2.txt"),
+            format!("{r}/a/1/3.txt"),
+        ]
+    );
+
+    // no roots reuses those already in the database
+    assert_eq!(update_db(&db, &[]).unwrap(), 4);
+    assert!(!db.with_extension("tmp").exists());
+}
+
+#[test]
+fn refresh_reads_changed_dirs() {
+    let temp = TempDir::new("locate_refresh");
+    let dir = fs::canonicalize(&temp).unwrap();
+    let db = dir.join("locate.db");
+    let root = dir.join("root");
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::create_dir_all(root.join("c")).unwrap();
+    fs::write(root.join("a/b/x.txt"), "").unwrap();
+    fs::write(root.join("c/y.txt"), "").unwrap();
+    for dir in ["", "a", "a/b", "c"] {
+        set_mtime(&root.join(dir), -3600);
+    }
+
+    let refresh = |changes: &mut Vec<String>| {
+        refresh_db(&db, std::slice::from_ref(&root), |change, path| {
+            let path = Path::new(OsStr::from_bytes(path))
+                .strip_prefix(&root)
+                .unwrap();
+            let sign = if change == Change::Added { "+" } else { "-" };
+            changes.push(format!("{sign}{}", path.display()));
+        })
+        .unwrap()
+    };
+    let mut changes = vec![];
+    let r = refresh(&mut changes);
+    assert_eq!((r.paths, r.added, r.dirs_read), (5, 5, 4));
+
+    changes.clear();
+    let r = refresh(&mut changes);
+    assert_eq!(
+        r,
+        Refresh {
+            paths: 5,
+            ..Default::default()
+        }
+    );
+    assert!(changes.is_empty());
+
+    // modified no earlier than the last refresh started, so maybe since it was read
+    set_mtime(&root.join("c"), 3600);
+    let r = refresh(&mut changes);
+    assert_eq!((r.paths, r.dirs_read), (5, 1));
+    let r = refresh(&mut changes);
+    assert_eq!((r.paths, r.dirs_read), (5, 1));
+    set_mtime(&root.join("c"), -3600);
+    let r = refresh(&mut changes);
+    assert_eq!((r.paths, r.dirs_read), (5, 1));
+    let r = refresh(&mut changes);
+    assert_eq!((r.paths, r.dirs_read), (5, 0));
+    assert!(changes.is_empty());
+
+    fs::write(root.join("a/b/z.txt"), "").unwrap();
+    fs::remove_file(root.join("c/y.txt")).unwrap();
+    let r = refresh(&mut changes);
+    assert_eq!((r.paths, r.dirs_read), (5, 2));
+    assert_eq!(changes, ["+a/b/z.txt", "-c/y.txt"]);
+    assert_eq!(
+        locate_lines(&db, "z.txt"),
+        [root.join("a/b/z.txt").display().to_string()]
+    );
+}
+
+#[test]
+fn reads_version_1() {
+    let dir = TempDir::new("locate_v1");
+    let db = dir.join("locate.db");
+    let mut data = MAGIC_V1.to_vec();
+    write_varint(&mut data, 1).unwrap();
+    write_bytes(&mut data, b"/r").unwrap();
+    for (shared, rest) in [(0, &b"/r/a"[..]), (4, b"/b.txt")] {
+        write_varint(&mut data, shared).unwrap();
+        write_bytes(&mut data, rest).unwrap();
+    }
+    fs::write(&db, data).unwrap();
+
+    assert_eq!(locate_lines(&db, "b"), ["/r/a/b.txt"]);
+}
+
+#[test]
+fn invalid_database() {
+    let dir = TempDir::new("locate_invalid");
+    let db = dir.join("locate.db");
+    fs::write(&db, "not a database").unwrap();
+
+    let err = locate(&db, "", vec![]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // a length too long for any path
+    let mut data = MAGIC.to_vec();
+    write_varint(&mut data, 0).unwrap();
+    write_varint(&mut data, 1).unwrap();
+    write_varint(&mut data, usize::MAX >> 1).unwrap();
+    fs::write(&db, data).unwrap();
+    let err = locate(&db, "", vec![]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // nothing to update
+    let err = update_db(&dir.join("none.db"), &[]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn varint() {
+    for n in [0, 1, 127, 128, 300, 1 << 20, usize::MAX] {
+        let mut data = vec![];
+        write_varint(&mut data, n).unwrap();
+        assert_eq!(read_varint(&mut data.as_slice()).unwrap(), Some(n));
+    }
+    assert_eq!(read_varint(&mut [].as_slice()).unwrap(), None);
+    assert!(read_varint(&mut [0x80].as_slice()).is_err());
+}
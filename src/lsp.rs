@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    pattern::Pattern,
+    server::{
+        walker::{Msg, Walker},
+        window::Window,
+    },
+};
+
+const MAX_SYMBOLS: usize = 500;
+const DEFAULT_FILES: usize = 100;
+/// The largest message body read; a bigger `Content-Length` is taken as a broken stream.
+const MAX_MESSAGE: usize = 64 << 20;
+
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INTERNAL_ERROR: i32 = -32603;
+
+/// Serve a minimal language server over `input` and `output`. `workspace/symbol` searches the
+/// ctags `tags` file at the workspace root and the custom `workspace/files` request (params
+/// `{query, limit}`) searches the paths under the root using the walker.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut root = PathBuf::from(".");
+    while let Some(msg) = read_message(&mut input)? {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                write_message(
+                    &mut output,
+                    &error(Value::Null, PARSE_ERROR, err.to_string()),
+                )?;
+                continue;
+            }
+        };
+        let params = &msg["params"];
+        let result = match msg["method"].as_str().unwrap_or_default() {
+            "initialize" => {
+                if let Some(dir) = workspace_root(params) {
+                    root = dir;
+                }
+                Ok(json!({
+                    "capabilities": {"workspaceSymbolProvider": true},
+                    "serverInfo": {"name": "koru_find"},
+                }))
+            }
+            "shutdown" => Ok(Value::Null),
+            "exit" => return Ok(()),
+            "workspace/symbol" => Ok(symbols(&root, params["query"].as_str().unwrap_or_default())),
+            "workspace/files" => files(
+                &root,
+                params["query"].as_str().unwrap_or_default(),
+                params["limit"]
+                    .as_u64()
+                    .map_or(DEFAULT_FILES, |n| n as usize),
+            )
+            .map_err(|msg| (INTERNAL_ERROR, msg)),
+            method => Err((METHOD_NOT_FOUND, format!("method not found: {method}"))),
+        };
+        // notifications have no id and get no response
+        if let Some(id) = msg.get("id").cloned() {
+            let response = match result {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                Err((code, msg)) => error(id, code, msg),
+            };
+            write_message(&mut output, &response)?;
+        }
+    }
+    Ok(())
+}
+
+fn error(id: Value, code: i32, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Value, serde_json::Error>>> {
+    let mut len = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if len.is_some() {
+                break;
+            }
+        } else if let Some(n) = line.strip_prefix("Content-Length:") {
+            len = n.trim().parse().ok();
+        }
+    }
+    let len = len.unwrap_or_default();
+    if len > MAX_MESSAGE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content-Length {len} is over {MAX_MESSAGE}"),
+        ));
+    }
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)))
+}
+
+fn write_message(output: &mut impl Write, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+fn workspace_root(params: &Value) -> Option<PathBuf> {
+    params["rootUri"]
+        .as_str()
+        .or_else(|| params["workspaceFolders"][0]["uri"].as_str())
+        .and_then(uri_to_path)
+        .or_else(|| params["rootPath"].as_str().map(PathBuf::from))
+}
+
+/// Search file paths under `root` returning the first `limit` in path order, or why the walk
+/// failed.
+fn files(root: &Path, query: &str, limit: usize) -> Result<Value, String> {
+    let Some(dir) = root.to_str() else {
+        return Ok(json!([]));
+    };
+    let (tx, rx) = mpsc::sync_channel(64);
+    let mut walker = Walker::new(Window::new(limit.max(1), tx));
+    // deterministic so the walk never waits for room in the window
+    for (cmd, arg) in [("deterministic", "on"), ("add", query), ("walk", dir)] {
+        walker.command(cmd, arg).map_err(|err| err.to_string())?;
+    }
+    let mut paths = vec![];
+    while let Ok(msg) = rx.recv() {
+        match msg {
+            Msg::AddFile(path) => paths.push(path),
+            Msg::RmFile(path) => paths.retain(|p| *p != path),
+            Msg::WalkDone | Msg::WalkTruncated => break,
+            Msg::Message(m) | Msg::Fatal(m) => return Err(m),
+            _ => {}
+        }
+    }
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    Ok(Value::Array(
+        paths
+            .iter()
+            .map(|p| {
+                json!({
+                    "path": String::from_utf8_lossy(p),
+                    "uri": path_to_uri(&root.join(Path::new(std::ffi::OsStr::from_bytes(p)))),
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Search the ctags file `root/tags` for symbol names matching `query`.
+fn symbols(root: &Path, query: &str) -> Value {
+    let Ok(tags) = fs::read(root.join("tags")) else {
+        return json!([]);
+    };
+    let pattern = Pattern::default();
+    pattern.add(query);
+    let mut sources = HashMap::new();
+    let mut result = vec![];
+    for line in tags.split(|c| *c == b'\n') {
+        if line.starts_with(b"!_") {
+            continue;
+        }
+        let Some(tag) = Tag::parse(line) else {
+            continue;
+        };
+        if !pattern.all_matches(tag.name.as_bytes()) {
+            continue;
+        }
+        let path = root.join(tag.file);
+        let line = match tag.address.parse::<usize>() {
+            Ok(n) => n.saturating_sub(1),
+            Err(_) => {
+                let text = sources
+                    .entry(path.clone())
+                    .or_insert_with(|| fs::read_to_string(&path).unwrap_or_default());
+                search_line(text, tag.address)
+            }
+        };
+        result.push(json!({
+            "name": tag.name,
+            "kind": tag.kind(),
+            "location": {
+                "uri": path_to_uri(&path),
+                "range": {
+                    "start": {"line": line, "character": 0},
+                    "end": {"line": line, "character": 0},
+                },
+            },
+        }));
+        if result.len() == MAX_SYMBOLS {
+            break;
+        }
+    }
+    Value::Array(result)
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    file: &'a str,
+    address: &'a str,
+    kind: &'a str,
+}
+impl<'a> Tag<'a> {
+    fn parse(line: &'a [u8]) -> Option<Self> {
+        let line = str::from_utf8(line).ok()?;
+        let mut fields = line.splitn(3, '\t');
+        let name = fields.next()?;
+        let file = fields.next()?;
+        let rest = fields.next()?;
+        let (address, ext) = rest.split_once(";\"").unwrap_or((rest, ""));
+        let kind = ext
+            .split('\t')
+            .map(|f| f.strip_prefix("kind:").unwrap_or(f))
+            .find(|f| !f.is_empty() && !f.contains(':'))
+            .unwrap_or_default();
+        Some(Self {
+            name,
+            file,
+            address,
+            kind,
+        })
+    }
+
+    /// The LSP `SymbolKind` for the ctags kind.
+    fn kind(&self) -> u8 {
+        match self.kind {
+            "c" | "class" => 5,
+            "m" | "method" => 6,
+            "f" | "function" => 12,
+            "v" | "variable" => 13,
+            "d" | "macro" | "constant" => 14,
+            "e" | "enumerator" => 22,
+            "g" | "enum" => 10,
+            "i" | "interface" | "trait" => 11,
+            "n" | "module" | "namespace" => 3,
+            "s" | "struct" => 23,
+            "t" | "typedef" => 26,
+            _ => 13,
+        }
+    }
+}
+
+/// Find the line matching a ctags `/^text$/` search address.
+fn search_line(text: &str, address: &str) -> usize {
+    let needle = address
+        .trim_start_matches(['/', '?'])
+        .trim_end_matches(['/', '?'])
+        .trim_start_matches('^');
+    let (needle, whole) = match needle.strip_suffix('$') {
+        Some(n) => (n, true),
+        None => (needle, false),
+    };
+    let needle = needle.replace("\\/", "/").replace("\\\\", "\\");
+    text.lines()
+        .position(|l| {
+            if whole {
+                l == needle
+            } else {
+                l.starts_with(&needle)
+            }
+        })
+        .unwrap_or_default()
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &c in path.as_os_str().as_bytes() {
+        if c.is_ascii_alphanumeric() || b"/-._~".contains(&c) {
+            uri.push(c as char);
+        } else {
+            uri.push_str(&format!("%{c:02X}"));
+        }
+    }
+    uri
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        if path[i] == b'%'
+            && let Some(c) = path
+                .get(i + 1..i + 3)
+                .and_then(|h| str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        {
+            bytes.push(c);
+            i += 3;
+        } else {
+            bytes.push(path[i]);
+            i += 1;
+        }
+    }
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(&bytes)))
+}
+
+#[cfg(test)]
+#[path = "lsp_test.rs"]
+mod test;
@@ -0,0 +1,154 @@
+use std::{env, io::Cursor, process};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn frame(msg: Value) -> String {
+    let body = msg.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+fn request(id: u64, method: &str, params: Value) -> String {
+    frame(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+}
+
+fn session(input: String) -> Vec<Value> {
+    let mut output = vec![];
+    run(Cursor::new(input), &mut output).unwrap();
+    let mut input = Cursor::new(output);
+    let mut result = vec![];
+    while let Some(msg) = read_message(&mut input).unwrap() {
+        result.push(msg.unwrap());
+    }
+    result
+}
+
+fn initialize(root: &Path) -> String {
+    request(
+        1,
+        "initialize",
+        json!({"rootUri": path_to_uri(root), "capabilities": {}}),
+    ) + &frame(json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}))
+}
+
+#[test]
+fn oversized_message() {
+    let input = format!("Content-Length: {}\r\n\r\n{{}}", MAX_MESSAGE + 1);
+    let err = read_message(&mut Cursor::new(input)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn initialize_and_shutdown() {
+    let input = initialize(Path::new("test"))
+        + &request(2, "shutdown", Value::Null)
+        + &frame(json!({"jsonrpc": "2.0", "method": "exit"}))
+        + &request(3, "shutdown", Value::Null);
+    let result = session(input);
+
+    assert_eq!(result.len(), 2);
+    assert_eq!(
+        result[0]["result"]["capabilities"]["workspaceSymbolProvider"],
+        true
+    );
+    assert_eq!(
+        result[1],
+        json!({"jsonrpc": "2.0", "id": 2, "result": null})
+    );
+}
+
+#[test]
+fn unknown_method_and_bad_json() {
+    let input = request(7, "textDocument/hover", json!({})) + "Content-Length: 3\r\n\r\n{x}";
+    let result = session(input);
+
+    assert_eq!(result[0]["id"], 7);
+    assert_eq!(result[0]["error"]["code"], -32601);
+    assert_eq!(result[1]["id"], Value::Null);
+    assert_eq!(result[1]["error"]["code"], -32700);
+}
+
+#[test]
+fn workspace_files() {
+    let root = fs::canonicalize("test").unwrap();
+    let input = initialize(&root)
+        + &request(2, "workspace/files", json!({"query": "txt"}))
+        + &request(3, "workspace/files", json!({"query": "txt", "limit": 1}));
+    let result = session(input);
+
+    let paths = |r: &Value| -> Vec<String> {
+        r["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["path"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(paths(&result[1]), ["a/1/2.txt", "a/1/3.txt"]);
+    assert_eq!(paths(&result[2]), ["a/1/2.txt"]);
+    assert_eq!(
+        result[2]["result"][0]["uri"],
+        path_to_uri(&root.join("a/1/2.txt"))
+    );
+}
+
+#[test]
+fn workspace_files_error() {
+    let input = initialize(Path::new("test/no-such-dir"))
+        + &request(2, "workspace/files", json!({"query": "txt"}));
+    let result = session(input);
+
+    assert_eq!(result[1]["id"], 2);
+    assert_eq!(result[1].get("result"), None);
+    assert_eq!(result[1]["error"]["code"], -32603);
+    assert!(result[1]["error"]["message"].is_string());
+}
+
+#[test]
+fn workspace_symbol() {
+    let dir = env::temp_dir().join(format!("koru_find_lsp {}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.rs"),
+        "// x\nfn run_server() {}\nstruct Server;\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("tags"),
+        "!_TAG_FILE_SORTED\t1\t/0=unsorted/\n\
+         Server\tlib.rs\t/^struct Server;$/;\"\ts\n\
+         other\tlib.rs\t1;\"\tkind:function\n\
+         run_server\tlib.rs\t/^fn run_server() {}$/;\"\tf\tline:2\n",
+    )
+    .unwrap();
+
+    let input = initialize(&dir) + &request(2, "workspace/symbol", json!({"query": "srv"}));
+    let result = session(input);
+    let _ = fs::remove_dir_all(&dir);
+
+    let uri = path_to_uri(&dir.join("lib.rs"));
+    assert!(uri.contains("koru_find_lsp%20"));
+    let symbols = result[1]["result"].as_array().unwrap();
+    assert_eq!(
+        symbols
+            .iter()
+            .map(|s| (
+                s["name"].as_str().unwrap(),
+                s["kind"].as_u64().unwrap(),
+                s["location"]["range"]["start"]["line"].as_u64().unwrap(),
+            ))
+            .collect::<Vec<_>>(),
+        [("Server", 23, 2), ("run_server", 12, 1)]
+    );
+    assert_eq!(symbols[0]["location"]["uri"], uri);
+}
+
+#[test]
+fn uris() {
+    let path = Path::new("/a b/c%d/é");
+    let uri = path_to_uri(path);
+    assert_eq!(uri, "file:///a%20b/c%25d/%C3%A9");
+    assert_eq!(uri_to_path(&uri).unwrap(), path);
+}
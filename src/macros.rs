@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+/// Named sequences of protocol commands for the `run` command, one `cmd arg` per line. `{1}` to
+/// `{9}` in a line are replaced by the words given to `run` and `{args}` by all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Macros {
+    bodies: HashMap<String, String>,
+}
+impl Macros {
+    pub fn set(&mut self, name: impl Into<String>, body: impl Into<String>) {
+        self.bodies.insert(name.into(), body.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+
+    /// The command lines of the macro `name` with `args` substituted, leaving out blank lines.
+    pub fn expand(&self, name: &str, args: &str) -> Result<Vec<String>, String> {
+        let body = self
+            .bodies
+            .get(name)
+            .ok_or_else(|| format!("unknown macro {name:?}"))?;
+        let words: Vec<_> = args.split_whitespace().collect();
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| substitute(line.trim_start(), args, &words))
+            .collect()
+    }
+}
+
+fn substitute(line: &str, args: &str, words: &[&str]) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(i) = rest.find('{') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        match &rest[1..end] {
+            "args" => result.push_str(args),
+            n if n.len() == 1 && n.as_bytes()[0].is_ascii_digit() && n != "0" => {
+                let n: usize = n.parse().expect("Digit should parse");
+                let word = words
+                    .get(n - 1)
+                    .ok_or_else(|| format!("missing parameter {n}"))?;
+                result.push_str(word);
+            }
+            _ => {
+                result.push('{');
+                rest = &rest[1..];
+                continue;
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+#[path = "macros_test.rs"]
+mod test;
@@ -0,0 +1,33 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn expand() {
+    let mut macros = Macros::default();
+    macros.set("rust", "walk {1}\n\n  add :rs {2}\nmatcher x{y} {args}");
+
+    assert_eq!(
+        macros.expand("rust", "src main"),
+        Ok(vec![
+            "walk src".to_string(),
+            "add :rs main".to_string(),
+            "matcher x{y} src main".to_string(),
+        ])
+    );
+    assert_eq!(
+        macros.expand("rust", "src"),
+        Err("missing parameter 2".to_string())
+    );
+    assert_eq!(
+        macros.expand("go", ""),
+        Err(r#"unknown macro "go""#.to_string())
+    );
+}
+
+#[test]
+fn unclosed_brace() {
+    let mut macros = Macros::default();
+    macros.set("m", "add {1} {");
+    assert_eq!(macros.expand("m", "a"), Ok(vec!["add a {".to_string()]));
+}
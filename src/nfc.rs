@@ -0,0 +1,73 @@
+//! Composing haystacks to NFC so queries typed composed match paths stored decomposed, as macOS
+//! does. Enabled with the `nfc` feature.
+
+use std::ops::Range;
+
+use unicode_normalization::{
+    IsNormalized, UnicodeNormalization,
+    char::{canonical_combining_class, compose},
+    is_nfc_quick,
+};
+
+/// Text in NFC along with where each of its bytes came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalized {
+    pub text: Vec<u8>,
+    /// The range of the original text each byte of `text` was composed from.
+    sources: Vec<Range<usize>>,
+}
+impl Normalized {
+    /// The range of the original text that `range` of `text` came from.
+    pub fn original(&self, range: Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            let at = self
+                .sources
+                .get(range.start)
+                .map_or(self.sources.last().map_or(0, |r| r.end), |r| r.start);
+            return at..at;
+        }
+        self.sources[range.start].start..self.sources[range.end - 1].end
+    }
+}
+
+/// `text` composed to NFC, or `None` if it already is or is not UTF-8.
+pub fn normalize(text: &[u8]) -> Option<Normalized> {
+    if text.is_ascii() {
+        return None;
+    }
+    let text = str::from_utf8(text).ok()?;
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return None;
+    }
+    let mut normalized = Normalized {
+        text: Vec::with_capacity(text.len()),
+        sources: Vec::with_capacity(text.len()),
+    };
+    // compose a starter with the marks, and any starters such as Hangul jamo, combining with it
+    let mut start = 0;
+    let mut last: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        if canonical_combining_class(c) == 0 {
+            match last.and_then(|l| compose(l, c)) {
+                Some(composed) => last = Some(composed),
+                None => {
+                    push_composed(&mut normalized, text, start..i);
+                    start = i;
+                    last = Some(c);
+                }
+            }
+        }
+    }
+    push_composed(&mut normalized, text, start..text.len());
+    Some(normalized)
+}
+
+fn push_composed(normalized: &mut Normalized, text: &str, range: Range<usize>) {
+    let composed: String = text[range.clone()].nfc().collect();
+    normalized.text.extend_from_slice(composed.as_bytes());
+    normalized.sources.resize(normalized.text.len(), range);
+}
+
+#[cfg(test)]
+#[path = "nfc_test.rs"]
+mod test;
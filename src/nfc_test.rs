@@ -0,0 +1,23 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn composes() {
+    assert_eq!(normalize(b"src/main.rs"), None);
+    assert_eq!(normalize("caf\u{e9}".as_bytes()), None);
+    assert_eq!(normalize(b"\xff"), None);
+
+    // "café/é.txt" decomposed
+    let normalized = normalize("cafe\u{301}/e\u{301}.txt".as_bytes()).unwrap();
+    assert_eq!(normalized.text, "caf\u{e9}/\u{e9}.txt".as_bytes());
+    assert_eq!(normalized.original(3..5), 3..6);
+    assert_eq!(normalized.original(6..8), 7..10);
+    assert_eq!(normalized.original(8..9), 10..11);
+    assert_eq!(normalized.original(12..12), 14..14);
+
+    // Hangul jamo compose into one syllable
+    let normalized = normalize("\u{1112}\u{1161}\u{11ab}".as_bytes()).unwrap();
+    assert_eq!(normalized.text, "\u{d55c}".as_bytes());
+    assert_eq!(normalized.original(0..3), 0..9);
+}
@@ -1,10 +1,24 @@
 use std::{
+    borrow::Cow,
     cmp::min,
+    collections::{HashMap, VecDeque},
+    ops::Range,
     sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, atomic::AtomicUsize},
 };
 
+use memchr::memmem;
 use regex::bytes::{Regex, RegexBuilder};
 
+#[cfg(feature = "nfc")]
+use crate::nfc;
+use crate::scorer::{self, Score};
+
+const REGEX_CACHE_SIZE: usize = 256;
+/// Terms after this many match literally.
+const MAX_TERMS: usize = 64;
+/// How many earlier queries `undo` can go back to.
+const UNDO_SIZE: usize = 100;
+
 #[derive(Debug)]
 pub enum PatternScope {
     Narrow,
@@ -12,62 +26,336 @@ pub enum PatternScope {
     Change,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 enum AddMode {
+    #[default]
     New,
     Fuzzy,
     Regex,
+    Exact,
     StartsWith,
     EndsWith,
+    Word,
+    Segment,
+    Extension,
+    /// A `|` starting a new group of terms.
+    Bar,
+}
+
+/// What part of a term's regex match is highlighted.
+#[derive(Debug)]
+enum Spans {
+    /// The whole match.
+    Whole,
+    /// Each of these characters, with escapes removed, as a fuzzy term matches them.
+    Fuzzy(Vec<u8>),
+    /// The first group, leaving out the boundary before a word term.
+    Group,
+}
+
+/// A compiled term, searched for directly when its regex is a plain case sensitive literal.
+#[derive(Clone)]
+struct Term {
+    regex: Regex,
+    literal: Option<memmem::Finder<'static>>,
+}
+impl Term {
+    fn new(regex: Regex) -> Self {
+        let literal = plain_literal(regex.as_str()).map(|l| memmem::Finder::new(&l).into_owned());
+        Self { regex, literal }
+    }
+
+    fn is_match(&self, haystack: &[u8]) -> bool {
+        match &self.literal {
+            Some(finder) => finder.find(haystack).is_some(),
+            None => self.regex.is_match(haystack),
+        }
+    }
+}
+impl std::fmt::Debug for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.regex.fmt(f)
+    }
 }
-impl Default for AddMode {
+
+/// The characters splitting candidates into segments: `/` for paths.
+struct Separators(Vec<u8>);
+impl Default for Separators {
     fn default() -> Self {
-        Self::New
+        Self(vec![b'/'])
     }
 }
+impl Separators {
+    fn new(chars: &str) -> Option<Self> {
+        if !valid_separators(chars) {
+            return None;
+        }
+        let mut chars = chars.as_bytes().to_vec();
+        chars.sort_unstable();
+        chars.dedup();
+        Some(Self(chars))
+    }
 
+    fn contains(&self, c: char) -> bool {
+        c.is_ascii() && self.0.contains(&(c as u8))
+    }
+
+    fn is(&self, c: &[u8]) -> bool {
+        matches!(c, [c] if self.0.contains(c))
+    }
+
+    /// The separators escaped for a regex character class.
+    fn class(&self) -> String {
+        self.0
+            .iter()
+            .map(|&c| regex::escape(&(c as char).to_string()))
+            .collect()
+    }
+}
+
+/// The query texts before and after the current one, for `undo` and `redo`.
 #[derive(Default)]
-struct Matcher {
-    patterns: Vec<Regex>,
+struct History {
+    undo: VecDeque<String>,
+    redo: Vec<String>,
+}
+impl History {
+    /// Remember `text` as the query before an edit, forgetting what was undone.
+    fn push(&mut self, text: String) {
+        if self.undo.len() == UNDO_SIZE {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(text);
+        self.redo.clear();
+    }
+
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// A group of terms which must all match.
+#[derive(Default)]
+struct Terms {
+    patterns: Vec<Term>,
+    /// How to highlight each term in `patterns`.
+    spans: Vec<Spans>,
     starts_with: Option<Vec<u8>>,
     ends_with: Option<Vec<u8>>,
+}
+impl Terms {
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.starts_with.is_none() && self.ends_with.is_none()
+    }
+
+    fn all_matches(&self, haystack: &[u8]) -> bool {
+        (match &self.starts_with {
+            Some(needle) => starts_with(haystack, needle),
+            None => true,
+        }) && (match &self.ends_with {
+            Some(needle) => ends_with(haystack, needle),
+            None => true,
+        }) && self.patterns.iter().all(|v| v.is_match(haystack))
+    }
+
+    fn match_spans(&self, haystack: &[u8], separators: &Separators) -> Option<Vec<Range<usize>>> {
+        let mut spans = vec![];
+        if let Some(needle) = &self.starts_with {
+            if !starts_with(haystack, needle) {
+                return None;
+            }
+            spans.push(0..needle.len());
+        }
+        if let Some(needle) = &self.ends_with {
+            if !ends_with(haystack, needle) {
+                return None;
+            }
+            spans.push(haystack.len() - needle.len()..haystack.len());
+        }
+        for (term, term_spans) in self.patterns.iter().zip(&self.spans) {
+            let regex = &term.regex;
+            match term_spans {
+                Spans::Whole => spans.push(regex.find(haystack)?.range()),
+                Spans::Fuzzy(literal) => {
+                    let found = regex.find(haystack)?;
+                    fuzzy_spans(haystack, found.start(), literal, separators, &mut spans)
+                }
+                Spans::Group => {
+                    let captures = regex.captures(haystack)?;
+                    let found = captures.get(1).or_else(|| captures.get(0))?;
+                    spans.push(found.range());
+                }
+            }
+        }
+        Some(spans)
+    }
+
+    fn any_matches(&self, haystack: &[u8]) -> bool {
+        (match &self.starts_with {
+            Some(needle) => starts_with(haystack, needle),
+            None => false,
+        }) || (match &self.ends_with {
+            Some(needle) => ends_with(haystack, needle),
+            None => false,
+        }) || self.patterns.iter().any(|v| v.is_match(haystack))
+    }
+}
+
+#[derive(Default)]
+struct Matcher {
+    terms: Terms,
+    /// The groups before the last `|`; a path matching any group matches.
+    alternatives: Vec<Terms>,
     mode: AddMode,
     escape: bool,
     text: String,
     bad_regex: Option<String>,
     skip_prefix: usize,
+    skip_suffix: usize,
+    /// Match against the final path component only.
+    basename: bool,
+    separators: Separators,
+    /// Compose haystacks to NFC before matching.
+    #[cfg(feature = "nfc")]
+    nfc: bool,
+    /// Compiled regexes by source so editing the query doesn't recompile terms seen before.
+    cache: HashMap<String, Term>,
+    /// The last term's regex was too big to compile.
+    too_big: bool,
+    /// The last term is matched literally as it was too complex.
+    degraded: bool,
+    /// Why the query was last degraded, until taken.
+    warning: Option<String>,
+    /// How each term of `text` was compiled, for `Pattern::terms`.
+    states: Vec<TermState>,
+    history: History,
 }
 impl Matcher {
+    /// Make the change `f` to the query, remembering the text before it for `undo`.
+    fn edit(&mut self, f: impl FnOnce(&mut Self) -> PatternScope) -> PatternScope {
+        let old = self.text.clone();
+        let scope = f(self);
+        if self.text != old {
+            self.history.push(old);
+        }
+        scope
+    }
+
+    /// Go back to the query before the last edit. Returns `None` if there is none.
+    fn undo(&mut self) -> Option<PatternScope> {
+        let text = self.history.undo.pop_back()?;
+        self.history.redo.push(self.text.clone());
+        Some(self.set(0, &text))
+    }
+
+    /// Go forward to the query before the last `undo`. Returns `None` if there is none.
+    fn redo(&mut self) -> Option<PatternScope> {
+        let text = self.history.redo.pop()?;
+        self.history.undo.push_back(self.text.clone());
+        Some(self.set(0, &text))
+    }
+
     fn add(&mut self, text: &str) -> PatternScope {
         let mut scope = PatternScope::Narrow;
-        self.text.push_str(text);
+        // the text grows a term at a time so `last_term` is the one being built
+        let mut separator = "";
 
         let mut iter = text.split(' ');
         if !matches!(self.mode, AddMode::New) {
             match iter.next() {
                 Some("") => {}
-                Some(p) => match self.mode {
-                    AddMode::Fuzzy => self.extend_regex(fuzzy_build(self.escape, p)),
-                    AddMode::Regex => self.extend_regex(regex_build(self.escape, p)),
-                    AddMode::StartsWith => self.extend_starts_with(p),
-                    AddMode::EndsWith => {
-                        scope = PatternScope::Change;
-                        self.extend_ends_with(p);
+                Some(_) if matches!(self.mode, AddMode::Bar) => {
+                    // `|` followed by more is a fuzzy term, not a separator
+                    let text = format!("{}{text}", std::mem::take(&mut self.text));
+                    self.reset();
+                    self.add(&text);
+                    return PatternScope::Change;
+                }
+                Some(p) if self.degraded => {
+                    self.text.push_str(p);
+                    self.literal_fallback();
+                }
+                Some(p) => {
+                    self.text.push_str(p);
+                    match self.mode {
+                        AddMode::Fuzzy => {
+                            let literal = fuzzy_literal(self.escape, p);
+                            self.extend_regex(fuzzy_build(self.escape, p, &self.separators));
+                            if let Some(Spans::Fuzzy(last)) = self.terms.spans.last_mut() {
+                                last.extend(literal);
+                            }
+                        }
+                        AddMode::Regex => {
+                            let term = self.last_term();
+                            let old = &term[..term.len() - p.len()];
+                            let separators = &self.separators;
+                            if is_segment_term(old, separators)
+                                && !is_segment_term(term, separators)
+                            {
+                                // the old term's `.`s couldn't match a separator
+                                scope = PatternScope::Change;
+                            }
+                            self.replace_regex(regex_term(&term[1..], separators));
+                        }
+                        AddMode::Exact => self.extend_regex(exact_build(self.escape, p)),
+                        AddMode::Word => {
+                            let term = self.last_term();
+                            self.replace_regex(word_build(&term[1..]));
+                        }
+                        AddMode::Segment => {
+                            let term = self.last_term();
+                            self.replace_regex(segment_build(&term[1..], &self.separators));
+                        }
+                        AddMode::Extension => {
+                            // `.r` paths are not a subset of `.rs` ones
+                            scope = PatternScope::Change;
+                            let term = self.last_term();
+                            self.replace_regex(extension_build(&term[1..], &self.separators));
+                        }
+                        AddMode::StartsWith => self.extend_starts_with(p),
+                        AddMode::EndsWith => {
+                            scope = PatternScope::Change;
+                            self.extend_ends_with(p);
+                        }
+                        AddMode::Bar | AddMode::New => unreachable!(),
                     }
-                    AddMode::New => unreachable!(),
-                },
+                }
                 None => {
                     return scope;
                 }
             }
+            if self.check_complexity() {
+                scope = PatternScope::Change;
+            }
+            self.update_state();
+            separator = " ";
         }
 
         for p in iter {
+            self.text.push_str(separator);
+            self.text.push_str(p);
+            separator = " ";
             if self.bad_regex.is_some() {
                 self.bad_regex.take();
-                self.patterns.pop();
+                self.terms.patterns.pop();
+                self.terms.spans.pop();
+            }
+            self.degraded = false;
+            if !p.is_empty()
+                && p != "|"
+                && self.terms.is_empty()
+                && self.alternatives.iter().any(|g| !g.is_empty())
+                && matches!(scope, PatternScope::Narrow)
+            {
+                // the new group matches paths the earlier groups rejected
+                scope = PatternScope::Widen;
             }
             match p.chars().next() {
+                Some('|') if p.len() == 1 => {
+                    self.alternatives.push(std::mem::take(&mut self.terms));
+                    self.mode = AddMode::Bar;
+                }
                 Some('<') => {
                     self.extend_starts_with(&p[1..]);
                     self.mode = AddMode::StartsWith;
@@ -80,21 +368,118 @@ impl Matcher {
                     self.mode = AddMode::EndsWith;
                 }
                 Some('*') => {
-                    self.add_regex(regex_build(false, &p[1..]));
+                    self.add_regex(regex_term(&p[1..], &self.separators));
+                    self.terms.spans.push(Spans::Whole);
                     self.mode = AddMode::Regex;
                 }
+                Some('\'') => {
+                    self.add_regex(exact_build(false, &p[1..]));
+                    self.terms.spans.push(Spans::Whole);
+                    self.mode = AddMode::Exact;
+                }
+                Some('=') => {
+                    self.add_regex(word_build(&p[1..]));
+                    self.terms.spans.push(Spans::Group);
+                    self.mode = AddMode::Word;
+                }
+                Some('^') => {
+                    self.add_regex(segment_build(&p[1..], &self.separators));
+                    self.terms.spans.push(Spans::Group);
+                    self.mode = AddMode::Segment;
+                }
+                Some(':') => {
+                    self.add_regex(extension_build(&p[1..], &self.separators));
+                    self.terms.spans.push(Spans::Group);
+                    self.mode = AddMode::Extension;
+                }
+                // includes `\<`, `\>`, `\*`, `\=`, `\^`, `\:` and `\|` which fuzzy match the mode
+                // character itself
                 Some(_) => {
-                    self.add_regex(fuzzy_build(false, p));
+                    self.add_regex(fuzzy_build(false, p, &self.separators));
+                    self.terms.spans.push(Spans::Fuzzy(fuzzy_literal(false, p)));
                     self.mode = AddMode::Fuzzy;
                 }
                 None => {
                     self.mode = AddMode::New;
                 }
             }
+            if self.check_complexity() {
+                scope = PatternScope::Change;
+            }
+            if !p.is_empty() {
+                self.states.push(TermState {
+                    kind: term_kind(p),
+                    ok: true,
+                    literal: false,
+                });
+                self.update_state();
+            }
         }
         scope
     }
 
+    /// Record how the last term was compiled.
+    fn update_state(&mut self) {
+        if let Some(state) = self.states.last_mut() {
+            state.ok = self.bad_regex.is_none();
+            state.literal = self.degraded;
+        }
+    }
+
+    /// The text of the term being typed.
+    fn last_term(&self) -> &str {
+        &self.text[self.text.rfind(' ').map_or(0, |i| i + 1)..]
+    }
+
+    /// Switch the last term to literal matching if its regex was too big or there are too many
+    /// terms, leaving a warning for the client. Returns true if it was switched.
+    fn check_complexity(&mut self) -> bool {
+        if self.degraded
+            || !matches!(
+                self.mode,
+                AddMode::Fuzzy
+                    | AddMode::Regex
+                    | AddMode::Exact
+                    | AddMode::Word
+                    | AddMode::Segment
+                    | AddMode::Extension
+            )
+        {
+            return false;
+        }
+        if std::mem::take(&mut self.too_big) {
+            self.warning = Some("query term too complex; matching it literally".to_string());
+        } else if self.terms.patterns.len() > MAX_TERMS {
+            self.warning = Some(format!(
+                "query has over {MAX_TERMS} terms; matching the rest literally"
+            ));
+        } else {
+            return false;
+        }
+        self.literal_fallback();
+        true
+    }
+
+    /// Match the last term as a literal substring.
+    fn literal_fallback(&mut self) {
+        let term = self.last_term();
+        let (esc, re) = exact_build(
+            false,
+            term.strip_prefix(['*', '\'', '=', '^', ':'])
+                .unwrap_or(term),
+        );
+        self.escape = esc;
+        self.bad_regex = None;
+        self.too_big = false;
+        self.degraded = true;
+        let regex = self
+            .compile(&re)
+            .or_else(|_| Regex::new(&re).map(Term::new))
+            .expect("Literal regex should be valid");
+        *self.terms.patterns.last_mut().expect("Last should exist") = regex;
+        *self.terms.spans.last_mut().expect("Last should exist") = Spans::Whole;
+    }
+
     fn rm(&mut self, amount: usize) -> PatternScope {
         let text = std::mem::take(&mut self.text);
         self.reset();
@@ -125,50 +510,104 @@ impl Matcher {
         self.skip_prefix = n;
     }
 
+    fn skip_suffix(&mut self, n: usize) {
+        self.skip_suffix = n;
+    }
+
+    fn basename(&mut self, on: bool) {
+        self.basename = on;
+    }
+
+    fn separators(&mut self, separators: Separators) {
+        self.separators = separators;
+        let text = std::mem::take(&mut self.text);
+        self.reset();
+        self.add(&text);
+    }
+
+    #[cfg(feature = "nfc")]
+    fn nfc(&mut self, on: bool) {
+        self.nfc = on;
+    }
+
     fn reset(&mut self) {
         self.text.truncate(0);
-        self.patterns.truncate(0);
-        self.starts_with = None;
-        self.ends_with = None;
+        self.terms.patterns.truncate(0);
+        self.terms.spans.truncate(0);
+        self.terms.starts_with = None;
+        self.terms.ends_with = None;
+        self.alternatives.clear();
         self.mode = AddMode::New;
+        self.too_big = false;
+        self.degraded = false;
+        self.states.clear();
     }
 
     fn all_matches(&self, haystack: &[u8]) -> bool {
         self.text.is_empty() || {
             let haystack = self.adjust_haystack(haystack);
-            (match &self.starts_with {
-                Some(needle) => haystack.starts_with(needle),
-                None => true,
-            }) && (match &self.ends_with {
-                Some(needle) => haystack.ends_with(needle),
-                None => true,
-            }) && self.patterns.iter().all(|v| v.is_match(haystack))
+            let mut groups = self.groups().peekable();
+            groups.peek().is_none() || groups.any(|g| g.all_matches(&haystack))
         }
     }
 
+    fn match_spans(&self, haystack: &[u8]) -> Option<Vec<Range<usize>>> {
+        if self.text.is_empty() {
+            return Some(vec![]);
+        }
+        let range = self.haystack_range(haystack);
+        let skip = range.start;
+        let adjusted = &haystack[range];
+        #[cfg(feature = "nfc")]
+        let normalized = self.nfc.then(|| nfc::normalize(adjusted)).flatten();
+        #[cfg(feature = "nfc")]
+        let adjusted = normalized.as_ref().map_or(adjusted, |n| &n.text[..]);
+        let mut groups = self.groups().peekable();
+        let mut spans = match groups.peek() {
+            None => vec![],
+            Some(_) => groups.find_map(|g| g.match_spans(adjusted, &self.separators))?,
+        };
+        spans.retain(|r| !r.is_empty());
+        #[cfg(feature = "nfc")]
+        if let Some(normalized) = &normalized {
+            for r in &mut spans {
+                *r = normalized.original(r.clone());
+            }
+            // the bytes of a composed character all come from the same characters
+            spans.dedup();
+        }
+        for r in &mut spans {
+            *r = r.start + skip..r.end + skip;
+        }
+        spans.sort_by_key(|r| (r.start, r.end));
+        Some(spans)
+    }
+
     fn any_matches(&self, haystack: &[u8]) -> bool {
         !self.text.is_empty() && {
             let haystack = self.adjust_haystack(haystack);
-            (match &self.starts_with {
-                Some(needle) => haystack.starts_with(needle),
-                None => false,
-            }) || (match &self.ends_with {
-                Some(needle) => haystack.ends_with(needle),
-                None => false,
-            }) || self.patterns.iter().any(|v| v.is_match(haystack))
+            self.groups().any(|g| g.any_matches(&haystack))
         }
     }
 
+    /// The `|` separated groups of terms which have any.
+    fn groups(&self) -> impl Iterator<Item = &Terms> {
+        self.alternatives
+            .iter()
+            .chain([&self.terms])
+            .filter(|g| !g.is_empty())
+    }
+
     fn extend_starts_with(&mut self, text: &str) {
-        let mut current = self.starts_with.take().unwrap_or_default();
+        let mut current = self.terms.starts_with.take().unwrap_or_default();
         self.unescape_extend(&mut current, text);
-        self.starts_with = Some(current);
+        self.terms.starts_with = Some(current);
     }
 
     fn extend_ends_with(&mut self, text: &str) {
-        let mut current = self.ends_with.take().unwrap_or_default();
+        let mut current = self.terms.ends_with.take().unwrap_or_default();
         self.unescape_extend(&mut current, text);
-        self.ends_with = Some(current);
+        self.terms.ends_with = Some(current);
     }
 
     fn unescape_extend(&mut self, text: &mut Vec<u8>, ext: &str) {
@@ -192,42 +631,115 @@ impl Matcher {
     }
 
     fn extend_regex(&mut self, esc_p: (bool, String)) {
-        let lre = self.patterns.last_mut().expect("Last should exist");
         let last = match self.bad_regex.take() {
             Some(s) => s,
-            None => lre.to_string(),
+            None => self
+                .terms
+                .patterns
+                .last()
+                .expect("Last should exist")
+                .regex
+                .to_string(),
         };
         self.escape = esc_p.0;
         let restr = format!("{last}{}", &esc_p.1);
-        match make_regex(&restr) {
-            Ok(regex) => *lre = regex,
+        match self.compile(&restr) {
+            Ok(regex) => *self.terms.patterns.last_mut().expect("Last should exist") = regex,
+            Err(regex::Error::CompiledTooBig(_)) => self.too_big = true,
             Err(_) => {
                 self.bad_regex = Some(restr);
             }
         }
     }
 
+    /// Recompile the last term, which was typed further.
+    fn replace_regex(&mut self, esc_p: (bool, String)) {
+        self.bad_regex = None;
+        self.escape = esc_p.0;
+        match self.compile(&esc_p.1) {
+            Ok(regex) => *self.terms.patterns.last_mut().expect("Last should exist") = regex,
+            Err(regex::Error::CompiledTooBig(_)) => self.too_big = true,
+            Err(_) => self.bad_regex = Some(esc_p.1),
+        }
+    }
+
     fn add_regex(&mut self, esc_p: (bool, String)) {
         self.escape = esc_p.0;
-        self.patterns.push(match make_regex(&esc_p.1) {
+        let regex = self.compile(&esc_p.1);
+        self.terms.patterns.push(match regex {
             Ok(regex) => regex,
+            Err(regex::Error::CompiledTooBig(_)) => {
+                self.too_big = true;
+                Term::new(Regex::new("").expect("Empty regex should be valid"))
+            }
             Err(_) => {
                 self.bad_regex = Some(esc_p.1);
-                Regex::new("").expect("Empty regex should be valid")
+                Term::new(Regex::new("").expect("Empty regex should be valid"))
             }
         });
     }
 
-    fn adjust_haystack<'a>(&self, haystack: &'a [u8]) -> &'a [u8] {
-        if self.skip_prefix > 0 {
-            &haystack[min(haystack.len(), self.skip_prefix)..]
-        } else {
-            haystack
+    fn compile(&mut self, text: &str) -> Result<Term, regex::Error> {
+        if let Some(term) = self.cache.get(text) {
+            return Ok(term.clone());
+        }
+        let term = Term::new(make_regex(text)?);
+        if self.cache.len() >= REGEX_CACHE_SIZE {
+            self.cache.clear();
+        }
+        self.cache.insert(text.to_string(), term.clone());
+        Ok(term)
+    }
+
+    fn adjust_haystack<'a>(&self, haystack: &'a [u8]) -> Cow<'a, [u8]> {
+        let haystack = &haystack[self.haystack_range(haystack)];
+        #[cfg(feature = "nfc")]
+        if self.nfc
+            && let Some(normalized) = nfc::normalize(haystack)
+        {
+            return Cow::Owned(normalized.text);
         }
+        Cow::Borrowed(haystack)
+    }
+
+    /// The part of `haystack` terms match against.
+    fn haystack_range(&self, haystack: &[u8]) -> Range<usize> {
+        let mut start = min(haystack.len(), self.skip_prefix);
+        let end = haystack.len().saturating_sub(self.skip_suffix).max(start);
+        if self.basename {
+            let path = &haystack[start..end];
+            let path = match path.split_last() {
+                Some((&c, rest)) if self.separators.0.contains(&c) => rest,
+                _ => path,
+            };
+            if let Some(i) = path.iter().rposition(|c| self.separators.0.contains(c)) {
+                start += i + 1;
+            }
+        }
+        start..end
     }
 }
 
-fn fuzzy_build(mut esc: bool, text: &str) -> (bool, String) {
+/// Compare like the regex terms do: ignoring ASCII case unless `needle` has an uppercase letter.
+fn smart_case_eq(text: &[u8], needle: &[u8]) -> bool {
+    if needle.iter().any(u8::is_ascii_uppercase) {
+        text == needle
+    } else {
+        text.eq_ignore_ascii_case(needle)
+    }
+}
+
+fn starts_with(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.len() >= needle.len() && smart_case_eq(&haystack[..needle.len()], needle)
+}
+
+fn ends_with(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.len() >= needle.len()
+        && smart_case_eq(&haystack[haystack.len() - needle.len()..], needle)
+}
+
+fn fuzzy_build(mut esc: bool, text: &str, separators: &Separators) -> (bool, String) {
+    let class = separators.class();
     let text = text
         .chars()
         .filter_map(|mut c| {
@@ -238,10 +750,11 @@ fn fuzzy_build(mut esc: bool, text: &str) -> (bool, String) {
                         c = ' '
                     }
                 }
-                if c == '/' {
-                    Some("/.*".to_owned())
+                let escaped = regex::escape(&c.to_string());
+                if separators.contains(c) {
+                    Some(format!("{escaped}.*"))
                 } else {
-                    Some(format!("{}[^/]*", regex::escape(&c.to_string())))
+                    Some(format!("{escaped}[^{class}]*"))
                 }
             } else {
                 esc = true;
@@ -252,6 +765,278 @@ fn fuzzy_build(mut esc: bool, text: &str) -> (bool, String) {
     (esc, text)
 }
 
+/// A regex matching `text` as a literal substring.
+fn exact_build(mut esc: bool, text: &str) -> (bool, String) {
+    let mut literal = String::new();
+    for c in text.chars() {
+        if esc || c != '\\' {
+            literal.push(if esc && c == 's' { ' ' } else { c });
+            esc = false;
+        } else {
+            esc = true;
+        }
+    }
+    (esc, regex::escape(&literal))
+}
+
+/// A regex matching `text` literally at the start of a word: the start of the path or after a
+/// character that is not a letter or digit.
+fn word_build(text: &str) -> (bool, String) {
+    let (esc, literal) = exact_build(false, text);
+    (esc, format!("(?:^|[^[:alnum:]])({literal})"))
+}
+
+/// Whether `chars` can be `Pattern::separators`: one or more ASCII characters other than space
+/// and `\`, which escapes.
+pub fn valid_separators(chars: &str) -> bool {
+    !chars.is_empty() && chars.bytes().all(|c| c.is_ascii_graphic() && c != b'\\')
+}
+
+/// Split a `length:text` field from the start of `saved`.
+fn counted(saved: &str) -> Option<(&str, &str)> {
+    let (len, rest) = saved.split_once(':')?;
+    let len = len.parse().ok()?;
+    Some((rest.get(..len)?, &rest[len..]))
+}
+
+/// A regex matching `text` literally at the start of a segment: the start of the path or after a
+/// separator.
+fn segment_build(text: &str, separators: &Separators) -> (bool, String) {
+    let (esc, literal) = exact_build(false, text);
+    (esc, format!("(?:^|[{}])({literal})", separators.class()))
+}
+
+/// A regex matching a basename ending in `.` and one of the comma separated extensions in
+/// `text`, or anything if none are given yet.
+fn extension_build(text: &str, separators: &Separators) -> (bool, String) {
+    let extensions: Vec<_> = text
+        .split(',')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| exact_build(false, ext).1)
+        .collect();
+    if extensions.is_empty() {
+        return (false, String::new());
+    }
+    (
+        false,
+        format!(
+            r"[^{}](\.(?:{}))$",
+            separators.class(),
+            extensions.join("|")
+        ),
+    )
+}
+
+/// The characters a fuzzy term matches, with escapes removed.
+fn fuzzy_literal(mut esc: bool, text: &str) -> Vec<u8> {
+    let mut literal = String::new();
+    for c in text.chars() {
+        if esc || c != '\\' {
+            literal.push(if esc && c == 's' { ' ' } else { c });
+            esc = false;
+        } else {
+            esc = true;
+        }
+    }
+    literal.into_bytes()
+}
+
+/// Find where each character of `literal` occurs in `haystack`, from `start` where its fuzzy
+/// term's regex matched. Characters are placed at the start of words where they can be, so
+/// `fbw` highlights the capitals of `FooBarWidget`, otherwise as early as possible.
+fn fuzzy_spans(
+    haystack: &[u8],
+    start: usize,
+    literal: &[u8],
+    separators: &Separators,
+    spans: &mut Vec<Range<usize>>,
+) {
+    let fold = !literal.iter().any(u8::is_ascii_uppercase);
+    let mut chars = vec![];
+    let mut rest = literal;
+    while let Some(&first) = rest.first() {
+        let (c, tail) = rest.split_at(utf8_len(first).min(rest.len()));
+        chars.push(c);
+        rest = tail;
+    }
+    let mut pos = start;
+    // the first character may be in any later segment; one after a separator too
+    let mut cross = true;
+    for (i, c) in chars.iter().enumerate() {
+        let mut found = Occurrences::new(haystack, pos, c, cross, fold, separators);
+        let Some(first) = found.next() else {
+            return;
+        };
+        let at = std::iter::once(first)
+            .chain(found)
+            .find(|&at| {
+                is_word_start(haystack, at, &separators.0)
+                    && fits(
+                        haystack,
+                        at + c.len(),
+                        &chars[i + 1..],
+                        separators.is(c),
+                        fold,
+                        separators,
+                    )
+            })
+            .unwrap_or(first);
+        spans.push(at..at + c.len());
+        pos = at + c.len();
+        cross = separators.is(c);
+    }
+}
+
+/// Whether `chars` occur in order in `haystack` from `pos`, as a fuzzy term would match them.
+fn fits(
+    haystack: &[u8],
+    mut pos: usize,
+    chars: &[&[u8]],
+    mut cross: bool,
+    fold: bool,
+    separators: &Separators,
+) -> bool {
+    for c in chars {
+        let Some(at) = Occurrences::new(haystack, pos, c, cross, fold, separators).next() else {
+            return false;
+        };
+        pos = at + c.len();
+        cross = separators.is(c);
+    }
+    true
+}
+
+/// The positions of a fuzzy term's next character: from `pos` up to the next separator unless
+/// the previous character was a separator and so may be followed by more segments.
+struct Occurrences<'a> {
+    haystack: &'a [u8],
+    pos: usize,
+    c: &'a [u8],
+    cross: bool,
+    fold: bool,
+    separators: &'a Separators,
+}
+impl<'a> Occurrences<'a> {
+    fn new(
+        haystack: &'a [u8],
+        pos: usize,
+        c: &'a [u8],
+        cross: bool,
+        fold: bool,
+        separators: &'a Separators,
+    ) -> Self {
+        Self {
+            haystack,
+            pos,
+            c,
+            cross,
+            fold,
+            separators,
+        }
+    }
+}
+impl Iterator for Occurrences<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.pos + self.c.len() <= self.haystack.len() {
+            let at = self.pos;
+            self.pos += 1;
+            let window = &self.haystack[at..at + self.c.len()];
+            let found = if self.fold {
+                window.eq_ignore_ascii_case(self.c)
+            } else {
+                window == self.c
+            };
+            if found {
+                return Some(at);
+            }
+            if self.separators.0.contains(&self.haystack[at]) && !self.cross {
+                self.pos = self.haystack.len();
+            }
+        }
+        None
+    }
+}
+
+/// Whether `at` starts a word of `text`: the start of the text or of a segment split by
+/// `separators`, after a `_`, `-`, `.` or space, or an uppercase letter after a lowercase one.
+pub fn is_word_start(text: &[u8], at: usize, separators: &[u8]) -> bool {
+    at == 0
+        || matches!(text[at - 1], b'_' | b'-' | b'.' | b' ')
+        || separators.contains(&text[at - 1])
+        || text[at - 1].is_ascii_lowercase() && text.get(at).is_some_and(u8::is_ascii_uppercase)
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first.leading_ones() {
+        2 => 2,
+        3 => 3,
+        4 => 4,
+        _ => 1,
+    }
+}
+
+/// Build the regex term `text`, without its `*`. A term ending in a separator matches within a
+/// single segment, so its `.`s and negated classes don't match separators.
+fn regex_term(text: &str, separators: &Separators) -> (bool, String) {
+    let (esc, re) = regex_build(false, text);
+    if !is_segment_term(text, separators) {
+        return (esc, re);
+    }
+    let (body, sep) = re.split_at(re.len() - 1);
+    let class = separators.class();
+    let sep = regex::escape(sep);
+    (esc, format!("{}[^{class}]*{sep}", in_segment(body, &class)))
+}
+
+/// Whether the regex `term` ends with an unescaped separator.
+fn is_segment_term(term: &str, separators: &Separators) -> bool {
+    term.char_indices().last().is_some_and(|(i, c)| {
+        separators.contains(c)
+            && term[..i].bytes().rev().take_while(|c| *c == b'\\').count() % 2 == 0
+    })
+}
+
+/// Stop `.` and negated classes in the regex `re` from matching the separators in `class`.
+fn in_segment(re: &str, class: &str) -> String {
+    let mut result = String::with_capacity(re.len() + 8);
+    let mut chars = re.chars().peekable();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                result.push(c);
+                if let Some(n) = chars.next() {
+                    result.push(n);
+                }
+            }
+            '[' if !in_class => {
+                in_class = true;
+                result.push(c);
+                if chars.next_if_eq(&'^').is_some() {
+                    result.push('^');
+                    result.push_str(class);
+                }
+                if let Some(n) = chars.next_if_eq(&']') {
+                    result.push(n);
+                }
+            }
+            ']' if in_class => {
+                in_class = false;
+                result.push(c);
+            }
+            '.' if !in_class => {
+                result.push_str("[^");
+                result.push_str(class);
+                result.push(']');
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
 fn regex_build(esc: bool, text: &str) -> (bool, String) {
     let lesc = text.ends_with('\\');
     let text = if lesc { &text[0..text.len() - 1] } else { text };
@@ -265,6 +1050,27 @@ fn regex_build(esc: bool, text: &str) -> (bool, String) {
     )
 }
 
+/// The bytes the regex `source` matches if it is a literal, such as `exact_build` makes, which
+/// `make_regex` would compile case sensitively.
+fn plain_literal(source: &str) -> Option<Vec<u8>> {
+    let mut literal = vec![];
+    let mut buf = [0; 4];
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            // `regex::escape` only escapes punctuation; others are classes like `\d`
+            '\\' => chars.next().filter(char::is_ascii_punctuation)?,
+            '.' | '^' | '$' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' => {
+                return None;
+            }
+            c => c,
+        };
+        literal.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+    (source != source.to_lowercase() || !literal.iter().any(u8::is_ascii_alphabetic))
+        .then_some(literal)
+}
+
 fn make_regex(text: &str) -> Result<Regex, regex::Error> {
     RegexBuilder::new(text)
         .case_insensitive(text == text.to_lowercase())
@@ -274,6 +1080,77 @@ fn make_regex(text: &str) -> Result<Regex, regex::Error> {
         .build()
 }
 
+/// How a term of the query is matched, going by its first character.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TermKind {
+    Fuzzy,
+    /// `*`
+    Regex,
+    /// `'`
+    Exact,
+    /// `=`
+    Word,
+    /// `^`
+    Segment,
+    /// `:`
+    Extension,
+    /// `<`
+    StartsWith,
+    /// `>`
+    EndsWith,
+    /// A `|` between groups of terms.
+    Or,
+}
+impl TermKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fuzzy => "fuzzy",
+            Self::Regex => "regex",
+            Self::Exact => "exact",
+            Self::Word => "word",
+            Self::Segment => "segment",
+            Self::Extension => "extension",
+            Self::StartsWith => "starts-with",
+            Self::EndsWith => "ends-with",
+            Self::Or => "or",
+        }
+    }
+}
+
+fn term_kind(term: &str) -> TermKind {
+    match term.chars().next() {
+        Some('|') if term.len() == 1 => TermKind::Or,
+        Some('*') => TermKind::Regex,
+        Some('\'') => TermKind::Exact,
+        Some('=') => TermKind::Word,
+        Some('^') => TermKind::Segment,
+        Some(':') => TermKind::Extension,
+        Some('<') => TermKind::StartsWith,
+        Some('>') => TermKind::EndsWith,
+        _ => TermKind::Fuzzy,
+    }
+}
+
+/// A term of the query as `Pattern::terms` describes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermInfo {
+    pub kind: TermKind,
+    /// The term as typed, including its mode character.
+    pub text: String,
+    /// False for a regex that does not compile, which is left out of matching.
+    pub ok: bool,
+    /// Matched as a literal substring, as it was too complex or the query has too many terms.
+    pub literal: bool,
+}
+
+/// How a term of the query was compiled.
+#[derive(Debug, Clone, Copy)]
+struct TermState {
+    kind: TermKind,
+    ok: bool,
+    literal: bool,
+}
+
 #[derive(Default)]
 struct PatternInner {
     matcher: RwLock<Matcher>,
@@ -289,10 +1166,12 @@ impl std::fmt::Debug for Pattern {
         let content = self.read_matcher();
         f.debug_struct("Pattern")
             .field("text", &content.text)
-            .field("<", &content.starts_with)
-            .field(">", &content.ends_with)
+            .field("<", &content.terms.starts_with)
+            .field(">", &content.terms.ends_with)
             .field("skip", &content.skip_prefix)
-            .field("patterns", &content.patterns)
+            .field("skip_suffix", &content.skip_suffix)
+            .field("basename", &content.basename)
+            .field("patterns", &content.terms.patterns)
             .finish()
     }
 }
@@ -305,6 +1184,35 @@ impl Pattern {
         self.read_matcher().any_matches(line)
     }
 
+    /// The byte ranges of `line` matched by each term, in order, or `None` if it doesn't match;
+    /// for highlighting results. Fuzzy terms give a range per character.
+    pub fn match_spans(&self, line: &[u8]) -> Option<Vec<Range<usize>>> {
+        self.read_matcher().match_spans(line)
+    }
+
+    /// How `FuzzyScorer` scores `line`, or `None` if it doesn't match; the same as
+    /// `all_matches` then `match_spans` but reading the query once.
+    pub fn matches_with_score(&self, line: &[u8]) -> Option<Score> {
+        let matcher = self.read_matcher();
+        if !matcher.all_matches(line) {
+            return None;
+        }
+        let spans = matcher.match_spans(line)?;
+        Some(scorer::score_spans(line, spans, &matcher.separators.0))
+    }
+
+    /// How `FuzzyScorer` scores `line`, or `None` if the spans of its terms can't be found.
+    pub fn spans_score(&self, line: &[u8]) -> Option<Score> {
+        let matcher = self.read_matcher();
+        let spans = matcher.match_spans(line)?;
+        Some(scorer::score_spans(line, spans, &matcher.separators.0))
+    }
+
+    /// Why the query was last made to match literally, if not already taken.
+    pub fn take_warning(&self) -> Option<String> {
+        self.write_matcher().warning.take()
+    }
+
     pub fn version(&self) -> usize {
         self.inner
             .version
@@ -314,19 +1222,41 @@ impl Pattern {
     #[inline(always)]
     pub fn add(&self, text: &str) -> PatternScope {
         self.inc_version();
-        self.write_matcher().add(text)
+        self.write_matcher().edit(|m| m.add(text))
     }
 
     #[inline(always)]
     pub fn rm(&self, amount: usize) -> PatternScope {
         self.inc_version();
-        self.write_matcher().rm(amount)
+        self.write_matcher().edit(|m| m.rm(amount))
     }
 
     #[inline(always)]
     pub fn set(&self, start: usize, text: &str) -> PatternScope {
         self.inc_version();
-        self.write_matcher().set(start, text)
+        self.write_matcher().edit(|m| m.set(start, text))
+    }
+
+    /// Go back to the query text before the last `add`, `rm`, `set` or `restore`. Returns `None`
+    /// if there is nothing to undo.
+    pub fn undo(&self) -> Option<PatternScope> {
+        let mut matcher = self.write_matcher();
+        if matcher.history.undo.is_empty() {
+            return None;
+        }
+        self.inc_version();
+        matcher.undo()
+    }
+
+    /// Undo the last `undo`, unless the query was edited since. Returns `None` if there is
+    /// nothing to redo.
+    pub fn redo(&self) -> Option<PatternScope> {
+        let mut matcher = self.write_matcher();
+        if matcher.history.redo.is_empty() {
+            return None;
+        }
+        self.inc_version();
+        matcher.redo()
     }
 
     #[inline(always)]
@@ -334,9 +1264,100 @@ impl Pattern {
         self.write_matcher().skip_prefix(n);
     }
 
+    #[inline(always)]
+    pub fn skip_suffix(&self, n: usize) {
+        self.write_matcher().skip_suffix(n);
+    }
+
+    /// The bytes skipped at the start and end of each line.
+    pub fn skips(&self) -> (usize, usize) {
+        let matcher = self.read_matcher();
+        (matcher.skip_prefix, matcher.skip_suffix)
+    }
+
+    #[inline(always)]
+    pub fn basename(&self, on: bool) {
+        self.write_matcher().basename(on);
+    }
+
+    /// Split candidates into segments at any of `chars` instead of `/`, for fuzzy gaps, regex
+    /// terms ending in a separator and basename mode. The query is rebuilt. Returns false,
+    /// leaving them unchanged, unless `valid_separators(chars)`.
+    pub fn separators(&self, chars: &str) -> bool {
+        let Some(separators) = Separators::new(chars) else {
+            return false;
+        };
+        self.write_matcher().separators(separators);
+        true
+    }
+
+    /// Compose haystacks to NFC, so composed queries match decomposed paths.
+    #[cfg(feature = "nfc")]
+    #[inline(always)]
+    pub fn nfc(&self, on: bool) {
+        self.write_matcher().nfc(on);
+    }
+
     #[inline(always)]
     pub fn reset(&self) {
-        self.write_matcher().reset();
+        let mut matcher = self.write_matcher();
+        matcher.reset();
+        matcher.history.clear();
+    }
+
+    /// The query and how it is applied as
+    /// `skip_prefix,skip_suffix,basename,nfc,length:separators,length:text`, which `restore` reads
+    /// back.
+    pub fn save(&self) -> String {
+        let matcher = self.read_matcher();
+        #[cfg(feature = "nfc")]
+        let nfc = matcher.nfc;
+        #[cfg(not(feature = "nfc"))]
+        let nfc = false;
+        let separators = String::from_utf8_lossy(&matcher.separators.0);
+        format!(
+            "{},{},{},{},{}:{separators},{}:{}",
+            matcher.skip_prefix,
+            matcher.skip_suffix,
+            matcher.basename as u8,
+            nfc as u8,
+            separators.len(),
+            matcher.text.len(),
+            matcher.text
+        )
+    }
+
+    /// Replace the query and how it is applied with those `save`d at the start of `saved`.
+    /// Returns the rest of `saved`, or `None`, leaving this unchanged, if it is not valid.
+    pub fn restore<'a>(&self, saved: &'a str) -> Option<&'a str> {
+        let flag = |field: Option<&str>| match field? {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        };
+        let mut fields = saved.splitn(5, ',');
+        let skip_prefix = fields.next()?.parse().ok()?;
+        let skip_suffix = fields.next()?.parse().ok()?;
+        let basename = flag(fields.next())?;
+        let nfc = flag(fields.next())?;
+        let (separators, rest) = counted(fields.next()?)?;
+        let separators = Separators::new(separators)?;
+        let (text, rest) = counted(rest.strip_prefix(',')?)?;
+
+        self.inc_version();
+        self.write_matcher().edit(|m| {
+            m.reset();
+            m.skip_prefix(skip_prefix);
+            m.skip_suffix(skip_suffix);
+            m.basename(basename);
+            m.separators = separators;
+            #[cfg(feature = "nfc")]
+            m.nfc(nfc);
+            #[cfg(not(feature = "nfc"))]
+            let _ = nfc;
+            m.add(text)
+        });
+        Some(rest)
     }
 
     #[inline(always)]
@@ -344,6 +1365,23 @@ impl Pattern {
         self.read_matcher().text.clone()
     }
 
+    /// The terms of the query in order, for showing how they were compiled.
+    pub fn terms(&self) -> Vec<TermInfo> {
+        let matcher = self.read_matcher();
+        matcher
+            .text
+            .split(' ')
+            .filter(|p| !p.is_empty())
+            .zip(&matcher.states)
+            .map(|(p, state)| TermInfo {
+                kind: state.kind,
+                text: p.to_string(),
+                ok: state.ok,
+                literal: state.literal,
+            })
+            .collect()
+    }
+
     #[inline(always)]
     fn write_matcher(&self) -> RwLockWriteGuard<'_, Matcher> {
         self.inner.matcher.write().expect(crate::LOCK_SHOULD_BE_OK)
@@ -362,6 +1400,159 @@ impl Pattern {
     }
 }
 
+/// Builds a `Pattern` from typed terms rather than query syntax, escaping each term so its text
+/// is matched as given.
+///
+/// ```
+/// use koru_find::pattern::PatternBuilder;
+///
+/// let pattern = PatternBuilder::new()
+///     .fuzzy("main")
+///     .starts_with("src/")
+///     .or()
+///     .regex("^lib.*rs$")
+///     .unwrap()
+///     .build();
+/// assert!(pattern.all_matches(b"src/bin/main.rs"));
+/// assert!(pattern.all_matches(b"lib/mod.rs"));
+/// assert!(!pattern.all_matches(b"tests/main.rs"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PatternBuilder {
+    query: String,
+    skip_prefix: usize,
+    skip_suffix: usize,
+    basename: bool,
+}
+impl PatternBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match these characters in order with anything between them.
+    pub fn fuzzy(self, text: &str) -> Self {
+        self.term("", text)
+    }
+
+    /// Match `text` as a substring.
+    pub fn exact(self, text: &str) -> Self {
+        self.term("'", text)
+    }
+
+    /// Match `text` at the start of a word.
+    pub fn word(self, text: &str) -> Self {
+        self.term("=", text)
+    }
+
+    /// Match `text` at the start of a path segment.
+    pub fn segment(self, text: &str) -> Self {
+        self.term("^", text)
+    }
+
+    /// Match the regex `re`, or fail if it is not valid.
+    pub fn regex(mut self, re: &str) -> Result<Self, regex::Error> {
+        let mut re = re.replace(' ', "\\x20");
+        make_regex(&re)?;
+        // a query term ending in `/` stays in one segment; this regex should not
+        if is_segment_term(&re, &Separators::default()) {
+            re.pop();
+            re.push_str("[/]");
+        }
+        if !re.is_empty() {
+            self.separate();
+            self.query.push('*');
+            self.query.push_str(&re);
+        }
+        Ok(self)
+    }
+
+    /// Match paths ending in one of `extensions`, which can't contain `,`.
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        if !extensions.is_empty() {
+            self.separate();
+            self.query.push(':');
+            for (i, ext) in extensions.iter().enumerate() {
+                if i > 0 {
+                    self.query.push(',');
+                }
+                escape_into(&mut self.query, ext);
+            }
+        }
+        self
+    }
+
+    /// Match paths starting with `text`, following any earlier `starts_with` in this group.
+    pub fn starts_with(self, text: &str) -> Self {
+        self.term("<", text)
+    }
+
+    /// Match paths ending with `text`, following any earlier `ends_with` in this group.
+    pub fn ends_with(self, text: &str) -> Self {
+        self.term(">", text)
+    }
+
+    /// Start another group of terms; a path matching all the terms of any group matches.
+    pub fn or(mut self) -> Self {
+        self.separate();
+        self.query.push('|');
+        self
+    }
+
+    pub fn skip_prefix(mut self, n: usize) -> Self {
+        self.skip_prefix = n;
+        self
+    }
+
+    pub fn skip_suffix(mut self, n: usize) -> Self {
+        self.skip_suffix = n;
+        self
+    }
+
+    /// Match terms against the final path component only.
+    pub fn basename(mut self, on: bool) -> Self {
+        self.basename = on;
+        self
+    }
+
+    pub fn build(self) -> Pattern {
+        let pattern = Pattern::default();
+        pattern.skip_prefix(self.skip_prefix);
+        pattern.skip_suffix(self.skip_suffix);
+        pattern.basename(self.basename);
+        pattern.add(&self.query);
+        pattern
+    }
+
+    fn term(mut self, mode: &str, text: &str) -> Self {
+        if !text.is_empty() {
+            self.separate();
+            self.query.push_str(mode);
+            if mode.is_empty() && text.starts_with(['<', '>', '*', '\'', '=', '^', ':', '|']) {
+                self.query.push('\\');
+            }
+            escape_into(&mut self.query, text);
+        }
+        self
+    }
+
+    fn separate(&mut self) {
+        if !self.query.is_empty() {
+            self.query.push(' ');
+        }
+    }
+}
+
+/// Append `text` to `query` with `\` and spaces escaped.
+fn escape_into(query: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            ' ' => query.push_str("\\s"),
+            '\\' => query.push_str("\\\\"),
+            c => query.push(c),
+        }
+    }
+}
+
 #[cfg(test)]
 #[path = "pattern_test.rs"]
 mod test;
@@ -1,6 +1,7 @@
 use pretty_assertions::{assert_eq, assert_matches};
 
 use super::*;
+use crate::scorer::Scorer;
 
 #[test]
 fn empty_pattern() {
@@ -61,6 +62,115 @@ fn skip_prefix() {
     assert!(pattern.any_matches(b"ahello"));
 }
 
+#[test]
+fn skip_suffix() {
+    let pattern = Pattern::default();
+    pattern.add(">rs");
+    pattern.skip_suffix(4);
+    assert!(!pattern.all_matches(b"main.rs"));
+    assert!(pattern.all_matches(b"main.rs:12:"));
+    assert!(pattern.any_matches(b"main.rs:12:"));
+
+    pattern.reset();
+    pattern.add("ma");
+    pattern.skip_prefix(2);
+    assert_eq!(
+        pattern.match_spans(b"./main.rs  [ma]"),
+        Some(vec![2..3, 3..4])
+    );
+    pattern.skip_suffix(6);
+    assert!(!pattern.all_matches(b"./mxn.rs  [ma]"));
+    // more skipped than there is
+    assert!(!pattern.all_matches(b"ma"));
+}
+
+#[test]
+fn basename() {
+    let pattern = Pattern::default();
+    pattern.add("src");
+    pattern.basename(true);
+    assert!(!pattern.all_matches(b"src/main.rs"));
+    assert!(pattern.all_matches(b"a/src"));
+    assert!(pattern.all_matches(b"a/src/"));
+    assert!(pattern.any_matches(b"src"));
+    assert!(!pattern.any_matches(b"src/main.rs"));
+    assert_eq!(pattern.match_spans(b"x/src/"), Some(vec![2..3, 3..4, 4..5]));
+
+    pattern.reset();
+    pattern.add("<ma");
+    assert!(pattern.all_matches(b"a/main.rs"));
+    pattern.skip_prefix(2);
+    pattern.skip_suffix(4);
+    assert!(pattern.all_matches(b"./ma [x]"));
+    let spans = pattern.match_spans(b"./a/ma [x]").unwrap();
+    assert_eq!((spans.len(), &spans[0]), (1, &(4..6)));
+
+    pattern.basename(false);
+    assert!(!pattern.all_matches(b"./a/ma [x]"));
+}
+
+#[cfg(feature = "nfc")]
+#[test]
+fn nfc() {
+    let pattern = Pattern::default();
+    pattern.add("caf\u{e9}");
+    let decomposed = "x/cafe\u{301}.txt".as_bytes();
+    assert!(!pattern.all_matches(decomposed));
+
+    pattern.nfc(true);
+    assert!(pattern.all_matches(decomposed));
+    assert!(pattern.any_matches(decomposed));
+    assert!(pattern.all_matches("caf\u{e9}".as_bytes()));
+    assert_eq!(
+        pattern.match_spans(decomposed),
+        Some(vec![2..3, 3..4, 4..5, 5..8])
+    );
+
+    let saved = pattern.save();
+    assert_eq!(saved, "0,0,0,1,1:/,5:caf\u{e9}");
+    pattern.nfc(false);
+    assert!(!pattern.all_matches(decomposed));
+
+    assert_eq!(pattern.restore(&saved), Some(""));
+    assert!(pattern.all_matches(decomposed));
+}
+
+#[test]
+fn save_restore() {
+    let pattern = Pattern::default();
+    pattern.add("<src :rs,toml");
+    pattern.skip_prefix(2);
+    pattern.basename(true);
+    pattern.separators("/,");
+    let saved = pattern.save();
+    assert_eq!(saved, "2,0,1,0,2:,/,13:<src :rs,toml");
+
+    let restored = Pattern::default();
+    restored.add("x");
+    assert_eq!(restored.restore(&(saved + "rest")), Some("rest"));
+    assert_eq!(restored.version(), 2);
+    assert_eq!(restored.clone_text(), "<src :rs,toml");
+    assert!(restored.all_matches(b"./a/src.rs"));
+    assert!(!restored.all_matches(b"./src/a.rs"));
+    assert!(restored.all_matches(b"./a,src.rs"));
+
+    for bad in [
+        "",
+        "1,0,2,0,1:/,0:",
+        "0,0,0,2,1:/,0:",
+        "0,0,0,0,1:/,5:abc",
+        "0,0,0,0,1:/0:",
+        "0,0,0,0,3:/,0:",
+        "0,0,0,0,0:,0:",
+        "0,0,0,1:/,0:",
+        "0,0,0,4:x",
+        "x,0,0,0,1:/,0:",
+    ] {
+        assert_eq!(restored.restore(bad), None, "{bad:?}");
+    }
+    assert_eq!(restored.clone_text(), "<src :rs,toml");
+}
+
 #[test]
 fn trailing_escape_regex() {
     let pattern = Pattern::default();
@@ -137,7 +247,7 @@ fn starts_with() {
     pattern.add(r#"a\s\\"#);
 
     assert_eq!(
-        String::from_utf8_lossy(&pattern.read_matcher().starts_with.clone().unwrap()),
+        String::from_utf8_lossy(&pattern.read_matcher().terms.starts_with.clone().unwrap()),
         r#" \a \"#
     );
 }
@@ -150,6 +260,21 @@ fn starts_with_and() {
     assert!(pattern.all_matches(b"Cargo.toml"));
 }
 
+#[test]
+fn smart_case_anchors() {
+    let pattern = Pattern::default();
+    pattern.add("<cargo >.TOML");
+    assert!(!pattern.all_matches(b"Cargo.toml"));
+    assert!(pattern.all_matches(b"Cargo.TOML"));
+    assert_eq!(pattern.match_spans(b"cargo.TOML"), Some(vec![0..5, 5..10]));
+
+    pattern.reset();
+    pattern.add("<Cargo");
+    assert!(pattern.all_matches(b"Cargo.toml"));
+    assert!(!pattern.all_matches(b"cargo.toml"));
+    assert!(pattern.any_matches(b"Cargo"));
+}
+
 #[test]
 fn ends_with_and() {
     let pattern = Pattern::default();
@@ -239,6 +364,56 @@ fn rm() {
     assert!(!pattern.all_matches(b"hhx"));
 }
 
+#[test]
+fn regex_cache() {
+    let pattern = Pattern::default();
+    pattern.add("ab");
+    pattern.add("c *x+");
+    let cached = pattern.read_matcher().cache.len();
+
+    pattern.rm(3);
+    pattern.add("*x+");
+    pattern.set(0, "abc *x+");
+    assert_eq!(pattern.read_matcher().cache.len(), cached);
+    assert!(pattern.all_matches(b"a_b_cxx"));
+    assert!(!pattern.all_matches(b"a_b_c"));
+
+    pattern.rm(5);
+    pattern.add("d");
+    assert_eq!(pattern.read_matcher().cache.len(), cached + 1);
+    assert!(pattern.all_matches(b"abcd"));
+}
+
+#[test]
+fn literal_terms() {
+    assert_eq!(
+        plain_literal(&regex::escape("Main.rs")),
+        Some(b"Main.rs".to_vec())
+    );
+    assert_eq!(plain_literal(&regex::escape("a/b c")), None);
+    assert_eq!(plain_literal("1+2"), None);
+    assert_eq!(plain_literal(r"\d"), None);
+    assert_eq!(plain_literal(r"-\+/"), Some(b"-+/".to_vec()));
+
+    let pattern = Pattern::default();
+    pattern.add("'Mai");
+    pattern.add("n.rs 'x 'Z.\\s");
+    {
+        let matcher = pattern.read_matcher();
+        let literals: Vec<_> = matcher
+            .terms
+            .patterns
+            .iter()
+            .map(|t| t.literal.is_some())
+            .collect();
+        assert_eq!(literals, [true, false, true]);
+    }
+    assert!(pattern.all_matches(b"src/Main.rs/X/Z. "));
+    assert!(!pattern.all_matches(b"src/main.rs/X/Z. "));
+    assert!(!pattern.all_matches(b"src/Main.rs/X/z. "));
+    assert!(pattern.any_matches(b"Main.rs"));
+}
+
 #[test]
 fn and_search() {
     let pattern = Pattern::default();
@@ -274,10 +449,521 @@ fn regex_chars() {
 
 #[test]
 fn fuzzy_build() {
-    let (esc, p) = super::fuzzy_build(false, "a\\\\\\c([.*]\\s)");
+    let (esc, p) = super::fuzzy_build(false, "a\\\\\\c([.*]\\s)", &Separators::default());
     assert_eq!(
         &p,
         &"a[^/]*\\\\[^/]*c[^/]*\\([^/]*\\[[^/]*\\.[^/]*\\*[^/]*\\][^/]* [^/]*\\)[^/]*"
     );
     assert!(!esc);
 }
+
+#[test]
+fn match_spans() {
+    let pattern = Pattern::default();
+    assert_eq!(pattern.match_spans(b"abc"), Some(vec![]));
+
+    pattern.add("ac *b.");
+    assert_eq!(pattern.match_spans(b"xbd"), None);
+    assert_eq!(pattern.match_spans(b"abcabd"), Some(vec![0..1, 1..3, 2..3]));
+
+    pattern.reset();
+    pattern.add("<a/ b\\s >z");
+    assert_eq!(
+        pattern.match_spans(b"a/b z"),
+        Some(vec![0..2, 2..3, 3..4, 4..5])
+    );
+
+    pattern.reset();
+    pattern.skip_prefix(2);
+    pattern.add("xé");
+    assert_eq!(
+        pattern.match_spans(b"\xc3\xa9x\xc3\xa9"),
+        Some(vec![2..3, 3..5])
+    );
+}
+
+#[test]
+fn undo_redo() {
+    let pattern = Pattern::default();
+    let version = pattern.version();
+    assert!(pattern.undo().is_none());
+    assert!(pattern.redo().is_none());
+    // nothing changed so nothing need be matched again
+    assert_eq!(pattern.version(), version);
+    pattern.add("ab");
+    pattern.add("c");
+    pattern.rm(2);
+    pattern.set(1, "xy");
+    pattern.set(0, "axy");
+    assert_eq!(pattern.clone_text(), "axy");
+
+    assert_matches!(pattern.undo(), Some(PatternScope::Change));
+    assert_eq!(pattern.clone_text(), "a");
+    assert_matches!(pattern.undo(), Some(PatternScope::Narrow));
+    assert_eq!(pattern.clone_text(), "abc");
+    assert!(pattern.all_matches(b"abc"));
+    pattern.undo();
+    assert_eq!(pattern.clone_text(), "ab");
+
+    assert_matches!(pattern.redo(), Some(PatternScope::Narrow));
+    assert_eq!(pattern.clone_text(), "abc");
+    pattern.redo();
+    assert_eq!(pattern.clone_text(), "a");
+
+    // an edit forgets what was undone
+    pattern.add("z");
+    assert!(pattern.redo().is_none());
+    pattern.undo();
+    assert_eq!(pattern.clone_text(), "a");
+
+    for _ in 0..UNDO_SIZE + 5 {
+        pattern.add("q");
+    }
+    let mut undone = 0;
+    while pattern.undo().is_some() {
+        undone += 1;
+    }
+    assert_eq!(undone, UNDO_SIZE);
+
+    pattern.reset();
+    assert!(pattern.undo().is_none());
+}
+
+#[test]
+fn terms() {
+    let pattern = Pattern::default();
+    assert_eq!(pattern.terms(), vec![]);
+    pattern.add("ab  *x[ | =w :rs 'e >z \\<q");
+    let terms: Vec<_> = pattern
+        .terms()
+        .into_iter()
+        .map(|t| (t.kind, t.text, t.ok, t.literal))
+        .collect();
+    assert_eq!(
+        terms,
+        vec![
+            (TermKind::Fuzzy, "ab".to_string(), true, false),
+            (TermKind::Regex, "*x[".to_string(), false, false),
+            (TermKind::Or, "|".to_string(), true, false),
+            (TermKind::Word, "=w".to_string(), true, false),
+            (TermKind::Extension, ":rs".to_string(), true, false),
+            (TermKind::Exact, "'e".to_string(), true, false),
+            (TermKind::EndsWith, ">z".to_string(), true, false),
+            (TermKind::Fuzzy, "\\<q".to_string(), true, false),
+        ]
+    );
+
+    // typed a character at a time
+    pattern.reset();
+    pattern.add("*x");
+    pattern.add("[");
+    assert!(!pattern.terms()[0].ok);
+    pattern.add("a]");
+    assert!(pattern.terms()[0].ok);
+    pattern.add(" *{");
+    let ok: Vec<_> = pattern.terms().iter().map(|t| t.ok).collect();
+    assert_eq!(ok, [true, false]);
+}
+
+#[test]
+fn separators() {
+    let pattern = Pattern::default();
+    pattern.add("fb");
+    assert!(pattern.all_matches(b"foo::bar"));
+    assert!(pattern.separators("::"));
+    assert!(!pattern.all_matches(b"foo::bar"));
+    assert!(pattern.all_matches(b"fb/x::y"));
+
+    pattern.reset();
+    pattern.add("f:b");
+    assert!(pattern.all_matches(b"foo::x::bar"));
+    assert!(!pattern.all_matches(b"foo/bar"));
+    assert_eq!(
+        pattern.match_spans(b"foo::bar"),
+        Some(vec![0..1, 4..5, 5..6])
+    );
+
+    // a character is placed at the start of a segment where it can be
+    pattern.reset();
+    pattern.add("ba");
+    assert_eq!(pattern.match_spans(b"ab::bar"), Some(vec![4..5, 5..6]));
+
+    pattern.reset();
+    pattern.add("*o.:");
+    assert!(pattern.all_matches(b"foo::bar"));
+    assert!(!pattern.all_matches(b"o:x:"));
+
+    pattern.reset();
+    pattern.add("bar");
+    pattern.basename(true);
+    assert!(pattern.all_matches(b"foo::bar"));
+    assert!(!pattern.all_matches(b"bar::foo"));
+
+    pattern.basename(false);
+    pattern.reset();
+    pattern.add("f:b");
+    assert!(pattern.all_matches(b"foo/x::bar"));
+    // the query is rebuilt
+    assert!(pattern.separators("/"));
+    assert!(!pattern.all_matches(b"foo/x::bar"));
+    assert!(pattern.all_matches(b"f:b"));
+
+    for bad in ["", "/\\", "/é", "/ "] {
+        assert!(!pattern.separators(bad), "{bad:?}");
+    }
+    assert!(!pattern.all_matches(b"foo/x::bar"));
+
+    // a regex character as a separator is matched literally
+    assert!(pattern.separators("."));
+    pattern.reset();
+    pattern.add("*o.");
+    assert!(pattern.all_matches(b"x.foo.y"));
+    assert!(!pattern.all_matches(b"x.foo"));
+    assert!(!pattern.all_matches(b"xfooy"));
+}
+
+#[test]
+fn escaped_mode_chars() {
+    let pattern = Pattern::default();
+    pattern.add("\\>>=");
+    assert!(pattern.all_matches(b"src/>>=.hs"));
+    assert!(!pattern.all_matches(b"src/=.hs"));
+
+    pattern.reset();
+    pattern.add("\\*scratch*");
+    assert!(pattern.all_matches(b"*scratch*"));
+    assert!(!pattern.all_matches(b"scratch"));
+
+    pattern.reset();
+    pattern.add("\\<a");
+    assert!(pattern.all_matches(b"x<a"));
+    assert!(!pattern.all_matches(b"a"));
+
+    // typed a character at a time
+    pattern.reset();
+    pattern.add("\\");
+    pattern.add(">");
+    pattern.add(">=");
+    assert!(pattern.all_matches(b"src/>>=.hs"));
+    assert!(!pattern.all_matches(b"src/x>=.hs"));
+    assert_eq!(pattern.match_spans(b">>="), Some(vec![0..1, 1..2, 2..3]));
+}
+
+#[test]
+fn segment_terms() {
+    let separators = Separators::default();
+    assert_eq!(
+        in_segment("a.b[^x]c[.]\\.[]]", "/"),
+        "a[^/]b[^/x]c[.]\\.[]]"
+    );
+    assert!(is_segment_term("*ab/", &separators));
+    assert!(!is_segment_term("*ab\\/", &separators));
+    assert!(is_segment_term("*ab\\\\/", &separators));
+
+    let pattern = Pattern::default();
+    pattern.add("*s.c/");
+    assert!(pattern.all_matches(b"lib/src/main.rs"));
+    assert!(pattern.all_matches(b"lib/sxc/main.rs"));
+    assert!(!pattern.all_matches(b"s/c/main.rs"));
+    assert!(!pattern.all_matches(b"lib/src"));
+
+    // typed a character at a time
+    pattern.reset();
+    assert_matches!(pattern.add("*s."), PatternScope::Narrow);
+    assert_matches!(pattern.add("c/"), PatternScope::Narrow);
+    assert!(!pattern.all_matches(b"s/c/main.rs"));
+    assert_matches!(pattern.add("m"), PatternScope::Change);
+    assert!(pattern.all_matches(b"s/c/main.rs"));
+
+    // a continued term followed by more in one add
+    pattern.reset();
+    pattern.add("*a");
+    pattern.add("bcd e");
+    assert!(pattern.all_matches(b"abcd/e"));
+    assert!(!pattern.all_matches(b"abc/e"));
+
+    // the other kinds of term never cross a separator before their last character either
+    for term in ["src/", "'src/", "=src/", "^src/", "<src/", "*src/"] {
+        pattern.reset();
+        pattern.add(term);
+        assert!(pattern.all_matches(b"src/main.rs"), "{term}");
+        assert!(!pattern.all_matches(b"s/r/c/main.rs"), "{term}");
+        assert!(!pattern.all_matches(b"s/rc/main.rs"), "{term}");
+        assert!(!pattern.all_matches(b"lib/src"), "{term}");
+    }
+    pattern.reset();
+    pattern.add("sc/");
+    assert!(pattern.all_matches(b"lib/src/main.rs"));
+    assert!(!pattern.all_matches(b"s/c/main.rs"));
+}
+
+#[test]
+fn exact() {
+    let pattern = Pattern::default();
+    pattern.add("'main.rs");
+    assert!(pattern.all_matches(b"src/main.rs"));
+    assert!(!pattern.all_matches(b"src/main_rs"));
+    assert!(!pattern.all_matches(b"src/maintain.rs"));
+    let spans = pattern.match_spans(b"src/main.rs").unwrap();
+    assert_eq!((spans.len(), &spans[0]), (1, &(4..11)));
+
+    // typed a character at a time, with an escaped space
+    pattern.reset();
+    pattern.add("'a\\");
+    pattern.add("sb");
+    assert!(pattern.all_matches(b"x/A b"));
+    assert!(!pattern.all_matches(b"x/a_b"));
+
+    pattern.reset();
+    pattern.add("\\'quoted");
+    assert!(pattern.all_matches(b"x/'quo_ted"));
+}
+
+#[test]
+fn fuzzy_word_starts() {
+    let pattern = Pattern::default();
+    pattern.add("fbw");
+    assert_eq!(
+        pattern.match_spans(b"src/FooBarWidget.rs"),
+        Some(vec![4..5, 7..8, 10..11])
+    );
+    pattern.set(0, "ob");
+    assert_eq!(pattern.match_spans(b"foo_obj_bar"), Some(vec![4..5, 8..9]));
+    // a later word start is only taken if the rest still fits after it
+    pattern.set(0, "ab");
+    assert_eq!(pattern.match_spans(b"xab_a"), Some(vec![1..2, 2..3]));
+    pattern.set(0, "a/b");
+    assert_eq!(pattern.match_spans(b"xa/y/b"), Some(vec![1..2, 2..3, 5..6]));
+
+    assert!(is_word_start(b"fooBar", 3, b"/"));
+    assert!(!is_word_start(b"FOOBAR", 3, b"/"));
+    assert!(is_word_start(b"a/b", 2, b"/"));
+    assert!(!is_word_start(b"a:b", 2, b"/"));
+    assert!(is_word_start(b"a::b", 3, b":"));
+}
+
+#[test]
+fn word() {
+    let pattern = Pattern::default();
+    pattern.add("=ser");
+    assert!(pattern.all_matches(b"src/server.rs"));
+    assert!(pattern.all_matches(b"src/http_server.rs"));
+    assert!(pattern.all_matches(b"server.rs"));
+    assert!(!pattern.all_matches(b"src/parser.rs"));
+    let spans = pattern.match_spans(b"src/http_server.rs").unwrap();
+    assert_eq!((spans.len(), &spans[0]), (1, &(9..12)));
+
+    // typed further
+    pattern.add("v");
+    assert!(pattern.all_matches(b"a/Server.rs"));
+    assert!(!pattern.all_matches(b"a/serial.rs"));
+    pattern.add(" main");
+    assert!(pattern.all_matches(b"a/serve/main.rs"));
+
+    pattern.reset();
+    pattern.add("\\=x");
+    assert!(pattern.all_matches(b"a=x"));
+}
+
+#[test]
+fn segment() {
+    let pattern = Pattern::default();
+    pattern.add("^test");
+    assert!(pattern.all_matches(b"a/test/b"));
+    assert!(pattern.all_matches(b"tests/b"));
+    assert!(!pattern.all_matches(b"a/latest/b"));
+    assert!(!pattern.all_matches(b"a/my_test/b"));
+    let spans = pattern.match_spans(b"a/latest/test").unwrap();
+    assert_eq!((spans.len(), &spans[0]), (1, &(9..13)));
+
+    // typed further
+    pattern.add("s/");
+    assert!(pattern.all_matches(b"x/tests/a.rs"));
+    assert!(!pattern.all_matches(b"x/tests.rs"));
+
+    pattern.reset();
+    pattern.separators(":");
+    pattern.add("^bar");
+    assert!(pattern.all_matches(b"foo::bar"));
+    assert!(!pattern.all_matches(b"foo::xbar"));
+
+    pattern.reset();
+    pattern.add("\\^x");
+    assert!(pattern.all_matches(b"a^x"));
+}
+
+#[test]
+fn complexity_guard() {
+    let pattern = Pattern::default();
+    let long = "ab".repeat(200);
+    pattern.add(&long);
+    assert_eq!(
+        pattern.take_warning().as_deref(),
+        Some("query term too complex; matching it literally")
+    );
+    assert_eq!(pattern.take_warning(), None);
+    assert!(pattern.all_matches(format!("x/{long}").as_bytes()));
+    assert!(!pattern.all_matches(format!("x/{}", "a_b".repeat(200)).as_bytes()));
+
+    // stays literal as typed further
+    pattern.add("c");
+    assert!(pattern.all_matches(format!("x/{long}c").as_bytes()));
+    assert_eq!(pattern.take_warning(), None);
+    let info = &pattern.terms()[0];
+    assert_eq!((info.ok, info.literal), (true, true));
+
+    pattern.reset();
+    let terms: Vec<_> = (0..70).map(|i| format!("t{i}")).collect();
+    pattern.add(&terms.join(" "));
+    assert_eq!(
+        pattern.take_warning().as_deref(),
+        Some("query has over 64 terms; matching the rest literally")
+    );
+    let path = terms.join("/");
+    assert!(pattern.all_matches(path.as_bytes()));
+    // the 65th term onwards must now match exactly
+    assert!(!pattern.all_matches(path.replace("t69", "t_69").as_bytes()));
+    assert!(pattern.all_matches(path.replace("t63", "t_63").as_bytes()));
+    let literal: Vec<_> = pattern.terms().iter().map(|t| t.literal).collect();
+    assert_eq!(literal.iter().position(|l| *l), Some(64));
+    assert!(literal[64..].iter().all(|l| *l));
+}
+
+#[test]
+fn extension() {
+    let pattern = Pattern::default();
+    assert_matches!(pattern.add(":"), PatternScope::Narrow);
+    assert!(pattern.all_matches(b"src/main.rs"));
+    assert_matches!(pattern.add("r"), PatternScope::Change);
+    assert!(pattern.all_matches(b"a/b.r"));
+    assert_matches!(pattern.add("s"), PatternScope::Change);
+    assert!(pattern.all_matches(b"src/main.rs"));
+    assert!(pattern.all_matches(b"src/Main.RS"));
+    assert!(!pattern.all_matches(b"a/b.r"));
+    assert!(!pattern.all_matches(b"src/main.rsx"));
+    assert!(!pattern.all_matches(b"a/rs"));
+    assert!(!pattern.all_matches(b"a/.rs"));
+    assert!(!pattern.all_matches(b"a.rs/b"));
+    let spans = pattern.match_spans(b"src/main.rs").unwrap();
+    assert_eq!((spans.len(), &spans[0]), (1, &(8..11)));
+
+    pattern.add(",toml main");
+    assert!(pattern.all_matches(b"src/main.rs"));
+    assert!(pattern.all_matches(b"main/Cargo.toml"));
+    assert!(!pattern.all_matches(b"main/build.sh"));
+
+    pattern.reset();
+    pattern.add("\\:x");
+    assert!(pattern.all_matches(b"a:x"));
+
+    // a basename is what follows the last separator
+    pattern.reset();
+    assert!(pattern.separators(":"));
+    pattern.add(":rs");
+    assert!(pattern.all_matches(b"a:b.rs"));
+    assert!(pattern.all_matches(b"a/.rs"));
+    assert!(!pattern.all_matches(b"a:.rs"));
+}
+
+#[test]
+fn alternation() {
+    let pattern = Pattern::default();
+    pattern.add("foo");
+    assert_matches!(pattern.add(" |"), PatternScope::Narrow);
+    assert!(!pattern.all_matches(b"a/bar/baz.rs"));
+    assert_matches!(pattern.add(" b"), PatternScope::Widen);
+    assert_matches!(pattern.add("ar baz"), PatternScope::Narrow);
+
+    assert!(pattern.all_matches(b"src/foo.rs"));
+    assert!(pattern.all_matches(b"a/bar/baz.rs"));
+    assert!(!pattern.all_matches(b"a/bar.rs"));
+    assert!(pattern.any_matches(b"a/bar.rs"));
+    assert_eq!(
+        pattern.match_spans(b"src/foo.rs"),
+        Some(vec![4..5, 5..6, 6..7])
+    );
+    assert_eq!(pattern.match_spans(b"a/bar.rs"), None);
+
+    // `|` with more typed after it is a term
+    pattern.reset();
+    pattern.add("foo |");
+    assert_matches!(pattern.add("x"), PatternScope::Change);
+    assert!(pattern.all_matches(b"foo|x"));
+    assert!(!pattern.all_matches(b"foo"));
+    pattern.reset();
+    pattern.add("foo |");
+    pattern.add("x ba");
+    assert!(pattern.all_matches(b"foo|x/bar"));
+    assert!(!pattern.all_matches(b"foo|x"));
+
+    pattern.reset();
+    pattern.add("\\| | foo");
+    assert!(pattern.all_matches(b"a|b"));
+    assert!(pattern.all_matches(b"foo"));
+
+    // empty groups are ignored
+    pattern.reset();
+    pattern.add("| foo |");
+    assert!(pattern.all_matches(b"foo"));
+    assert!(!pattern.all_matches(b"bar"));
+
+    assert_matches!(pattern.rm(2), PatternScope::Change);
+    assert!(!pattern.all_matches(b"bar"));
+}
+
+#[test]
+fn builder() {
+    let pattern = PatternBuilder::new()
+        .fuzzy("<a b")
+        .exact("x\\y")
+        .word("")
+        .build();
+    assert_eq!(pattern.clone_text(), "\\<a\\sb 'x\\\\y");
+    assert!(pattern.all_matches(b"d/<a b/x\\y"));
+    assert!(!pattern.all_matches(b"d/a b/x\\y"));
+
+    let pattern = PatternBuilder::new()
+        .regex("a b$")
+        .unwrap()
+        .or()
+        .extensions(&["rs", "toml"])
+        .starts_with("x")
+        .basename(true)
+        .build();
+    assert!(pattern.all_matches(b"d/a b"));
+    assert!(pattern.all_matches(b"d/src/x.rs"));
+    assert!(!pattern.all_matches(b"x/y.rs"));
+
+    assert!(PatternBuilder::new().regex("(").is_err());
+    let pattern = PatternBuilder::new().regex("a.c/").unwrap().build();
+    assert_eq!(pattern.clone_text(), "*a.c[/]");
+    assert!(pattern.all_matches(b"a/c/x"));
+    let pattern = PatternBuilder::new().regex("a\\/").unwrap().build();
+    assert_eq!(pattern.clone_text(), "*a\\/");
+    assert!(pattern.all_matches(b"x/a/"));
+    let pattern = PatternBuilder::new().word("ser").skip_prefix(2).build();
+    assert!(pattern.all_matches(b"./server"));
+    assert!(!pattern.all_matches(b"./parser"));
+
+    let pattern = PatternBuilder::new().segment("a b").fuzzy("^x").build();
+    assert_eq!(pattern.clone_text(), "^a\\sb \\^x");
+    assert!(pattern.all_matches(b"d/a b/^x"));
+    assert!(!pattern.all_matches(b"da b/^x"));
+}
+
+#[test]
+fn matches_with_score() {
+    let pattern = Pattern::default();
+    pattern.add("mrs | lib");
+    let score = pattern.matches_with_score(b"src/main.rs").unwrap();
+    assert_eq!(score.positions, [4, 9, 10]);
+    assert_eq!(
+        Some(score),
+        scorer::FuzzyScorer.score(&pattern, b"src/main.rs")
+    );
+    assert_eq!(pattern.matches_with_score(b"src/mod.c"), None);
+    assert_eq!(
+        pattern.matches_with_score(b"lib").unwrap().positions,
+        [0, 1, 2]
+    );
+}
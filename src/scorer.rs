@@ -0,0 +1,136 @@
+use std::{ops::Range, os::unix::ffi::OsStrExt, path::Path};
+
+use ignore::overrides::{Override, OverrideBuilder};
+
+use crate::pattern::{self, Pattern};
+
+const MATCH: i64 = 16;
+const BOUNDARY: i64 = 8;
+const CONSECUTIVE: i64 = 4;
+
+/// How well a candidate matched a query; higher scores are better.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Score {
+    pub score: i64,
+    /// The byte offsets of the candidate which matched, in order.
+    pub positions: Vec<usize>,
+}
+
+/// Ranks the candidates matching the query. The server only passes a scorer candidates which
+/// `Pattern::all_matches`, and only when sorting by score or holding a minimum score; returning
+/// `None` sorts them last and fails any minimum.
+pub trait Scorer: Send + Sync {
+    fn score(&self, pattern: &Pattern, candidate: &[u8]) -> Option<Score>;
+
+    /// Score `candidate` only if `pattern` matches it.
+    fn score_match(&self, pattern: &Pattern, candidate: &[u8]) -> Option<Score> {
+        if pattern.all_matches(candidate) {
+            self.score(pattern, candidate)
+        } else {
+            None
+        }
+    }
+}
+
+/// The default scorer: matched characters at the start of path segments and words, including
+/// the capitals of camelCase words, and runs of consecutive matched characters, score highest;
+/// shorter paths win ties.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FuzzyScorer;
+impl Scorer for FuzzyScorer {
+    fn score(&self, pattern: &Pattern, candidate: &[u8]) -> Option<Score> {
+        pattern.spans_score(candidate)
+    }
+
+    fn score_match(&self, pattern: &Pattern, candidate: &[u8]) -> Option<Score> {
+        pattern.matches_with_score(candidate)
+    }
+}
+
+/// How `FuzzyScorer` scores `candidate` with the terms of its query matching `spans`, words
+/// starting after any of `separators` too.
+pub fn score_spans(candidate: &[u8], spans: Vec<Range<usize>>, separators: &[u8]) -> Score {
+    let mut positions: Vec<usize> = spans.into_iter().flatten().collect();
+    positions.sort_unstable();
+    positions.dedup();
+
+    let mut score = -(candidate.len() as i64);
+    let mut prev = None;
+    for &p in &positions {
+        score += MATCH;
+        if pattern::is_word_start(candidate, p, separators) {
+            score += BOUNDARY;
+        }
+        if prev.is_some_and(|q| q + 1 == p) {
+            score += CONSECUTIVE;
+        }
+        prev = Some(p);
+    }
+    Score { score, positions }
+}
+
+/// Multipliers of the scores of the paths matching a glob, or with an extension, ranking them
+/// above (`> 1`) or below (`< 1`) the rest. The last rule matching a path applies.
+#[derive(Debug, Clone, Default)]
+pub struct Weights {
+    rules: Vec<(String, Override, f64)>,
+}
+impl PartialEq for Weights {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules.len() == other.rules.len()
+            && self
+                .rules
+                .iter()
+                .zip(&other.rules)
+                .all(|(a, b)| a.0 == b.0 && a.2 == b.2)
+    }
+}
+impl Weights {
+    /// Weight paths matching `pattern`, a glob or a bare extension such as `lock`; a multiplier of
+    /// 1 removes the rule.
+    pub fn set(&mut self, pattern: &str, multiplier: f64) -> Result<(), ignore::Error> {
+        let glob = if pattern.contains(['*', '?', '[', '/']) {
+            pattern.to_string()
+        } else {
+            format!("*.{}", pattern.trim_start_matches('.'))
+        };
+        self.rules.retain(|(g, ..)| *g != glob);
+        if multiplier != 1.0 {
+            let matcher = OverrideBuilder::new("").add(&glob)?.build()?;
+            self.rules.push((glob, matcher, multiplier));
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// `score` of `path` weighted by its multiplier. Scores may be negative, which are divided
+    /// instead so a larger multiplier always scores higher.
+    pub fn apply(&self, score: i64, path: &[u8]) -> i64 {
+        let multiplier = self.get(path);
+        if score < 0 {
+            (score as f64 / multiplier).round() as i64
+        } else {
+            (score as f64 * multiplier).round() as i64
+        }
+    }
+
+    /// The multiplier for `path`, 1 if no rule matches.
+    pub fn get(&self, path: &[u8]) -> f64 {
+        if self.rules.is_empty() {
+            return 1.0;
+        }
+        let path = Path::new(std::ffi::OsStr::from_bytes(path));
+        self.rules
+            .iter()
+            .rev()
+            .find(|(_, matcher, _)| matcher.matched(path, false).is_whitelist())
+            .map_or(1.0, |(.., multiplier)| *multiplier)
+    }
+}
+
+#[cfg(test)]
+#[path = "scorer_test.rs"]
+mod test;
@@ -0,0 +1,86 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn score(query: &str, candidate: &str) -> Option<Score> {
+    let pattern = Pattern::default();
+    pattern.add(query);
+    FuzzyScorer.score(&pattern, candidate.as_bytes())
+}
+
+#[test]
+fn positions() {
+    assert_eq!(score("xyz", "src/main.rs"), None);
+    assert_eq!(score("", "ab").unwrap().positions, Vec::<usize>::new());
+    assert_eq!(score("mrs", "src/main.rs").unwrap().positions, [4, 9, 10]);
+    assert_eq!(
+        score("<src *ma.n", "src/main.rs").unwrap().positions,
+        [0, 1, 2, 4, 5, 6, 7]
+    );
+    assert_eq!(score("M", "src/main.rs"), None);
+    assert_eq!(
+        score("m >.rs", "src/Main.rs").unwrap().positions,
+        [4, 8, 9, 10]
+    );
+}
+
+#[test]
+fn score_match() {
+    struct Length;
+    impl Scorer for Length {
+        fn score(&self, _: &Pattern, candidate: &[u8]) -> Option<Score> {
+            Some(Score {
+                score: candidate.len() as i64,
+                positions: vec![],
+            })
+        }
+    }
+    let pattern = Pattern::default();
+    pattern.add("ma");
+    assert_eq!(Length.score_match(&pattern, b"main").unwrap().score, 4);
+    assert_eq!(Length.score_match(&pattern, b"lib"), None);
+    assert_eq!(
+        FuzzyScorer.score_match(&pattern, b"src/main.rs"),
+        FuzzyScorer.score(&pattern, b"src/main.rs")
+    );
+    assert_eq!(FuzzyScorer.score_match(&pattern, b"lib"), None);
+}
+
+#[test]
+fn ranking() {
+    let s = |c| score("main", c).unwrap().score;
+    // consecutive beats scattered
+    assert!(s("src/main.rs") > s("src/mxaxixn.rs"));
+    // word start beats mid word
+    assert!(s("src/main.rs") > s("src/xmain.rs"));
+    // shorter wins ties
+    assert!(s("main.rs") > s("main.rs.bak"));
+    // camelCase word starts count
+    let s = |c| score("fbw", c).unwrap().score;
+    assert!(s("FooBarWidget.rs") > s("ifbrowse.rs"));
+}
+
+#[test]
+fn weights() {
+    let mut weights = Weights::default();
+    assert_eq!(weights.get(b"Cargo.lock"), 1.0);
+
+    weights.set("lock", 0.1).unwrap();
+    weights.set("src/gen/*", 0.5).unwrap();
+    weights.set("*.rs", 2.0).unwrap();
+    assert_eq!(weights.get(b"Cargo.lock"), 0.1);
+    assert_eq!(weights.get(b"a/yarn.lock"), 0.1);
+    assert_eq!(weights.get(b"src/gen/x.txt"), 0.5);
+    // the last matching rule applies
+    assert_eq!(weights.get(b"src/gen/x.rs"), 2.0);
+    assert_eq!(weights.get(b"lock"), 1.0);
+
+    // a larger multiplier scores higher, whatever the sign of the score
+    assert_eq!(weights.apply(40, b"a.rs"), 80);
+    assert_eq!(weights.apply(-40, b"a.rs"), -20);
+    assert_eq!(weights.apply(-40, b"Cargo.lock"), -400);
+    assert_eq!(weights.apply(-40, b"README"), -40);
+
+    weights.set(".lock", 1.0).unwrap();
+    assert_eq!(weights.get(b"Cargo.lock"), 1.0);
+}
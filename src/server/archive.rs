@@ -0,0 +1,108 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{self, BufReader, Read},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// The separator between an archive's path and the path of an entry within it.
+pub const SEP: u8 = b'!';
+
+/// A file stored in an archive.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    /// The path within the archive.
+    pub name: Vec<u8>,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn format(path: &Path) -> Option<Format> {
+    let name = path.file_name()?.as_bytes();
+    if name.ends_with(b".zip") {
+        Some(Format::Zip)
+    } else if name.ends_with(b".tar") {
+        Some(Format::Tar)
+    } else if name.ends_with(b".tar.gz") || name.ends_with(b".tgz") {
+        Some(Format::TarGz)
+    } else {
+        None
+    }
+}
+
+/// Whether `path` is named as an archive whose entries can be listed.
+pub fn is_archive(path: &Path) -> bool {
+    format(path).is_some()
+}
+
+/// The files stored in the archive at `path`; directories are left out.
+pub fn entries(path: &Path) -> io::Result<Vec<Entry>> {
+    let Some(format) = format(path) else {
+        return Ok(vec![]);
+    };
+    let file = BufReader::new(fs::File::open(path)?);
+    match format {
+        Format::Zip => zip_entries(file),
+        Format::Tar => tar_entries(file),
+        Format::TarGz => tar_entries(flate2::bufread::GzDecoder::new(file)),
+    }
+}
+
+fn zip_entries(file: BufReader<fs::File>) -> io::Result<Vec<Entry>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if !file.is_dir() {
+            entries.push(Entry {
+                name: file.name_raw().to_vec(),
+                size: file.size(),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+fn tar_entries(reader: impl Read) -> io::Result<Vec<Entry>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = vec![];
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.header().entry_type().is_file() {
+            entries.push(Entry {
+                name: entry.path_bytes().into_owned(),
+                size: entry.size(),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// The virtual path of the entry `name` within the archive at `archive`.
+pub fn entry_path(archive: &Path, name: &[u8]) -> PathBuf {
+    let mut path = archive.as_os_str().as_bytes().to_vec();
+    path.push(SEP);
+    path.extend_from_slice(name.strip_prefix(b"./").unwrap_or(name));
+    OsStr::from_bytes(&path).into()
+}
+
+/// The path of the archive holding `path`, if `path` names a file within one as `entry_path`
+/// does.
+pub fn archive_of(path: &[u8]) -> Option<&[u8]> {
+    path.iter()
+        .enumerate()
+        .filter(|(_, c)| **c == SEP)
+        .map(|(i, _)| &path[..i])
+        .find(|archive| is_archive(Path::new(OsStr::from_bytes(archive))))
+}
+
+#[cfg(test)]
+#[path = "archive_test.rs"]
+mod test;
@@ -0,0 +1,95 @@
+use std::{env, io::Write, process};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn entry(name: &str, size: u64) -> Entry {
+    Entry {
+        name: name.into(),
+        size,
+    }
+}
+
+fn tar_data() -> Vec<u8> {
+    let mut builder = tar::Builder::new(vec![]);
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_size(0);
+    builder
+        .append_data(&mut header, "src/", io::empty())
+        .unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(5);
+    builder
+        .append_data(&mut header, "src/main.rs", &b"fn(){"[..])
+        .unwrap();
+    builder.into_inner().unwrap()
+}
+
+#[test]
+fn is_archive() {
+    assert!(super::is_archive(Path::new("a/b.zip")));
+    assert!(super::is_archive(Path::new("b.tar")));
+    assert!(super::is_archive(Path::new("b.tar.gz")));
+    assert!(super::is_archive(Path::new("b.tgz")));
+    assert!(!super::is_archive(Path::new("b.gz")));
+    assert!(!super::is_archive(Path::new("zip")));
+}
+
+#[test]
+fn archive_of() {
+    assert_eq!(super::archive_of(b"a/b.zip!c/d.txt"), Some(&b"a/b.zip"[..]));
+    assert_eq!(super::archive_of(b"a!b.tar!c"), Some(&b"a!b.tar"[..]));
+    assert_eq!(super::archive_of(b"a/b!c.txt"), None);
+    assert_eq!(super::archive_of(b"a/b.zip"), None);
+}
+
+#[test]
+fn zip() {
+    let file = env::temp_dir().join(format!("koru_find_archive_{}.zip", process::id()));
+    let mut zip = zip::ZipWriter::new(fs::File::create(&file).unwrap());
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.add_directory("docs/", options).unwrap();
+    zip.start_file("docs/a.txt", options).unwrap();
+    zip.write_all(b"hello").unwrap();
+    zip.start_file("b", options).unwrap();
+    zip.finish().unwrap();
+
+    assert_eq!(
+        entries(&file).unwrap(),
+        vec![entry("docs/a.txt", 5), entry("b", 0)]
+    );
+    let _ = fs::remove_file(&file);
+}
+
+#[test]
+fn tar() {
+    let file = env::temp_dir().join(format!("koru_find_archive_{}.tar", process::id()));
+    fs::write(&file, tar_data()).unwrap();
+    assert_eq!(entries(&file).unwrap(), vec![entry("src/main.rs", 5)]);
+    let _ = fs::remove_file(&file);
+}
+
+#[test]
+fn tar_gz() {
+    let file = env::temp_dir().join(format!("koru_find_archive_{}.tgz", process::id()));
+    let mut gz =
+        flate2::write::GzEncoder::new(fs::File::create(&file).unwrap(), Default::default());
+    gz.write_all(&tar_data()).unwrap();
+    gz.finish().unwrap();
+    assert_eq!(entries(&file).unwrap(), vec![entry("src/main.rs", 5)]);
+
+    fs::write(&file, "not gzip").unwrap();
+    assert!(entries(&file).is_err());
+    let _ = fs::remove_file(&file);
+}
+
+#[test]
+fn entry_path() {
+    assert_eq!(
+        super::entry_path(Path::new("/a/b.zip"), b"./c/d"),
+        Path::new("/a/b.zip!c/d")
+    );
+}
@@ -0,0 +1,461 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::pattern;
+
+use super::{
+    export::ExportFormat,
+    head_watch::HeadWatch,
+    order::{FIELD_SEP, Order},
+    profile::ProfileAction,
+    root_watch::RootWatch,
+    stale::Staleness,
+    walker::{Backend, BrokenLinks, Dedup, Error, NewerThan},
+    watchdog::Watchdog,
+};
+
+/// A validated protocol command. Arguments borrow from the command buffer so matching lines are
+/// not copied until needed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command<'a> {
+    Walk(&'a str),
+    WalkAdd(&'a str),
+    Switch {
+        dir: &'a str,
+        query: &'a str,
+    },
+    Match(&'a str),
+    Stop,
+    Pause,
+    Resume,
+    Add(&'a str),
+    Ignore(&'a str),
+    SkipPrefix(usize),
+    SkipSuffix(usize),
+    Basename(bool),
+    Nfc(bool),
+    Separators(&'a str),
+    Rm(usize),
+    Set {
+        start: usize,
+        text: &'a str,
+    },
+    Undo,
+    Redo,
+    Redraw,
+    RetryErrors,
+    Doctor,
+    Snapshot,
+    Save,
+    Load(&'a str),
+    PatternTerms,
+    State,
+    WindowSize(usize),
+    Scroll(usize),
+    Watchdog(Option<Watchdog>),
+    FdLimit(Option<usize>),
+    WatchLimit(Option<usize>),
+    RootWatch(Option<RootWatch>),
+    GitWatch(Option<HeadWatch>),
+    WalkBudget(Option<Duration>),
+    MaxScan(Option<usize>),
+    Throttle(Option<u8>),
+    ShallowFirst(bool),
+    Count(bool),
+    Progress(Option<Duration>),
+    StaleAfter(Option<Staleness>),
+    FsWatch(bool),
+    Index(Option<&'a str>),
+    Deterministic(bool),
+    Sort(Order),
+    Profile(ProfileAction),
+    Timing(bool),
+    RateLimit(Option<u64>),
+    Protocol(u32),
+    Matcher(Option<&'a str>),
+    Accepted(&'a str),
+    Opened(&'a str),
+    Visited(&'a str),
+    WhyIgnored(&'a str),
+    Export {
+        dest: &'a str,
+        format: ExportFormat,
+    },
+    /// Paths separated by `FIELD_SEP`.
+    Copy(&'a str),
+    Classify(bool),
+    Metadata(bool),
+    Type(Option<&'a str>),
+    TypeNot(&'a str),
+    TypeAdd(&'a str),
+    Executable(bool),
+    BrokenLinks(BrokenLinks),
+    DedupHardlinks(Dedup),
+    Backend(Backend),
+    NewerThan(Option<NewerThan>),
+    MaxSize(Option<u64>),
+    WalkErrors(bool),
+    Archives(bool),
+    Sparse(bool),
+    Submodules(bool),
+    MaxDepth(Option<usize>),
+    Hidden(bool),
+    IgnoreFiles(&'a str, bool),
+    FollowSymlinks(bool),
+    SameFs(bool),
+    Weight {
+        multiplier: f64,
+        pattern: &'a str,
+    },
+    MinScore(Option<i64>),
+    Refine(&'a str),
+    RefineClear,
+    Run {
+        name: &'a str,
+        args: &'a str,
+    },
+    Session(SessionAction<'a>),
+}
+impl<'a> Command<'a> {
+    pub fn parse(cmd: &str, arg: &'a str) -> Result<Self, Error> {
+        let p = Parser { cmd, arg };
+        Ok(match cmd {
+            "walk" => Self::Walk(p.non_empty("dir", arg)?),
+            "walk-add" => Self::WalkAdd(p.non_empty("dir", arg)?),
+            "switch" => {
+                let (dir, query) = super::chars_split_at_space(arg);
+                Self::Switch {
+                    dir: p.non_empty("dir", dir)?,
+                    query,
+                }
+            }
+            "match" => Self::Match(arg),
+            "stop" => p.no_args(Self::Stop)?,
+            "pause" => p.no_args(Self::Pause)?,
+            "resume" => p.no_args(Self::Resume)?,
+            "add" => Self::Add(arg),
+            "ignore" => Self::Ignore(arg),
+            "skip-prefix" => Self::SkipPrefix(p.number("n", arg)?),
+            "skip-suffix" => Self::SkipSuffix(p.number("n", arg)?),
+            "basename" => Self::Basename(p.on_off("mode", arg)?),
+            "nfc" => Self::Nfc(p.on_off("mode", arg)?),
+            "separators" => Self::Separators(match p.non_empty("chars", arg)? {
+                chars if pattern::valid_separators(chars) => chars,
+                chars => {
+                    return Err(p.error(
+                        "chars",
+                        format!("expected ASCII characters other than \\; got {chars:?}"),
+                    ));
+                }
+            }),
+            "rm" => Self::Rm(p.number("amount", arg)?),
+            "set" => {
+                let (start, text) = super::chars_split_at_space(arg);
+                Self::Set {
+                    start: p.number("start", start)?,
+                    text,
+                }
+            }
+            "undo" => p.no_args(Self::Undo)?,
+            "redo" => p.no_args(Self::Redo)?,
+            "redraw" => p.no_args(Self::Redraw)?,
+            "retry-errors" => p.no_args(Self::RetryErrors)?,
+            "doctor" => p.no_args(Self::Doctor)?,
+            "snapshot" => p.no_args(Self::Snapshot)?,
+            "save" => p.no_args(Self::Save)?,
+            "load" => Self::Load(p.non_empty("state", arg)?),
+            "pattern?" => p.no_args(Self::PatternTerms)?,
+            "state" => p.no_args(Self::State)?,
+            "window_size" => Self::WindowSize(p.positive("size", arg)?),
+            "scroll" => Self::Scroll(p.number("offset", arg)?),
+            "watchdog" => Self::Watchdog(match super::chars_split_at_space(arg) {
+                ("off", "") => None,
+                (ms, cancel) => Some(Watchdog {
+                    period: Duration::from_millis(p.positive("ms", ms)? as u64),
+                    cancel: p.one_of("action", cancel, &["", "cancel"])? == "cancel",
+                }),
+            }),
+            "fd-limit" => Self::FdLimit(match arg {
+                "off" => None,
+                _ => Some(p.positive("n", arg)?),
+            }),
+            "watch-limit" => Self::WatchLimit(match arg {
+                "off" => None,
+                _ => Some(p.positive("n", arg)?),
+            }),
+            "root-watch" => Self::RootWatch(match super::chars_split_at_space(arg) {
+                ("off", "") => None,
+                (ms, rewalk) => Some(RootWatch {
+                    period: Duration::from_millis(p.positive("ms", ms)? as u64),
+                    rewalk: p.one_of("action", rewalk, &["", "rewalk"])? == "rewalk",
+                }),
+            }),
+            "walk-budget" => Self::WalkBudget(match arg {
+                "off" => None,
+                ms => Some(Duration::from_millis(p.positive("ms", ms)? as u64)),
+            }),
+            "max-scan" => Self::MaxScan(match arg {
+                "off" => None,
+                _ => Some(p.positive("n", arg)?),
+            }),
+            "throttle" => Self::Throttle(match arg {
+                "off" | "100" => None,
+                _ => match p.positive("percent", arg)? {
+                    percent @ 1..100 => Some(percent as u8),
+                    _ => return Err(p.error("percent", "must be at most 100")),
+                },
+            }),
+            "shallow-first" => Self::ShallowFirst(p.on_off("mode", arg)?),
+            "count" => Self::Count(p.on_off("mode", arg)?),
+            "progress" => Self::Progress(match arg {
+                "off" => None,
+                ms => Some(Duration::from_millis(p.positive("ms", ms)? as u64)),
+            }),
+            "git-watch" => Self::GitWatch(match arg {
+                "off" => None,
+                ms => Some(HeadWatch {
+                    period: Duration::from_millis(p.positive("ms", ms)? as u64),
+                }),
+            }),
+            "stale-after" => Self::StaleAfter(match super::chars_split_at_space(arg) {
+                ("off", "") => None,
+                (ms, verify) => Some(Staleness {
+                    age: Duration::from_millis(p.positive("ms", ms)? as u64),
+                    verify: p.one_of("action", verify, &["", "verify"])? == "verify",
+                }),
+            }),
+            "fs-watch" => Self::FsWatch(p.on_off("mode", arg)?),
+            "index" => Self::Index(match arg {
+                "off" => None,
+                file => Some(p.non_empty("file", file)?),
+            }),
+            "deterministic" => Self::Deterministic(p.on_off("mode", arg)?),
+            "executable" => Self::Executable(p.on_off("mode", arg)?),
+            "broken-links" => Self::BrokenLinks(p.parse("mode", arg, "show, hide or only")?),
+            "dedup-hardlinks" => Self::DedupHardlinks(p.parse("mode", arg, "on, off or files")?),
+            "backend" => Self::Backend(p.parse("backend", arg, "walk or git")?),
+            "newer-than" => Self::NewerThan(match arg {
+                "off" => None,
+                _ => Some(p.parse(
+                    "time",
+                    arg,
+                    "a duration such as 7d, seconds since the epoch or off",
+                )?),
+            }),
+            "max-size" => Self::MaxSize(match arg {
+                "off" => None,
+                _ => Some(p.parse("bytes", arg, "a number of bytes or off")?),
+            }),
+            "walk-errors" => Self::WalkErrors(p.on_off("mode", arg)?),
+            "archives" => Self::Archives(p.on_off("mode", arg)?),
+            "sparse" => Self::Sparse(p.on_off("mode", arg)?),
+            "submodules" => Self::Submodules(p.on_off("mode", arg)?),
+            "max-depth" => Self::MaxDepth(match arg {
+                "off" => None,
+                depth => Some(p.positive("depth", depth)?),
+            }),
+            "hidden" => Self::Hidden(p.on_off("mode", arg)?),
+            "follow-symlinks" => Self::FollowSymlinks(p.on_off("mode", arg)?),
+            "same-fs" => Self::SameFs(p.on_off("mode", arg)?),
+            "ignore-files" => {
+                let (source, mode) = super::chars_split_at_space(arg);
+                Self::IgnoreFiles(
+                    p.one_of(
+                        "source",
+                        source,
+                        &["gitignore", "global", "exclude", "parents"],
+                    )?,
+                    p.on_off("mode", mode)?,
+                )
+            }
+            "weight" => {
+                let (multiplier, pattern) = super::chars_split_at_space(arg);
+                Self::Weight {
+                    multiplier: match p.parse("multiplier", multiplier, "a number")? {
+                        m if f64::is_finite(m) && m >= 0.0 => m,
+                        _ => {
+                            return Err(p.error(
+                                "multiplier",
+                                format!(
+                                    "expected a finite number of at least 0; got {multiplier:?}"
+                                ),
+                            ));
+                        }
+                    },
+                    pattern: p.non_empty("pattern", pattern)?,
+                }
+            }
+            "min-score" => Self::MinScore(match arg {
+                "off" => None,
+                _ => Some(p.parse("score", arg, "a number or off")?),
+            }),
+            "refine" => Self::Refine(arg),
+            "refine-clear" => p.no_args(Self::RefineClear)?,
+            "run" => {
+                let (name, args) = super::chars_split_at_space(arg);
+                Self::Run {
+                    name: p.non_empty("name", name)?,
+                    args,
+                }
+            }
+            "classify" => Self::Classify(p.on_off("mode", arg)?),
+            "metadata" => Self::Metadata(p.on_off("mode", arg)?),
+            "type" => Self::Type(match arg {
+                "off" => None,
+                name => Some(p.non_empty("name", name)?),
+            }),
+            "type-not" => Self::TypeNot(p.non_empty("name", arg)?),
+            "type-add" => Self::TypeAdd(p.non_empty("def", arg)?),
+            "sort" => Self::Sort(p.parse(
+                "order",
+                arg,
+                "a comma separated list of path, natural, locale, mtime, score or len",
+            )?),
+            // the format is split off with FIELD_SEP so the path may contain spaces
+            "export" => match arg.rsplit_once(FIELD_SEP as char) {
+                Some((dest, format)) => Self::Export {
+                    dest: p.non_empty("path", dest)?,
+                    format: p.parse("format", format, "text, nul or json")?,
+                },
+                None => Self::Export {
+                    dest: p.non_empty("path", arg)?,
+                    format: ExportFormat::default(),
+                },
+            },
+            "copy" => Self::Copy(p.non_empty("paths", arg)?),
+            "accepted" => Self::Accepted(p.non_empty("path", arg)?),
+            "opened" => Self::Opened(p.non_empty("path", arg)?),
+            "visited" => Self::Visited(p.non_empty("path", arg)?),
+            "why-ignored" => Self::WhyIgnored(p.non_empty("path", arg)?),
+            "matcher" => Self::Matcher(match p.non_empty("command", arg.trim())? {
+                "off" => None,
+                command => Some(command),
+            }),
+            "protocol" => Self::Protocol(p.positive("version", arg)? as u32),
+            "rate-limit" => Self::RateLimit(match arg {
+                "off" => None,
+                _ => Some(p.positive("per-second", arg)? as u64),
+            }),
+            "timing" => Self::Timing(p.on_off("mode", arg)?),
+            "profile" => {
+                Self::Profile(match p.one_of("action", arg, &["start", "stop", "dump"])? {
+                    "start" => ProfileAction::Start,
+                    "stop" => ProfileAction::Stop,
+                    _ => ProfileAction::Dump,
+                })
+            }
+            "session" => {
+                let (action, name) = super::chars_split_at_space(arg);
+                match p.one_of("action", action, &["create", "close", "list"])? {
+                    "list" if name.is_empty() => Self::Session(SessionAction::List),
+                    "list" => return Err(p.error("args", format!("unexpected {name:?}"))),
+                    action => {
+                        let name = p.non_empty("name", name)?;
+                        if name.contains(' ') {
+                            return Err(p.error("name", "must not contain spaces"));
+                        }
+                        Self::Session(match action {
+                            "create" => SessionAction::Create(name),
+                            _ => SessionAction::Close(name),
+                        })
+                    }
+                }
+            }
+            _ => return Err(Error::UnknownCommand(cmd.to_string())),
+        })
+    }
+}
+
+/// What the `session` command does to the sessions addressed as `@name`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionAction<'a> {
+    Create(&'a str),
+    /// Stop the session's walk and forget it.
+    Close(&'a str),
+    List,
+}
+
+/// Why a command's argument was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgError {
+    pub command: String,
+    pub arg: &'static str,
+    pub reason: String,
+}
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.command, self.arg, self.reason)
+    }
+}
+
+struct Parser<'c> {
+    cmd: &'c str,
+    arg: &'c str,
+}
+impl Parser<'_> {
+    fn error(&self, arg: &'static str, reason: impl Into<String>) -> Error {
+        Error::InvalidArgument(ArgError {
+            command: self.cmd.to_string(),
+            arg,
+            reason: reason.into(),
+        })
+    }
+
+    fn no_args<T>(&self, value: T) -> Result<T, Error> {
+        if self.arg.is_empty() {
+            Ok(value)
+        } else {
+            Err(self.error("args", format!("unexpected {:?}", self.arg)))
+        }
+    }
+
+    fn non_empty<'a>(&self, name: &'static str, value: &'a str) -> Result<&'a str, Error> {
+        if value.is_empty() {
+            Err(self.error(name, "missing"))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn parse<T: FromStr>(
+        &self,
+        name: &'static str,
+        value: &str,
+        expected: &str,
+    ) -> Result<T, Error> {
+        value
+            .parse()
+            .map_err(|_| self.error(name, format!("expected {expected}; got {value:?}")))
+    }
+
+    fn number(&self, name: &'static str, value: &str) -> Result<usize, Error> {
+        self.parse(name, value, "a number")
+    }
+
+    fn positive(&self, name: &'static str, value: &str) -> Result<usize, Error> {
+        match self.number(name, value)? {
+            0 => Err(self.error(name, "must be at least 1")),
+            n => Ok(n),
+        }
+    }
+
+    fn on_off(&self, name: &'static str, value: &str) -> Result<bool, Error> {
+        Ok(self.one_of(name, value, &["on", "off"])? == "on")
+    }
+
+    fn one_of<'a>(
+        &self,
+        name: &'static str,
+        value: &'a str,
+        choices: &[&str],
+    ) -> Result<&'a str, Error> {
+        if choices.contains(&value) {
+            Ok(value)
+        } else {
+            Err(self.error(name, format!("expected one of {choices:?}; got {value:?}")))
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "command_test.rs"]
+mod test;
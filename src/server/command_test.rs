@@ -0,0 +1,891 @@
+use std::time::SystemTime;
+
+use pretty_assertions::{assert_eq, assert_matches};
+
+use super::*;
+use crate::server::order::SortKey;
+
+fn arg_error(cmd: &str, arg: &'static str, reason: &str) -> Result<Command<'static>, Error> {
+    Err(Error::InvalidArgument(ArgError {
+        command: cmd.to_string(),
+        arg,
+        reason: reason.to_string(),
+    }))
+}
+
+#[test]
+fn unknown() {
+    assert_eq!(
+        Command::parse("wlak", "test"),
+        Err(Error::UnknownCommand("wlak".to_string()))
+    );
+}
+
+#[test]
+fn text_args() {
+    assert_eq!(Command::parse("walk", "~/src"), Ok(Command::Walk("~/src")));
+    assert_eq!(
+        Command::parse("walk-add", "../lib"),
+        Ok(Command::WalkAdd("../lib"))
+    );
+    assert_eq!(
+        Command::parse("walk", ""),
+        arg_error("walk", "dir", "missing")
+    );
+    assert_eq!(Command::parse("match", ""), Ok(Command::Match("")));
+    assert_eq!(Command::parse("add", "a b "), Ok(Command::Add("a b ")));
+    assert_eq!(
+        Command::parse("ignore", ">.rs"),
+        Ok(Command::Ignore(">.rs"))
+    );
+}
+
+#[test]
+fn no_args() {
+    assert_eq!(Command::parse("stop", ""), Ok(Command::Stop));
+    assert_eq!(Command::parse("redraw", ""), Ok(Command::Redraw));
+    assert_eq!(Command::parse("undo", ""), Ok(Command::Undo));
+    assert_eq!(Command::parse("state", ""), Ok(Command::State));
+    assert_eq!(Command::parse("redo", ""), Ok(Command::Redo));
+    assert_eq!(Command::parse("snapshot", ""), Ok(Command::Snapshot));
+    assert_eq!(Command::parse("retry-errors", ""), Ok(Command::RetryErrors));
+    assert_eq!(Command::parse("doctor", ""), Ok(Command::Doctor));
+    assert_eq!(Command::parse("save", ""), Ok(Command::Save));
+    assert_eq!(Command::parse("pause", ""), Ok(Command::Pause));
+    assert_eq!(Command::parse("resume", ""), Ok(Command::Resume));
+    assert_eq!(
+        Command::parse("stop", "now"),
+        arg_error("stop", "args", r#"unexpected "now""#)
+    );
+}
+
+#[test]
+fn numbers() {
+    assert_eq!(Command::parse("rm", "3"), Ok(Command::Rm(3)));
+    assert_eq!(
+        Command::parse("skip-prefix", "0"),
+        Ok(Command::SkipPrefix(0))
+    );
+    assert_eq!(
+        Command::parse("skip-suffix", "5"),
+        Ok(Command::SkipSuffix(5))
+    );
+    assert_eq!(
+        Command::parse("basename", "on"),
+        Ok(Command::Basename(true))
+    );
+    assert_eq!(Command::parse("nfc", "off"), Ok(Command::Nfc(false)));
+    assert_eq!(Command::parse("pattern?", ""), Ok(Command::PatternTerms));
+    assert_eq!(
+        Command::parse("separators", "::"),
+        Ok(Command::Separators("::"))
+    );
+    assert_eq!(
+        Command::parse("separators", ""),
+        arg_error("separators", "chars", "missing")
+    );
+    assert_eq!(
+        Command::parse("separators", "→"),
+        arg_error(
+            "separators",
+            "chars",
+            r#"expected ASCII characters other than \; got "→""#
+        )
+    );
+    assert_eq!(
+        Command::parse("separators", "/ "),
+        arg_error(
+            "separators",
+            "chars",
+            r#"expected ASCII characters other than \; got "/ ""#
+        )
+    );
+    assert_eq!(
+        Command::parse("rm", "-1"),
+        arg_error("rm", "amount", r#"expected a number; got "-1""#)
+    );
+    assert_eq!(
+        Command::parse("window_size", "20"),
+        Ok(Command::WindowSize(20))
+    );
+    assert_eq!(
+        Command::parse("window_size", "0"),
+        arg_error("window_size", "size", "must be at least 1")
+    );
+    assert_eq!(Command::parse("scroll", "0"), Ok(Command::Scroll(0)));
+    assert_eq!(
+        Command::parse("scroll", "x"),
+        arg_error("scroll", "offset", r#"expected a number; got "x""#)
+    );
+}
+
+#[test]
+fn set() {
+    assert_eq!(
+        Command::parse("set", "4 a b"),
+        Ok(Command::Set {
+            start: 4,
+            text: "a b"
+        })
+    );
+    assert_eq!(
+        Command::parse("set", "x a"),
+        arg_error("set", "start", r#"expected a number; got "x""#)
+    );
+}
+
+#[test]
+fn choices() {
+    assert_eq!(
+        Command::parse("deterministic", "on"),
+        Ok(Command::Deterministic(true))
+    );
+    assert_eq!(
+        Command::parse("deterministic", "yes"),
+        arg_error(
+            "deterministic",
+            "mode",
+            r#"expected one of ["on", "off"]; got "yes""#
+        )
+    );
+    assert_eq!(
+        Command::parse("sort", "natural"),
+        Ok(Command::Sort(SortKey::Natural.into()))
+    );
+    assert_eq!(
+        Command::parse("sort", "score,len"),
+        Ok(Command::Sort(Order::from_iter([
+            SortKey::Score,
+            SortKey::Len
+        ])))
+    );
+    assert_eq!(
+        Command::parse("sort", "mtime,path"),
+        Ok(Command::Sort(SortKey::Mtime.into()))
+    );
+    assert_eq!(
+        Command::parse("sort", "len,size"),
+        arg_error(
+            "sort",
+            "order",
+            r#"expected a comma separated list of path, natural, locale, mtime, score or len; got "len,size""#
+        )
+    );
+}
+
+#[test]
+fn fd_limit() {
+    assert_eq!(
+        Command::parse("fd-limit", "off"),
+        Ok(Command::FdLimit(None))
+    );
+    assert_eq!(
+        Command::parse("fd-limit", "64"),
+        Ok(Command::FdLimit(Some(64)))
+    );
+    assert_matches!(
+        Command::parse("fd-limit", "0"),
+        Err(Error::InvalidArgument(ArgError { arg: "n", .. }))
+    );
+}
+
+#[test]
+fn watch_limit() {
+    assert_eq!(
+        Command::parse("watch-limit", "off"),
+        Ok(Command::WatchLimit(None))
+    );
+    assert_eq!(
+        Command::parse("watch-limit", "100"),
+        Ok(Command::WatchLimit(Some(100)))
+    );
+    assert_matches!(
+        Command::parse("watch-limit", "0"),
+        Err(Error::InvalidArgument(ArgError { arg: "n", .. }))
+    );
+}
+
+#[test]
+fn watchdog() {
+    assert_eq!(
+        Command::parse("watchdog", "off"),
+        Ok(Command::Watchdog(None))
+    );
+    assert_eq!(
+        Command::parse("watchdog", "100 cancel"),
+        Ok(Command::Watchdog(Some(Watchdog {
+            period: Duration::from_millis(100),
+            cancel: true
+        })))
+    );
+    assert_matches!(
+        Command::parse("watchdog", "100 stop"),
+        Err(Error::InvalidArgument(ArgError { arg: "action", .. }))
+    );
+}
+
+#[test]
+fn root_watch() {
+    assert_eq!(
+        Command::parse("root-watch", "off"),
+        Ok(Command::RootWatch(None))
+    );
+    assert_eq!(
+        Command::parse("root-watch", "500 rewalk"),
+        Ok(Command::RootWatch(Some(RootWatch {
+            period: Duration::from_millis(500),
+            rewalk: true
+        })))
+    );
+    assert_matches!(
+        Command::parse("root-watch", "0"),
+        Err(Error::InvalidArgument(ArgError { arg: "ms", .. }))
+    );
+}
+
+#[test]
+fn git_watch() {
+    assert_eq!(
+        Command::parse("git-watch", "off"),
+        Ok(Command::GitWatch(None))
+    );
+    assert_eq!(
+        Command::parse("git-watch", "250"),
+        Ok(Command::GitWatch(Some(HeadWatch {
+            period: Duration::from_millis(250),
+        })))
+    );
+}
+
+#[test]
+fn progress() {
+    assert_eq!(
+        Command::parse("progress", "off"),
+        Ok(Command::Progress(None))
+    );
+    assert_eq!(
+        Command::parse("progress", "250"),
+        Ok(Command::Progress(Some(Duration::from_millis(250))))
+    );
+    assert_matches!(
+        Command::parse("progress", "0"),
+        Err(Error::InvalidArgument(ArgError { arg: "ms", .. }))
+    );
+}
+
+#[test]
+fn stale_after() {
+    assert_eq!(
+        Command::parse("stale-after", "off"),
+        Ok(Command::StaleAfter(None))
+    );
+    assert_eq!(
+        Command::parse("stale-after", "60000 verify"),
+        Ok(Command::StaleAfter(Some(Staleness {
+            age: Duration::from_secs(60),
+            verify: true
+        })))
+    );
+    assert_matches!(
+        Command::parse("stale-after", "100 check"),
+        Err(Error::InvalidArgument(ArgError { arg: "action", .. }))
+    );
+}
+
+#[test]
+fn index() {
+    assert_eq!(
+        Command::parse("index", "/tmp/koru.idx"),
+        Ok(Command::Index(Some("/tmp/koru.idx")))
+    );
+    assert_eq!(Command::parse("index", "off"), Ok(Command::Index(None)));
+    assert_eq!(
+        Command::parse("index", ""),
+        arg_error("index", "file", "missing")
+    );
+}
+
+#[test]
+fn fs_watch() {
+    assert_eq!(Command::parse("fs-watch", "on"), Ok(Command::FsWatch(true)));
+    assert_eq!(
+        Command::parse("fs-watch", "off"),
+        Ok(Command::FsWatch(false))
+    );
+    assert_matches!(
+        Command::parse("fs-watch", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn timing() {
+    assert_eq!(Command::parse("timing", "on"), Ok(Command::Timing(true)));
+    assert_matches!(
+        Command::parse("timing", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn rate_limit() {
+    assert_eq!(
+        Command::parse("rate-limit", "500"),
+        Ok(Command::RateLimit(Some(500)))
+    );
+    assert_eq!(
+        Command::parse("rate-limit", "off"),
+        Ok(Command::RateLimit(None))
+    );
+    assert_matches!(
+        Command::parse("rate-limit", "0"),
+        Err(Error::InvalidArgument(ArgError {
+            arg: "per-second",
+            ..
+        }))
+    );
+}
+
+#[test]
+fn protocol() {
+    assert_eq!(Command::parse("protocol", "1"), Ok(Command::Protocol(1)));
+    assert_matches!(
+        Command::parse("protocol", "0"),
+        Err(Error::InvalidArgument(ArgError { arg: "version", .. }))
+    );
+}
+
+#[test]
+fn display() {
+    let err = Command::parse("rm", "x").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"invalid argument: rm amount: expected a number; got "x""#
+    );
+}
+
+#[test]
+fn profile() {
+    assert_eq!(
+        Command::parse("profile", "dump"),
+        Ok(Command::Profile(ProfileAction::Dump))
+    );
+    assert_matches!(
+        Command::parse("profile", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "action", .. }))
+    );
+}
+
+#[test]
+fn copy() {
+    assert_eq!(
+        Command::parse("copy", "a b.rs\x1fc.rs"),
+        Ok(Command::Copy("a b.rs\x1fc.rs"))
+    );
+    assert_matches!(
+        Command::parse("copy", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "paths", .. }))
+    );
+}
+
+#[test]
+fn export() {
+    assert_eq!(
+        Command::parse("export", "/tmp/out"),
+        Ok(Command::Export {
+            dest: "/tmp/out",
+            format: ExportFormat::Text
+        })
+    );
+    assert_eq!(
+        Command::parse("export", "/tmp/my out\x1fjson"),
+        Ok(Command::Export {
+            dest: "/tmp/my out",
+            format: ExportFormat::Json
+        })
+    );
+    assert_eq!(
+        Command::parse("export", "/tmp/my out json"),
+        Ok(Command::Export {
+            dest: "/tmp/my out json",
+            format: ExportFormat::Text
+        })
+    );
+    assert_matches!(
+        Command::parse("export", "/tmp/out\x1fcsv"),
+        Err(Error::InvalidArgument(ArgError { arg: "format", .. }))
+    );
+}
+
+#[test]
+fn classify() {
+    assert_eq!(
+        Command::parse("classify", "on"),
+        Ok(Command::Classify(true))
+    );
+    assert_matches!(
+        Command::parse("classify", "yes"),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn metadata() {
+    assert_eq!(
+        Command::parse("metadata", "on"),
+        Ok(Command::Metadata(true))
+    );
+}
+
+#[test]
+fn file_types() {
+    assert_eq!(
+        Command::parse("type", "rust"),
+        Ok(Command::Type(Some("rust")))
+    );
+    assert_eq!(Command::parse("type", "off"), Ok(Command::Type(None)));
+    assert_eq!(
+        Command::parse("type-not", "test"),
+        Ok(Command::TypeNot("test"))
+    );
+    assert_eq!(
+        Command::parse("type-add", "web:*.{html,css}"),
+        Ok(Command::TypeAdd("web:*.{html,css}"))
+    );
+    assert_eq!(
+        Command::parse("type-not", ""),
+        arg_error("type-not", "name", "missing")
+    );
+}
+
+#[test]
+fn executable() {
+    assert_eq!(
+        Command::parse("executable", "off"),
+        Ok(Command::Executable(false))
+    );
+}
+
+#[test]
+fn broken_links() {
+    assert_eq!(
+        Command::parse("broken-links", "hide"),
+        Ok(Command::BrokenLinks(BrokenLinks::Hide))
+    );
+    assert_matches!(
+        Command::parse("broken-links", "on"),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn max_depth() {
+    assert_eq!(
+        Command::parse("max-depth", "2"),
+        Ok(Command::MaxDepth(Some(2)))
+    );
+    assert_eq!(
+        Command::parse("max-depth", "off"),
+        Ok(Command::MaxDepth(None))
+    );
+    assert_eq!(
+        Command::parse("max-depth", "0"),
+        arg_error("max-depth", "depth", "must be at least 1")
+    );
+}
+
+#[test]
+fn hidden() {
+    assert_eq!(Command::parse("hidden", "on"), Ok(Command::Hidden(true)));
+    assert_matches!(
+        Command::parse("hidden", "all"),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn ignore_files() {
+    assert_eq!(
+        Command::parse("ignore-files", "gitignore off"),
+        Ok(Command::IgnoreFiles("gitignore", false))
+    );
+    assert_eq!(
+        Command::parse("ignore-files", "parents on"),
+        Ok(Command::IgnoreFiles("parents", true))
+    );
+    assert_matches!(
+        Command::parse("ignore-files", "ignore off"),
+        Err(Error::InvalidArgument(ArgError { arg: "source", .. }))
+    );
+    assert_matches!(
+        Command::parse("ignore-files", "exclude"),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn follow_symlinks() {
+    assert_eq!(
+        Command::parse("follow-symlinks", "on"),
+        Ok(Command::FollowSymlinks(true))
+    );
+}
+
+#[test]
+fn same_fs() {
+    assert_eq!(Command::parse("same-fs", "on"), Ok(Command::SameFs(true)));
+    assert_matches!(
+        Command::parse("same-fs", "nfs"),
+        Err(Error::InvalidArgument(ArgError { arg: "mode", .. }))
+    );
+}
+
+#[test]
+fn dedup_hardlinks() {
+    assert_eq!(
+        Command::parse("dedup-hardlinks", "on"),
+        Ok(Command::DedupHardlinks(Dedup::Hardlinks))
+    );
+    assert_eq!(
+        Command::parse("dedup-hardlinks", "files"),
+        Ok(Command::DedupHardlinks(Dedup::Files))
+    );
+    assert_matches!(Command::parse("dedup-hardlinks", "maybe"), Err(_));
+}
+
+#[test]
+fn session() {
+    assert_eq!(
+        Command::parse("session", "create files"),
+        Ok(Command::Session(SessionAction::Create("files")))
+    );
+    assert_eq!(
+        Command::parse("session", "close files"),
+        Ok(Command::Session(SessionAction::Close("files")))
+    );
+    assert_eq!(
+        Command::parse("session", "list"),
+        Ok(Command::Session(SessionAction::List))
+    );
+    assert_eq!(
+        Command::parse("session", "create"),
+        arg_error("session", "name", "missing")
+    );
+    assert_eq!(
+        Command::parse("session", "create a b"),
+        arg_error("session", "name", "must not contain spaces")
+    );
+    assert_eq!(
+        Command::parse("session", "list x"),
+        arg_error("session", "args", r#"unexpected "x""#)
+    );
+    assert_eq!(
+        Command::parse("session", "open x"),
+        arg_error(
+            "session",
+            "action",
+            r#"expected one of ["create", "close", "list"]; got "open""#
+        )
+    );
+}
+
+#[test]
+fn count() {
+    assert_eq!(Command::parse("count", "on"), Ok(Command::Count(true)));
+    assert_eq!(
+        Command::parse("count", "1"),
+        arg_error("count", "mode", r#"expected one of ["on", "off"]; got "1""#)
+    );
+}
+
+#[test]
+fn shallow_first() {
+    assert_eq!(
+        Command::parse("shallow-first", "on"),
+        Ok(Command::ShallowFirst(true))
+    );
+    assert_eq!(
+        Command::parse("shallow-first", "off"),
+        Ok(Command::ShallowFirst(false))
+    );
+}
+
+#[test]
+fn backend() {
+    assert_eq!(
+        Command::parse("backend", "git"),
+        Ok(Command::Backend(Backend::Git))
+    );
+    assert_eq!(
+        Command::parse("backend", "walk"),
+        Ok(Command::Backend(Backend::Walk))
+    );
+    assert_eq!(
+        Command::parse("backend", "hg"),
+        arg_error("backend", "backend", r#"expected walk or git; got "hg""#)
+    );
+}
+
+#[test]
+fn walk_budget() {
+    assert_eq!(
+        Command::parse("walk-budget", "500"),
+        Ok(Command::WalkBudget(Some(Duration::from_millis(500))))
+    );
+    assert_eq!(
+        Command::parse("walk-budget", "off"),
+        Ok(Command::WalkBudget(None))
+    );
+    assert_eq!(
+        Command::parse("walk-budget", "0"),
+        arg_error("walk-budget", "ms", "must be at least 1")
+    );
+}
+
+#[test]
+fn max_scan() {
+    assert_eq!(
+        Command::parse("max-scan", "100000"),
+        Ok(Command::MaxScan(Some(100_000)))
+    );
+    assert_eq!(
+        Command::parse("max-scan", "off"),
+        Ok(Command::MaxScan(None))
+    );
+}
+
+#[test]
+fn throttle() {
+    assert_eq!(
+        Command::parse("throttle", "25"),
+        Ok(Command::Throttle(Some(25)))
+    );
+    assert_eq!(
+        Command::parse("throttle", "100"),
+        Ok(Command::Throttle(None))
+    );
+    assert_eq!(
+        Command::parse("throttle", "off"),
+        Ok(Command::Throttle(None))
+    );
+    assert_eq!(
+        Command::parse("throttle", "150"),
+        arg_error("throttle", "percent", "must be at most 100")
+    );
+    assert_eq!(
+        Command::parse("throttle", "0"),
+        arg_error("throttle", "percent", "must be at least 1")
+    );
+}
+
+#[test]
+fn newer_than() {
+    assert_eq!(
+        Command::parse("newer-than", "7d"),
+        Ok(Command::NewerThan(Some(NewerThan::Ago(
+            Duration::from_secs(7 * 86_400)
+        ))))
+    );
+    assert_eq!(
+        Command::parse("newer-than", "1700000000"),
+        Ok(Command::NewerThan(Some(NewerThan::At(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        ))))
+    );
+    assert_eq!(
+        Command::parse("newer-than", "off"),
+        Ok(Command::NewerThan(None))
+    );
+    assert_eq!(
+        Command::parse("newer-than", "7y"),
+        arg_error(
+            "newer-than",
+            "time",
+            r#"expected a duration such as 7d, seconds since the epoch or off; got "7y""#
+        )
+    );
+}
+
+#[test]
+fn max_size() {
+    assert_eq!(
+        Command::parse("max-size", "4096"),
+        Ok(Command::MaxSize(Some(4096)))
+    );
+    assert_eq!(
+        Command::parse("max-size", "off"),
+        Ok(Command::MaxSize(None))
+    );
+    assert_eq!(
+        Command::parse("max-size", "4k"),
+        arg_error(
+            "max-size",
+            "bytes",
+            r#"expected a number of bytes or off; got "4k""#
+        )
+    );
+}
+
+#[test]
+fn walk_errors() {
+    assert_eq!(
+        Command::parse("walk-errors", "on"),
+        Ok(Command::WalkErrors(true))
+    );
+}
+
+#[test]
+fn archives() {
+    assert_eq!(
+        Command::parse("archives", "on"),
+        Ok(Command::Archives(true))
+    );
+}
+
+#[test]
+fn sparse() {
+    assert_eq!(Command::parse("sparse", "on"), Ok(Command::Sparse(true)));
+}
+
+#[test]
+fn submodules() {
+    assert_eq!(
+        Command::parse("submodules", "off"),
+        Ok(Command::Submodules(false))
+    );
+}
+
+#[test]
+fn opened() {
+    assert_eq!(Command::parse("opened", "a/b"), Ok(Command::Opened("a/b")));
+    assert_matches!(
+        Command::parse("opened", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "path", .. }))
+    );
+}
+
+#[test]
+fn visited() {
+    assert_eq!(
+        Command::parse("visited", "a/b"),
+        Ok(Command::Visited("a/b"))
+    );
+    assert_matches!(
+        Command::parse("visited", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "path", .. }))
+    );
+}
+
+#[test]
+fn switch() {
+    assert_eq!(
+        Command::parse("switch", "src main rs"),
+        Ok(Command::Switch {
+            dir: "src",
+            query: "main rs"
+        })
+    );
+    assert_eq!(
+        Command::parse("switch", "src"),
+        Ok(Command::Switch {
+            dir: "src",
+            query: ""
+        })
+    );
+    assert_matches!(
+        Command::parse("switch", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "dir", .. }))
+    );
+}
+
+#[test]
+fn why_ignored() {
+    assert_eq!(
+        Command::parse("why-ignored", "a/b.log"),
+        Ok(Command::WhyIgnored("a/b.log"))
+    );
+    assert_matches!(
+        Command::parse("why-ignored", ""),
+        Err(Error::InvalidArgument(ArgError { arg: "path", .. }))
+    );
+}
+
+#[test]
+fn weight() {
+    assert_eq!(
+        Command::parse("weight", "0.5 *.snap"),
+        Ok(Command::Weight {
+            multiplier: 0.5,
+            pattern: "*.snap"
+        })
+    );
+    assert_matches!(
+        Command::parse("weight", "low *.snap"),
+        Err(Error::InvalidArgument(ArgError {
+            arg: "multiplier",
+            ..
+        }))
+    );
+    assert_matches!(
+        Command::parse("weight", "2"),
+        Err(Error::InvalidArgument(ArgError { arg: "pattern", .. }))
+    );
+    for bad in ["-1", "NaN", "inf"] {
+        assert_matches!(
+            Command::parse("weight", &format!("{bad} *.snap")),
+            Err(Error::InvalidArgument(ArgError {
+                arg: "multiplier",
+                ..
+            }))
+        );
+    }
+}
+
+#[test]
+fn min_score() {
+    assert_eq!(
+        Command::parse("min-score", "-5"),
+        Ok(Command::MinScore(Some(-5)))
+    );
+    assert_eq!(
+        Command::parse("min-score", "off"),
+        Ok(Command::MinScore(None))
+    );
+    assert_matches!(
+        Command::parse("min-score", "high"),
+        Err(Error::InvalidArgument(ArgError { arg: "score", .. }))
+    );
+}
+
+#[test]
+fn refine() {
+    assert_eq!(Command::parse("refine", "a b"), Ok(Command::Refine("a b")));
+    assert_eq!(Command::parse("refine-clear", ""), Ok(Command::RefineClear));
+}
+
+#[test]
+fn run() {
+    assert_eq!(
+        Command::parse("run", "rust src main"),
+        Ok(Command::Run {
+            name: "rust",
+            args: "src main"
+        })
+    );
+    assert_eq!(
+        Command::parse("run", ""),
+        arg_error("run", "name", "missing")
+    );
+}
+
+#[test]
+fn load() {
+    assert_eq!(
+        Command::parse("load", "0,0,0,0,1:/,1:x0,0,0,0,1:/,0:"),
+        Ok(Command::Load("0,0,0,0,1:/,1:x0,0,0,0,1:/,0:"))
+    );
+    assert_eq!(
+        Command::parse("load", ""),
+        arg_error("load", "state", "missing")
+    );
+}
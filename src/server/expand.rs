@@ -0,0 +1,109 @@
+use std::{
+    env,
+    ffi::{CStr, CString, OsStr},
+    mem,
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    ptr,
+};
+
+/// `dir` with a leading `~` or `~user` replaced by the home directory, and each `$VAR` or
+/// `${VAR}` by the variable's value, as a shell would; `$$` is a literal `$`. `None` if there was
+/// nothing to expand.
+pub fn expand(dir: &str) -> Result<Option<String>, String> {
+    expand_with(dir, |name| env::var(name).ok(), home_of)
+}
+
+fn expand_with(
+    dir: &str,
+    var: impl Fn(&str) -> Option<String>,
+    home_of: impl Fn(&str) -> Option<PathBuf>,
+) -> Result<Option<String>, String> {
+    if !dir.starts_with('~') && !dir.contains('$') {
+        return Ok(None);
+    }
+    let mut out = String::new();
+    let mut rest = dir;
+    if let Some(tilde) = dir.strip_prefix('~') {
+        let end = tilde.find('/').unwrap_or(tilde.len());
+        let (user, after) = tilde.split_at(end);
+        let home = if user.is_empty() {
+            var("HOME").ok_or("HOME is not set")?
+        } else {
+            home_of(user)
+                .ok_or_else(|| format!("no such user {user}"))?
+                .to_string_lossy()
+                .into_owned()
+        };
+        out.push_str(&home);
+        rest = after;
+    }
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        if let Some(next) = after.strip_prefix('$') {
+            out.push('$');
+            rest = next;
+            continue;
+        }
+        let (name, next) = match after.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed ${{ in {dir}"))?;
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                after.split_at(end)
+            }
+        };
+        if name.is_empty() {
+            // not a variable, such as a `$` on its own
+            out.push('$');
+        } else {
+            out.push_str(&var(name).ok_or_else(|| format!("{name} is not set"))?);
+        }
+        rest = next;
+    }
+    out.push_str(rest);
+    Ok(Some(out))
+}
+
+/// The home directory of `user` from the password database.
+fn home_of(user: &str) -> Option<PathBuf> {
+    let name = CString::new(user).ok()?;
+    let mut buf = vec![0u8; 1024];
+    // SAFETY: passwd is plain data and is only read once getpwnam_r has filled it in
+    let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut found = ptr::null_mut();
+    loop {
+        // SAFETY: every pointer is to memory owned here that outlives the call
+        let rc = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                &mut found,
+            )
+        };
+        if rc == libc::ERANGE && buf.len() < 1 << 20 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if rc != 0 || found.is_null() || pwd.pw_dir.is_null() {
+            return None;
+        }
+        break;
+    }
+    // SAFETY: pw_dir points into buf, NUL terminated, when an entry was found
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+#[cfg(test)]
+#[path = "expand_test.rs"]
+mod test;
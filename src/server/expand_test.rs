@@ -0,0 +1,51 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn expanded(dir: &str) -> Result<Option<String>, String> {
+    expand_with(
+        dir,
+        |name| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "SRC" => Some("/opt/src".to_string()),
+            _ => None,
+        },
+        |user| (user == "bob").then(|| PathBuf::from("/home/bob")),
+    )
+}
+
+#[test]
+fn tilde() {
+    assert_eq!(expanded("src/a"), Ok(None));
+    assert_eq!(expanded("~"), Ok(Some("/home/me".to_string())));
+    assert_eq!(expanded("~/src"), Ok(Some("/home/me/src".to_string())));
+    assert_eq!(expanded("~bob/src"), Ok(Some("/home/bob/src".to_string())));
+    assert_eq!(expanded("~eve/src"), Err("no such user eve".to_string()));
+    // only at the start
+    assert_eq!(expanded("a/~/b"), Ok(None));
+}
+
+#[test]
+fn variables() {
+    assert_eq!(expanded("$SRC/a"), Ok(Some("/opt/src/a".to_string())));
+    assert_eq!(expanded("${SRC}a"), Ok(Some("/opt/srca".to_string())));
+    assert_eq!(
+        expanded("$HOME/$SRC"),
+        Ok(Some("/home/me//opt/src".to_string()))
+    );
+    assert_eq!(
+        expanded("~/$SRC"),
+        Ok(Some("/home/me//opt/src".to_string()))
+    );
+    assert_eq!(expanded("a/$/b"), Ok(Some("a/$/b".to_string())));
+    assert_eq!(expanded("a/$$SRC"), Ok(Some("a/$SRC".to_string())));
+    assert_eq!(expanded("$$$SRC"), Ok(Some("$/opt/src".to_string())));
+    assert_eq!(expanded("$NOPE/a"), Err("NOPE is not set".to_string()));
+    assert_eq!(expanded("${SRC"), Err("unclosed ${ in ${SRC".to_string()));
+}
+
+#[test]
+fn home_of_root() {
+    assert!(home_of("root").is_some());
+    assert_eq!(home_of("koru_find_no_such_user"), None);
+}
@@ -0,0 +1,59 @@
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use bytes::Bytes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExportFormat {
+    /// One path per line.
+    #[default]
+    Text,
+    /// Each path followed by a NUL, for `xargs -0`.
+    Nul,
+    /// A JSON array of strings.
+    Json,
+}
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "nul" => Ok(Self::Nul),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Write `paths` to the file `dest`.
+pub fn write(dest: &Path, format: ExportFormat, paths: &[Bytes]) -> io::Result<()> {
+    let mut out = BufWriter::new(fs::File::create(dest)?);
+    match format {
+        ExportFormat::Text | ExportFormat::Nul => {
+            let sep = if format == ExportFormat::Nul {
+                0
+            } else {
+                b'\n'
+            };
+            for path in paths {
+                out.write_all(path)?;
+                out.write_all(&[sep])?;
+            }
+        }
+        ExportFormat::Json => {
+            let paths: Vec<_> = paths.iter().map(|p| String::from_utf8_lossy(p)).collect();
+            serde_json::to_writer(&mut out, &paths)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+#[path = "export_test.rs"]
+mod test;
@@ -0,0 +1,31 @@
+use std::{env, process};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn formats() {
+    let dest = env::temp_dir().join(format!("koru_find_export_{}", process::id()));
+    let paths = [Bytes::from_static(b"a/1"), Bytes::from_static(b"b \"2\"")];
+
+    write(&dest, ExportFormat::Text, &paths).unwrap();
+    assert_eq!(fs::read(&dest).unwrap(), b"a/1\nb \"2\"\n");
+
+    write(&dest, ExportFormat::Nul, &paths).unwrap();
+    assert_eq!(fs::read(&dest).unwrap(), b"a/1\0b \"2\"\0");
+
+    write(&dest, ExportFormat::Json, &paths).unwrap();
+    assert_eq!(
+        fs::read_to_string(&dest).unwrap(),
+        "[\"a/1\",\"b \\\"2\\\"\"]\n"
+    );
+
+    let _ = fs::remove_file(&dest);
+}
+
+#[test]
+fn parse() {
+    assert_eq!("nul".parse(), Ok(ExportFormat::Nul));
+    assert_eq!("csv".parse::<ExportFormat>(), Err(()));
+}
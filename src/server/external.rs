@@ -0,0 +1,131 @@
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+
+use bytes::Bytes;
+
+use super::{walker::WalkerVersion, window::Window};
+
+/// A command, such as `fzf --filter`, which reads candidates one per line and prints those
+/// matching the query. `{}` in an argument is replaced by the query, otherwise the query is
+/// appended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalMatcher {
+    command: Vec<String>,
+}
+impl ExternalMatcher {
+    pub fn new(command_line: &str) -> Self {
+        Self {
+            command: command_line
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    fn args(&self, query: &str) -> Vec<String> {
+        let mut args: Vec<_> = self.command[1..]
+            .iter()
+            .map(|a| a.replace("{}", query))
+            .collect();
+        if !self.command[1..].iter().any(|a| a.contains("{}")) {
+            args.push(query.to_string());
+        }
+        args
+    }
+
+    /// Start the matcher for `query`. Each line it prints is added to `out` and `on_exit` is
+    /// called once it exits.
+    pub(super) fn spawn(
+        &self,
+        query: &str,
+        out: Window,
+        pattern_version: usize,
+        walker_version: WalkerVersion,
+        on_exit: impl FnOnce() + Send + 'static,
+    ) -> io::Result<Process> {
+        let mut child = Command::new(&self.command[0])
+            .args(self.args(query))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("matcher has no stdio"));
+        };
+        let child = Arc::new(Mutex::new(child));
+        let reader = {
+            let child = child.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).split(b'\n') {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if !line.is_empty()
+                        && out
+                            .add(Bytes::from(line), pattern_version, &walker_version)
+                            .is_none()
+                    {
+                        let _ = lock(&child).kill();
+                        break;
+                    }
+                }
+                let _ = lock(&child).wait();
+                on_exit();
+            })
+        };
+        Ok(Process {
+            child,
+            feed: Arc::new(Feed(Mutex::new(Some(BufWriter::new(stdin))))),
+            reader,
+        })
+    }
+}
+
+/// A running matcher.
+pub(super) struct Process {
+    child: Arc<Mutex<Child>>,
+    pub feed: Arc<Feed>,
+    reader: thread::JoinHandle<()>,
+}
+impl Process {
+    pub fn kill(self) {
+        self.feed.close();
+        let _ = lock(&self.child).kill();
+        let _ = self.reader.join();
+    }
+}
+
+/// The matcher's input.
+pub(super) struct Feed(Mutex<Option<BufWriter<ChildStdin>>>);
+impl Feed {
+    /// Send a candidate; false if the matcher no longer reads them.
+    pub fn write(&self, candidate: &[u8]) -> bool {
+        match lock(&self.0).as_mut() {
+            Some(input) => input
+                .write_all(candidate)
+                .and_then(|_| input.write_all(b"\n"))
+                .is_ok(),
+            None => false,
+        }
+    }
+
+    /// Signal the end of the candidates.
+    pub fn close(&self) {
+        if let Some(mut input) = lock(&self.0).take() {
+            let _ = input.flush();
+        }
+    }
+}
+
+#[inline(always)]
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().expect(crate::LOCK_SHOULD_BE_OK)
+}
+
+#[cfg(test)]
+#[path = "external_test.rs"]
+mod test;
@@ -0,0 +1,15 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn args() {
+    assert_eq!(
+        ExternalMatcher::new("fzf  --filter").args("a b"),
+        ["--filter", "a b"]
+    );
+    assert_eq!(
+        ExternalMatcher::new("grep -e {} -i").args("x"),
+        ["-e", "x", "-i"]
+    );
+}
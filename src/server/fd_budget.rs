@@ -0,0 +1,117 @@
+use std::{fs, path::Path, thread};
+
+/// Descriptors a walker thread may hold at once: the directory it is reading and an ignore file.
+const PER_THREAD: usize = 2;
+/// Kept back from the process limit for the client, a matcher and exports.
+const RESERVE: usize = 16;
+const INOTIFY_WATCHES: &str = "/proc/sys/fs/inotify/max_user_watches";
+
+/// How many threads a walk may use so its open files stay within a budget.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FdBudget {
+    /// The descriptors the walk may use, if limited.
+    pub fds: Option<usize>,
+    pub threads: usize,
+    /// The threads used when there is no limit.
+    pub wanted: usize,
+}
+impl FdBudget {
+    /// The budget for `limit` descriptors, or else what the process's soft limit leaves.
+    pub fn new(limit: Option<usize>) -> Self {
+        let wanted = thread::available_parallelism().map_or(2, |n| n.get().min(12));
+        Self::with(
+            limit.or_else(|| open_file_limit().map(|n| n.saturating_sub(RESERVE))),
+            wanted,
+        )
+    }
+
+    pub fn with(fds: Option<usize>, wanted: usize) -> Self {
+        Self {
+            fds,
+            threads: fds.map_or(wanted, |fds| (fds / PER_THREAD).clamp(1, wanted)),
+            wanted,
+        }
+    }
+
+    /// Explains why the walk uses fewer threads than usual, if it does.
+    pub fn warning(&self) -> Option<String> {
+        let fds = self.fds?;
+        (self.threads < self.wanted).then(|| {
+            format!(
+                "{fds} open files allow {} of {} walker threads",
+                self.threads, self.wanted
+            )
+        })
+    }
+}
+
+/// How many watches fs-watch may use; it takes one for each directory under the root.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchBudget {
+    /// The watches fs-watch may use, if limited.
+    pub watches: Option<usize>,
+}
+impl WatchBudget {
+    /// The budget for `limit` watches, or else the system's `max_user_watches`.
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            watches: limit.or_else(max_user_watches),
+        }
+    }
+
+    /// Explains why `root` can't be watched within the budget, if it can't.
+    pub fn warning(&self, root: &Path) -> Option<String> {
+        let watches = self.watches?;
+        (count_dirs(root, watches + 1) > watches).then(|| {
+            format!(
+                "{} has over {watches} directories to watch; fs-watch is off",
+                root.display()
+            )
+        })
+    }
+}
+
+/// The inotify watches each user may have, if the system has inotify.
+pub fn max_user_watches() -> Option<usize> {
+    fs::read_to_string(INOTIFY_WATCHES)
+        .ok()
+        .and_then(|n| n.trim().parse().ok())
+}
+
+/// The directories from `root` down, counting no further than `max`. Symlinks aren't followed.
+fn count_dirs(root: &Path, max: usize) -> usize {
+    let mut count = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop()
+        && count < max
+    {
+        count += 1;
+        if let Ok(entries) = fs::read_dir(&dir) {
+            pending.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_dir()))
+                    .map(|entry| entry.path()),
+            );
+        }
+    }
+    count
+}
+
+/// The soft limit on open file descriptors, if there is one.
+fn open_file_limit() -> Option<usize> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0
+        || limit.rlim_cur == libc::RLIM_INFINITY
+    {
+        return None;
+    }
+    usize::try_from(limit.rlim_cur).ok()
+}
+
+#[cfg(test)]
+#[path = "fd_budget_test.rs"]
+mod test;
@@ -0,0 +1,52 @@
+use pretty_assertions::assert_eq;
+
+use std::fs;
+
+use super::*;
+use crate::test_util::TempDir;
+
+#[test]
+fn threads() {
+    assert_eq!(FdBudget::with(None, 8).threads, 8);
+    assert_eq!(FdBudget::with(Some(1000), 8).threads, 8);
+    assert_eq!(FdBudget::with(Some(7), 8).threads, 3);
+    assert_eq!(FdBudget::with(Some(0), 8).threads, 1);
+}
+
+#[test]
+fn warning() {
+    assert_eq!(FdBudget::with(None, 8).warning(), None);
+    assert_eq!(FdBudget::with(Some(16), 8).warning(), None);
+    assert_eq!(
+        FdBudget::with(Some(6), 8).warning().as_deref(),
+        Some("6 open files allow 3 of 8 walker threads")
+    );
+}
+
+#[test]
+fn process_limit() {
+    let budget = FdBudget::new(None);
+    assert!(budget.threads >= 1 && budget.threads <= budget.wanted);
+    assert_eq!(FdBudget::new(Some(4)).fds, Some(4));
+}
+
+#[test]
+fn watch_budget() {
+    let dir = TempDir::new("watch_budget");
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    fs::create_dir_all(dir.join("c")).unwrap();
+    fs::write(dir.join("a/1.txt"), "").unwrap();
+
+    assert_eq!(count_dirs(&dir, 10), 4);
+    assert_eq!(count_dirs(&dir, 2), 2);
+    assert_eq!(WatchBudget { watches: None }.warning(&dir), None);
+    assert_eq!(WatchBudget { watches: Some(4) }.warning(&dir), None);
+    assert_eq!(
+        WatchBudget { watches: Some(3) }.warning(&dir),
+        Some(format!(
+            "{} has over 3 directories to watch; fs-watch is off",
+            dir.display()
+        ))
+    );
+    assert_eq!(WatchBudget::new(Some(5)).watches, Some(5));
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    io::{self, BufWriter, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// When the visit counts add up to more than this they are all aged, forgetting the rarest.
+const MAX_TOTAL: f64 = 10_000.0;
+/// What each count is multiplied by when aged.
+const AGING: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Visit {
+    count: f64,
+    /// Seconds since the epoch.
+    last: u64,
+}
+
+/// How often and how recently whole paths were visited, ranking the most used first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frecency {
+    visits: HashMap<PathBuf, Visit>,
+}
+impl Frecency {
+    /// Read the visits saved in `file`; none if it does not exist.
+    pub fn load(file: &Path) -> io::Result<Self> {
+        let data = match fs::read(file) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a frecency file");
+        let mut visits = HashMap::new();
+        for record in data.split(|c| *c == 0).filter(|r| !r.is_empty()) {
+            let mut fields = record.splitn(3, |c| *c == b' ');
+            let mut number = || str::from_utf8(fields.next()?).ok();
+            let count = number().and_then(|n| n.parse().ok()).ok_or_else(invalid)?;
+            let last = number().and_then(|n| n.parse().ok()).ok_or_else(invalid)?;
+            let path = fields.next().ok_or_else(invalid)?;
+            visits.insert(
+                PathBuf::from(OsStr::from_bytes(path)),
+                Visit { count, last },
+            );
+        }
+        Ok(Self { visits })
+    }
+
+    /// Write the visits to `file`, each as its count, time and path terminated by a NUL.
+    pub fn save(&self, file: &Path) -> io::Result<()> {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = file.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut out = BufWriter::new(fs::File::create(&tmp)?);
+        for (path, visit) in &self.visits {
+            out.write_all(format!("{} {} ", visit.count, visit.last).as_bytes())?;
+            out.write_all(path.as_os_str().as_bytes())?;
+            out.write_all(b"\0")?;
+        }
+        out.into_inner()?.sync_all()?;
+        fs::rename(tmp, file)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.visits.is_empty()
+    }
+
+    /// Count a visit to `path` at `now`, aging every count if they have grown too large.
+    pub fn visit(&mut self, path: PathBuf, now: SystemTime) {
+        let visit = self.visits.entry(path).or_insert(Visit {
+            count: 0.0,
+            last: 0,
+        });
+        visit.count += 1.0;
+        visit.last = secs(now);
+        if self.visits.values().map(|v| v.count).sum::<f64>() > MAX_TOTAL {
+            self.visits.retain(|_, visit| {
+                visit.count *= AGING;
+                visit.count >= 1.0
+            });
+        }
+    }
+
+    /// The multiplier for the weight of `path` at `now`: 1 if it was never visited, growing
+    /// with the visits, which count for less the longer ago the last was.
+    pub fn boost(&self, path: &Path, now: SystemTime) -> f64 {
+        let Some(visit) = self.visits.get(path) else {
+            return 1.0;
+        };
+        let age = secs(now).saturating_sub(visit.last);
+        let recency = match age {
+            0..3600 => 4.0,
+            3600..86_400 => 2.0,
+            86_400..604_800 => 0.5,
+            _ => 0.25,
+        };
+        1.0 + (visit.count * recency).ln_1p() / 2.0
+    }
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+#[path = "frecency_test.rs"]
+mod test;
@@ -0,0 +1,62 @@
+use std::{env, process, time::Duration};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[test]
+fn boost() {
+    let now = SystemTime::now();
+    let mut frecency = Frecency::default();
+    frecency.visit("/a".into(), now);
+    frecency.visit("/b".into(), now);
+    frecency.visit("/b".into(), now);
+    frecency.visit("/c".into(), now - 48 * HOUR);
+
+    let boost = |path: &str| frecency.boost(Path::new(path), now);
+    assert_eq!(boost("/d"), 1.0);
+    assert!(boost("/b") > boost("/a"));
+    assert!(boost("/a") > boost("/c"));
+    assert!(boost("/c") > 1.0);
+    // decays as the visit gets older
+    assert!(frecency.boost(Path::new("/a"), now + 2 * HOUR) < boost("/a"));
+}
+
+#[test]
+fn aging() {
+    let now = SystemTime::now();
+    let mut frecency = Frecency::default();
+    frecency.visit("/once".into(), now);
+    for _ in 0..MAX_TOTAL as usize {
+        frecency.visit("/often".into(), now);
+    }
+    assert_eq!(frecency.visits.len(), 1);
+    assert_eq!(
+        frecency.visits[Path::new("/often")].count,
+        MAX_TOTAL * AGING
+    );
+}
+
+#[test]
+fn save_and_load() {
+    let file = env::temp_dir().join(format!("koru_find_frecency_{}", process::id()));
+    let _ = fs::remove_file(&file);
+    assert_eq!(Frecency::load(&file).unwrap(), Frecency::default());
+
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut frecency = Frecency::default();
+    frecency.visit("/a b/c".into(), now);
+    frecency.visit("/d".into(), now);
+    frecency.visit("/d".into(), now);
+    frecency.save(&file).unwrap();
+    assert_eq!(Frecency::load(&file).unwrap(), frecency);
+
+    fs::write(&file, "x /a\0").unwrap();
+    assert_eq!(
+        Frecency::load(&file).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    let _ = fs::remove_file(&file);
+}
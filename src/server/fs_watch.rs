@@ -0,0 +1,91 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use notify::{
+    Event, EventKind, RecursiveMode, Watcher,
+    event::{ModifyKind, RenameMode},
+};
+
+/// How often a watcher checks whether it should stop when there are no events.
+const POLL: Duration = Duration::from_millis(100);
+
+/// An entry created or removed under the watched root, relative to it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(PathBuf),
+    Removed(PathBuf),
+    /// Events were missed for the reason given, so the root needs walking again.
+    Lost(String),
+}
+
+/// The changes `event` reports under `root`; renames are a removal and an addition.
+pub fn changes(event: &Event, root: &Path) -> Vec<Change> {
+    let rel = |path: &PathBuf| {
+        path.strip_prefix(root)
+            .ok()
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+    };
+    let added = |path| rel(path).map(Change::Added);
+    let removed = |path| rel(path).map(Change::Removed);
+    if event.need_rescan() {
+        return vec![Change::Lost("events were dropped".to_string())];
+    }
+    let paths = &event.paths;
+    match event.kind {
+        EventKind::Create(_) => paths.iter().filter_map(added).collect(),
+        EventKind::Remove(_) => paths.iter().filter_map(removed).collect(),
+        EventKind::Modify(ModifyKind::Name(mode)) => match (mode, paths.as_slice()) {
+            (RenameMode::From, _) => paths.iter().filter_map(removed).collect(),
+            (RenameMode::To, _) => paths.iter().filter_map(added).collect(),
+            (RenameMode::Both, [from, to]) => removed(from).into_iter().chain(added(to)).collect(),
+            // not knowing which side this is, go by what is there now
+            _ => paths
+                .iter()
+                .filter_map(|path| match path.symlink_metadata() {
+                    Ok(_) => added(path),
+                    Err(_) => removed(path),
+                })
+                .collect(),
+        },
+        _ => vec![],
+    }
+}
+
+/// Watch `root` recursively on a new thread, calling `apply` for each change until `stop`
+/// returns true.
+pub fn spawn(
+    root: PathBuf,
+    stop: impl Fn() -> bool + Send + 'static,
+    mut apply: impl FnMut(Change) + Send + 'static,
+) -> notify::Result<thread::JoinHandle<()>> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    Ok(thread::spawn(move || {
+        let _watcher = watcher;
+        while !stop() {
+            match rx.recv_timeout(POLL) {
+                Ok(Ok(event)) => {
+                    for change in changes(&event, &root) {
+                        if stop() {
+                            return;
+                        }
+                        apply(change);
+                    }
+                }
+                Ok(Err(err)) => apply(Change::Lost(err.to_string())),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+#[path = "fs_watch_test.rs"]
+mod test;
@@ -0,0 +1,82 @@
+use notify::event::{CreateKind, Flag, RemoveKind};
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn event(kind: EventKind, paths: &[&str]) -> Event {
+    paths
+        .iter()
+        .fold(Event::new(kind), |event, path| event.add_path(path.into()))
+}
+
+#[test]
+fn event_changes() {
+    let root = Path::new("root/");
+    let added = |p: &str| Change::Added(p.into());
+    let removed = |p: &str| Change::Removed(p.into());
+    assert_eq!(
+        changes(
+            &event(EventKind::Create(CreateKind::File), &["root/a/b.rs"]),
+            root
+        ),
+        vec![added("a/b.rs")]
+    );
+    assert_eq!(
+        changes(
+            &event(EventKind::Remove(RemoveKind::Any), &["root/a", "other/x"]),
+            root
+        ),
+        vec![removed("a")]
+    );
+    assert_eq!(
+        changes(
+            &event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &["root/a.rs", "root/b.rs"]
+            ),
+            root
+        ),
+        vec![removed("a.rs"), added("b.rs")]
+    );
+    assert_eq!(
+        changes(
+            &event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+                &["root/b.rs"]
+            ),
+            root
+        ),
+        vec![added("b.rs")]
+    );
+    assert_eq!(
+        changes(
+            &event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+                &["root/no_such_file"]
+            ),
+            root
+        ),
+        vec![removed("no_such_file")]
+    );
+    assert_eq!(
+        changes(
+            &event(EventKind::Create(CreateKind::Folder), &["root/"]),
+            root
+        ),
+        vec![]
+    );
+    assert_eq!(
+        changes(
+            &event(EventKind::Modify(ModifyKind::Any), &["root/a.rs"]),
+            root
+        ),
+        vec![]
+    );
+    assert_eq!(
+        changes(
+            &event(EventKind::Create(CreateKind::File), &["root/a.rs"]).set_flag(Flag::Rescan),
+            root
+        ),
+        vec![Change::Lost("events were dropped".to_string())]
+    );
+}
@@ -0,0 +1,259 @@
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt, fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// The closest directory at or above `path` holding a `.git` directory or, for linked worktrees
+/// and submodules, a `.git` file.
+pub fn worktree_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| is_worktree(dir))
+        .map(Path::to_path_buf)
+}
+
+/// Whether `dir` is the top of a git worktree, such as a submodule's checkout.
+pub fn is_worktree(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// The git directory of `worktree`; for a linked worktree this is the directory named by the
+/// `gitdir:` line of its `.git` file.
+pub fn git_dir(worktree: &Path) -> Option<PathBuf> {
+    let dot_git = worktree.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let text = fs::read_to_string(&dot_git).ok()?;
+    let dir = text.strip_prefix("gitdir:")?.trim();
+    Some(worktree.join(dir))
+}
+
+/// The files git knows of under `dir`, relative to it: those tracked and, unless `ignored` is
+/// set, only the untracked files that are not ignored.
+pub fn ls_files(dir: &Path, ignored: bool) -> io::Result<Vec<PathBuf>> {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(dir)
+        .args(["ls-files", "-z", "--cached", "--others"]);
+    if !ignored {
+        command.arg("--exclude-standard");
+    }
+    let output = command.stdin(Stdio::null()).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    let mut paths: Vec<_> = output
+        .stdout
+        .split(|c| *c == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(OsStr::from_bytes(path)))
+        .collect();
+    // a file with a merge conflict is listed once for each stage
+    paths.dedup();
+    Ok(paths)
+}
+
+/// The directory shared by all worktrees of a repository.
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(dir) => git_dir.join(dir.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Whether `key` in `section` is set to true in any of the git config `files`. Only plain
+/// `[section]` headers are understood.
+fn config_is_true(files: &[PathBuf], section: &str, key: &str) -> bool {
+    let mut value = false;
+    for file in files {
+        let Ok(text) = fs::read_to_string(file) else {
+            continue;
+        };
+        let mut in_section = false;
+        for line in text.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_section = name.trim().eq_ignore_ascii_case(section);
+            } else if in_section
+                && let Some((k, v)) = line.split_once('=')
+                && k.trim().eq_ignore_ascii_case(key)
+            {
+                value = matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "true" | "yes" | "on" | "1"
+                );
+            }
+        }
+    }
+    value
+}
+
+/// The paths a sparse checkout leaves out of the worktree.
+pub struct Sparse {
+    root: PathBuf,
+    include: Gitignore,
+    /// The path loaded from and its canonical form, which paths below it are mapped onto so
+    /// they compare with the canonical `root`.
+    path: PathBuf,
+    canonical: PathBuf,
+}
+impl Sparse {
+    /// Read the sparse-checkout patterns of the worktree containing `path`, if sparse checkout is
+    /// enabled for it.
+    pub fn load(path: &Path) -> Option<Self> {
+        let canonical = fs::canonicalize(path).ok()?;
+        let root = worktree_root(&canonical)?;
+        let git_dir = git_dir(&root)?;
+        let common = common_dir(&git_dir);
+        let configs = [common.join("config"), git_dir.join("config.worktree")];
+        if !config_is_true(&configs, "core", "sparseCheckout") {
+            return None;
+        }
+        let text = fs::read_to_string(git_dir.join("info/sparse-checkout")).ok()?;
+        // the patterns use gitignore syntax but name what is kept
+        let mut builder = GitignoreBuilder::new(&root);
+        for line in text.lines() {
+            let _ = builder.add_line(None, line);
+        }
+        Some(Self {
+            include: builder.build().ok()?,
+            root,
+            path: path.to_path_buf(),
+            canonical,
+        })
+    }
+
+    /// Whether `path`, below the path loaded from or the worktree root, is left out. Directories
+    /// are only excluded when a pattern names them, as cone mode does, since other patterns may
+    /// match files within them.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        let path = match path.strip_prefix(&self.path) {
+            Ok(rel) if self.path != self.canonical => Cow::Owned(self.canonical.join(rel)),
+            _ => Cow::Borrowed(path),
+        };
+        if !path.starts_with(&self.root) {
+            return false;
+        }
+        let found = self.include.matched_path_or_any_parents(path, is_dir);
+        if is_dir {
+            found.is_whitelist()
+        } else {
+            !found.is_ignore()
+        }
+    }
+}
+
+/// The ignore file line that excludes a path from a walk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IgnoreRule {
+    pub file: PathBuf,
+    /// Counted from 1; `None` if the line could no longer be found in `file`.
+    pub line: Option<usize>,
+    pub glob: String,
+}
+impl fmt::Display for IgnoreRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ": {}", self.glob)
+    }
+}
+
+/// The ignore files a walk reads, as turned on or off by the client. `.ignore` files are always
+/// read unless ignore files are turned off altogether.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IgnoreFiles {
+    pub gitignore: bool,
+    /// The user's `core.excludesFile`.
+    pub global: bool,
+    /// The repository's `info/exclude`.
+    pub exclude: bool,
+    /// Those in directories above the root.
+    pub parents: bool,
+}
+impl Default for IgnoreFiles {
+    fn default() -> Self {
+        Self {
+            gitignore: true,
+            global: true,
+            exclude: true,
+            parents: true,
+        }
+    }
+}
+
+/// Find the rule in `.ignore`, `.gitignore`, git exclude or `excludes_file` files that ignores
+/// `path`, using the same precedence as a walk: the closest directory's files first, `.ignore`
+/// before `.gitignore`. Only the `sources` a walk of `root` reads are searched. `None` if the
+/// path is not ignored or a `!` rule includes it again.
+pub fn ignore_rule(
+    path: &Path,
+    is_dir: bool,
+    excludes_file: Option<&Path>,
+    sources: IgnoreFiles,
+    root: &Path,
+) -> Option<IgnoreRule> {
+    let worktree = path.parent().and_then(worktree_root);
+    let mut files = vec![];
+    for dir in path.ancestors().skip(1) {
+        if !sources.parents && !dir.starts_with(root) {
+            break;
+        }
+        files.push((dir.to_path_buf(), dir.join(".ignore")));
+        if sources.gitignore && worktree.as_ref().is_some_and(|w| dir.starts_with(w)) {
+            files.push((dir.to_path_buf(), dir.join(".gitignore")));
+        }
+    }
+    if let Some(worktree) = &worktree {
+        if sources.exclude
+            && let Some(git_dir) = git_dir(worktree)
+        {
+            files.push((worktree.clone(), common_dir(&git_dir).join("info/exclude")));
+        }
+        if sources.global
+            && let Some(file) = excludes_file
+        {
+            files.push((worktree.clone(), file.to_path_buf()));
+        }
+    }
+    for (root, file) in files {
+        if !file.is_file() {
+            continue;
+        }
+        let mut builder = GitignoreBuilder::new(&root);
+        builder.add(&file);
+        let Ok(ignore) = builder.build() else {
+            continue;
+        };
+        match ignore.matched_path_or_any_parents(path, is_dir) {
+            ignore::Match::None => {}
+            ignore::Match::Whitelist(_) => return None,
+            ignore::Match::Ignore(glob) => {
+                let line = fs::read_to_string(&file).ok().and_then(|text| {
+                    text.lines()
+                        .position(|l| l.trim_end() == glob.original())
+                        .map(|i| i + 1)
+                });
+                return Some(IgnoreRule {
+                    file,
+                    line,
+                    glob: glob.original().to_string(),
+                });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+#[path = "git_test.rs"]
+mod test;
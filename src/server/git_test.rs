@@ -0,0 +1,217 @@
+use std::{env, process};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::test_util::TempDir;
+
+fn repo(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("koru_find_git_{name}_{}", process::id()));
+    fs::create_dir_all(dir.join(".git/info")).unwrap();
+    fs::canonicalize(dir).unwrap()
+}
+
+#[test]
+fn linked_worktree() {
+    let main = repo("main");
+    let work = main.with_extension("work");
+    fs::create_dir_all(main.join(".git/worktrees/work")).unwrap();
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(
+        work.join(".git"),
+        format!("gitdir: {}\n", main.join(".git/worktrees/work").display()),
+    )
+    .unwrap();
+    fs::write(main.join(".git/worktrees/work/commondir"), "../..\n").unwrap();
+
+    assert_eq!(worktree_root(&work.join("src")), Some(work.clone()));
+    let git_dir = git_dir(&work).unwrap();
+    assert_eq!(git_dir, main.join(".git/worktrees/work"));
+    assert_eq!(
+        fs::canonicalize(common_dir(&git_dir)).unwrap(),
+        main.join(".git")
+    );
+
+    let _ = fs::remove_dir_all(&main);
+    let _ = fs::remove_dir_all(&work);
+}
+
+#[test]
+fn sparse_cone() {
+    let dir = repo("sparse");
+    assert!(Sparse::load(&dir).is_none());
+
+    fs::write(
+        dir.join(".git/info/sparse-checkout"),
+        "/*\n!/*/\n/kept/\n!/kept/*/\n/kept/sub/\n",
+    )
+    .unwrap();
+    assert!(Sparse::load(&dir).is_none());
+
+    fs::write(dir.join(".git/config"), "[core]\n\tsparseCheckout = true\n").unwrap();
+    let sparse = Sparse::load(&dir).unwrap();
+    assert!(!sparse.excludes(&dir.join("README"), false));
+    assert!(sparse.excludes(&dir.join("gone"), true));
+    assert!(sparse.excludes(&dir.join("gone/file"), false));
+    assert!(!sparse.excludes(&dir.join("kept"), true));
+    assert!(!sparse.excludes(&dir.join("kept/file"), false));
+    assert!(sparse.excludes(&dir.join("kept/other"), true));
+    assert!(!sparse.excludes(&dir.join("kept/sub/deep/file"), false));
+
+    // paths below a symlink to the worktree are mapped like the root
+    let link = dir.with_extension("link");
+    let _ = fs::remove_file(&link);
+    std::os::unix::fs::symlink(&dir, &link).unwrap();
+    let sparse = Sparse::load(&link).unwrap();
+    assert!(sparse.excludes(&link.join("gone"), true));
+    assert!(!sparse.excludes(&link.join("kept/file"), false));
+    assert!(sparse.excludes(&dir.join("gone"), true));
+
+    let _ = fs::remove_file(&link);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn ignore_rule() {
+    let dir = repo("ignore_rule");
+    fs::create_dir_all(dir.join("src/gen")).unwrap();
+    fs::write(dir.join(".gitignore"), "# build output\n*.log\ntarget/\n").unwrap();
+    fs::write(dir.join("src/.gitignore"), "!keep.log\n").unwrap();
+    fs::write(dir.join("src/.ignore"), "gen\n").unwrap();
+    fs::write(dir.join(".git/info/exclude"), "*.tmp\n").unwrap();
+
+    let rule = super::ignore_rule(
+        &dir.join("a.log"),
+        false,
+        None,
+        IgnoreFiles::default(),
+        &dir,
+    )
+    .unwrap();
+    assert_eq!(rule.line, Some(2));
+    assert_eq!(
+        rule.to_string(),
+        format!("{}:2: *.log", dir.join(".gitignore").display())
+    );
+    assert_eq!(
+        super::ignore_rule(
+            &dir.join("target/debug/x"),
+            false,
+            None,
+            IgnoreFiles::default(),
+            &dir
+        )
+        .map(|r| r.glob),
+        Some("target/".to_string())
+    );
+    assert_eq!(
+        super::ignore_rule(
+            &dir.join("src/keep.log"),
+            false,
+            None,
+            IgnoreFiles::default(),
+            &dir
+        ),
+        None
+    );
+    assert_eq!(
+        super::ignore_rule(
+            &dir.join("src/gen/a.rs"),
+            false,
+            None,
+            IgnoreFiles::default(),
+            &dir
+        )
+        .map(|r| r.file),
+        Some(dir.join("src/.ignore"))
+    );
+    assert_eq!(
+        super::ignore_rule(
+            &dir.join("x.tmp"),
+            false,
+            None,
+            IgnoreFiles::default(),
+            &dir
+        )
+        .map(|r| r.file),
+        Some(dir.join(".git/info/exclude"))
+    );
+    assert_eq!(
+        super::ignore_rule(
+            &dir.join("src/main.rs"),
+            false,
+            None,
+            IgnoreFiles::default(),
+            &dir
+        ),
+        None
+    );
+
+    let sources = IgnoreFiles {
+        gitignore: false,
+        exclude: false,
+        ..Default::default()
+    };
+    assert_eq!(
+        super::ignore_rule(&dir.join("a.log"), false, None, sources, &dir),
+        None
+    );
+    assert_eq!(
+        super::ignore_rule(&dir.join("x.tmp"), false, None, sources, &dir),
+        None
+    );
+    assert_eq!(
+        super::ignore_rule(&dir.join("src/gen/a.rs"), false, None, sources, &dir).map(|r| r.file),
+        Some(dir.join("src/.ignore"))
+    );
+    let sources = IgnoreFiles {
+        parents: false,
+        ..Default::default()
+    };
+    let src = dir.join("src");
+    assert_eq!(
+        super::ignore_rule(&src.join("a.log"), false, None, sources, &src),
+        None
+    );
+    assert_eq!(
+        super::ignore_rule(&src.join("gen/a.rs"), false, None, sources, &src).map(|r| r.file),
+        Some(dir.join("src/.ignore"))
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn ls_files() {
+    let dir = TempDir::new("git_ls_files");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&*dir)
+            .args(args)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    git(&["init", "-q"]);
+    fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(dir.join("src/a.rs"), "").unwrap();
+    fs::write(dir.join("new.rs"), "").unwrap();
+    fs::write(dir.join("out.log"), "").unwrap();
+    git(&["add", ".gitignore", "src/a.rs"]);
+
+    let mut paths = super::ls_files(&dir, false).unwrap();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [".gitignore", "new.rs", "src/a.rs"].map(PathBuf::from)
+    );
+    let mut paths = super::ls_files(&dir.join("src"), true).unwrap();
+    paths.sort();
+    assert_eq!(paths, [PathBuf::from("a.rs")]);
+    assert_eq!(super::ls_files(&dir, true).unwrap().len(), 4);
+
+    assert!(super::ls_files(&env::temp_dir().join("koru_find_no_such_dir"), false).is_err());
+}
@@ -0,0 +1,55 @@
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use super::{git, walker::Signal};
+
+type Stamp = Option<(SystemTime, u64, u64)>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadWatch {
+    pub period: Duration,
+}
+impl HeadWatch {
+    /// Check the `HEAD` and index files of the repository holding `root` every `period` until
+    /// `done` is disconnected, sending `Signal::Refresh` when either changes. Returns `None` if
+    /// `root` is not in a git worktree.
+    pub fn spawn(
+        self,
+        root: &Path,
+        done: mpsc::Receiver<()>,
+        signals: mpsc::Sender<Signal>,
+    ) -> Option<thread::JoinHandle<()>> {
+        let root = fs::canonicalize(root).ok()?;
+        let git_dir = git::git_dir(&git::worktree_root(&root)?)?;
+        let files = [git_dir.join("HEAD"), git_dir.join("index")];
+        let mut last = stamps(&files);
+        Some(thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = done.recv_timeout(self.period) {
+                let current = stamps(&files);
+                if current != last {
+                    last = current;
+                    if signals.send(Signal::Refresh).is_err() {
+                        break;
+                    }
+                }
+            }
+        }))
+    }
+}
+
+fn stamps(files: &[PathBuf; 2]) -> [Stamp; 2] {
+    files.each_ref().map(|file| {
+        let m = fs::metadata(file).ok()?;
+        Some((m.modified().ok()?, m.len(), m.ino()))
+    })
+}
+
+#[cfg(test)]
+#[path = "head_watch_test.rs"]
+mod test;
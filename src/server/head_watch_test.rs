@@ -0,0 +1,40 @@
+use pretty_assertions::assert_matches;
+
+use super::*;
+use crate::test_util::TempDir;
+
+const WT: Duration = Duration::from_millis(500);
+
+#[test]
+fn head_changed() {
+    let dir = TempDir::new("head_watch");
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    let (signal_tx, signal_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let watch = HeadWatch {
+        period: Duration::from_millis(10),
+    };
+    let t = watch.spawn(&dir.join("src"), done_rx, signal_tx).unwrap();
+    thread::sleep(Duration::from_millis(30));
+    assert_matches!(signal_rx.try_recv(), Err(_));
+
+    fs::write(dir.join(".git/HEAD"), "ref: refs/heads/feature\n").unwrap();
+    assert_matches!(signal_rx.recv_timeout(WT), Ok(Signal::Refresh));
+
+    drop(done_tx);
+    t.join().unwrap();
+}
+
+#[test]
+fn not_a_repo() {
+    let (signal_tx, _signal_rx) = mpsc::channel();
+    let (_done_tx, done_rx) = mpsc::channel();
+
+    let watch = HeadWatch {
+        period: Duration::from_millis(10),
+    };
+    assert!(watch.spawn(Path::new("/"), done_rx, signal_tx).is_none());
+}
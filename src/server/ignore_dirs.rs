@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// The directory rules of the `ignore` command: words ending in `/`, as in a gitignore file,
+/// naming directory trees a walk does not descend into. A rule with no other `/`, such as
+/// `target/`, matches at any depth; others are relative to the root.
+#[derive(Debug, Clone)]
+pub struct IgnoreDirs {
+    rules: Vec<String>,
+    globs: GlobSet,
+}
+impl Default for IgnoreDirs {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            globs: GlobSet::empty(),
+        }
+    }
+}
+impl IgnoreDirs {
+    pub fn new(rules: &[&str]) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        for rule in rules {
+            let name = rule.trim_end_matches('/');
+            let glob = match name.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if name.contains('/') => name.to_string(),
+                None => format!("**/{name}"),
+            };
+            let glob = GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| format!("{rule}: {}", err.kind()))?;
+            builder.add(glob);
+        }
+        Ok(Self {
+            rules: rules.iter().map(|r| r.to_string()).collect(),
+            globs: builder.build().map_err(|err| err.to_string())?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The rule matching the directory `rel`, relative to the root.
+    pub fn matching(&self, rel: &Path) -> Option<&str> {
+        let i = *self.globs.matches(rel).first()?;
+        Some(&self.rules[i])
+    }
+
+    /// The rule excluding a directory `rel` is in, or `rel` itself if it is a directory.
+    pub fn excluding(&self, rel: &Path, is_dir: bool) -> Option<&str> {
+        if self.is_empty() {
+            return None;
+        }
+        rel.ancestors()
+            .skip(usize::from(!is_dir))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .find_map(|dir| self.matching(dir))
+    }
+}
+
+/// Split the text of the `ignore` command into its directory rules and the rest, which is the
+/// ignore pattern's query.
+pub fn split(text: &str) -> (Vec<&str>, String) {
+    let (rules, rest): (Vec<_>, Vec<_>) = text.split(' ').partition(|word| is_rule(word));
+    (rules, rest.join(" "))
+}
+
+/// Whether `word` is a plain word ending in an unescaped `/`, not a term of another kind.
+fn is_rule(word: &str) -> bool {
+    word.len() > 1
+        && word.ends_with('/')
+        && !word.ends_with("\\/")
+        && !word.starts_with(['<', '>', '*', '\'', '=', '^', ':', '|', '\\'])
+}
+
+#[cfg(test)]
+#[path = "ignore_dirs_test.rs"]
+mod test;
@@ -0,0 +1,44 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn split_rules() {
+    assert_eq!(
+        split("target/ foo node_modules/ *src/ \\x/ / a\\/ b"),
+        (
+            vec!["target/", "node_modules/"],
+            "foo *src/ \\x/ / a\\/ b".to_string()
+        )
+    );
+    assert_eq!(split(""), (vec![], "".to_string()));
+}
+
+#[test]
+fn matching() {
+    let dirs = IgnoreDirs::new(&["target/", "a/build/", "/top/", "*.tmp/"]).unwrap();
+    assert_eq!(dirs.matching(Path::new("target")), Some("target/"));
+    assert_eq!(dirs.matching(Path::new("x/y/target")), Some("target/"));
+    assert_eq!(dirs.matching(Path::new("x/targets")), None);
+    assert_eq!(dirs.matching(Path::new("a/build")), Some("a/build/"));
+    assert_eq!(dirs.matching(Path::new("x/a/build")), None);
+    assert_eq!(dirs.matching(Path::new("top")), Some("/top/"));
+    assert_eq!(dirs.matching(Path::new("x/top")), None);
+    assert_eq!(dirs.matching(Path::new("x/b.tmp")), Some("*.tmp/"));
+
+    assert_eq!(
+        dirs.excluding(Path::new("x/target/debug/a.rs"), false),
+        Some("target/")
+    );
+    assert_eq!(dirs.excluding(Path::new("x/target"), false), None);
+    assert_eq!(dirs.excluding(Path::new("x/target"), true), Some("target/"));
+    assert_eq!(
+        IgnoreDirs::default().excluding(Path::new("target/a"), false),
+        None
+    );
+
+    assert_eq!(
+        IgnoreDirs::new(&["a[/"]).unwrap_err(),
+        "a[/: unclosed character class; missing ']'"
+    );
+}
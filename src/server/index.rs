@@ -0,0 +1,94 @@
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fs,
+    io::{self, BufWriter, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+const MAGIC: &[u8] = b"koru_find index 1\n";
+
+/// The paths a walk of a root reported, as saved by `save`.
+#[derive(Debug, PartialEq)]
+pub struct Index {
+    /// When the walk was saved.
+    pub saved: SystemTime,
+    /// Whole paths, as the walk found them.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Write `paths`, found by walking `root`, to `file`. Paths under `root` are stored relative to
+/// it; the file is replaced whole so a reader never sees part of it.
+pub fn save<'a>(file: &Path, root: &Path, paths: impl Iterator<Item = &'a Path>) -> io::Result<()> {
+    static SAVES: AtomicUsize = AtomicUsize::new(0);
+    let saved = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    // unique so concurrent saves to the same file don't write over each other's
+    let mut tmp = file.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        SAVES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let write = || {
+        let mut out = BufWriter::new(fs::File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        out.write_all(format!("{saved}\n").as_bytes())?;
+        out.write_all(canonical(root).as_os_str().as_bytes())?;
+        out.write_all(b"\0")?;
+        for path in paths {
+            let path = path.strip_prefix(root).unwrap_or(path);
+            out.write_all(path.as_os_str().as_bytes())?;
+            out.write_all(b"\0")?;
+        }
+        out.into_inner()?.sync_all()?;
+        fs::rename(&tmp, file)
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// `root` with symlinks and `.` resolved, so it is the same however the walk named it.
+fn canonical(root: &Path) -> Cow<'_, Path> {
+    match fs::canonicalize(root) {
+        Ok(root) => Cow::Owned(root),
+        Err(_) => Cow::Borrowed(root),
+    }
+}
+
+/// Read the index saved for `root` in `file`; `None` if it was saved for another root. The
+/// paths are under `root` as named here.
+pub fn load(file: &Path, root: &Path) -> io::Result<Option<Index>> {
+    let data = fs::read(file)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an index");
+    let rest = data.strip_prefix(MAGIC).ok_or_else(invalid)?;
+    let end = rest.iter().position(|c| *c == b'\n').ok_or_else(invalid)?;
+    let saved: u64 = str::from_utf8(&rest[..end])
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .ok_or_else(invalid)?;
+    let mut fields = rest[end + 1..].split(|c| *c == 0);
+    if fields.next() != Some(canonical(root).as_os_str().as_bytes()) {
+        return Ok(None);
+    }
+    let mut paths: Vec<_> = fields
+        .map(|rel| root.join(OsStr::from_bytes(rel)))
+        .collect();
+    // the empty field after the last terminator
+    paths.pop();
+    Ok(Some(Index {
+        saved: SystemTime::UNIX_EPOCH + Duration::from_secs(saved),
+        paths,
+    }))
+}
+
+#[cfg(test)]
+#[path = "index_test.rs"]
+mod test;
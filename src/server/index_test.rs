@@ -0,0 +1,56 @@
+use std::{env, process};
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+use crate::test_util::TempDir;
+
+#[test]
+fn save_and_load() {
+    let file = env::temp_dir().join(format!("koru_find_index_{}", process::id()));
+    let root = Path::new("/walked/");
+    let paths = [
+        Path::new("/walked/a/1.rs"),
+        Path::new("/walked/b"),
+        Path::new("/added/c"),
+    ];
+
+    save(&file, root, paths.into_iter()).unwrap();
+    let index = load(&file, root).unwrap().unwrap();
+    assert_eq!(index.paths, paths);
+    assert!(SystemTime::now().duration_since(index.saved).unwrap() < Duration::from_secs(5));
+    assert_eq!(load(&file, Path::new("/other/")).unwrap(), None);
+
+    save(&file, root, [].into_iter()).unwrap();
+    assert_eq!(
+        load(&file, root).unwrap().unwrap().paths,
+        Vec::<PathBuf>::new()
+    );
+
+    fs::write(&file, "a/1.rs\0").unwrap();
+    assert_eq!(
+        load(&file, root).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    let _ = fs::remove_file(&file);
+}
+
+#[test]
+fn keyed_by_canonical_root() {
+    let dir = TempDir::new("index_root");
+    fs::create_dir_all(dir.join("real")).unwrap();
+    let link = dir.join("link");
+    std::os::unix::fs::symlink(dir.join("real"), &link).unwrap();
+    let file = dir.join("index");
+
+    save(&file, &link, [link.join("a").as_path()].into_iter()).unwrap();
+    let real = dir.join("real");
+    let index = load(&file, &real).unwrap().unwrap();
+    assert_eq!(index.paths, [real.join("a")]);
+    let leftover: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert_eq!(leftover, Vec::<std::ffi::OsString>::new());
+}
@@ -1,13 +1,52 @@
 use std::{
+    any::Any,
+    cell::Cell,
+    collections::HashMap,
     io::{self, Read, Write},
-    sync::mpsc,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    },
     thread,
+    time::Instant,
 };
 
-use walker::Msg;
+use crate::{
+    hooks::Hooks,
+    macros::Macros,
+    scorer::{FuzzyScorer, Scorer, Weights},
+};
+
+use profile::{Profile, Stage};
+use quota::Quotas;
+use rate::{Bucket, RateLimit};
+use walker::{Msg, Signal};
 use window::Window;
 
+#[cfg(feature = "archives")]
+pub mod archive;
+pub mod command;
+pub mod expand;
+pub mod export;
+pub mod external;
+pub mod fd_budget;
+pub mod frecency;
+pub mod fs_watch;
+pub mod git;
+pub mod head_watch;
+pub mod ignore_dirs;
+pub mod index;
+pub mod order;
+pub mod profile;
+pub mod quota;
+pub mod rate;
+pub mod root_watch;
+pub mod stale;
 pub mod walker;
+pub mod watchdog;
 pub mod window;
 
 struct CommandReader<R: Read> {
@@ -56,61 +95,476 @@ impl<R: Read> CommandReader<R> {
         }
     }
 
-    fn get_cmd(&self) -> Result<(&str, &str), walker::Error> {
+    /// Whether another command is already buffered, so `read` won't block.
+    fn has_next(&self) -> bool {
+        self.buf[self.startp..self.endp].contains(&0)
+    }
+
+    /// The command last read, without its terminator.
+    fn frame(&self) -> Result<&[u8], walker::Error> {
         if self.startp > 0 {
-            let buf = &self.buf[..self.startp - 1];
-            let (cmd, arg) = split_at_space(buf);
-            let Ok(cmd) = str::from_utf8(cmd) else {
-                return Err(walker::Error::Utf8Error);
-            };
-            let Ok(arg) = str::from_utf8(arg) else {
-                return Err(walker::Error::Utf8Error);
-            };
-            Ok((cmd, arg))
+            Ok(&self.buf[..self.startp - 1])
         } else {
             Err(walker::Error::InvalidCommand)
         }
     }
 }
 
+/// Split the client's request ID, `#` and digits followed by a space, from the start of `frame`.
+fn split_request(frame: &[u8]) -> (Option<&str>, &[u8]) {
+    if let [b'#', rest @ ..] = frame {
+        let (id, rest) = split_at_space(rest);
+        if !id.is_empty()
+            && id.iter().all(u8::is_ascii_digit)
+            && let Ok(id) = str::from_utf8(id)
+        {
+            return (Some(id), rest);
+        }
+    }
+    (None, frame)
+}
+
+fn split_cmd(frame: &[u8]) -> Result<(&str, &str), walker::Error> {
+    let (cmd, arg) = split_at_space(frame);
+    let Ok(cmd) = str::from_utf8(cmd) else {
+        return Err(walker::Error::Utf8Error);
+    };
+    let Ok(arg) = str::from_utf8(arg) else {
+        return Err(walker::Error::Utf8Error);
+    };
+    Ok((cmd, arg))
+}
+
+/// Split a stream of NUL terminated messages.
+pub struct FrameReader<R: Read> {
+    input: R,
+    buf: Vec<u8>,
+    startp: usize,
+    endp: usize,
+}
+impl<R: Read> FrameReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            buf: vec![0; 1024],
+            startp: 0,
+            endp: 0,
+        }
+    }
+
+    /// Read the next message without its terminator. Returns `None` at end of stream.
+    pub fn next_frame(&mut self) -> io::Result<Option<&[u8]>> {
+        self.buf.copy_within(self.startp..self.endp, 0);
+        self.endp -= self.startp;
+        self.startp = 0;
+        loop {
+            if let Some(len) = self.buf[..self.endp].iter().position(|c| *c == 0) {
+                self.startp = len + 1;
+                return Ok(Some(&self.buf[..len]));
+            }
+            if self.endp == self.buf.len() {
+                self.buf.extend_from_within(..);
+            }
+            let n = self.input.read(&mut self.buf[self.endp..])?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.endp += n;
+        }
+    }
+}
+
 pub fn run(
     threads: usize,
-    inp: impl Read,
+    inp: impl Read + Send + 'static,
     out: impl Write + Send + 'static,
 ) -> Result<(), walker::Error> {
-    let mut commander = CommandReader::new(inp);
+    ServerBuilder::new().threads(threads).run(inp, out)
+}
+
+/// Configures a server for embedders; `run` serves with the defaults.
+pub struct ServerBuilder {
+    threads: usize,
+    scorer: Arc<dyn Scorer>,
+    weights: Weights,
+    hooks: Hooks,
+    quotas: Quotas,
+    macros: Macros,
+    frecency: Option<PathBuf>,
+    diagnostics: Option<Box<dyn Write + Send>>,
+}
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self {
+            threads: num_cpus::get(),
+            scorer: Arc::new(FuzzyScorer),
+            weights: Weights::default(),
+            hooks: Hooks::default(),
+            quotas: Quotas::default(),
+            macros: Macros::default(),
+            frecency: None,
+            diagnostics: None,
+        }
+    }
+}
+impl ServerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The initial window size and output buffer depth.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Replace the `FuzzyScorer` used to accept and rank matches.
+    pub fn scorer(mut self, scorer: impl Scorer + 'static) -> Self {
+        self.scorer = Arc::new(scorer);
+        self
+    }
+
+    /// Weight the scores of matches by these, normally from the config file.
+    pub fn weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Commands to run on lifecycle events, normally from the config file.
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Limit what the client may ask for, normally from the config file.
+    pub fn quotas(mut self, quotas: Quotas) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    /// Command sequences the client may `run`, normally from the config file.
+    pub fn macros(mut self, macros: Macros) -> Self {
+        self.macros = macros;
+        self
+    }
+
+    /// Rank the results visited often or recently first, keeping the visits in `file`.
+    pub fn frecency(mut self, file: PathBuf) -> Self {
+        self.frecency = Some(file);
+        self
+    }
+
+    /// Write messages, warnings and fatal errors to `diag`, one per line, instead of to the
+    /// client, which then only receives results and walk progress.
+    pub fn diagnostics(mut self, diag: impl Write + Send + 'static) -> Self {
+        self.diagnostics = Some(Box::new(diag));
+        self
+    }
+
+    /// Serve commands from `inp` until it closes, writing results to `out`.
+    pub fn run(
+        self,
+        inp: impl Read + Send + 'static,
+        out: impl Write + Send + 'static,
+    ) -> Result<(), walker::Error> {
+        serve(self, inp, out)
+    }
+}
+
+fn serve(
+    builder: ServerBuilder,
+    inp: impl Read + Send + 'static,
+    out: impl Write + Send + 'static,
+) -> Result<(), walker::Error> {
+    let threads = builder.threads;
     let (tx, rx) = mpsc::sync_channel(threads * 2);
+    let fatal_tx = tx.clone();
 
-    let win = Window::new(threads, tx);
+    let win = Window::with_scorer(threads, tx, builder.scorer);
+    win.set_weights(builder.weights);
+    let profile = win.profile().clone();
+    let rate_limit = win.rate_limit().clone();
+    let protocol = win.protocol().clone();
     let mut walker = walker::Walker::new(win);
-    let _t1 = thread::spawn(move || {
-        let _ = relay_to_out(rx, out);
-    });
+    walker.set_hooks(builder.hooks);
+    walker.set_quotas(builder.quotas);
+    walker.set_macros(builder.macros);
+    if let Some(file) = builder.frecency {
+        walker.set_frecency(file);
+    }
+    let (signal_tx, signal_rx) = mpsc::channel();
+    walker.set_signals(signal_tx.clone());
+    let (input_tx, input_rx) = mpsc::channel();
+    let relay = {
+        let profile = profile.clone();
+        let mut diag = builder.diagnostics;
+        let input_tx = input_tx.clone();
+        thread::spawn(move || {
+            let diag = diag.as_deref_mut().map(|d| d as &mut dyn Write);
+            let result = relay_to_out(rx, out, diag, &profile, &rate_limit, &protocol);
+            let _ = input_tx.send(Input::RelayDone);
+            result
+        })
+    };
+
+    // Commands run on their own thread so watchers can signal it while `inp` is read. Those
+    // already read are sent together and acknowledged before blocking for more input, so they run
+    // back to back and errors end the server promptly.
+    let (ack_tx, ack_rx) = mpsc::channel();
+    let worker = {
+        let fatal_tx = fatal_tx.clone();
+        thread::spawn(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_signals(&mut walker, &profile, signal_rx, ack_tx)
+            })) {
+                let _ = fatal_tx.send(Msg::Fatal(panic_message(payload.as_ref())));
+            }
+        })
+    };
+    // `inp` is read on its own thread too, so a fatal message ends the server while it waits
+    let (more_tx, more_rx) = mpsc::channel();
+    thread::spawn(move || read_commands(inp, input_tx, more_rx));
+    let result = loop {
+        match input_rx.recv() {
+            Ok(Input::Commands(frames)) => {
+                // a fatal message has been written so no more commands are run
+                if relay.is_finished() || signal_tx.send(Signal::Commands(frames)).is_err() {
+                    break Ok(());
+                }
+                match ack_rx.recv() {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => break Err(err),
+                    // the worker panicked and has sent the fatal message
+                    Err(_) => break Ok(()),
+                }
+                let _ = more_tx.send(());
+            }
+            Ok(Input::Failed(err)) => break Err(err),
+            Ok(Input::Panicked(msg)) => {
+                let _ = fatal_tx.send(Msg::Fatal(msg));
+                break Ok(());
+            }
+            Ok(Input::RelayDone) | Err(_) => break Ok(()),
+        }
+    };
+    let _ = signal_tx.send(Signal::Quit);
+    let _ = worker.join();
+    result?;
+    drop(fatal_tx);
+    match relay.join() {
+        Ok(Ok(Some(msg))) => Err(walker::Error::Panic(msg)),
+        Ok(Err(err)) => Err(walker::Error::from_io(err)),
+        _ => Err(walker::Error::Eof),
+    }
+}
+
+/// What `serve` waits for.
+enum Input {
+    /// The commands read together from the client.
+    Commands(Vec<Vec<u8>>),
+    /// Reading failed, with `Error::Eof` when the input closed.
+    Failed(walker::Error),
+    /// Reading panicked.
+    Panicked(String),
+    /// The relay stopped, after writing a fatal message or because the client went away.
+    RelayDone,
+}
+
+/// Read batches of commands from `inp` to `input`, each after `more` asks for the next, until
+/// reading fails.
+fn read_commands(inp: impl Read, input: mpsc::Sender<Input>, more: mpsc::Receiver<()>) {
+    let mut commander = CommandReader::new(inp);
     loop {
-        commander.read()?;
-        match commander.get_cmd() {
-            Ok((ct, arg)) => {
-                walker.command(ct, arg)?;
+        let read = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut frames = vec![];
+            loop {
+                commander.read()?;
+                frames.push(commander.frame()?.to_vec());
+                if !commander.has_next() {
+                    return Ok(frames);
+                }
             }
-            Err(err) => {
-                walker.message(format!("Command read error: {err:?}"));
+        }));
+        let batch = match read {
+            Ok(Ok(frames)) => Input::Commands(frames),
+            Ok(Err(err)) => Input::Failed(err),
+            Err(payload) => Input::Panicked(panic_message(payload.as_ref())),
+        };
+        let again = matches!(batch, Input::Commands(_));
+        if input.send(batch).is_err() || !again || more.recv().is_err() {
+            return;
+        }
+    }
+}
+
+/// Execute commands and watcher requests until `Signal::Quit`, acknowledging each batch of
+/// commands on `ack` with any error that should end the server.
+fn run_signals(
+    walker: &mut walker::Walker,
+    profile: &Profile,
+    signals: mpsc::Receiver<Signal>,
+    ack: mpsc::Sender<Result<(), walker::Error>>,
+) {
+    while let Ok(signal) = signals.recv() {
+        match signal {
+            Signal::Commands(frames) => {
+                let result = frames
+                    .iter()
+                    .try_for_each(|frame| execute_frame(walker, profile, frame));
+                if ack.send(result).is_err() {
+                    return;
+                }
             }
+            Signal::Rewalk => walker.rewalk(),
+            Signal::Refresh => walker.refresh(),
+            Signal::Quit => return,
         }
     }
 }
 
+/// Run one command, reporting mistakes in it to the client rather than ending the server.
+fn execute_frame(
+    walker: &mut walker::Walker,
+    profile: &Profile,
+    frame: &[u8],
+) -> Result<(), walker::Error> {
+    let (request, frame) = split_request(frame);
+    let (ct, arg) = match split_cmd(frame) {
+        Ok(cmd) => cmd,
+        Err(err) => {
+            walker.message(format!("Command read error: {err:?}"));
+            return Ok(());
+        }
+    };
+    let number = profile.received(ct);
+    let result = match walker.request_command(request, ct, arg) {
+        Err(err @ (walker::Error::InvalidArgument(_) | walker::Error::UnknownCommand(_))) => {
+            walker.message(format!("{err}"));
+            Ok(())
+        }
+        result => result,
+    };
+    walker.command_done(number);
+    result
+}
+
+/// Write messages to `out` for the `protocol` version, no faster than `rate_limit` allows each
+/// session, until all senders are gone or a `Msg::Fatal` is written. Returns the fatal message if
+/// there was one.
 fn relay_to_out(
     rx: mpsc::Receiver<Msg>,
-    mut out: impl Write + Send + 'static,
-) -> Result<(), io::Error> {
+    mut out: impl Write,
+    mut diag: Option<&mut dyn Write>,
+    profile: &Profile,
+    rate_limit: &RateLimit,
+    protocol: &AtomicU32,
+) -> Result<Option<String>, io::Error> {
+    // named sessions have their own buckets so a busy one doesn't use up the others' rate
+    let mut buckets: HashMap<Option<String>, Bucket> = HashMap::new();
     while let Ok(msg) = rx.recv() {
-        msg.write(&mut out)?;
-        while let Ok(msg) = rx.try_recv() {
-            msg.write(&mut out)?;
+        let start = profile.is_enabled().then(Instant::now);
+        let mut msg = Some(msg);
+        while let Some(m) = msg {
+            if let Msg::CommandDone(number) = m {
+                profile.command_done(number);
+                msg = rx.try_recv().ok();
+                continue;
+            }
+            if let Some(per_sec) = rate_limit.get() {
+                let bucket = buckets.entry(session(&m).map(str::to_string)).or_default();
+                let wait = bucket.take(per_sec, Instant::now());
+                if !wait.is_zero() {
+                    out.flush()?;
+                    thread::sleep(wait);
+                }
+            }
+            let version = protocol.load(Ordering::Relaxed);
+            write_msg(&m, &mut out, diag.as_deref_mut(), version)?;
+            if let Msg::Fatal(m) = m {
+                out.flush()?;
+                if let Some(diag) = diag.as_deref_mut() {
+                    diag.flush()?;
+                }
+                return Ok(Some(m));
+            }
+            if let Some(timing) = profile.output() {
+                write_msg(
+                    &Msg::Message(timing),
+                    &mut out,
+                    diag.as_deref_mut(),
+                    version,
+                )?;
+            }
+            msg = rx.try_recv().ok();
         }
         out.flush()?;
+        if let Some(diag) = diag.as_deref_mut() {
+            diag.flush()?;
+        }
+        if let Some(start) = start {
+            profile.add(Stage::Output, start.elapsed());
+        }
+    }
+    Ok(None)
+}
+
+/// The name of the session `m` is for, if it is a named one.
+fn session(m: &Msg) -> Option<&str> {
+    match m {
+        Msg::Named { name, .. } => Some(name),
+        Msg::Request { msg, .. } => session(msg),
+        _ => None,
+    }
+}
+
+/// Write `m` to `out`, or as a line to `diag` if it is a diagnostic and there is one.
+fn write_msg(
+    m: &Msg,
+    out: &mut impl Write,
+    diag: Option<&mut (dyn Write + '_)>,
+    version: u32,
+) -> Result<(), io::Error> {
+    match diag {
+        Some(diag) if matches!(m, Msg::Message(_) | Msg::Warning { .. } | Msg::Fatal(_)) => {
+            let mut line = vec![];
+            m.write(&mut line)?;
+            line.pop();
+            line.push(b'\n');
+            diag.write_all(&line)
+        }
+        _ => m.write_version(out, version),
+    }
+}
+
+thread_local! {
+    /// Where the last panic on this thread happened, kept by the panic hook for `panic_message`.
+    static PANIC_LOCATION: Cell<Option<String>> = const { Cell::new(None) };
+}
+
+/// Note where each panic happens, so the `Msg::Fatal` of one the server catches says where, then
+/// report it as the hook already set does.
+pub fn set_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        PANIC_LOCATION.set(info.location().map(|l| l.to_string()));
+        previous(info);
+    }));
+}
+
+/// Describe a caught panic for a `Msg::Fatal`, with where it happened if `set_panic_hook` was
+/// called.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    };
+    match PANIC_LOCATION.take() {
+        Some(location) => format!("{message} at {location}"),
+        None => message,
     }
-    Ok(())
 }
 
 fn split_at_space(data: &[u8]) -> (&[u8], &[u8]) {
@@ -1,6 +1,8 @@
 use std::{
     cmp::min,
-    io::{self, pipe},
+    env, fs,
+    io::{self, BufRead, pipe},
+    process,
     sync::mpsc,
     thread,
     time::Duration,
@@ -9,6 +11,7 @@ use std::{
 use pretty_assertions::assert_matches;
 
 use super::*;
+use crate::scorer::Score;
 
 struct MsgReader<R: Read> {
     input: R,
@@ -35,8 +38,8 @@ impl<R: Read> MsgReader<R> {
             self.buf.copy_within(self.startp..self.endp, 0);
             self.endp -= self.startp;
             self.startp = 0;
-            if self.endp == self.startp {
-                let n = self.input.read(self.buf.as_mut_slice())?;
+            if !self.buf[..self.endp].contains(&0) {
+                let n = self.input.read(&mut self.buf.as_mut_slice()[self.endp..])?;
                 if n == 0 {
                     return Ok(String::new());
                 }
@@ -89,23 +92,31 @@ fn command_reader() {
         .extend_from_slice(b"ignore >_test.rs\x00window_size 85\x00walk ~/src/koru-find\x00");
 
     cr.read().unwrap();
-    let (c, a) = cr.get_cmd().unwrap();
+    let (c, a) = split_cmd(cr.frame().unwrap()).unwrap();
     assert_eq!(c, "ignore");
     assert_eq!(a, ">_test.rs");
 
     cr.read().unwrap();
-    let (c, a) = cr.get_cmd().unwrap();
+    let (c, a) = split_cmd(cr.frame().unwrap()).unwrap();
     assert_eq!(c, "window_size");
     assert_eq!(a, "85");
 
     cr.read().unwrap();
-    let (c, a) = cr.get_cmd().unwrap();
+    let (c, a) = split_cmd(cr.frame().unwrap()).unwrap();
     assert_eq!(c, "walk");
     assert_eq!(a, "~/src/koru-find");
 
     assert_matches!(cr.read(), Err(walker::Error::Eof));
 }
 
+#[test]
+fn split_request_id() {
+    assert_eq!(split_request(b"#42 add foo"), (Some("42"), &b"add foo"[..]));
+    assert_eq!(split_request(b"add #42"), (None, &b"add #42"[..]));
+    assert_eq!(split_request(b"#x add"), (None, &b"#x add"[..]));
+    assert_eq!(split_request(b"# add"), (None, &b"# add"[..]));
+}
+
 #[test]
 fn exceed_window_size() {
     let (out_reader, out_writer) = pipe().unwrap();
@@ -178,3 +189,314 @@ fn run() {
 
     assert!(timeout_rx.recv_timeout(Duration::from_millis(500)).unwrap());
 }
+
+#[test]
+fn root_changed_rewalk() {
+    let base = env::temp_dir().join(format!("koru_find_root_changed_{}", process::id()));
+    let _ = fs::remove_dir_all(&base);
+    let root = base.join("root");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("x.txt"), "").unwrap();
+    let replacement = base.join("new");
+    fs::create_dir_all(&replacement).unwrap();
+    fs::write(replacement.join("y.txt"), "").unwrap();
+
+    let old = base.join("old");
+    let (out_reader, out_writer) = pipe().unwrap();
+    let (in_reader, mut in_writer) = pipe().unwrap();
+    let (timeout_tx, timeout_rx) = mpsc::channel();
+
+    let cmd = format!("root-watch 10 rewalk\x00walk {}\x00", root.display());
+    let _ = thread::spawn(move || {
+        let mut mr = MsgReader::new(out_reader);
+        let _ = in_writer.write(cmd.as_bytes()).unwrap();
+        assert_eq!(mr.read(), "started");
+        assert_eq!(mr.read(), "+x.txt");
+        assert_eq!(mr.read(), "done");
+
+        fs::rename(&root, old).unwrap();
+        fs::rename(&replacement, &root).unwrap();
+
+        let mut seen = vec![];
+        loop {
+            let msg = mr.read();
+            if msg == "+y.txt" {
+                break;
+            }
+            seen.push(msg);
+        }
+        assert!(seen.iter().any(|m| m.starts_with("root-changed ")));
+        assert!(seen.contains(&"clear".to_string()));
+        timeout_tx.send(true).unwrap();
+        drop(in_writer);
+    });
+
+    let _ = thread::spawn(move || super::run(4, in_reader, out_writer));
+
+    assert!(
+        timeout_rx
+            .recv_timeout(Duration::from_millis(2000))
+            .unwrap()
+    );
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn fatal_on_panic() {
+    struct Reader;
+    impl Read for Reader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            panic!("reader broke");
+        }
+    }
+
+    let (out_reader, out_writer) = pipe().unwrap();
+    let mut mr = MsgReader::new(out_reader);
+
+    assert_eq!(
+        super::run(4, Reader, out_writer),
+        Err(walker::Error::Panic("reader broke".to_string()))
+    );
+    assert_eq!(mr.read(), "fatal reader broke");
+    assert_eq!(mr.read(), "");
+}
+
+#[test]
+fn fatal_ends_server() {
+    struct Broken;
+    impl Scorer for Broken {
+        fn score(&self, _pattern: &crate::pattern::Pattern, _candidate: &[u8]) -> Option<Score> {
+            panic!("scorer broke");
+        }
+    }
+
+    let (out_reader, out_writer) = pipe().unwrap();
+    let (in_reader, mut in_writer) = pipe().unwrap();
+    let server = thread::spawn(move || {
+        ServerBuilder::new()
+            .threads(4)
+            .scorer(Broken)
+            .run(in_reader, out_writer)
+    });
+
+    let mut mr = MsgReader::new(out_reader);
+    let _ = in_writer
+        .write(b"min-score -1000\x00match abc\x00")
+        .unwrap();
+    assert_eq!(mr.read(), "fatal scorer broke");
+    // without waiting for the input to close
+    assert_eq!(
+        server.join().unwrap(),
+        Err(walker::Error::Panic("scorer broke".to_string()))
+    );
+    assert_eq!(mr.read(), "");
+}
+
+#[test]
+fn timing() {
+    let (out_reader, out_writer) = pipe().unwrap();
+    let (in_reader, mut in_writer) = pipe().unwrap();
+
+    let _ = thread::spawn(move || super::run(4, in_reader, out_writer));
+
+    let mut mr = MsgReader::new(out_reader);
+    // the sort is already by path so has no output to time
+    let _ = in_writer
+        .write(b"timing on\x00sort path\x00wlak test\x00")
+        .unwrap();
+
+    assert_eq!(mr.read(), "message unknown command: wlak");
+    assert!(mr.read().starts_with("message timing wlak "));
+}
+
+#[test]
+fn relay_rate_limited() {
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..110 {
+        tx.send(Msg::WalkDone).unwrap();
+    }
+    drop(tx);
+    let rate_limit = RateLimit::default();
+    rate_limit.set(Some(100));
+
+    let start = Instant::now();
+    let mut out = vec![];
+    relay_to_out(
+        rx,
+        &mut out,
+        None,
+        &Profile::default(),
+        &rate_limit,
+        &AtomicU32::new(2),
+    )
+    .unwrap();
+    // a burst of 100 then 10 more at 10ms apart
+    assert_eq!(out.len(), 110 * 5);
+    assert!(start.elapsed() >= Duration::from_millis(95));
+}
+
+#[test]
+fn relay_rate_limited_per_session() {
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..100 {
+        tx.send(Msg::Named {
+            name: "a".to_string(),
+            msg: Box::new(Msg::WalkDone),
+        })
+        .unwrap();
+    }
+    for _ in 0..100 {
+        tx.send(Msg::WalkDone).unwrap();
+    }
+    drop(tx);
+    let rate_limit = RateLimit::default();
+    rate_limit.set(Some(100));
+
+    let start = Instant::now();
+    let mut out = vec![];
+    relay_to_out(
+        rx,
+        &mut out,
+        None,
+        &Profile::default(),
+        &rate_limit,
+        &AtomicU32::new(2),
+    )
+    .unwrap();
+    // each session had its own burst of 100; sharing one would take a second
+    assert_eq!(out.len(), 100 * 8 + 100 * 5);
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert_eq!(session(&Msg::WalkDone), None);
+    assert_eq!(
+        session(&Msg::Request {
+            id: "1".to_string(),
+            msg: Box::new(Msg::Named {
+                name: "a".to_string(),
+                msg: Box::new(Msg::WalkDone)
+            })
+        }),
+        Some("a")
+    );
+}
+
+#[test]
+fn relay_stops_after_fatal() {
+    let (tx, rx) = mpsc::channel();
+    tx.send(Msg::WalkStarted).unwrap();
+    tx.send(Msg::Fatal("oops".to_string())).unwrap();
+    tx.send(Msg::WalkDone).unwrap();
+
+    let mut out = vec![];
+    assert_eq!(
+        relay_to_out(
+            rx,
+            &mut out,
+            None,
+            &Profile::default(),
+            &RateLimit::default(),
+            &AtomicU32::new(2)
+        )
+        .unwrap(),
+        Some("oops".to_string())
+    );
+    assert_eq!(out, b"started\x00fatal oops\x00");
+}
+
+#[test]
+fn relay_diagnostics() {
+    let (tx, rx) = mpsc::channel();
+    tx.send(Msg::WalkStarted).unwrap();
+    tx.send(Msg::Message("hello".to_string())).unwrap();
+    tx.send(Msg::AddFile(bytes::Bytes::from_static(b"a.rs")))
+        .unwrap();
+    tx.send(Msg::Warning {
+        kind: "fd-limit".to_string(),
+        detail: "low".to_string(),
+    })
+    .unwrap();
+    tx.send(Msg::Fatal("oops".to_string())).unwrap();
+
+    let (mut out, mut diag) = (vec![], vec![]);
+    relay_to_out(
+        rx,
+        &mut out,
+        Some(&mut diag),
+        &Profile::default(),
+        &RateLimit::default(),
+        &AtomicU32::new(2),
+    )
+    .unwrap();
+    assert_eq!(out, b"started\x00+a.rs\x00");
+    assert_eq!(diag, b"message hello\nwarning fd-limit low\nfatal oops\n");
+}
+
+#[test]
+fn invalid_command_reported() {
+    let (out_reader, out_writer) = pipe().unwrap();
+    let (in_reader, mut in_writer) = pipe().unwrap();
+
+    let _ = thread::spawn(move || super::run(4, in_reader, out_writer));
+
+    let mut mr = MsgReader::new(out_reader);
+    let _ = in_writer.write(b"rm x\x00wlak test\x00").unwrap();
+
+    assert_eq!(
+        mr.read(),
+        r#"message invalid argument: rm amount: expected a number; got "x""#
+    );
+    assert_eq!(mr.read(), "message unknown command: wlak");
+}
+
+#[test]
+fn builder_scorer() {
+    struct No2;
+    impl Scorer for No2 {
+        fn score(&self, pattern: &crate::pattern::Pattern, candidate: &[u8]) -> Option<Score> {
+            (!candidate.contains(&b'2'))
+                .then(|| FuzzyScorer.score(pattern, candidate))
+                .flatten()
+        }
+    }
+
+    let (out_reader, out_writer) = pipe().unwrap();
+    let (in_reader, mut in_writer) = pipe().unwrap();
+
+    let _ = thread::spawn(move || {
+        ServerBuilder::new()
+            .threads(4)
+            .scorer(No2)
+            .run(in_reader, out_writer)
+    });
+
+    let mut mr = MsgReader::new(out_reader);
+    let _ = in_writer
+        .write(b"min-score -1000\x00add a\x00match a2\x00match a1\x00match b1\x00")
+        .unwrap();
+    assert_eq!(mr.read(), "+a1");
+    let _ = in_writer.write(b"redraw\x00").unwrap();
+    assert_eq!(mr.read(), "clear");
+    assert_eq!(mr.read(), "+a1");
+}
+
+#[test]
+fn builder_diagnostics() {
+    let (out_reader, out_writer) = pipe().unwrap();
+    let (in_reader, mut in_writer) = pipe().unwrap();
+    let (diag_reader, diag_writer) = pipe().unwrap();
+
+    let _ = thread::spawn(move || {
+        ServerBuilder::new()
+            .threads(4)
+            .diagnostics(diag_writer)
+            .run(in_reader, out_writer)
+    });
+
+    let _ = in_writer.write(b"wlak test\x00match abc\x00").unwrap();
+    let mut line = String::new();
+    io::BufReader::new(diag_reader)
+        .read_line(&mut line)
+        .unwrap();
+    assert_eq!(line, "message unknown command: wlak\n");
+    let mut mr = MsgReader::new(out_reader);
+    assert_eq!(mr.read(), "+abc");
+}
@@ -0,0 +1,258 @@
+use std::{
+    ffi::{CString, OsStr},
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    str::FromStr,
+    sync::Once,
+    time::{Instant, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+
+/// One of the keys the window orders its entries by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Raw byte ordering of the path.
+    Path,
+    /// Runs of digits compare numerically so `file2` comes before `file10`.
+    Natural,
+    /// Collate using the `LC_COLLATE` locale of the environment.
+    Locale,
+    /// Most recently modified first.
+    Mtime,
+    /// Best scoring match of the query first, so a full window keeps the best matches found.
+    Score,
+    /// Shortest path first.
+    Len,
+}
+impl FromStr for SortKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Self::Path),
+            "natural" => Ok(Self::Natural),
+            "locale" => Ok(Self::Locale),
+            "mtime" => Ok(Self::Mtime),
+            "score" => Ok(Self::Score),
+            "len" => Ok(Self::Len),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How the window orders its entries: by each key in turn, such as `mtime,path`. The path is
+/// always the last key, as paths are unique, so keys after it are dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    keys: Box<[SortKey]>,
+}
+impl Default for Order {
+    fn default() -> Self {
+        SortKey::Path.into()
+    }
+}
+impl From<SortKey> for Order {
+    fn from(key: SortKey) -> Self {
+        [key].into_iter().collect()
+    }
+}
+impl FromIterator<SortKey> for Order {
+    fn from_iter<T: IntoIterator<Item = SortKey>>(iter: T) -> Self {
+        let mut keys = vec![];
+        for key in iter {
+            keys.push(key);
+            if key == SortKey::Path {
+                break;
+            }
+        }
+        if keys.last() != Some(&SortKey::Path) {
+            keys.push(SortKey::Path);
+        }
+        Self { keys: keys.into() }
+    }
+}
+impl FromStr for Order {
+    type Err = ();
+
+    /// Parse a comma separated list of keys.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').map(str::parse).collect()
+    }
+}
+impl Order {
+    #[inline(always)]
+    pub fn has(&self, key: SortKey) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Whether an entry's keys are built from its path alone, so can be built again to find it;
+    /// the file's mtime or query may have changed since.
+    pub fn is_by_path(&self) -> bool {
+        !self.has(SortKey::Mtime) && !self.has(SortKey::Score)
+    }
+
+    /// Build the window entry for `path`, relative to `root`; entries compare by their sort keys
+    /// and then by path. The score key depends on the query, so is left to `set_score`.
+    pub fn entry(&self, path: Bytes, root: &Path) -> Entry {
+        let key = self
+            .keys
+            .iter()
+            .filter_map(|key| match key {
+                SortKey::Path => None,
+                SortKey::Natural => Some(natural_key(&path)),
+                SortKey::Locale => Some(locale_key(&path)),
+                SortKey::Mtime => Some(mtime_key(&root.join(OsStr::from_bytes(&path)))),
+                SortKey::Score => Some(Box::default()),
+                SortKey::Len => Some(Box::new((path.len() as u64).to_be_bytes()) as Box<[u8]>),
+            })
+            .collect();
+        Entry {
+            rank: 0,
+            key,
+            path,
+            fields: Bytes::new(),
+            verified: Instant::now(),
+            stale: false,
+        }
+    }
+
+    /// Key `entry` on its match `score`, for the score key.
+    pub fn set_score(&self, entry: &mut Entry, score: Option<i64>) {
+        // the path is last and has no key of its own
+        let slots = self.keys.iter().filter(|k| **k != SortKey::Path);
+        for (slot, key) in entry.key.iter_mut().zip(slots) {
+            if *key == SortKey::Score {
+                *slot = score_key(score);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Lower ranks sort first, before the order's keys.
+    pub rank: i64,
+    key: Box<[Box<[u8]>]>,
+    pub path: Bytes,
+    /// Metadata sent after the path, each field preceded by a `FIELD_SEP`, so an empty field
+    /// still holds its place; not part of the entry's identity.
+    pub fields: Bytes,
+    /// When a walk last found the path.
+    pub verified: Instant,
+    /// Not found by a walk for longer than the stale age; sent as a trailing `stale` field.
+    pub stale: bool,
+}
+impl Entry {
+    /// The path followed by the fields, if there are any, and a `stale` field after them.
+    pub fn to_bytes(&self) -> Bytes {
+        if self.fields.is_empty() && !self.stale {
+            return self.path.clone();
+        }
+        let mut data = Vec::with_capacity(self.path.len() + self.fields.len() + 6);
+        data.extend_from_slice(&self.path);
+        data.extend_from_slice(&self.fields);
+        if self.stale {
+            data.push(FIELD_SEP);
+            data.extend_from_slice(b"stale");
+        }
+        data.into()
+    }
+}
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank == other.rank && self.key == other.key && self.path == other.path
+    }
+}
+impl Eq for Entry {}
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.rank, &self.key, &self.path).cmp(&(other.rank, &other.key, &other.path))
+    }
+}
+
+/// Separates a path from its metadata fields in `AddFile` messages.
+pub const FIELD_SEP: u8 = 0x1f;
+
+/// Encode `path` so that byte comparison of the result is a natural ordering. Each run of digits
+/// is replaced by a `0` marker, the length of the run without leading zeros and then the
+/// significant digits; so longer numbers sort after shorter ones.
+fn natural_key(path: &[u8]) -> Box<[u8]> {
+    let mut key = Vec::with_capacity(path.len() + 8);
+    let mut rest = path;
+    while let Some(&c) = rest.first() {
+        if c.is_ascii_digit() {
+            let len = rest
+                .iter()
+                .position(|c| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (digits, tail) = rest.split_at(len);
+            let zeros = digits.iter().position(|c| *c != b'0').unwrap_or(len);
+            let digits = &digits[zeros..];
+            key.push(b'0');
+            key.extend_from_slice(&(digits.len() as u32).to_be_bytes());
+            key.extend_from_slice(digits);
+            rest = tail;
+        } else {
+            key.push(c);
+            rest = &rest[1..];
+        }
+    }
+    key.into_boxed_slice()
+}
+
+/// Encode the modification time of `path` so that byte comparison puts the most recent first.
+/// Paths that can't be read sort last.
+fn mtime_key(path: &Path) -> Box<[u8]> {
+    let nanos = fs::symlink_metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |age| age.as_nanos() as u64);
+    Box::new((!nanos).to_be_bytes())
+}
+
+/// Encode the match `score` so that byte comparison puts the best first. `None`, no match, sorts
+/// last.
+fn score_key(score: Option<i64>) -> Box<[u8]> {
+    let biased = score.map_or(0, |s| (s as u64) ^ (1 << 63));
+    Box::new((!biased).to_be_bytes())
+}
+
+/// Transform `path` with `strxfrm` so byte comparison of the result follows the locale's
+/// collation rules. Falls back to the raw path if the transform fails.
+fn locale_key(path: &[u8]) -> Box<[u8]> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe {
+        libc::setlocale(libc::LC_COLLATE, c"".as_ptr());
+    });
+
+    let Ok(src) = CString::new(path) else {
+        return path.into();
+    };
+    let mut buf = vec![0u8; path.len() * 4 + 1];
+    loop {
+        let n = unsafe {
+            libc::strxfrm(
+                buf.as_mut_ptr() as *mut libc::c_char,
+                src.as_ptr(),
+                buf.len(),
+            )
+        };
+        if n < buf.len() {
+            buf.truncate(n);
+            return buf.into_boxed_slice();
+        }
+        buf.resize(n + 1, 0);
+    }
+}
+
+#[cfg(test)]
+#[path = "order_test.rs"]
+mod test;
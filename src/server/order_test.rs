@@ -0,0 +1,166 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+fn sorted(order: &str, paths: &[&'static str]) -> Vec<String> {
+    let order: Order = order.parse().unwrap();
+    let mut entries: Vec<_> = paths
+        .iter()
+        .map(|p| order.entry(Bytes::from_static(p.as_bytes()), Path::new("")))
+        .collect();
+    entries.sort();
+    entries
+        .iter()
+        .map(|e| String::from_utf8_lossy(&e.path).to_string())
+        .collect()
+}
+
+#[test]
+fn from_str() {
+    assert_eq!("path".parse(), Ok(SortKey::Path));
+    assert_eq!("natural".parse(), Ok(SortKey::Natural));
+    assert_eq!("locale".parse(), Ok(SortKey::Locale));
+    assert_eq!("mtime".parse(), Ok(SortKey::Mtime));
+    assert_eq!("score".parse(), Ok(SortKey::Score));
+    assert_eq!("len".parse(), Ok(SortKey::Len));
+    assert_eq!("size".parse::<SortKey>(), Err(()));
+
+    assert_eq!("path".parse(), Ok(Order::default()));
+    assert_eq!("natural".parse(), Ok(Order::from(SortKey::Natural)));
+    assert_eq!("natural,path".parse(), Ok(Order::from(SortKey::Natural)));
+    assert_eq!(
+        "score,len,path,mtime".parse(),
+        Ok(Order::from_iter([SortKey::Score, SortKey::Len]))
+    );
+    assert_eq!("score,size".parse::<Order>(), Err(()));
+    assert_eq!("score,".parse::<Order>(), Err(()));
+}
+
+#[test]
+fn path() {
+    assert_eq!(
+        sorted("path", &["file10", "file2", "File3", "file1"]),
+        ["File3", "file1", "file10", "file2"]
+    );
+}
+
+#[test]
+fn natural() {
+    assert_eq!(
+        sorted(
+            "natural",
+            &[
+                "file10", "file2", "file1a", "file1", "file01", "a/9/x", "a/10", "file", "filea"
+            ]
+        ),
+        [
+            "a/9/x", "a/10", "file", "file01", "file1", "file1a", "file2", "file10", "filea"
+        ]
+    );
+}
+
+#[test]
+fn natural_large_numbers() {
+    assert_eq!(
+        sorted("natural", &["v123456789012345678901", "v99", "v0000100"]),
+        ["v99", "v0000100", "v123456789012345678901"]
+    );
+}
+
+#[test]
+fn locale() {
+    // the collation rules depend on the environment but plain ascii letters agree everywhere
+    assert_eq!(sorted("locale", &["c", "a", "b"]), ["a", "b", "c"]);
+}
+
+#[test]
+fn mtime() {
+    let dir = std::env::temp_dir().join(format!("koru_find_order_mtime_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (name, secs) in [("old", 1_000), ("new", 3_000), ("mid", 2_000)] {
+        let file = fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(secs))
+            .unwrap();
+    }
+    let mut entries: Vec<_> = ["old", "missing", "new", "mid"]
+        .iter()
+        .map(|p| Order::from(SortKey::Mtime).entry(Bytes::from_static(p.as_bytes()), &dir))
+        .collect();
+    entries.sort();
+    let paths: Vec<_> = entries.iter().map(|e| e.path.as_ref()).collect();
+    assert_eq!(paths, [&b"new"[..], b"mid", b"old", b"missing"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn score() {
+    let order = Order::from(SortKey::Score);
+    let mut entries: Vec<_> = [
+        ("none", None),
+        ("low", Some(-5)),
+        ("high", Some(40)),
+        ("mid", Some(0)),
+    ]
+    .into_iter()
+    .map(|(p, score)| {
+        let mut entry = order.entry(Bytes::from_static(p.as_bytes()), Path::new(""));
+        order.set_score(&mut entry, score);
+        entry
+    })
+    .collect();
+    entries.sort();
+    let paths: Vec<_> = entries.iter().map(|e| e.path.as_ref()).collect();
+    assert_eq!(paths, [b"high".as_ref(), b"mid", b"low", b"none"]);
+}
+
+#[test]
+fn len() {
+    assert_eq!(
+        sorted("len,path", &["bb", "c", "aa", "a/b"]),
+        ["c", "aa", "bb", "a/b"]
+    );
+    assert_eq!(
+        sorted("len,natural", &["f10", "f9", "f1", "f100"]),
+        ["f1", "f9", "f10", "f100"]
+    );
+}
+
+#[test]
+fn secondary_keys() {
+    let order: Order = "score,len".parse().unwrap();
+    let mut entries: Vec<_> = [
+        ("ccc", Some(1)),
+        ("bbbb", Some(1)),
+        ("a", Some(0)),
+        ("dd", Some(1)),
+    ]
+    .into_iter()
+    .map(|(p, score)| {
+        let mut entry = order.entry(Bytes::from_static(p.as_bytes()), Path::new(""));
+        order.set_score(&mut entry, score);
+        entry
+    })
+    .collect();
+    entries.sort();
+    let paths: Vec<_> = entries.iter().map(|e| e.path.as_ref()).collect();
+    assert_eq!(paths, [b"dd".as_ref(), b"ccc", b"bbbb", b"a"]);
+}
+
+#[test]
+fn fields() {
+    let mut entry = Order::default().entry(Bytes::from_static(b"a/b"), Path::new(""));
+    assert_eq!(entry.to_bytes(), &b"a/b"[..]);
+    entry.fields = Bytes::from_static(b"\x1f/");
+    assert_eq!(entry.to_bytes(), &b"a/b\x1f/"[..]);
+    assert_eq!(
+        entry,
+        Order::default().entry(Bytes::from_static(b"a/b"), Path::new(""))
+    );
+    entry.stale = true;
+    assert_eq!(entry.to_bytes(), &b"a/b\x1f/\x1fstale"[..]);
+    // an empty classify field keeps the marker out of its place
+    entry.fields = Bytes::from_static(b"\x1f");
+    assert_eq!(entry.to_bytes(), &b"a/b\x1f\x1fstale"[..]);
+    entry.fields = Bytes::new();
+    assert_eq!(entry.to_bytes(), &b"a/b\x1fstale"[..]);
+}
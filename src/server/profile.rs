@@ -0,0 +1,163 @@
+use std::{
+    fmt::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Walk,
+    Ignore,
+    Match,
+    Lock,
+    Output,
+}
+impl Stage {
+    const ALL: [Stage; 5] = [
+        Stage::Walk,
+        Stage::Ignore,
+        Stage::Match,
+        Stage::Lock,
+        Stage::Output,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Walk => "walk",
+            Stage::Ignore => "ignore",
+            Stage::Match => "match",
+            Stage::Lock => "lock",
+            Stage::Output => "output",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfileAction {
+    Start,
+    Stop,
+    Dump,
+}
+
+/// Time spent in each stage of the most recent walk while profiling is on.
+#[derive(Default)]
+pub struct Profile {
+    enabled: AtomicBool,
+    nanos: [AtomicU64; 5],
+    counts: [AtomicU64; 5],
+    timing: AtomicBool,
+    /// While timing: the last command received, when and its number, until its first output.
+    awaiting: Mutex<Option<(String, Instant, u64)>>,
+    /// The number of commands received.
+    received: AtomicU64,
+}
+impl Profile {
+    pub fn start(&self) {
+        self.reset();
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        for stage in Stage::ALL {
+            self.nanos[stage as usize].store(0, Ordering::Relaxed);
+            self.counts[stage as usize].store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Run `f`, counting its time against `stage` when profiling.
+    #[inline]
+    pub fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        if !self.is_enabled() {
+            return f();
+        }
+        let start = Instant::now();
+        let result = f();
+        self.add(stage, start.elapsed());
+        result
+    }
+
+    pub fn add(&self, stage: Stage, time: Duration) {
+        self.nanos[stage as usize].fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+        self.counts[stage as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn total(&self, stage: Stage) -> (Duration, u64) {
+        (
+            Duration::from_nanos(self.nanos[stage as usize].load(Ordering::Relaxed)),
+            self.counts[stage as usize].load(Ordering::Relaxed),
+        )
+    }
+
+    /// Report how long each command takes to produce its first output.
+    pub fn set_timing(&self, on: bool) {
+        self.timing.store(on, Ordering::Relaxed);
+        *self.awaiting() = None;
+    }
+
+    pub fn is_timing(&self) -> bool {
+        self.timing.load(Ordering::Relaxed)
+    }
+
+    /// Note the command `cmd` was received, returning its number for `command_done`.
+    pub fn received(&self, cmd: &str) -> u64 {
+        let number = self.received.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.is_timing() {
+            *self.awaiting() = Some((cmd.to_string(), Instant::now(), number));
+        }
+        number
+    }
+
+    /// The command `number` has finished and all its output is written, so later output is not
+    /// its.
+    pub fn command_done(&self, number: u64) {
+        let mut awaiting = self.awaiting();
+        if awaiting.as_ref().is_some_and(|(.., n)| *n == number) {
+            *awaiting = None;
+        }
+    }
+
+    /// Called after each output; the first after a command is received gives `timing walk
+    /// 0.412ms`, the time from receiving the command to its output.
+    pub fn output(&self) -> Option<String> {
+        let (cmd, at, _) = self.awaiting().take()?;
+        Some(format!(
+            "timing {cmd} {:.3}ms",
+            at.elapsed().as_secs_f64() * 1000.0
+        ))
+    }
+
+    fn awaiting(&self) -> std::sync::MutexGuard<'_, Option<(String, Instant, u64)>> {
+        self.awaiting.lock().expect(crate::LOCK_SHOULD_BE_OK)
+    }
+
+    /// `profile walk 1.200ms/1 ignore 0.031ms/40 ...` giving the total time and number of
+    /// calls for each stage.
+    pub fn report(&self) -> String {
+        let mut report = "profile".to_string();
+        for stage in Stage::ALL {
+            let (time, count) = self.total(stage);
+            let _ = write!(
+                report,
+                " {} {:.3}ms/{count}",
+                stage.name(),
+                time.as_secs_f64() * 1000.0
+            );
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+#[path = "profile_test.rs"]
+mod test;
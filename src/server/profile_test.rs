@@ -0,0 +1,55 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn time() {
+    let profile = Profile::default();
+    assert_eq!(profile.time(Stage::Match, || 3), 3);
+    assert_eq!(profile.total(Stage::Match), (Duration::ZERO, 0));
+
+    profile.start();
+    profile.time(Stage::Match, || {});
+    profile.add(Stage::Walk, Duration::from_micros(1500));
+    assert_eq!(profile.total(Stage::Match).1, 1);
+    assert_eq!(profile.total(Stage::Walk), (Duration::from_micros(1500), 1));
+    assert!(
+        profile
+            .report()
+            .starts_with("profile walk 1.500ms/1 ignore 0.000ms/0 match ")
+    );
+
+    profile.stop();
+    profile.time(Stage::Match, || {});
+    assert_eq!(profile.total(Stage::Match).1, 1);
+
+    profile.start();
+    assert_eq!(profile.total(Stage::Walk), (Duration::ZERO, 0));
+}
+
+#[test]
+fn timing() {
+    let profile = Profile::default();
+    profile.received("walk");
+    assert_eq!(profile.output(), None);
+
+    profile.set_timing(true);
+    profile.received("walk");
+    assert!(profile.output().unwrap().starts_with("timing walk 0."));
+    assert_eq!(profile.output(), None);
+
+    // later output is not that of a command which finished without any
+    let sort = profile.received("sort");
+    profile.command_done(sort);
+    assert_eq!(profile.output(), None);
+
+    // only that command's
+    let sort = profile.received("sort");
+    profile.received("walk");
+    profile.command_done(sort);
+    assert!(profile.output().unwrap().starts_with("timing walk 0."));
+
+    profile.set_timing(false);
+    profile.received("walk");
+    assert_eq!(profile.output(), None);
+}
@@ -0,0 +1,39 @@
+/// Caps on what one client may ask of its server, normally from the `[quotas]` section of the
+/// config file, so a greedy client can't starve others on the same machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quotas {
+    /// The most results the window may retain.
+    pub window_size: Option<usize>,
+    /// The most threads a walk may use.
+    pub walker_threads: Option<usize>,
+    /// How many of root-watch, git-watch, stale-after and fs-watch may be on at once.
+    pub watchers: Option<usize>,
+}
+impl Quotas {
+    /// Set the quota named `key` as it is written in the config file.
+    pub fn set(&mut self, key: &str, value: usize) -> Result<(), String> {
+        let quota = match key {
+            "window-size" => &mut self.window_size,
+            "walker-threads" => &mut self.walker_threads,
+            "watchers" => &mut self.watchers,
+            _ => return Err(format!("unknown quota {key:?}")),
+        };
+        *quota = Some(value);
+        Ok(())
+    }
+}
+
+/// `wanted` cut down to `quota`, with why if it was.
+pub fn within(name: &str, quota: Option<usize>, wanted: usize) -> (usize, Option<String>) {
+    match quota {
+        Some(max) if wanted > max => (
+            max,
+            Some(format!("{name} {wanted} is over the quota of {max}")),
+        ),
+        _ => (wanted, None),
+    }
+}
+
+#[cfg(test)]
+#[path = "quota_test.rs"]
+mod test;
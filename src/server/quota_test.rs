@@ -0,0 +1,35 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn set() {
+    let mut quotas = Quotas::default();
+    quotas.set("window-size", 100).unwrap();
+    quotas.set("watchers", 1).unwrap();
+    assert_eq!(
+        quotas,
+        Quotas {
+            window_size: Some(100),
+            watchers: Some(1),
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        quotas.set("threads", 2),
+        Err(r#"unknown quota "threads""#.to_string())
+    );
+}
+
+#[test]
+fn within_quota() {
+    assert_eq!(within("window_size", None, 500), (500, None));
+    assert_eq!(within("window_size", Some(500), 500), (500, None));
+    assert_eq!(
+        within("window_size", Some(100), 500),
+        (
+            100,
+            Some("window_size 500 is over the quota of 100".to_string())
+        )
+    );
+}
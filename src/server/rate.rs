@@ -0,0 +1,57 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Caps the messages per second written to the client for each of its sessions, so one busy
+/// connection can't hog the process it shares with others.
+#[derive(Debug, Default)]
+pub struct RateLimit {
+    /// 0 for no limit.
+    per_sec: AtomicU64,
+}
+impl RateLimit {
+    pub fn set(&self, per_sec: Option<u64>) {
+        self.per_sec.store(per_sec.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Option<u64> {
+        match self.per_sec.load(Ordering::Relaxed) {
+            0 => None,
+            n => Some(n),
+        }
+    }
+}
+
+/// A token bucket holding up to a second's worth of messages.
+#[derive(Debug)]
+pub struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            tokens: f64::INFINITY,
+            last: Instant::now(),
+        }
+    }
+}
+impl Bucket {
+    /// Take a token at `now`, returning how long to wait before sending the message.
+    pub fn take(&mut self, per_sec: u64, now: Instant) -> Duration {
+        let rate = per_sec as f64;
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "rate_test.rs"]
+mod test;
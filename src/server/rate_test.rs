@@ -0,0 +1,30 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn limit() {
+    let limit = RateLimit::default();
+    assert_eq!(limit.get(), None);
+    limit.set(Some(100));
+    assert_eq!(limit.get(), Some(100));
+    limit.set(None);
+    assert_eq!(limit.get(), None);
+}
+
+#[test]
+fn bucket() {
+    let mut bucket = Bucket::default();
+    let now = Instant::now();
+    for _ in 0..10 {
+        assert_eq!(bucket.take(10, now), Duration::ZERO);
+    }
+    assert_eq!(bucket.take(10, now), Duration::from_millis(100));
+
+    // the wait has been served, and half a second refills five tokens
+    let now = now + Duration::from_millis(600);
+    for _ in 0..5 {
+        assert_eq!(bucket.take(10, now), Duration::ZERO);
+    }
+    assert!(bucket.take(10, now) > Duration::ZERO);
+}
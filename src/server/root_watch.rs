@@ -0,0 +1,93 @@
+use std::{
+    fmt, fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use super::{walker::Signal, window::Window};
+
+/// How the walk root changed since it was last looked at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RootChange {
+    /// Deleted or renamed away.
+    Missing,
+    /// A different directory is now at the path, e.g. recreated by a checkout.
+    Replaced,
+    /// Back after being missing.
+    Restored,
+}
+impl fmt::Display for RootChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Missing => "missing",
+            Self::Replaced => "replaced",
+            Self::Restored => "restored",
+        })
+    }
+}
+impl FromStr for RootChange {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "missing" => Ok(Self::Missing),
+            "replaced" => Ok(Self::Replaced),
+            "restored" => Ok(Self::Restored),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootWatch {
+    pub period: Duration,
+    pub rewalk: bool,
+}
+impl RootWatch {
+    /// Check `root` every `period` until `done` is disconnected, reporting when it goes missing
+    /// or its device and inode change. If `rewalk` is set a replaced or restored root is walked
+    /// again by sending `Signal::Rewalk`.
+    pub fn spawn(
+        self,
+        root: PathBuf,
+        done: mpsc::Receiver<()>,
+        out: Window,
+        signals: Option<mpsc::Sender<Signal>>,
+    ) -> thread::JoinHandle<()> {
+        let mut last = identity(&root);
+        thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = done.recv_timeout(self.period) {
+                let current = identity(&root);
+                let change = match (last, current) {
+                    (a, b) if a == b => continue,
+                    (_, None) => RootChange::Missing,
+                    (None, Some(_)) => RootChange::Restored,
+                    (Some(_), Some(_)) => RootChange::Replaced,
+                };
+                last = current;
+                out.root_changed(change);
+                if self.rewalk
+                    && change != RootChange::Missing
+                    && let Some(signals) = &signals
+                {
+                    let _ = signals.send(Signal::Rewalk);
+                }
+            }
+        })
+    }
+}
+
+fn identity(root: &Path) -> Option<(u64, u64)> {
+    fs::metadata(root)
+        .ok()
+        .filter(|m| m.is_dir())
+        .map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(test)]
+#[path = "root_watch_test.rs"]
+mod test;
@@ -0,0 +1,74 @@
+use std::{env, process};
+
+use pretty_assertions::assert_matches;
+
+use super::*;
+use crate::server::walker::Msg;
+
+const WT: Duration = Duration::from_millis(500);
+
+#[test]
+fn removed_and_recreated() {
+    let root = env::temp_dir().join(format!("koru_find_root_watch_{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let (tx, rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let (signal_tx, signal_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let watch = RootWatch {
+        period: Duration::from_millis(10),
+        rewalk: true,
+    };
+    let t = watch.spawn(root.clone(), done_rx, out, Some(signal_tx));
+
+    fs::remove_dir(&root).unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::RootChanged(RootChange::Missing)
+    );
+    assert_matches!(signal_rx.try_recv(), Err(_));
+
+    fs::create_dir(&root).unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::RootChanged(RootChange::Restored)
+    );
+    assert_matches!(signal_rx.recv_timeout(WT), Ok(Signal::Rewalk));
+
+    drop(done_tx);
+    t.join().unwrap();
+    fs::remove_dir(&root).unwrap();
+}
+
+#[test]
+fn unchanged() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let watch = RootWatch {
+        period: Duration::from_millis(5),
+        rewalk: false,
+    };
+    let t = watch.spawn(env::temp_dir(), done_rx, out, None);
+    thread::sleep(Duration::from_millis(30));
+    drop(done_tx);
+    t.join().unwrap();
+
+    assert_matches!(rx.try_recv(), Err(_));
+}
+
+#[test]
+fn parse() {
+    assert_eq!(
+        Msg::parse(b"root-changed replaced"),
+        Some(Msg::RootChanged(RootChange::Replaced))
+    );
+    let mut out = vec![];
+    Msg::RootChanged(RootChange::Missing)
+        .write(&mut out)
+        .unwrap();
+    assert_eq!(out, b"root-changed missing\x00");
+}
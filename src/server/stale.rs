@@ -0,0 +1,30 @@
+use std::{path::PathBuf, sync::mpsc, thread, time::Duration};
+
+use super::window::Window;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Staleness {
+    /// How long since a walk last found an entry before it is stale.
+    pub age: Duration,
+    /// `stat` stale entries, removing the missing, instead of marking them.
+    pub verify: bool,
+}
+impl Staleness {
+    /// Check the entries of `out` every quarter of `age` until `done` is disconnected.
+    pub fn spawn(
+        self,
+        root: PathBuf,
+        done: mpsc::Receiver<()>,
+        out: Window,
+    ) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = done.recv_timeout(self.age / 4) {
+                out.check_stale(&root, self.age, self.verify);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+#[path = "stale_test.rs"]
+mod test;
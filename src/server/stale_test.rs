@@ -0,0 +1,73 @@
+use std::{env, fs, process};
+
+use bytes::Bytes;
+use pretty_assertions::assert_matches;
+
+use super::*;
+use crate::server::walker::{Msg, WalkerVersion};
+
+const WT: Duration = Duration::from_millis(500);
+
+fn window(names: &[&'static str]) -> (Window, mpsc::Receiver<Msg>) {
+    let (tx, rx) = mpsc::sync_channel(10);
+    let out = Window::new(5, tx);
+    let wv = WalkerVersion::default();
+    for name in names {
+        out.add(*name, 0, &wv).unwrap();
+    }
+    let _: Vec<_> = rx.try_iter().collect();
+    (out, rx)
+}
+
+#[test]
+fn marks_stale() {
+    let (out, rx) = window(&["a", "b"]);
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let staleness = Staleness {
+        age: Duration::from_millis(20),
+        verify: false,
+    };
+    let t = staleness.spawn(PathBuf::from("/nonexistent"), done_rx, out);
+
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"a\x1fstale"))
+    );
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"b\x1fstale"))
+    );
+    // marked only once
+    assert_matches!(rx.recv_timeout(Duration::from_millis(50)), Err(_));
+
+    drop(done_tx);
+    t.join().unwrap();
+}
+
+#[test]
+fn verify() {
+    let root = env::temp_dir().join(format!("koru_find_stale_{}", process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("a"), "").unwrap();
+    let (out, rx) = window(&["a", "b"]);
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let staleness = Staleness {
+        age: Duration::from_millis(20),
+        verify: true,
+    };
+    let t = staleness.spawn(root.clone(), done_rx, out.clone());
+
+    // the missing entry is removed and the one still there kept unmarked
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::RmFile(Bytes::from_static(b"b"))
+    );
+    assert_matches!(rx.recv_timeout(Duration::from_millis(50)), Err(_));
+    assert_eq!(out.paths(), [Bytes::from_static(b"a")]);
+
+    drop(done_tx);
+    t.join().unwrap();
+    let _ = fs::remove_dir_all(&root);
+}
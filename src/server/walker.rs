@@ -1,17 +1,57 @@
 use std::{
-    env, fs, io,
-    os::unix::ffi::OsStrExt,
-    path::PathBuf,
-    sync::{Arc, atomic, mpsc},
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fs, io, mem,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    },
+    panic::{self, AssertUnwindSafe},
+    path::{Component, Path, PathBuf},
+    process::{self, Stdio},
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock, atomic, mpsc},
     thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use bytes::Bytes;
-use ignore::{ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState};
+use ignore::{
+    ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState,
+    types::{Types, TypesBuilder},
+};
+use serde_json::{Value, json};
 
-use crate::pattern::{Pattern, PatternScope};
+use crate::{
+    clipboard, doctor,
+    hooks::{Event, Hooks},
+    macros::Macros,
+    pattern::{Pattern, PatternScope},
+};
 
-use super::window::Window;
+#[cfg(feature = "archives")]
+use super::archive;
+use super::{
+    command::{ArgError, Command, SessionAction},
+    expand,
+    export::{self, ExportFormat},
+    external::{ExternalMatcher, Feed, Process},
+    fd_budget::{FdBudget, WatchBudget},
+    frecency::Frecency,
+    fs_watch::{self, Change},
+    git::{self, IgnoreFiles},
+    head_watch::HeadWatch,
+    ignore_dirs::{self, IgnoreDirs},
+    index,
+    order::{FIELD_SEP, SortKey},
+    profile::{ProfileAction, Stage},
+    quota::{self, Quotas},
+    root_watch::{RootChange, RootWatch},
+    stale::Staleness,
+    watchdog::{self, Progress, Throttle, Watchdog},
+    window::Window,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
@@ -20,14 +60,22 @@ pub enum Error {
     Utf8Error,
     IoError(io::ErrorKind),
     Eof,
-    InvalidArgument,
+    InvalidArgument(ArgError),
     NotADirectory,
     UnknownCommand(String),
     CdInvalid,
+    /// The walk path could not be expanded, such as for an unset variable.
+    Expand(String),
+    Panic(String),
 }
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Error::InvalidArgument(err) => write!(f, "invalid argument: {err}"),
+            Error::UnknownCommand(cmd) => write!(f, "unknown command: {cmd}"),
+            Error::Expand(reason) => write!(f, "{reason}"),
+            _ => write!(f, "{self:?}"),
+        }
     }
 }
 impl std::error::Error for Error {}
@@ -41,12 +89,16 @@ impl Error {
 pub struct WalkerVersion {
     current_version: Arc<atomic::AtomicUsize>,
     my_version: usize,
+    /// The ID of the client request the walk answers, shared by the walk's clones so a request
+    /// that refines the walk, rather than starting another, can retag it.
+    request: Arc<RwLock<Option<Arc<str>>>>,
 }
 impl Default for WalkerVersion {
     fn default() -> Self {
         Self {
             current_version: Arc::new(1.into()),
             my_version: 1,
+            request: Default::default(),
         }
     }
 }
@@ -59,29 +111,169 @@ impl WalkerVersion {
         self.current_version.fetch_add(1, atomic::Ordering::Relaxed);
     }
 
+    /// Begin a new walk, untagged until `set_request`; clones made for a walk already killed keep
+    /// its request ID.
     pub fn start(&mut self) {
         self.my_version = self.current_version.load(atomic::Ordering::Relaxed);
+        self.request = Default::default();
+    }
+
+    pub fn request(&self) -> Option<Arc<str>> {
+        self.request.read().expect(crate::LOCK_SHOULD_BE_OK).clone()
+    }
+
+    pub fn set_request(&self, id: Option<Arc<str>>) {
+        *self.request.write().expect(crate::LOCK_SHOULD_BE_OK) = id;
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// The version of the protocol written by `Msg::write`. Version 1 has only `clear`, `done`,
+/// `+`, `-`, `started`, `message`, `resync` and `fatal`.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// A message from the server to the client. New kinds of message may be added, so clients should
+/// ignore those they don't know; `Msg::parse` returns `None` for them.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Msg {
     Clear,
     WalkDone,
+    /// The walk was stopped by its time budget, so the window may lack matches; protocol 1
+    /// clients get a plain `done`.
+    WalkTruncated,
+    /// A path, followed by `\x1f` and its metadata fields when there are any.
     AddFile(Bytes),
     RmFile(Bytes),
     WalkStarted,
     Message(String),
+    /// Something the client may want to show; `kind` is a single word such as `complex-query`.
+    Warning {
+        kind: String,
+        detail: String,
+    },
     Resync,
+    RootChanged(RootChange),
+    /// How far a walk has got, sent periodically while it runs.
+    Progress {
+        scanned: usize,
+        matched: usize,
+    },
+    /// How many entries the walk has matched so far, including those not in the window.
+    Count(usize),
+    /// The totals of a walk, sent after its `WalkDone`.
+    WalkStats {
+        scanned: usize,
+        matched: usize,
+        elapsed: Duration,
+    },
+    /// Brackets the `AddFile`s of a snapshot of the whole window, numbered from 1.
+    SnapshotBegin(u64),
+    SnapshotEnd(u64),
+    /// The protocol version in use, in reply to the `protocol` command.
+    Protocol(u32),
+    /// A message added by a fork or plugin; `name` has no spaces.
+    Extension {
+        name: String,
+        payload: Bytes,
+    },
+    Fatal(String),
+    /// A message about the pattern addressed as `@name`, written as `@name` and a space before
+    /// the message.
+    Named {
+        name: String,
+        msg: Box<Msg>,
+    },
+    /// A message sent after the command given the request `#id`, written as `#id` and a space
+    /// before the message.
+    Request {
+        id: String,
+        msg: Box<Msg>,
+    },
+    /// Follows the output of the command numbered by `Profile::received` when it left nothing
+    /// running, so timing stops waiting for more; never written.
+    CommandDone(u64),
 }
 impl Msg {
     pub(crate) fn write(&self, out: &mut impl io::Write) -> Result<(), io::Error> {
+        self.write_version(out, PROTOCOL_VERSION)
+    }
+
+    /// Write the message as understood by clients of protocol `version`; for version 1, messages
+    /// it lacks are sent as a `message` holding their text.
+    pub(crate) fn write_version(
+        &self,
+        out: &mut impl io::Write,
+        version: u32,
+    ) -> Result<(), io::Error> {
+        if let Msg::Named { name, msg } = self {
+            out.write_all(format!("@{name} ").as_bytes())?;
+            return msg.write_version(out, version);
+        }
+        if let Msg::Request { id, msg } = self {
+            out.write_all(format!("#{id} ").as_bytes())?;
+            return msg.write_version(out, version);
+        }
+        if matches!(self, Msg::CommandDone(_)) {
+            return Ok(());
+        }
+        if version < 2 && matches!(self, Msg::WalkTruncated) {
+            return Msg::WalkDone.write_version(out, version);
+        }
+        let basic = matches!(
+            self,
+            Msg::Clear
+                | Msg::WalkDone
+                | Msg::AddFile(_)
+                | Msg::RmFile(_)
+                | Msg::WalkStarted
+                | Msg::Message(_)
+                | Msg::Resync
+                | Msg::Fatal(_)
+        );
+        if version < 2 && !basic {
+            let mut text = vec![];
+            self.write(&mut text)?;
+            text.pop();
+            out.write_all(b"message ")?;
+            out.write_all(&text)?;
+            return out.write_all(b"\x00");
+        }
         match self {
             Msg::Clear => out.write_all(b"clear\x00")?,
             Msg::WalkDone => out.write_all(b"done\x00")?,
+            Msg::WalkTruncated => out.write_all(b"done truncated\x00")?,
             Msg::WalkStarted => out.write_all(b"started\x00")?,
             Msg::Resync => out.write_all(b"resync\x00")?,
             Msg::Message(m) => out.write_all(format!("message {m}\x00").as_bytes())?,
+            Msg::Fatal(m) => out.write_all(format!("fatal {m}\x00").as_bytes())?,
+            Msg::Warning { kind, detail } => {
+                out.write_all(format!("warning {kind} {detail}\x00").as_bytes())?
+            }
+            Msg::SnapshotBegin(n) => out.write_all(format!("snapshot-begin {n}\x00").as_bytes())?,
+            Msg::SnapshotEnd(n) => out.write_all(format!("snapshot-end {n}\x00").as_bytes())?,
+            Msg::Protocol(v) => out.write_all(format!("protocol {v}\x00").as_bytes())?,
+            Msg::Progress { scanned, matched } => out.write_all(
+                format!("progress scanned={scanned} matched={matched}\x00").as_bytes(),
+            )?,
+            Msg::Count(n) => out.write_all(format!("count {n}\x00").as_bytes())?,
+            Msg::WalkStats {
+                scanned,
+                matched,
+                elapsed,
+            } => out.write_all(
+                format!(
+                    "stats scanned={scanned} matched={matched} ms={}\x00",
+                    elapsed.as_millis()
+                )
+                .as_bytes(),
+            )?,
+            Msg::Extension { name, payload } => {
+                out.write_all(format!("ext {name} ").as_bytes())?;
+                out.write_all(payload)?;
+                out.write_all(b"\x00")?
+            }
+            Msg::RootChanged(c) => out.write_all(format!("root-changed {c}\x00").as_bytes())?,
+            Msg::Named { .. } | Msg::Request { .. } | Msg::CommandDone(_) => unreachable!(),
             Msg::AddFile(msg) => {
                 out.write_all(b"+")?;
                 out.write_all(msg)?;
@@ -95,65 +287,626 @@ impl Msg {
         }
         Ok(())
     }
+
+    /// Parse one message, without its NUL terminator, as written by `write`.
+    pub fn parse(data: &[u8]) -> Option<Msg> {
+        let text = || String::from_utf8_lossy(data).to_string();
+        Some(match data {
+            b"clear" => Msg::Clear,
+            b"done" => Msg::WalkDone,
+            b"done truncated" => Msg::WalkTruncated,
+            b"started" => Msg::WalkStarted,
+            b"resync" => Msg::Resync,
+            [b'+', path @ ..] => Msg::AddFile(Bytes::copy_from_slice(path)),
+            [b'-', path @ ..] => Msg::RmFile(Bytes::copy_from_slice(path)),
+            _ if data.starts_with(b"message ") => Msg::Message(text()[8..].to_string()),
+            _ if data.starts_with(b"fatal ") => Msg::Fatal(text()[6..].to_string()),
+            _ if data.starts_with(b"warning ") => {
+                let text = text();
+                let (kind, detail) = text[8..].split_once(' ').unwrap_or((&text[8..], ""));
+                Msg::Warning {
+                    kind: kind.to_string(),
+                    detail: detail.to_string(),
+                }
+            }
+            _ if data.starts_with(b"snapshot-begin ") => {
+                Msg::SnapshotBegin(text()[15..].parse().ok()?)
+            }
+            _ if data.starts_with(b"snapshot-end ") => Msg::SnapshotEnd(text()[13..].parse().ok()?),
+            _ if data.starts_with(b"protocol ") => Msg::Protocol(text()[9..].parse().ok()?),
+            _ if data.starts_with(b"progress ") => {
+                let text = text();
+                let counts = counts(&text[9..])?;
+                Msg::Progress {
+                    scanned: *counts.get("scanned")?,
+                    matched: *counts.get("matched")?,
+                }
+            }
+            _ if data.starts_with(b"count ") => Msg::Count(text()[6..].parse().ok()?),
+            _ if data.starts_with(b"stats ") => {
+                let text = text();
+                let counts = counts(&text[6..])?;
+                Msg::WalkStats {
+                    scanned: *counts.get("scanned")?,
+                    matched: *counts.get("matched")?,
+                    elapsed: Duration::from_millis(*counts.get("ms")? as u64),
+                }
+            }
+            [b'e', b'x', b't', b' ', rest @ ..] => {
+                let end = rest.iter().position(|c| *c == b' ').unwrap_or(rest.len());
+                Msg::Extension {
+                    name: String::from_utf8_lossy(&rest[..end]).to_string(),
+                    payload: Bytes::copy_from_slice(rest.get(end + 1..).unwrap_or_default()),
+                }
+            }
+            _ if data.starts_with(b"root-changed ") => Msg::RootChanged(text()[13..].parse().ok()?),
+            [b'@', rest @ ..] => {
+                let end = rest.iter().position(|c| *c == b' ')?;
+                Msg::Named {
+                    name: String::from_utf8_lossy(&rest[..end]).to_string(),
+                    msg: Box::new(Msg::parse(&rest[end + 1..])?),
+                }
+            }
+            [b'#', rest @ ..] => {
+                let end = rest.iter().position(|c| *c == b' ')?;
+                Msg::Request {
+                    id: String::from_utf8_lossy(&rest[..end]).to_string(),
+                    msg: Box::new(Msg::parse(&rest[end + 1..])?),
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The `name=number` pairs of a message such as `progress`.
+fn counts(text: &str) -> Option<HashMap<&str, usize>> {
+    text.split(' ')
+        .map(|pair| {
+            let (name, n) = pair.split_once('=')?;
+            Some((name, n.parse().ok()?))
+        })
+        .collect()
+}
+
+/// The most entries each walk thread holds back, with `shallow-first`, before it sends those
+/// nearest the root.
+const SHALLOW_BUFFER: usize = 256;
+
+/// Work for the thread that owns the `Walker`.
+#[derive(Debug)]
+pub enum Signal {
+    /// NUL terminated commands from the client, without their terminators.
+    Commands(Vec<Vec<u8>>),
+    /// Walk the root again, as requested by a watcher.
+    Rewalk,
+    /// Walk the root again reporting only what was added or removed.
+    Refresh,
+    Quit,
 }
 
 struct Visitor {
     out: Window,
-    pattern: Pattern,
     ignore_pattern: Pattern,
     walker_version: WalkerVersion,
-    dir_len: usize,
+    progress: Arc<Progress>,
+    options: Arc<WalkOptions>,
+    links: Arc<Mutex<HashMap<(u64, u64), Bytes>>>,
+    feed: Option<Arc<Feed>>,
+    failed: Arc<Mutex<Vec<PathBuf>>>,
+    ignore_dirs: Arc<IgnoreDirs>,
+    root: Bytes,
+    candidates: Arc<Mutex<Candidates>>,
+    gather: bool,
+    max_scan: Option<usize>,
+    throttle: Option<Throttle>,
+    /// The time spent waiting for room in the window during the current visit.
+    waited: Cell<Duration>,
+    shallow_first: bool,
+    count: bool,
+    /// The wanted entries held back by depth, with `shallow_first`, to be offered shallowest
+    /// first.
+    held: BTreeMap<usize, Vec<ignore::DirEntry>>,
+    /// The entries this visitor found, added to `candidates` when it is dropped.
+    found: Vec<Candidate>,
+}
+impl Visitor {
+    /// Add `path` to the window if it matches; `fields` are only gathered for matches.
+    fn offer(
+        &self,
+        path: &Path,
+        fields: impl FnOnce() -> Bytes,
+        inode: Option<(u64, u64)>,
+    ) -> WalkState {
+        let data = relative(&self.root, path);
+        let profile = self.out.profile();
+        if profile.time(Stage::Ignore, || self.ignore_pattern.any_matches(data)) {
+            return WalkState::Continue;
+        }
+        if let Some(feed) = &self.feed {
+            return if feed.write(data) {
+                WalkState::Continue
+            } else {
+                WalkState::Quit
+            };
+        }
+        let version = self.out.version(); // get before test
+        if !profile.time(Stage::Match, || self.out.matches(data)) {
+            return WalkState::Continue;
+        }
+        let matched = self.progress.found();
+        if self.count && self.progress.count_due(matched) {
+            self.out.count(matched);
+        }
+        if let Some(inode) = inode {
+            let replaced = {
+                let mut seen = self.links.lock().expect(crate::LOCK_SHOULD_BE_OK);
+                if seen
+                    .get(&inode)
+                    .is_some_and(|kept| (kept.len(), kept.as_ref()) <= (data.len(), data))
+                {
+                    return WalkState::Continue;
+                }
+                seen.insert(inode, Bytes::copy_from_slice(data))
+            };
+            if let Some(kept) = replaced {
+                let _ = self.out.remove(kept, self.out.version());
+            }
+        }
+        let start = (self.throttle.is_some() && self.out.is_full()).then(Instant::now);
+        let added = self.out.add_with_fields(
+            Bytes::copy_from_slice(data),
+            fields(),
+            version,
+            &self.walker_version,
+        );
+        if let Some(start) = start {
+            self.waited.set(self.waited.get() + start.elapsed());
+        }
+        // a shorter link may have replaced this one while it was being added
+        if let Some(inode) = inode
+            && self
+                .links
+                .lock()
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .get(&inode)
+                .is_some_and(|kept| kept.as_ref() != data)
+        {
+            let _ = self
+                .out
+                .remove(Bytes::copy_from_slice(data), self.out.version());
+        }
+        if added.is_none() {
+            WalkState::Quit
+        } else {
+            WalkState::Continue
+        }
+    }
+
+    /// Offer `rel`, created under `root` since the walk, if a walk would report it. Returns
+    /// whether it is a directory with entries of its own, which only a walk will find.
+    fn created(&self, root: &Path, rel: &Path, excludes_file: Option<&Path>) -> bool {
+        let full = root.join(rel);
+        let Ok(meta) = fs::symlink_metadata(&full) else {
+            return false;
+        };
+        let is_dir = meta.is_dir();
+        let names: Vec<_> = rel.iter().collect();
+        let options = &self.options;
+        if names.iter().any(|n| *n == ".git")
+            || (!options.hidden && names.iter().any(|n| n.as_bytes().starts_with(b".")))
+            || options.max_depth.is_some_and(|max| names.len() > max)
+            || self.ignore_dirs.excluding(rel, is_dir).is_some()
+            || excluded_type(options.types.matcher().as_ref(), rel, is_dir)
+            || (!options.no_ignore
+                && git::ignore_rule(&full, is_dir, excludes_file, options.ignore_files, root)
+                    .is_some())
+        {
+            return false;
+        }
+        if let Some(entry) = lookup(&full)
+            && wanted_alone(options, &entry)
+        {
+            self.offer(&full, || fields(options, &entry), self.hardlink(&entry));
+        }
+        is_dir && fs::read_dir(&full).is_ok_and(|mut dir| dir.next().is_some())
+    }
+
+    /// Hold back `entry` until it is among the shallowest, offering those once too many are held.
+    fn hold(&mut self, entry: &ignore::DirEntry) -> WalkState {
+        self.held
+            .entry(entry.depth())
+            .or_default()
+            .push(entry.clone());
+        if self.held.values().map(Vec::len).sum::<usize>() <= SHALLOW_BUFFER {
+            return WalkState::Continue;
+        }
+        let (_, entries) = self.held.pop_first().expect("just pushed");
+        self.offer_all(&entries)
+    }
+
+    /// Offer every held entry, shallowest first.
+    fn release(&mut self) -> WalkState {
+        while let Some((_, entries)) = self.held.pop_first() {
+            if matches!(self.offer_all(&entries), WalkState::Quit) {
+                return WalkState::Quit;
+            }
+        }
+        WalkState::Continue
+    }
+
+    fn offer_all(&self, entries: &[ignore::DirEntry]) -> WalkState {
+        let options = &self.options;
+        for entry in entries {
+            if self.walker_version.is_wrong() {
+                return WalkState::Quit;
+            }
+            let offered = self.offer(
+                entry.path(),
+                || fields(options, entry),
+                self.hardlink(entry),
+            );
+            if matches!(offered, WalkState::Quit) {
+                return WalkState::Quit;
+            }
+        }
+        WalkState::Continue
+    }
+
+    /// Offer the kept `entries` as a walk finding them would, until killed.
+    fn rematch(&self, entries: &[Candidate]) {
+        let options = &self.options;
+        let mut entries: Vec<_> = entries.iter().collect();
+        if self.shallow_first {
+            entries.sort_by_key(|c| c.path().components().count());
+        }
+        for candidate in entries {
+            self.progress.wait_while_paused(&self.walker_version);
+            if self.walker_version.is_wrong() || self.progress.is_truncated() {
+                return;
+            }
+            self.progress.visited();
+            let looked_up;
+            let entry = match candidate {
+                Candidate::Walked(entry) => entry,
+                #[cfg(feature = "archives")]
+                Candidate::Archived { path, size } => {
+                    let offered = self.offer(path, || archive_fields(options, *size), None);
+                    if matches!(offered, WalkState::Quit) {
+                        return;
+                    }
+                    continue;
+                }
+                Candidate::Indexed(path) => {
+                    // only a match is worth checking on disk
+                    if !self.out.matches(relative(&self.root, path)) {
+                        continue;
+                    }
+                    match lookup(path) {
+                        Some(entry) if wanted_alone(options, &entry) => {
+                            looked_up = entry;
+                            &looked_up
+                        }
+                        _ => continue,
+                    }
+                }
+            };
+            let offered = self.offer(
+                entry.path(),
+                || fields(options, entry),
+                self.hardlink(entry),
+            );
+            if matches!(offered, WalkState::Quit) {
+                return;
+            }
+        }
+    }
+
+    /// Offer the files inside `entry`, if it is an archive and they are reported, each as
+    /// `entry!inner/path`.
+    #[cfg(feature = "archives")]
+    fn visit_archive(&mut self, entry: &ignore::DirEntry) -> WalkState {
+        let options = self.options.clone();
+        if !options.archives
+            || !entry.file_type().is_some_and(|ft| ft.is_file())
+            || !archive::is_archive(entry.path())
+            || options.kinds.is_some_and(|k| !k.file)
+            || options.broken_links == BrokenLinks::Only
+            || (options.newer_than.is_some() && beyond_limits(&options, entry).is_some())
+        {
+            return WalkState::Continue;
+        }
+        let inner = match archive::entries(entry.path()) {
+            Ok(inner) => inner,
+            Err(err) => {
+                if options.report_errors {
+                    let rel = relative(&self.root, entry.path());
+                    let message = format!("{}: {err}", String::from_utf8_lossy(rel));
+                    self.out.warning("walk-error", message);
+                }
+                return WalkState::Continue;
+            }
+        };
+        for archive::Entry { name, size } in inner {
+            if options.max_size.is_some_and(|max| size > max) {
+                continue;
+            }
+            let path = archive::entry_path(entry.path(), &name);
+            let offered = self.offer(&path, || archive_fields(&options, size), None);
+            if self.gather {
+                self.found.push(Candidate::Archived { path, size });
+            }
+            if matches!(offered, WalkState::Quit) {
+                return WalkState::Quit;
+            }
+        }
+        WalkState::Continue
+    }
+
+    /// The device and inode of `entry` if duplicates of it are suppressed by `dedup`.
+    fn hardlink(&self, entry: &ignore::DirEntry) -> Option<(u64, u64)> {
+        match self.options.dedup {
+            Dedup::Off => None,
+            Dedup::Hardlinks => {
+                if !entry.file_type()?.is_file() {
+                    return None;
+                }
+                let meta = entry.metadata().ok()?;
+                (meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+            }
+            Dedup::Files => {
+                let meta = if entry.path_is_symlink() {
+                    fs::metadata(entry.path()).ok()?
+                } else {
+                    entry.metadata().ok()?
+                };
+                (!meta.is_dir()).then(|| (meta.dev(), meta.ino()))
+            }
+        }
+    }
 }
 impl ParallelVisitor for Visitor {
     fn visit(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> WalkState {
-        if self.walker_version.is_wrong() {
+        self.progress.wait_while_paused(&self.walker_version);
+        let start = Instant::now();
+        let state = self.visit_entry(entry);
+        if let Some(throttle) = &mut self.throttle {
+            // only the time spent visiting counts, not waiting for room in the window
+            let busy = start.elapsed().saturating_sub(self.waited.take());
+            if throttle.visited(busy) {
+                self.progress.sleep(throttle.pause(), &self.walker_version);
+            }
+        }
+        state
+    }
+}
+impl Visitor {
+    fn visit_entry(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> WalkState {
+        if self.walker_version.is_wrong() || self.progress.is_truncated() {
+            return WalkState::Quit;
+        }
+        let scanned = self.progress.visited();
+        if let Some(max) = self.max_scan
+            && scanned > max
+        {
+            // only the visitor that passed the limit warns
+            if scanned == max + 1 {
+                self.progress.truncate();
+                self.out
+                    .warning("max-scan", format!("walk stopped after {max} entries"));
+            }
             return WalkState::Quit;
         }
         match &entry {
             Ok(entry) => {
-                if let Some(ft) = entry.file_type()
-                    && ft.is_dir()
-                {
-                    WalkState::Continue
-                } else {
-                    let data = &entry.path().as_os_str().as_bytes()[self.dir_len..];
-                    if self.ignore_pattern.any_matches(data) {
-                        return WalkState::Continue;
-                    }
-                    let version = self.pattern.version(); // get before test
-                    if self.pattern.all_matches(data)
+                if entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                    let rel = relative(&self.root, entry.path());
+                    if entry.depth() > 0
+                        && !self.ignore_dirs.is_empty()
                         && self
-                            .out
-                            .add(Bytes::copy_from_slice(data), version, &self.walker_version)
-                            .is_none()
+                            .ignore_dirs
+                            .matching(Path::new(OsStr::from_bytes(rel)))
+                            .is_some()
                     {
-                        WalkState::Quit
-                    } else {
-                        WalkState::Continue
+                        return WalkState::Skip;
+                    }
+                    self.progress.enter_dir(entry.path());
+                }
+                #[cfg(feature = "archives")]
+                if matches!(self.visit_archive(entry), WalkState::Quit) {
+                    return WalkState::Quit;
+                }
+                if wanted(&self.options, entry) {
+                    if self.gather {
+                        self.found.push(Candidate::Walked(entry.clone()));
+                    }
+                    if self.shallow_first {
+                        return self.hold(entry);
                     }
+                    let options = &self.options;
+                    self.offer(
+                        entry.path(),
+                        || fields(options, entry),
+                        self.hardlink(entry),
+                    )
+                } else {
+                    WalkState::Continue
                 }
             }
-            Err(_) => WalkState::Continue,
+            Err(err) => {
+                if let Some(path) = transient_failure(err) {
+                    let mut failed = self.failed.lock().expect(crate::LOCK_SHOULD_BE_OK);
+                    failed.push(path.to_path_buf());
+                }
+                if self.options.report_errors {
+                    self.out.warning("walk-error", walk_error(&self.root, err));
+                }
+                WalkState::Continue
+            }
+        }
+    }
+}
+
+impl Drop for Visitor {
+    fn drop(&mut self) {
+        if !self.progress.is_truncated() {
+            self.release();
+        }
+        if !self.found.is_empty() && !self.walker_version.is_wrong() {
+            let mut candidates = self.candidates.lock().expect(crate::LOCK_SHOULD_BE_OK);
+            candidates.found.append(&mut self.found);
+        }
+    }
+}
+
+/// An entry a walk would report, kept to be matched again.
+enum Candidate {
+    Walked(ignore::DirEntry),
+    /// A path read from the index, looked up on disk only once it matches.
+    Indexed(PathBuf),
+    /// A file inside an archive, by its virtual path.
+    #[cfg(feature = "archives")]
+    Archived {
+        path: PathBuf,
+        size: u64,
+    },
+}
+impl Candidate {
+    fn path(&self) -> &Path {
+        match self {
+            Candidate::Walked(entry) => entry.path(),
+            Candidate::Indexed(path) => path,
+            #[cfg(feature = "archives")]
+            Candidate::Archived { path, .. } => path,
+        }
+    }
+}
+
+/// The entries a walk of every root would report, before the query and ignore pattern are
+/// applied, so a wider query can be matched without walking the disk again.
+#[derive(Default)]
+struct Candidates {
+    /// Gathered so far by the walk in progress.
+    found: Vec<Candidate>,
+    /// Everything the last complete walk found; `None` once anything may have changed.
+    complete: Option<Arc<Vec<Candidate>>>,
+    /// `complete` was read from the index so may be out of date; the roots are walked again
+    /// while it is matched.
+    indexed: bool,
+}
+
+/// What `start_walk` visits.
+enum Source {
+    /// A walk of every root, whose entries are kept as candidates.
+    Roots(WalkBuilder),
+    /// A walk of some paths, such as those that failed to be read.
+    Paths(WalkBuilder),
+    /// The candidates kept from the last complete walk.
+    Candidates(Arc<Vec<Candidate>>),
+    /// The candidates read from the index, offered before walking every root to find what has
+    /// changed since it was saved.
+    Indexed(Arc<Vec<Candidate>>, WalkBuilder),
+    /// The files git lists under the root, kept as candidates.
+    Git(PathBuf),
+}
+
+/// The entry for `path` on its own, as if at the top of a walk.
+fn lookup(path: &Path) -> Option<ignore::DirEntry> {
+    WalkBuilder::new(path)
+        .standard_filters(false)
+        .max_depth(Some(0))
+        .build()
+        .next()?
+        .ok()
+}
+
+/// `path` as reported: relative to `root`, or whole if it is under another root.
+fn relative<'a>(root: &[u8], path: &'a Path) -> &'a [u8] {
+    let path = path.as_os_str().as_bytes();
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// `err` as the path it is about, as reported, and why it could not be read.
+fn walk_error(root: &[u8], err: &ignore::Error) -> String {
+    match err {
+        ignore::Error::WithPath { path, err } => {
+            format!("{}: {err}", String::from_utf8_lossy(relative(root, path)))
+        }
+        ignore::Error::WithDepth { err, .. } => walk_error(root, err),
+        err => err.to_string(),
+    }
+}
+
+/// The path that could not be read if `err` is one that may not happen next time, such as
+/// running out of file descriptors.
+fn transient_failure(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, err } => {
+            let io = err.io_error()?;
+            (matches!(
+                io.kind(),
+                io::ErrorKind::WouldBlock
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::ResourceBusy
+                    | io::ErrorKind::TimedOut
+            ) || matches!(io.raw_os_error(), Some(libc::EMFILE | libc::ENFILE)))
+            .then_some(path.as_path())
         }
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            transient_failure(err)
+        }
+        ignore::Error::Partial(errs) => errs.iter().find_map(transient_failure),
+        _ => None,
     }
 }
 
 #[derive(Clone)]
 struct VisitorBuilder {
     out: Window,
-    pattern: Pattern,
     ignore_pattern: Pattern,
     walker_version: WalkerVersion,
-    dir_len: usize,
+    progress: Arc<Progress>,
+    options: Arc<WalkOptions>,
+    /// The path kept for each hard linked inode seen this walk.
+    links: Arc<Mutex<HashMap<(u64, u64), Bytes>>>,
+    feed: Option<Arc<Feed>>,
+    /// Paths that failed to be read this walk for reasons that may pass.
+    failed: Arc<Mutex<Vec<PathBuf>>>,
+    /// The directory trees the `ignore` command excludes.
+    ignore_dirs: Arc<IgnoreDirs>,
+    /// The walk's first root, which is stripped from the paths under it.
+    root: Bytes,
+    candidates: Arc<Mutex<Candidates>>,
+    /// Whether this walk's entries are gathered into `candidates`.
+    gather: bool,
+    /// The most entries a walk visits before it stops and reports `done truncated`.
+    max_scan: Option<usize>,
+    /// The percentage of the time each walk thread may be busy.
+    throttle: Option<u8>,
+    /// Whether each walk thread sends the entries nearest the root first.
+    shallow_first: bool,
+    /// Whether the client is told how many entries match, as a `count` message.
+    count: bool,
 }
 impl VisitorBuilder {
-    fn new(out: Window, pattern: Pattern, ignore_pattern: Pattern, dir_len: usize) -> Self {
+    fn new(out: Window, ignore_pattern: Pattern, root: Bytes) -> Self {
         Self {
             out,
-            pattern,
             ignore_pattern,
             walker_version: WalkerVersion::default(),
-            dir_len,
+            progress: Default::default(),
+            options: Default::default(),
+            links: Default::default(),
+            feed: None,
+            failed: Default::default(),
+            ignore_dirs: Default::default(),
+            root,
+            candidates: Default::default(),
+            gather: false,
+            max_scan: None,
+            throttle: None,
+            shallow_first: false,
+            count: false,
         }
     }
 
@@ -161,158 +914,1520 @@ impl VisitorBuilder {
         self.walker_version.kill();
         self.out.killed();
     }
-}
-impl<'s> ParallelVisitorBuilder<'s> for VisitorBuilder {
-    fn build(&mut self) -> Box<dyn ignore::ParallelVisitor + 's> {
-        Box::new(Visitor {
+
+    fn visitor(&self) -> Visitor {
+        Visitor {
             out: self.out.clone(),
-            pattern: self.pattern.clone(),
             ignore_pattern: self.ignore_pattern.clone(),
             walker_version: self.walker_version.clone(),
-            dir_len: self.dir_len,
-        })
+            progress: self.progress.clone(),
+            options: self.options.clone(),
+            links: self.links.clone(),
+            feed: self.feed.clone(),
+            failed: self.failed.clone(),
+            ignore_dirs: self.ignore_dirs.clone(),
+            root: self.root.clone(),
+            candidates: self.candidates.clone(),
+            gather: self.gather,
+            max_scan: self.max_scan,
+            throttle: self.throttle.map(Throttle::new),
+            waited: Cell::default(),
+            shallow_first: self.shallow_first,
+            count: self.count,
+            held: BTreeMap::new(),
+            found: vec![],
+        }
     }
-}
 
-#[derive(Debug)]
-enum MatchState {
-    Walking,
-    Matching,
-    Stopped,
-}
+    /// Offer the kept `entries` again, split between `threads` threads.
+    fn rematch(&self, entries: &[Candidate], threads: usize) {
+        let chunk = entries.len().div_ceil(threads.max(1)).max(1);
+        thread::scope(|scope| {
+            for entries in entries.chunks(chunk) {
+                let visitor = self.visitor();
+                scope.spawn(move || visitor.rematch(entries));
+            }
+        });
+    }
 
-pub struct Walker {
-    pattern: Pattern,
-    ignore_pattern: Pattern,
-    path: PathBuf,
-    visitor: VisitorBuilder,
-    walker_thread: Option<thread::JoinHandle<()>>,
-    match_thread: Option<thread::JoinHandle<()>>,
-    match_sender: Option<mpsc::Sender<Bytes>>,
-    state: MatchState,
-}
-impl Walker {
-    pub fn new(out: Window) -> Self {
-        let pattern = out.pattern().clone();
-        let ignore_pattern = Pattern::default();
-        let visitor = VisitorBuilder::new(out, pattern.clone(), ignore_pattern.clone(), 2);
-        Self {
-            pattern,
-            ignore_pattern,
-            path: "./".into(),
-            visitor,
-            walker_thread: None,
-            match_thread: None,
-            match_sender: None,
-            state: MatchState::Stopped,
+    /// Keep what this walk gathered as the candidates if it walked everything, returning them.
+    fn keep_candidates(&self) -> Option<Arc<Vec<Candidate>>> {
+        let complete = self.gather
+            && self.feed.is_none()
+            && !self.walker_version.is_wrong()
+            && !self.progress.is_truncated()
+            && self
+                .failed
+                .lock()
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .is_empty();
+        let mut candidates = self.candidates.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        let found = mem::take(&mut candidates.found);
+        if !complete {
+            return None;
         }
+        let found = Arc::new(found);
+        candidates.complete = Some(found.clone());
+        candidates.indexed = false;
+        Some(found)
     }
 
-    pub fn command(&mut self, ct: &str, arg: &str) -> Result<(), Error> {
-        match ct {
-            "walk" => match self.walk(arg) {
-                Ok(()) => {
-                    self.ensure_running();
-                }
-                Err(err) => {
-                    self.message(format!("walk {arg} failed: {err:?}"));
-                }
-            },
-            "match" => self.match_line(arg),
-            "stop" => {
-                self.kill_thread();
-                self.state = MatchState::Stopped;
-                self.visitor.out.clear();
-                self.pattern.reset();
-                self.pattern.skip_prefix(0);
-                self.ignore_pattern.reset();
-                self.ignore_pattern.skip_prefix(0);
-            }
-            "add" => self.change_pattern(self.pattern.add(arg)),
-            "ignore" => {
-                self.ignore_pattern.set(0, arg);
-                self.kill_thread();
-                self.visitor.out.clear();
-            }
-            "skip-prefix" => {
-                let n = arg.parse().map_err(|_| Error::InvalidArgument)?;
-                self.ignore_pattern.skip_prefix(n);
-                self.pattern.skip_prefix(n);
-                self.change_pattern(PatternScope::Change);
-            }
-            "rm" => self.change_pattern(
-                self.pattern
-                    .rm(arg.parse().map_err(|_| Error::InvalidArgument)?),
-            ),
-            "set" => {
-                let (start, text) = super::chars_split_at_space(arg);
-                self.change_pattern(
-                    self.pattern
-                        .set(start.parse().map_err(|_| Error::InvalidArgument)?, text),
-                );
-            }
-            "redraw" => {
-                self.visitor.out.redraw();
-            }
-            "window_size" => {
-                self.visitor
-                    .out
-                    .set_size(arg.parse().map_err(|_| Error::InvalidArgument)?);
-            }
-            _ => {
-                return Err(Error::UnknownCommand(ct.to_string()));
-            }
+    /// Keep the `entries` git listed as the candidates if they were all offered, returning them.
+    fn keep_listed(&self, entries: Vec<Candidate>) -> Option<Arc<Vec<Candidate>>> {
+        if self.feed.is_some() || self.walker_version.is_wrong() || self.progress.is_truncated() {
+            return None;
         }
-        Ok(())
+        let entries = Arc::new(entries);
+        let mut candidates = self.candidates.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        candidates.complete = Some(entries.clone());
+        candidates.indexed = false;
+        Some(entries)
     }
 
-    #[inline(always)]
-    pub fn message(&self, value: String) {
-        self.visitor.out.message(value);
+    /// The files git lists under `root` that a walk would visit, to be looked up on disk once
+    /// they match.
+    fn list_git(&self, root: &Path) -> io::Result<Vec<Candidate>> {
+        let options = &self.options;
+        let types = options.types.matcher();
+        let listed = git::ls_files(root, options.no_ignore)?;
+        Ok(listed
+            .into_iter()
+            .filter(|rel| {
+                (options.hidden || !rel.iter().any(|n| n.as_bytes().starts_with(b".")))
+                    && options
+                        .max_depth
+                        .is_none_or(|max| rel.iter().count() <= max)
+                    && self.ignore_dirs.excluding(rel, false).is_none()
+                    && !excluded_type(types.as_ref(), rel, false)
+            })
+            .map(|rel| Candidate::Indexed(root.join(rel)))
+            .collect())
     }
 
-    fn change_pattern(&mut self, scope: PatternScope) {
-        if matches!(scope, PatternScope::Narrow) {
-            self.visitor.out.remove_unmatched();
-        } else {
-            match self.state {
-                MatchState::Walking => {
-                    self.kill_walker();
-                    self.visitor.out.remove_unmatched();
-                    self.ensure_running();
-                }
-                MatchState::Matching => {
-                    self.kill_match_thread();
-                    self.visitor.out.request_resync();
-                }
-                MatchState::Stopped => {}
-            }
-        }
+    /// The candidates of the last complete walk, if `indexed` says whether they were read from
+    /// the index.
+    fn kept(&self, indexed: bool) -> Option<Arc<Vec<Candidate>>> {
+        let candidates = self.candidates.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        candidates
+            .complete
+            .clone()
+            .filter(|_| candidates.indexed == indexed)
     }
 
-    fn walk(&mut self, dir: &str) -> Result<(), Error> {
-        self.path = dir.into();
-        if self.path.starts_with("~/") {
-            let rest = &self
-                .path
-                .strip_prefix("~/")
-                .map_err(|_| Error::InvalidArgument)?
-                .to_str()
-                .ok_or(Error::Utf8Error)?;
-            self.path = fs::canonicalize(format!(
-                "{}/{rest}/",
-                env::var("HOME").map_err(|_| Error::CdInvalid)?
-            ))
-            .map_err(Error::from_io)?;
-        }
-        if !self.path.is_dir() {
-            return Err(Error::NotADirectory);
-        }
-        self.path.push("");
-        self.kill_thread();
-        self.visitor.dir_len = self.path.as_os_str().len();
-        self.state = MatchState::Walking;
-        Ok(())
+    /// Stop using the candidates, as when what a walk would find has changed.
+    fn forget_candidates(&self) {
+        let mut candidates = self.candidates.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        candidates.complete = None;
+        candidates.indexed = false;
+    }
+}
+impl<'s> ParallelVisitorBuilder<'s> for VisitorBuilder {
+    fn build(&mut self) -> Box<dyn ignore::ParallelVisitor + 's> {
+        Box::new(self.visitor())
+    }
+}
+
+/// Which entries a walk visits and reports, besides those the patterns reject.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WalkOptions {
+    /// Include hidden files and directories.
+    pub hidden: bool,
+    /// Don't read `.gitignore`, `.ignore` or git exclude files.
+    pub no_ignore: bool,
+    /// Which ignore files are read when `no_ignore` is off.
+    pub ignore_files: IgnoreFiles,
+    /// Walk into symlinked directories; links that lead back to an ancestor are not followed.
+    pub follow_links: bool,
+    /// Don't descend into other file systems, such as network mounts, under the root.
+    pub same_file_system: bool,
+    pub max_depth: Option<usize>,
+    /// Report only these kinds of entry; by default everything except directories.
+    pub kinds: Option<Kinds>,
+    /// Send each entry's `classify` character as a field.
+    pub classify: bool,
+    /// Send each entry's size and mtime as fields.
+    pub metadata: bool,
+    pub broken_links: BrokenLinks,
+    pub dedup: Dedup,
+    /// Skip what the git sparse checkout of the worktree leaves out.
+    pub sparse: bool,
+    /// Don't descend into nested worktrees such as git submodules.
+    pub skip_submodules: bool,
+    /// Only report files of these types, when any are selected or negated.
+    pub types: TypeFilter,
+    /// Warn of each entry that could not be read, such as a directory without permission.
+    pub report_errors: bool,
+    pub backend: Backend,
+    /// Report only entries, other than directories, modified since this time.
+    pub newer_than: Option<SystemTime>,
+    /// Report only entries, other than directories, of at most this many bytes.
+    pub max_size: Option<u64>,
+    /// Report the files inside zip and tar archives, as `archive.zip!inner/path`.
+    #[cfg(feature = "archives")]
+    pub archives: bool,
+}
+
+/// The ripgrep-style file types a walk reports, such as `rust`, from the `ignore` crate's
+/// built-in definitions and any added.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypeFilter {
+    pub select: Vec<String>,
+    pub negate: Vec<String>,
+    /// Added definitions, each `name:glob`.
+    pub defs: Vec<String>,
+}
+impl TypeFilter {
+    pub fn is_empty(&self) -> bool {
+        self.select.is_empty() && self.negate.is_empty()
+    }
+
+    pub fn build(&self) -> Result<Types, ignore::Error> {
+        let mut builder = TypesBuilder::new();
+        builder.add_defaults();
+        for def in &self.defs {
+            builder.add_def(def)?;
+        }
+        for name in &self.select {
+            builder.select(name);
+        }
+        for name in &self.negate {
+            builder.negate(name);
+        }
+        builder.build()
+    }
+
+    /// The types to report, or `None` if every type is.
+    fn matcher(&self) -> Option<Types> {
+        if self.is_empty() {
+            None
+        } else {
+            self.build().ok()
+        }
+    }
+}
+
+/// Whether `types`, from `TypeFilter::matcher`, leaves out the entry at `path`. Directories are
+/// never left out, as they may hold files of the types.
+fn excluded_type(types: Option<&Types>, path: &Path, is_dir: bool) -> bool {
+    types.is_some_and(|types| types.matched(path, is_dir).is_ignore())
+}
+
+/// How a walk finds the entries under the root.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Backend {
+    /// Read every directory.
+    #[default]
+    Walk,
+    /// List the files git tracks, and the untracked ones it does not ignore, with
+    /// `git ls-files`; much faster in a large repository.
+    Git,
+}
+impl FromStr for Backend {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "walk" => Ok(Self::Walk),
+            "git" => Ok(Self::Git),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What to do with symlinks whose target does not exist.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BrokenLinks {
+    #[default]
+    Show,
+    Hide,
+    /// Report nothing but broken links.
+    Only,
+}
+impl FromStr for BrokenLinks {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "show" => Ok(Self::Show),
+            "hide" => Ok(Self::Hide),
+            "only" => Ok(Self::Only),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which files found more than once are reported only under their shortest path.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Dedup {
+    #[default]
+    Off,
+    /// Files hard linked to the same inode.
+    Hardlinks,
+    /// Every file other than a directory, however it is reached: through hard links, symlinks or
+    /// symlinked directories.
+    Files,
+}
+impl FromStr for Dedup {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "on" => Ok(Self::Hardlinks),
+            "files" => Ok(Self::Files),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The cutoff of `newer-than`: a duration before the command, such as `7d`, or a time in seconds
+/// since the epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NewerThan {
+    Ago(Duration),
+    At(SystemTime),
+}
+impl NewerThan {
+    pub fn cutoff(self, now: SystemTime) -> SystemTime {
+        match self {
+            Self::Ago(duration) => now.checked_sub(duration).unwrap_or(SystemTime::UNIX_EPOCH),
+            Self::At(time) => time,
+        }
+    }
+}
+impl FromStr for NewerThan {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(secs) = s.parse() {
+            return Ok(Self::At(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)));
+        }
+        let unit = match s.as_bytes().last() {
+            Some(b's') => 1,
+            Some(b'm') => 60,
+            Some(b'h') => 3600,
+            Some(b'd') => 86_400,
+            Some(b'w') => 604_800,
+            _ => return Err(()),
+        };
+        let count: u64 = s[..s.len() - 1].parse().map_err(|_| ())?;
+        Ok(Self::Ago(Duration::from_secs(
+            count.checked_mul(unit).ok_or(())?,
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Kinds {
+    pub file: bool,
+    pub dir: bool,
+    pub symlink: bool,
+    pub executable: bool,
+}
+impl Kinds {
+    /// Whether to report the non-directory `entry`.
+    fn wants(&self, entry: &ignore::DirEntry) -> bool {
+        let Some(ft) = entry.file_type() else {
+            return false;
+        };
+        (self.file && ft.is_file())
+            || (self.symlink && ft.is_symlink())
+            || (self.executable
+                && ft.is_file()
+                && entry
+                    .metadata()
+                    .is_ok_and(|m| m.permissions().mode() & 0o111 != 0))
+    }
+}
+
+/// The fields sent after `entry`'s path, each after a `FIELD_SEP`: its `classify` character,
+/// then its size and mtime, in seconds since the epoch, for those options that are on.
+fn fields(options: &WalkOptions, entry: &ignore::DirEntry) -> Bytes {
+    let mut fields: Vec<Vec<u8>> = vec![];
+    if options.classify {
+        fields.push(classify(entry).into());
+    }
+    if options.metadata {
+        match entry.metadata() {
+            Ok(meta) => {
+                fields.push(meta.len().to_string().into());
+                fields.push(meta.mtime().to_string().into());
+            }
+            Err(_) => fields.extend([vec![], vec![]]),
+        }
+    }
+    join_fields(fields)
+}
+
+/// The fields sent after a file inside an archive, as `fields` would for a regular file; it has
+/// no mtime of its own.
+#[cfg(feature = "archives")]
+fn archive_fields(options: &WalkOptions, size: u64) -> Bytes {
+    let mut fields: Vec<Vec<u8>> = vec![];
+    if options.classify {
+        fields.push(vec![]);
+    }
+    if options.metadata {
+        fields.extend([size.to_string().into(), vec![]]);
+    }
+    join_fields(fields)
+}
+
+/// `fields` each preceded by a `FIELD_SEP`.
+fn join_fields(fields: Vec<Vec<u8>>) -> Bytes {
+    fields
+        .into_iter()
+        .fold(vec![], |mut data, field| {
+            data.push(FIELD_SEP);
+            data.extend(field);
+            data
+        })
+        .into()
+}
+
+/// The `ls -F` style character for `entry`: `/` directory, `@` symlink, `!` broken symlink,
+/// `*` executable, `|` fifo, `=` socket; regular files have none.
+fn classify(entry: &ignore::DirEntry) -> &'static str {
+    let Some(ft) = entry.file_type() else {
+        return "";
+    };
+    if ft.is_dir() {
+        "/"
+    } else if ft.is_symlink() && is_broken_link(entry) {
+        "!"
+    } else if ft.is_symlink() {
+        "@"
+    } else if ft.is_fifo() {
+        "|"
+    } else if ft.is_socket() {
+        "="
+    } else if ft.is_file()
+        && entry
+            .metadata()
+            .is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+    {
+        "*"
+    } else {
+        ""
+    }
+}
+
+/// Whether a walk reports `entry`, going by its kind.
+fn wanted(options: &WalkOptions, entry: &ignore::DirEntry) -> bool {
+    let kinds = options.kinds;
+    if entry.file_type().is_some_and(|ft| ft.is_dir()) {
+        return entry.depth() > 0 && kinds.is_some_and(|k| k.dir);
+    }
+    let kind = match options.broken_links {
+        BrokenLinks::Show => kinds.is_none_or(|k| k.wants(entry)),
+        BrokenLinks::Hide => kinds.is_none_or(|k| k.wants(entry)) && !is_broken_link(entry),
+        BrokenLinks::Only => is_broken_link(entry),
+    };
+    kind && beyond_limits(options, entry).is_none()
+}
+
+/// Why the non-directory `entry` is left out by `newer_than` or `max_size`, if it is.
+fn beyond_limits(options: &WalkOptions, entry: &ignore::DirEntry) -> Option<&'static str> {
+    if options.newer_than.is_none() && options.max_size.is_none() {
+        return None;
+    }
+    let Ok(meta) = entry.metadata() else {
+        return Some("without metadata to compare");
+    };
+    if let Some(since) = options.newer_than
+        && meta.modified().is_ok_and(|modified| modified < since)
+    {
+        return Some("older than newer-than");
+    }
+    if options.max_size.is_some_and(|max| meta.len() > max) {
+        return Some("larger than max-size");
+    }
+    None
+}
+
+/// Whether a walk reports `entry`, looked up on its own rather than found by walking.
+fn wanted_alone(options: &WalkOptions, entry: &ignore::DirEntry) -> bool {
+    match entry.file_type() {
+        Some(ft) if ft.is_dir() => options.kinds.is_some_and(|k| k.dir),
+        _ => wanted(options, entry),
+    }
+}
+
+fn is_broken_link(entry: &ignore::DirEntry) -> bool {
+    entry.path_is_symlink() && fs::metadata(entry.path()).is_err()
+}
+
+#[derive(Debug)]
+enum MatchState {
+    Walking,
+    Matching,
+    Stopped,
+}
+
+pub struct Walker {
+    pattern: Pattern,
+    ignore_pattern: Pattern,
+    /// The text of the `ignore` command, including its directory rules.
+    ignore_text: String,
+    path: PathBuf,
+    /// The roots added by `walk-add`, canonical so their paths, reported whole, can be opened
+    /// from anywhere.
+    roots: Vec<PathBuf>,
+    excludes_file: Option<PathBuf>,
+    watchdog: Option<Watchdog>,
+    /// How long a walk may spend walking, not paused or waiting for room in the window, before
+    /// it stops and reports `done truncated`.
+    walk_budget: Option<Duration>,
+    /// How often to send `progress` during a walk; a walk sends `stats` when done if set.
+    progress_period: Option<Duration>,
+    /// The most file descriptors a walk may use.
+    fd_limit: Option<usize>,
+    /// The most watches fs-watch may use.
+    watch_limit: Option<usize>,
+    root_watch: Option<RootWatch>,
+    root_watcher: Option<mpsc::Sender<()>>,
+    head_watch: Option<HeadWatch>,
+    head_watcher: Option<mpsc::Sender<()>>,
+    staleness: Option<Staleness>,
+    stale_checker: Option<mpsc::Sender<()>>,
+    /// Report files created or removed under the root once a walk is done.
+    fs_watch: bool,
+    /// Where the paths of a complete walk are saved, to be matched at once by the next walk of
+    /// the same root.
+    index: Option<PathBuf>,
+    /// Where the visits ranking results are saved.
+    frecency_file: Option<PathBuf>,
+    signals: Option<mpsc::Sender<Signal>>,
+    options: WalkOptions,
+    matcher: Option<ExternalMatcher>,
+    matcher_process: Option<Process>,
+    hooks: Arc<Hooks>,
+    quotas: Quotas,
+    macros: Macros,
+    /// The patterns addressed as `@name`, each with its own window and walk.
+    named: HashMap<String, Walker>,
+    visitor: VisitorBuilder,
+    walker_thread: Option<thread::JoinHandle<()>>,
+    match_thread: Option<thread::JoinHandle<()>>,
+    match_sender: Option<mpsc::Sender<Bytes>>,
+    state: MatchState,
+}
+impl Walker {
+    pub fn new(out: Window) -> Self {
+        let pattern = out.pattern().clone();
+        let ignore_pattern = Pattern::default();
+        let visitor = VisitorBuilder::new(out, ignore_pattern.clone(), Bytes::from_static(b"./"));
+        Self {
+            pattern,
+            ignore_pattern,
+            ignore_text: String::new(),
+            path: "./".into(),
+            roots: vec![],
+            excludes_file: None,
+            watchdog: None,
+            walk_budget: None,
+            progress_period: None,
+            fd_limit: None,
+            watch_limit: None,
+            root_watch: None,
+            root_watcher: None,
+            head_watch: None,
+            head_watcher: None,
+            staleness: None,
+            stale_checker: None,
+            fs_watch: false,
+            index: None,
+            frecency_file: None,
+            signals: None,
+            options: WalkOptions::default(),
+            matcher: None,
+            matcher_process: None,
+            hooks: Default::default(),
+            quotas: Quotas::default(),
+            macros: Macros::default(),
+            named: HashMap::new(),
+            visitor,
+            walker_thread: None,
+            match_thread: None,
+            match_sender: None,
+            state: MatchState::Stopped,
+        }
+    }
+
+    /// Run the command `ct`. An `arg` starting with `@name`, where `name` is a session made by
+    /// `session create`, addresses that session's pattern; the rest of `arg` is the command's
+    /// argument. Any other `arg` is for this pattern, with a leading `@@` read as `@` so text
+    /// starting with a session's name can still be given.
+    pub fn command(&mut self, ct: &str, arg: &str) -> Result<(), Error> {
+        self.request_command(None, ct, arg)
+    }
+
+    /// As `command`, for the client's `request` ID: the messages sent from then on are tagged with
+    /// it, other than those of a walk the command stopped.
+    pub fn request_command(
+        &mut self,
+        request: Option<&str>,
+        ct: &str,
+        arg: &str,
+    ) -> Result<(), Error> {
+        let mut arg = arg;
+        if let Some(rest) = arg.strip_prefix('@') {
+            let (name, named_arg) = super::chars_split_at_space(rest);
+            if let Some(walker) = self.named.get_mut(name) {
+                return walker.request_command(request, ct, named_arg);
+            }
+            if rest.starts_with('@') {
+                arg = rest;
+            }
+        }
+        let cmd = Command::parse(ct, arg)?;
+        let Some(id) = request else {
+            self.execute(cmd);
+            return Ok(());
+        };
+        self.visitor.out.set_request(id);
+        self.execute(cmd);
+        // a walk started by the command has the ID already; one it refined takes it on
+        if self.walker_thread.is_some() || self.match_thread.is_some() {
+            self.visitor.walker_version.set_request(Some(id.into()));
+        }
+        Ok(())
+    }
+
+    /// The walker of the pattern named `name`, sharing this one's settings from the server.
+    fn named(&mut self, name: &str) -> &mut Walker {
+        self.named.entry(name.to_string()).or_insert_with(|| {
+            let mut walker = Walker::new(self.visitor.out.named(name));
+            walker.hooks = self.hooks.clone();
+            walker.set_quotas(self.quotas);
+            walker.macros = self.macros.clone();
+            walker.frecency_file = self.frecency_file.clone();
+            walker.signals = self.signals.clone();
+            walker
+        })
+    }
+
+    /// Create, close or list the sessions: the named patterns, each with its own window and walk,
+    /// addressed as `@name`.
+    fn session(&mut self, action: SessionAction) {
+        match action {
+            SessionAction::Create(name) => {
+                if self.named.contains_key(name) {
+                    self.message(format!("session create {name}: already exists"));
+                } else {
+                    self.named(name);
+                }
+            }
+            SessionAction::Close(name) => match self.named.remove(name) {
+                Some(mut walker) => walker.execute(Command::Stop),
+                None => self.message(format!("session close {name}: no such session")),
+            },
+            SessionAction::List => {
+                let mut names: Vec<_> = self.named.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                self.message(format!("sessions {}", Value::from(names)));
+            }
+        }
+    }
+
+    pub fn execute(&mut self, cmd: Command) {
+        match cmd {
+            Command::Walk(dir) => match self.walk(dir) {
+                Ok(()) => {
+                    self.warn_fd_budget();
+                    self.start_watchers();
+                    self.ensure_running();
+                }
+                Err(Error::Expand(reason)) => {
+                    self.message(format!("walk {dir} failed: {reason}"));
+                }
+                Err(err) => {
+                    self.message(format!("walk {dir} failed: {err:?}"));
+                }
+            },
+            Command::WalkAdd(dir) => match self.walk_add(dir) {
+                Ok(()) => {
+                    // walk everything again, as refresh does, so the window is kept
+                    self.kill_walker();
+                    self.ensure_running();
+                }
+                Err(err) => {
+                    self.message(format!("walk-add {dir} failed: {err}"));
+                }
+            },
+            Command::Switch { dir, query } => {
+                self.execute(Command::Stop);
+                self.pattern.add(query);
+                self.warn_complex_query();
+                self.execute(Command::Walk(dir));
+            }
+            Command::Match(line) => self.match_line(line),
+            Command::Pause => self.visitor.progress.pause(),
+            Command::Resume => self.visitor.progress.resume(),
+            Command::Session(action) => self.session(action),
+            Command::Stop => {
+                self.visitor.progress.resume();
+                self.kill_thread();
+                self.state = MatchState::Stopped;
+                self.root_watcher = None;
+                self.head_watcher = None;
+                self.stale_checker = None;
+                self.visitor.out.clear();
+                self.pattern.reset();
+                self.pattern.skip_prefix(0);
+                self.pattern.skip_suffix(0);
+                self.pattern.basename(false);
+                self.pattern.separators("/");
+                self.set_ignore("");
+                self.ignore_pattern.reset();
+                self.ignore_pattern.skip_prefix(0);
+                self.ignore_pattern.skip_suffix(0);
+                self.visitor.out.refine().set(0, "");
+            }
+            Command::Add(text) => self.change_pattern(self.pattern.add(text)),
+            Command::Ignore(text) => {
+                self.set_ignore(text);
+                self.kill_thread();
+                self.visitor.out.clear();
+            }
+            Command::SkipPrefix(n) => {
+                self.ignore_pattern.skip_prefix(n);
+                self.pattern.skip_prefix(n);
+                self.change_pattern(PatternScope::Change);
+            }
+            Command::SkipSuffix(n) => {
+                self.ignore_pattern.skip_suffix(n);
+                self.pattern.skip_suffix(n);
+                self.change_pattern(PatternScope::Change);
+            }
+            Command::Basename(on) => {
+                self.pattern.basename(on);
+                self.change_pattern(PatternScope::Change);
+            }
+            Command::Separators(chars) => {
+                self.pattern.separators(chars);
+                self.change_pattern(PatternScope::Change);
+            }
+            #[cfg(feature = "nfc")]
+            Command::Nfc(on) => {
+                self.pattern.nfc(on);
+                self.ignore_pattern.nfc(on);
+                self.change_pattern(PatternScope::Change);
+            }
+            #[cfg(not(feature = "nfc"))]
+            Command::Nfc(_) => self.message("nfc: built without the nfc feature".to_string()),
+            Command::Refine(text) => {
+                let scope = self.visitor.out.refine().set(0, text);
+                self.change_pattern(scope);
+            }
+            Command::RefineClear => {
+                self.visitor.out.refine().set(0, "");
+                self.change_pattern(PatternScope::Widen);
+            }
+            Command::Rm(amount) => self.change_pattern(self.pattern.rm(amount)),
+            Command::Set { start, text } => self.change_pattern(self.pattern.set(start, text)),
+            Command::Undo => match self.pattern.undo() {
+                Some(scope) => self.change_pattern(scope),
+                None => self.message("undo: nothing to undo".to_string()),
+            },
+            Command::Redo => match self.pattern.redo() {
+                Some(scope) => self.change_pattern(scope),
+                None => self.message("redo: nothing to redo".to_string()),
+            },
+            Command::RetryErrors => self.retry_errors(),
+            Command::Doctor => {
+                let findings = doctor::check();
+                let problems = findings.iter().filter(|f| f.fix.is_some()).count();
+                for finding in findings {
+                    match finding.fix {
+                        Some(_) => self.visitor.out.warning("doctor", finding.to_string()),
+                        None => self.message(format!("doctor {finding}")),
+                    }
+                }
+                self.message(format!("doctor: {problems} problems"));
+            }
+            Command::Redraw => {
+                self.visitor.out.redraw();
+            }
+            Command::Snapshot => {
+                self.visitor.out.snapshot();
+            }
+            Command::Save => {
+                // saved with its directory rules
+                let ignore = Pattern::default();
+                ignore.restore(&self.ignore_pattern.save());
+                ignore.set(0, &self.ignore_text);
+                let state = self.pattern.save() + &ignore.save();
+                self.message(format!("saved {state}"));
+            }
+            Command::PatternTerms => {
+                let terms: Vec<_> = self
+                    .pattern
+                    .terms()
+                    .into_iter()
+                    .map(|t| {
+                        json!({
+                            "kind": t.kind.as_str(),
+                            "text": t.text,
+                            "ok": t.ok,
+                            "literal": t.literal,
+                        })
+                    })
+                    .collect();
+                self.message(format!("pattern? {}", Value::from(terms)));
+            }
+            Command::State => {
+                let (skip_prefix, skip_suffix) = self.pattern.skips();
+                let root = match self.state {
+                    MatchState::Walking => Value::from(self.path.to_string_lossy()),
+                    MatchState::Matching | MatchState::Stopped => Value::Null,
+                };
+                let state = json!({
+                    "pattern": self.pattern.clone_text(),
+                    "ignore": self.ignore_text,
+                    "skip_prefix": skip_prefix,
+                    "skip_suffix": skip_suffix,
+                    "root": root,
+                    "window_size": self.visitor.out.size(),
+                    "offset": self.visitor.out.offset(),
+                    "paused": self.visitor.progress.is_paused(),
+                });
+                self.message(format!("state {state}"));
+            }
+            Command::Load(state) => {
+                let valid = Pattern::default()
+                    .restore(state)
+                    .and_then(|rest| Pattern::default().restore(rest))
+                    .is_some_and(str::is_empty);
+                if valid {
+                    let rest = self.pattern.restore(state).expect("State should be valid");
+                    self.ignore_pattern.restore(rest);
+                    self.set_ignore(&self.ignore_pattern.clone_text());
+                    self.warn_complex_query();
+                    self.restart();
+                } else {
+                    self.message(format!("load {state}: invalid state"));
+                }
+            }
+            Command::WindowSize(size) => {
+                let size = self.within_quota("window_size", self.quotas.window_size, size);
+                self.visitor.out.set_size(size);
+            }
+            Command::Scroll(offset) => {
+                if self.visitor.out.scroll(offset) {
+                    if self.matcher.is_some() && matches!(self.state, MatchState::Walking) {
+                        self.restart();
+                    } else {
+                        self.rematch();
+                    }
+                }
+            }
+            Command::Watchdog(watchdog) => {
+                self.watchdog = watchdog;
+            }
+            Command::Throttle(percent) => {
+                self.visitor.throttle = percent;
+            }
+            Command::MaxScan(max) => {
+                self.visitor.max_scan = max;
+            }
+            Command::ShallowFirst(on) => {
+                self.visitor.shallow_first = on;
+            }
+            Command::Count(on) => {
+                self.visitor.count = on;
+            }
+            Command::WalkBudget(budget) => {
+                self.walk_budget = budget;
+            }
+            Command::Progress(period) => {
+                self.progress_period = period;
+            }
+            Command::FdLimit(limit) => {
+                if self.fd_limit != limit {
+                    self.fd_limit = limit;
+                    if matches!(self.state, MatchState::Walking) {
+                        self.warn_fd_budget();
+                    }
+                    self.restart();
+                }
+            }
+            Command::WatchLimit(limit) => {
+                if self.watch_limit != limit {
+                    self.watch_limit = limit;
+                    if self.fs_watch {
+                        self.restart();
+                    }
+                }
+            }
+            Command::RootWatch(root_watch) => {
+                if self.root_watch.is_some() || !self.over_watcher_quota(root_watch.is_some()) {
+                    self.root_watch = root_watch;
+                    self.start_watchers();
+                }
+            }
+            Command::GitWatch(head_watch) => {
+                if self.head_watch.is_some() || !self.over_watcher_quota(head_watch.is_some()) {
+                    self.head_watch = head_watch;
+                    self.start_watchers();
+                }
+            }
+            Command::StaleAfter(staleness) => {
+                if self.staleness.is_some() || !self.over_watcher_quota(staleness.is_some()) {
+                    self.staleness = staleness;
+                    self.start_watchers();
+                }
+            }
+            Command::FsWatch(on) => {
+                if self.fs_watch != on && (!on || !self.over_watcher_quota(true)) {
+                    self.fs_watch = on;
+                    self.restart();
+                }
+            }
+            Command::Index(file) => self.index = file.map(PathBuf::from),
+            Command::Deterministic(on) => {
+                self.visitor.out.set_deterministic(on);
+            }
+            Command::Sort(order) => {
+                if self.visitor.out.set_order(order) {
+                    self.restart();
+                }
+            }
+            Command::Export { dest, format } => self.export(dest, format),
+            Command::Copy(paths) => self.copy(paths),
+            Command::Executable(on) => {
+                let kinds = self.options.kinds.unwrap_or_default();
+                let kinds = Kinds {
+                    executable: on,
+                    ..kinds
+                };
+                self.set_options(WalkOptions {
+                    kinds: (kinds != Kinds::default()).then_some(kinds),
+                    ..self.options.clone()
+                })
+            }
+            Command::BrokenLinks(mode) => self.set_options(WalkOptions {
+                broken_links: mode,
+                ..self.options.clone()
+            }),
+            Command::DedupHardlinks(dedup) => self.set_options(WalkOptions {
+                dedup,
+                ..self.options.clone()
+            }),
+            Command::Backend(backend) => self.set_options(WalkOptions {
+                backend,
+                ..self.options.clone()
+            }),
+            Command::NewerThan(newer) => self.set_options(WalkOptions {
+                newer_than: newer.map(|newer| newer.cutoff(SystemTime::now())),
+                ..self.options.clone()
+            }),
+            Command::MaxSize(max_size) => self.set_options(WalkOptions {
+                max_size,
+                ..self.options.clone()
+            }),
+            Command::WalkErrors(on) => self.set_options(WalkOptions {
+                report_errors: on,
+                ..self.options.clone()
+            }),
+            #[cfg(feature = "archives")]
+            Command::Archives(on) => self.set_options(WalkOptions {
+                archives: on,
+                ..self.options.clone()
+            }),
+            #[cfg(not(feature = "archives"))]
+            Command::Archives(_) => {
+                self.message("archives: built without the archives feature".to_string())
+            }
+            Command::Sparse(on) => self.set_options(WalkOptions {
+                sparse: on,
+                ..self.options.clone()
+            }),
+            Command::Submodules(on) => self.set_options(WalkOptions {
+                skip_submodules: !on,
+                ..self.options.clone()
+            }),
+            Command::MaxDepth(max_depth) => self.set_options(WalkOptions {
+                max_depth,
+                ..self.options.clone()
+            }),
+            Command::Hidden(hidden) => self.set_options(WalkOptions {
+                hidden,
+                ..self.options.clone()
+            }),
+            Command::FollowSymlinks(follow_links) => self.set_options(WalkOptions {
+                follow_links,
+                ..self.options.clone()
+            }),
+            Command::SameFs(same_file_system) => self.set_options(WalkOptions {
+                same_file_system,
+                ..self.options.clone()
+            }),
+            Command::IgnoreFiles(source, on) => {
+                let mut files = self.options.ignore_files;
+                match source {
+                    "gitignore" => files.gitignore = on,
+                    "global" => files.global = on,
+                    "exclude" => files.exclude = on,
+                    _ => files.parents = on,
+                }
+                self.set_options(WalkOptions {
+                    ignore_files: files,
+                    ..self.options.clone()
+                });
+            }
+            Command::Weight {
+                multiplier,
+                pattern,
+            } => match self.visitor.out.set_weight(pattern, multiplier) {
+                Ok(true) => self.restart(),
+                Ok(false) => {}
+                Err(err) => self.message(format!("weight {pattern}: {err}")),
+            },
+            Command::MinScore(score) => {
+                let old = self.visitor.out.set_min_score(score);
+                if old != score {
+                    self.change_pattern(if score.unwrap_or(i64::MIN) > old.unwrap_or(i64::MIN) {
+                        PatternScope::Narrow
+                    } else {
+                        PatternScope::Widen
+                    });
+                }
+            }
+            Command::Classify(on) => self.set_options(WalkOptions {
+                classify: on,
+                ..self.options.clone()
+            }),
+            Command::Type(name) => {
+                let mut types = self.options.types.clone();
+                match name {
+                    Some(name) => types.select.push(name.to_string()),
+                    None => {
+                        types.select.clear();
+                        types.negate.clear();
+                    }
+                }
+                self.set_types(types);
+            }
+            Command::TypeNot(name) => {
+                let mut types = self.options.types.clone();
+                types.negate.push(name.to_string());
+                self.set_types(types);
+            }
+            Command::TypeAdd(def) => {
+                let mut types = self.options.types.clone();
+                types.defs.push(def.to_string());
+                self.set_types(types);
+            }
+            Command::Metadata(on) => self.set_options(WalkOptions {
+                metadata: on,
+                ..self.options.clone()
+            }),
+            Command::Accepted(path) => {
+                self.hooks.run(Event::SelectionAccepted, path, 1);
+                self.set_context(path);
+            }
+            Command::Opened(path) => self.set_context(path),
+            Command::Visited(path) => self.visited(path),
+            Command::Run { name, args } => self.run_macro(name, args),
+            Command::WhyIgnored(path) => {
+                let reason = self.why_ignored(path);
+                self.message(format!("why-ignored {path}: {reason}"));
+            }
+            Command::Matcher(command_line) => {
+                let matcher = command_line.map(ExternalMatcher::new);
+                if self.matcher != matcher {
+                    self.matcher = matcher;
+                    self.restart();
+                }
+            }
+            Command::Protocol(version) => {
+                let version = version.min(PROTOCOL_VERSION);
+                self.visitor.out.set_protocol(version);
+                self.visitor.out.protocol_version(version);
+            }
+            Command::RateLimit(per_sec) => self.visitor.out.rate_limit().set(per_sec),
+            Command::Timing(on) => self.visitor.out.profile().set_timing(on),
+            Command::Profile(action) => {
+                let profile = self.visitor.out.profile();
+                match action {
+                    ProfileAction::Start => profile.start(),
+                    ProfileAction::Stop => profile.stop(),
+                    ProfileAction::Dump => self.message(profile.report()),
+                }
+            }
+        }
+    }
+
+    /// Set the ignore pattern and directory rules from the text of the `ignore` command.
+    fn set_ignore(&mut self, text: &str) {
+        let (rules, rest) = ignore_dirs::split(text);
+        self.ignore_pattern.set(0, &rest);
+        let dirs = IgnoreDirs::new(&rules).unwrap_or_else(|err| {
+            self.visitor.out.warning("ignore", err);
+            IgnoreDirs::default()
+        });
+        self.visitor.ignore_dirs = Arc::new(dirs);
+        self.visitor.forget_candidates();
+        self.ignore_text = text.to_string();
+    }
+
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        self.hooks = Arc::new(hooks);
+    }
+
+    pub fn set_macros(&mut self, macros: Macros) {
+        self.macros = macros;
+    }
+
+    /// Rank by the visits saved in `file`, saving them there as `visited` counts more.
+    pub fn set_frecency(&mut self, file: PathBuf) {
+        match Frecency::load(&file) {
+            Ok(frecency) => self.visitor.out.set_frecency(frecency),
+            Err(err) => self
+                .visitor
+                .out
+                .warning("frecency", format!("{}: {err}", file.display())),
+        }
+        self.frecency_file = Some(file);
+    }
+
+    /// Count a visit to `path`, relative to the root, and refill the window ranking it higher.
+    fn visited(&mut self, path: &str) {
+        let root = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        self.visitor.out.visited(root.join(path));
+        if let Some(file) = &self.frecency_file
+            && let Err(err) = self.visitor.out.save_frecency(file)
+        {
+            self.visitor
+                .out
+                .warning("frecency", format!("{}: {err}", file.display()));
+        }
+        self.restart();
+    }
+
+    /// Execute the commands of the macro `name`, stopping at the first that is invalid.
+    fn run_macro(&mut self, name: &str, args: &str) {
+        let lines = match self.macros.expand(name, args) {
+            Ok(lines) => lines,
+            Err(err) => return self.message(format!("run {name}: {err}")),
+        };
+        for line in lines {
+            let (cmd, arg) = super::chars_split_at_space(&line);
+            let command = match cmd {
+                "run" => Err("a macro can't run another".to_string()),
+                _ => Command::parse(cmd, arg).map_err(|err| err.to_string()),
+            };
+            match command {
+                Ok(command) => self.execute(command),
+                Err(err) => return self.message(format!("run {name}: {err}")),
+            }
+        }
+    }
+
+    /// Limit what the client may ask for, shrinking the window if it is already over.
+    pub fn set_quotas(&mut self, quotas: Quotas) {
+        self.quotas = quotas;
+        let size = self.within_quota("window_size", quotas.window_size, self.visitor.out.size());
+        self.visitor.out.set_size(size);
+    }
+
+    /// `wanted` cut down to `quota`, warning the client if it was.
+    fn within_quota(&self, name: &str, quota: Option<usize>, wanted: usize) -> usize {
+        let (value, warning) = quota::within(name, quota, wanted);
+        if let Some(detail) = warning {
+            self.visitor.out.warning("quota", detail);
+        }
+        value
+    }
+
+    /// Whether turning on another watcher would go over its quota, warning the client if so.
+    fn over_watcher_quota(&self, starting: bool) -> bool {
+        let on = [
+            self.root_watch.is_some(),
+            self.head_watch.is_some(),
+            self.staleness.is_some(),
+            self.fs_watch,
+        ];
+        let on = on.into_iter().filter(|on| *on).count();
+        starting && self.within_quota("watchers", self.quotas.watchers, on + 1) <= on
+    }
+
+    /// Where watchers send requests, such as `Signal::Rewalk`, for the thread running commands.
+    pub fn set_signals(&mut self, signals: mpsc::Sender<Signal>) {
+        self.signals = Some(signals);
+    }
+
+    /// Walk the root again from scratch, if walking. Watchers don't say which pattern they
+    /// watch for, so the named patterns walk again too.
+    pub fn rewalk(&mut self) {
+        if matches!(self.state, MatchState::Walking) {
+            self.restart();
+        }
+        for walker in self.named.values_mut() {
+            walker.rewalk();
+        }
+    }
+
+    /// Walk again without clearing: files gone from disk are removed and new ones added, so the
+    /// client only sees what changed.
+    pub fn refresh(&mut self) {
+        if matches!(self.state, MatchState::Walking) {
+            self.kill_walker();
+            self.visitor.out.remove_missing(&self.path);
+            self.ensure_running();
+        }
+        for walker in self.named.values_mut() {
+            walker.refresh();
+        }
+    }
+
+    /// Walk again just the subtrees the last walk failed to read, adding what they hold.
+    fn retry_errors(&mut self) {
+        if !matches!(self.state, MatchState::Walking) {
+            return;
+        }
+        let failed = mem::take(&mut *self.visitor.failed.lock().expect(crate::LOCK_SHOULD_BE_OK));
+        let Some((first, rest)) = failed.split_first() else {
+            self.message("retry-errors: nothing to retry".to_string());
+            return;
+        };
+        let running = self
+            .walker_thread
+            .as_ref()
+            .is_some_and(|t| !t.is_finished());
+        if running || self.matcher.is_some() {
+            // an unfinished walk has more to do, and the matcher has to be fed everything
+            self.refresh();
+            return;
+        }
+        self.kill_walker();
+        let mut walker = self.walk_builder(first);
+        for path in rest {
+            walker.add(path);
+        }
+        self.start_walk(Source::Paths(walker));
+    }
+
+    fn warn_fd_budget(&self) {
+        if let Some(detail) = FdBudget::new(self.fd_limit).warning() {
+            self.visitor.out.warning("fd-limit", detail);
+        }
+    }
+
+    /// Restart the wanted watchers for the current walk.
+    fn start_watchers(&mut self) {
+        self.root_watcher = None;
+        self.head_watcher = None;
+        self.stale_checker = None;
+        if !matches!(self.state, MatchState::Walking) {
+            return;
+        }
+        if let Some(root_watch) = self.root_watch {
+            let (done_tx, done_rx) = mpsc::channel();
+            root_watch.spawn(
+                self.path.clone(),
+                done_rx,
+                self.visitor.out.clone(),
+                self.signals.clone(),
+            );
+            self.root_watcher = Some(done_tx);
+        }
+        if let (Some(head_watch), Some(signals)) = (self.head_watch, &self.signals) {
+            let (done_tx, done_rx) = mpsc::channel();
+            if head_watch
+                .spawn(&self.path, done_rx, signals.clone())
+                .is_some()
+            {
+                self.head_watcher = Some(done_tx);
+            }
+        }
+        if let Some(staleness) = self.staleness {
+            let (done_tx, done_rx) = mpsc::channel();
+            staleness.spawn(self.path.clone(), done_rx, self.visitor.out.clone());
+            self.stale_checker = Some(done_tx);
+        }
+    }
+
+    /// Change the file types walked, unless `types` names one that is not defined.
+    fn set_types(&mut self, types: TypeFilter) {
+        match types.build() {
+            Ok(_) => self.set_options(WalkOptions {
+                types,
+                ..self.options.clone()
+            }),
+            Err(err) => self.visitor.out.warning("type", err.to_string()),
+        }
+    }
+
+    /// Change what the walk visits, restarting it if running.
+    pub fn set_options(&mut self, options: WalkOptions) {
+        if self.options != options {
+            self.visitor.options = Arc::new(options.clone());
+            self.options = options;
+            self.visitor.forget_candidates();
+            self.restart();
+        }
+    }
+
+    /// Explain what, if anything, stops a walk reporting `path`, relative to the root.
+    fn why_ignored(&self, path: &str) -> String {
+        let rel = Path::new(path);
+        // `./` is not part of the path reported
+        let rel: PathBuf = rel
+            .strip_prefix(&self.path)
+            .unwrap_or(rel)
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect();
+        let rel = rel.as_path();
+        let full = self.path.join(rel);
+        let Ok(meta) = fs::symlink_metadata(&full) else {
+            return "does not exist".to_string();
+        };
+        let is_dir = meta.is_dir();
+        let names: Vec<_> = rel
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        if names.iter().any(|n| *n == ".git") {
+            return "git's own files are never walked".to_string();
+        }
+        if !self.options.hidden
+            && let Some(name) = names.iter().find(|n| n.as_bytes().starts_with(b"."))
+        {
+            return format!("hidden by {}", name.to_string_lossy());
+        }
+        if let Some(max) = self.options.max_depth
+            && names.len() > max
+        {
+            return format!("deeper than max depth {max}");
+        }
+        if !self.options.no_ignore
+            && let Some(rule) = git::ignore_rule(
+                &full,
+                is_dir,
+                self.excludes_file.as_deref(),
+                self.options.ignore_files,
+                &self.path,
+            )
+        {
+            return format!("ignored by {rule}");
+        }
+        if let Some(rule) = self.visitor.ignore_dirs.excluding(rel, is_dir) {
+            return format!("in a directory ignored by {rule}");
+        }
+        if excluded_type(self.options.types.matcher().as_ref(), rel, is_dir) {
+            return "not of a type being walked".to_string();
+        }
+        let data = rel.as_os_str().as_bytes();
+        if self.ignore_pattern.any_matches(data) {
+            return format!("matches ignore {}", self.ignore_pattern.clone_text());
+        }
+        if self.options.skip_submodules
+            && let Some(dir) = full
+                .ancestors()
+                .take(names.len())
+                .find(|d| git::is_worktree(d))
+        {
+            return format!("in submodule {}", dir.display());
+        }
+        if self.options.sparse
+            && let Some(sparse) = git::Sparse::load(&self.path)
+            && sparse.excludes(&full, is_dir)
+        {
+            return "outside the sparse checkout".to_string();
+        }
+        let entry = WalkBuilder::new(&full)
+            .standard_filters(false)
+            .max_depth(Some(0))
+            .build()
+            .next();
+        if !is_dir
+            && let Some(Ok(entry)) = &entry
+            && let Some(reason) = beyond_limits(&self.options, entry)
+        {
+            return reason.to_string();
+        }
+        let reported = match entry {
+            Some(Ok(_)) if is_dir => self.options.kinds.is_some_and(|k| k.dir),
+            Some(Ok(entry)) => wanted(&self.options, &entry),
+            _ => false,
+        };
+        if !reported {
+            return "not a kind of entry being reported".to_string();
+        }
+        if !self.visitor.out.matches(data) {
+            return "not ignored, but does not match the query".to_string();
+        }
+        "not ignored".to_string()
+    }
+
+    /// Rank results in the directory of `path` first, as the next file wanted is usually near the
+    /// last.
+    fn set_context(&mut self, path: &str) {
+        let dir = path
+            .rfind('/')
+            .map(|i| Bytes::copy_from_slice(&path.as_bytes()[..=i]));
+        // the window holds only the best entries so those now ranked first may be missing
+        if self.visitor.out.set_context(dir) {
+            self.restart();
+        }
+    }
+
+    #[inline(always)]
+    pub fn message(&self, value: String) {
+        self.visitor.out.message(value);
+    }
+
+    /// Called after the command `number`; if it left no walk or match running there is no more
+    /// output to come from it.
+    pub fn command_done(&self, number: u64) {
+        if self.match_thread.is_none()
+            && self.walker_thread.as_ref().is_none_or(|t| t.is_finished())
+        {
+            self.visitor.out.command_done(number);
+        }
+    }
+
+    fn warn_complex_query(&self) {
+        for pattern in [&self.pattern, self.visitor.out.refine()] {
+            if let Some(detail) = pattern.take_warning() {
+                self.visitor.out.warning("complex-query", detail);
+            }
+        }
+    }
+
+    fn change_pattern(&mut self, scope: PatternScope) {
+        self.warn_complex_query();
+        // the score order ranks by the query, so a narrower one may want what a full window
+        // evicted
+        let rescore = self.visitor.out.order().has(SortKey::Score)
+            && !matches!(self.state, MatchState::Stopped);
+        if self.matcher.is_some() && matches!(self.state, MatchState::Walking) {
+            // only the matcher knows what the new query selects
+            self.restart();
+        } else if matches!(scope, PatternScope::Narrow) && !rescore {
+            self.visitor.out.remove_unmatched();
+        } else {
+            self.rematch();
+        }
+    }
+
+    /// Match everything again, keeping what still matches, for when the window may want entries
+    /// it did not keep.
+    fn rematch(&mut self) {
+        match self.state {
+            MatchState::Walking => {
+                self.kill_walker();
+                self.visitor.out.remove_unmatched();
+                match self.visitor.kept(false) {
+                    Some(entries) => self.start_walk(Source::Candidates(entries)),
+                    None => self.ensure_running(),
+                }
+            }
+            MatchState::Matching => {
+                self.kill_match_thread();
+                self.visitor.out.request_resync();
+            }
+            MatchState::Stopped => {}
+        }
+    }
+
+    /// Refill the window from scratch; used when a change invalidates everything matched so far.
+    fn restart(&mut self) {
+        self.kill_thread();
+        self.visitor.out.clear();
+        match self.state {
+            MatchState::Walking => self.ensure_running(),
+            MatchState::Matching => self.visitor.out.request_resync(),
+            MatchState::Stopped => {}
+        }
+    }
+
+    fn walk(&mut self, dir: &str) -> Result<(), Error> {
+        let expanded = expand::expand(dir).map_err(Error::Expand)?;
+        self.path = expanded.as_deref().unwrap_or(dir).into();
+        if expanded.is_some() {
+            self.path = fs::canonicalize(&self.path).map_err(Error::from_io)?;
+        }
+        if !self.path.is_dir() {
+            return Err(Error::NotADirectory);
+        }
+        self.path.push("");
+        self.excludes_file = git_excludes_file(&self.path);
+        self.kill_thread();
+        self.visitor.root = Bytes::copy_from_slice(self.path.as_os_str().as_bytes());
+        // whole, so frecency is kept by the same paths whichever way the root is named
+        self.visitor
+            .out
+            .set_root(&fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone()));
+        self.roots.clear();
+        self.state = MatchState::Walking;
+        self.visitor.forget_candidates();
+        if expanded.is_some() {
+            // so the client knows what `~` and the variables named
+            self.message(format!("walk-root {}", self.path.display()));
+        }
+        if let Some(file) = &self.index {
+            self.load_index(&file.clone());
+        }
+        Ok(())
+    }
+
+    /// Use the index in `file`, if it was saved for the root, as the candidates of the walk.
+    fn load_index(&mut self, file: &Path) {
+        let index = match index::load(file, &self.path) {
+            Ok(Some(index)) => index,
+            Ok(None) => return,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+            Err(err) => {
+                self.visitor
+                    .out
+                    .warning("index", format!("{}: {err}", file.display()));
+                return;
+            }
+        };
+        let age = index.saved.elapsed().unwrap_or_default().as_secs();
+        self.message(format!(
+            "index {} paths saved {age}s ago",
+            index.paths.len()
+        ));
+        let mut candidates = self
+            .visitor
+            .candidates
+            .lock()
+            .expect(crate::LOCK_SHOULD_BE_OK);
+        candidates.complete = Some(Arc::new(
+            index.paths.into_iter().map(Candidate::Indexed).collect(),
+        ));
+        candidates.indexed = true;
+    }
+
+    /// Add `dir` to the roots being walked. It must not overlap any of them.
+    fn walk_add(&mut self, dir: &str) -> Result<(), String> {
+        if !matches!(self.state, MatchState::Walking) {
+            return Err("not walking".to_string());
+        }
+        let dir = expand::expand(dir)?.unwrap_or_else(|| dir.to_string());
+        let mut root = fs::canonicalize(&dir).map_err(|err| err.to_string())?;
+        if !root.is_dir() {
+            return Err("not a directory".to_string());
+        }
+        root.push("");
+        let path = fs::canonicalize(&self.path).map_err(|err| err.to_string())?;
+        if let Some(walked) = [&path]
+            .into_iter()
+            .chain(&self.roots)
+            .find(|r| r.starts_with(&root) || root.starts_with(r))
+        {
+            return Err(format!("overlaps {}", walked.display()));
+        }
+        self.roots.push(root);
+        Ok(())
     }
 
     fn kill_thread(&mut self) {
@@ -324,11 +2439,16 @@ impl Walker {
     }
 
     fn kill_walker(&mut self) {
-        let Some(t) = self.walker_thread.take() else {
+        if self.walker_thread.is_none() && self.matcher_process.is_none() {
             return;
-        };
+        }
         self.visitor.kill();
-        let _ = t.join();
+        if let Some(t) = self.walker_thread.take() {
+            let _ = t.join();
+        }
+        if let Some(process) = self.matcher_process.take() {
+            process.kill();
+        }
     }
 
     fn kill_match_thread(&mut self) {
@@ -340,16 +2460,389 @@ impl Walker {
         let _ = t.join();
     }
 
+    /// The threads a walk may use within the fd budget and quota.
+    fn walker_threads(&self) -> usize {
+        let threads = FdBudget::new(self.fd_limit).threads;
+        self.quotas
+            .walker_threads
+            .map_or(threads, |max| threads.min(max))
+    }
+
+    /// A builder walking the root and every root added by `walk-add`.
+    fn roots_builder(&self) -> WalkBuilder {
+        let mut walker = self.walk_builder(&self.path);
+        for root in &self.roots {
+            walker.add(root);
+        }
+        walker
+    }
+
+    fn walk_builder(&self, root: &Path) -> WalkBuilder {
+        let mut walker = WalkBuilder::new(root);
+        walker
+            .hidden(!self.options.hidden)
+            .max_depth(self.options.max_depth)
+            .follow_links(self.options.follow_links)
+            .same_file_system(self.options.same_file_system)
+            .threads(self.walker_threads());
+        if let Some(types) = self.options.types.matcher() {
+            walker.types(types);
+        }
+        if self.options.no_ignore {
+            walker
+                .ignore(false)
+                .git_ignore(false)
+                .git_global(false)
+                .git_exclude(false);
+        } else {
+            let files = self.options.ignore_files;
+            walker
+                .git_ignore(files.gitignore)
+                .git_global(files.global)
+                .git_exclude(files.exclude)
+                .parents(files.parents);
+            if files.global
+                && let Some(file) = &self.excludes_file
+            {
+                // replaces ignore's own lookup which only reads ~/.gitconfig
+                walker.git_global(false);
+                walker.add_ignore(file);
+            }
+        }
+        let sparse = self
+            .options
+            .sparse
+            .then(|| git::Sparse::load(&self.path))
+            .flatten();
+        let skip_submodules = self.options.skip_submodules;
+        walker.filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+            // git's own files, or a linked worktree's pointer to them
+            entry.file_name() != ".git"
+                && !(entry.depth() > 0
+                    && (skip_submodules && is_dir && git::is_worktree(entry.path())
+                        || sparse
+                            .as_ref()
+                            .is_some_and(|sparse| sparse.excludes(entry.path(), is_dir))))
+        });
+        walker
+    }
+
+    /// Write every match, not just those in the window, to `dest` in the background. Only a walk
+    /// can be redone; otherwise the window's entries are written.
+    fn export(&self, dest: &str, format: ExportFormat) {
+        let out = self.visitor.out.clone();
+        let dest = PathBuf::from(dest);
+        let walker = (matches!(self.state, MatchState::Walking) && self.matcher.is_none())
+            .then(|| self.roots_builder().build());
+        let options = self.options.clone();
+        let ignore_pattern = self.ignore_pattern.clone();
+        let ignore_dirs = self.visitor.ignore_dirs.clone();
+        let root = self.visitor.root.clone();
+        thread::spawn(move || {
+            let mut paths = match walker {
+                Some(walker) => walker
+                    .flatten()
+                    .filter(|entry| wanted(&options, entry))
+                    .filter_map(|entry| {
+                        let data = relative(&root, entry.path());
+                        let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+                        (ignore_dirs
+                            .excluding(Path::new(OsStr::from_bytes(data)), is_dir)
+                            .is_none()
+                            && !ignore_pattern.any_matches(data)
+                            && out.matches(data))
+                        .then(|| Bytes::copy_from_slice(data))
+                    })
+                    .collect(),
+                None => out.paths(),
+            };
+            paths.sort_by_cached_key(|p| out.entry(p.clone()));
+            match export::write(&dest, format, &paths) {
+                Ok(()) => out.message(format!("exported {} to {}", paths.len(), dest.display())),
+                Err(err) => out.message(format!("export {} failed: {err}", dest.display())),
+            }
+        });
+    }
+
+    /// Copy `paths`, separated by `FIELD_SEP`, to the clipboard on a new thread as a clipboard
+    /// tool may take a while.
+    fn copy(&self, paths: &str) {
+        let out = self.visitor.out.clone();
+        let paths: Vec<String> = paths.split(FIELD_SEP as char).map(String::from).collect();
+        thread::spawn(move || match clipboard::copy(&paths) {
+            Ok(()) => out.message(format!("copied {} to the clipboard", paths.len())),
+            Err(err) => out.message(format!("copy failed: {err}")),
+        });
+    }
+
     fn ensure_running(&mut self) {
         if self.walker_thread.is_none() {
-            self.visitor.out.started();
-            let walker = WalkBuilder::new(&self.path).build_parallel();
-            self.visitor.walker_version.start();
-            let mut builder = self.visitor.clone();
-            self.walker_thread = Some(thread::spawn(move || {
-                walker.visit(&mut builder);
-                builder.out.done();
-            }));
+            self.visitor
+                .failed
+                .lock()
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .clear();
+            if self.options.backend == Backend::Git
+                && let Some(root) = self.git_root()
+            {
+                self.start_walk(Source::Git(root));
+                return;
+            }
+            let walker = self.roots_builder();
+            self.start_walk(match self.visitor.kept(true) {
+                Some(entries) => Source::Indexed(entries, walker),
+                None => Source::Roots(walker),
+            });
+        }
+    }
+
+    /// The root for git to list, warning why the root is walked instead when it can't be.
+    fn git_root(&self) -> Option<PathBuf> {
+        if !self.roots.is_empty() {
+            self.visitor
+                .out
+                .warning("backend", "git: walk-add roots are walked".to_string());
+            return None;
+        }
+        if git::worktree_root(&self.path).is_none() {
+            self.visitor.out.warning(
+                "backend",
+                format!(
+                    "git: {} is not in a worktree so is walked",
+                    self.path.display()
+                ),
+            );
+            return None;
+        }
+        Some(self.path.clone())
+    }
+
+    fn start_walk(&mut self, source: Source) {
+        self.visitor.out.started();
+        let threads = self.walker_threads();
+        let gather = matches!(source, Source::Roots(_) | Source::Indexed(..));
+        if gather {
+            let mut candidates = self
+                .visitor
+                .candidates
+                .lock()
+                .expect(crate::LOCK_SHOULD_BE_OK);
+            candidates.found.clear();
+            if matches!(source, Source::Roots(_)) {
+                candidates.complete = None;
+            }
+        }
+        if matches!(source, Source::Git(_)) {
+            self.visitor.forget_candidates();
+        }
+        let index = self
+            .index
+            .clone()
+            .filter(|_| self.roots.is_empty())
+            .map(|file| (file, self.path.clone()));
+        self.visitor.walker_version.start();
+        self.visitor
+            .walker_version
+            .set_request(self.visitor.out.request());
+        self.visitor.progress.reset();
+        self.visitor.out.profile().reset();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        if let Some(watchdog) = self.watchdog {
+            watchdog.spawn(
+                done_rx,
+                self.visitor.progress.clone(),
+                self.visitor.out.clone(),
+                self.visitor.walker_version.clone(),
+            );
+        }
+        let (budget_tx, budget_rx) = mpsc::channel::<()>();
+        if let Some(budget) = self.walk_budget {
+            watchdog::budget(
+                budget,
+                budget_rx,
+                self.visitor.progress.clone(),
+                self.visitor.out.clone(),
+                self.visitor.walker_version.clone(),
+            );
+        }
+        let (report_tx, report_rx) = mpsc::channel::<()>();
+        if let Some(period) = self.progress_period {
+            watchdog::report(
+                period,
+                report_rx,
+                self.visitor.progress.clone(),
+                self.visitor.out.clone(),
+                self.visitor.walker_version.clone(),
+            );
+        }
+        let mut builder = self.visitor.clone();
+        builder.links = Default::default();
+        builder.feed = None;
+        builder.gather = gather;
+        let mut walk_done = Some(self.walk_done());
+        if let Some(matcher) = &self.matcher {
+            match matcher.spawn(
+                &self.pattern.clone_text(),
+                self.visitor.out.clone(),
+                self.visitor.out.version(),
+                self.visitor.walker_version.clone(),
+                walk_done.take().expect("walk_done should be unused"),
+            ) {
+                Ok(process) => {
+                    builder.feed = Some(process.feed.clone());
+                    self.matcher_process = Some(process);
+                }
+                Err(err) => {
+                    self.message(format!("matcher failed: {err}"));
+                    self.visitor.out.done(&self.visitor.walker_version);
+                    return;
+                }
+            }
+        }
+        let fs_watch = (self.fs_watch && builder.feed.is_none()).then(|| self.fs_watcher());
+        self.walker_thread = Some(thread::spawn(move || {
+            let _done_tx = (done_tx, budget_tx, report_tx);
+            let profile = builder.out.profile().clone();
+            let mut kept = None;
+            if let Err(err) = panic::catch_unwind(AssertUnwindSafe(|| {
+                profile.time(Stage::Walk, || match source {
+                    Source::Roots(walker) => {
+                        walker.build_parallel().visit(&mut builder);
+                        kept = builder.keep_candidates();
+                    }
+                    Source::Paths(walker) => walker.build_parallel().visit(&mut builder),
+                    Source::Candidates(entries) => builder.rematch(&entries, threads),
+                    Source::Indexed(entries, walker) => {
+                        builder.rematch(&entries, threads);
+                        walker.build_parallel().visit(&mut builder);
+                        kept = builder.keep_candidates();
+                    }
+                    Source::Git(root) => match builder.list_git(&root) {
+                        Ok(entries) => {
+                            builder.rematch(&entries, threads);
+                            kept = builder.keep_listed(entries);
+                        }
+                        Err(err) => builder.out.warning("backend", format!("git: {err}")),
+                    },
+                })
+            })) {
+                builder.out.fatal(super::panic_message(err.as_ref()));
+            }
+            if let Some(feed) = &builder.feed {
+                // the matcher reports done once it has read everything
+                feed.close();
+            }
+            if let Some(walk_done) = walk_done {
+                walk_done();
+            }
+            if let (Some(kept), Some((file, root))) = (kept, index)
+                && let Err(err) = index::save(&file, &root, kept.iter().map(Candidate::path))
+            {
+                builder
+                    .out
+                    .warning("index", format!("{}: {err}", file.display()));
+            }
+            if let Some(fs_watch) = fs_watch
+                && !builder.walker_version.is_wrong()
+            {
+                fs_watch(builder.visitor());
+            }
+        }));
+    }
+
+    /// Watch the root, once walked, until the walk is killed: removed entries are removed from
+    /// the window and created ones offered. A new directory with entries, or missed events,
+    /// refresh the walk. Nothing is watched if the root has more directories than the watch
+    /// budget allows.
+    fn fs_watcher(&self) -> impl FnOnce(Visitor) + Send + 'static + use<> {
+        let root = self.path.clone();
+        let budget = WatchBudget::new(self.watch_limit);
+        let excludes_file = self.excludes_file.clone();
+        let signals = self.signals.clone();
+        move |visitor| {
+            let walker_version = visitor.walker_version.clone();
+            let out = visitor.out.clone();
+            if let Some(detail) = budget.warning(&root) {
+                out.warning("watch-limit", detail);
+                return;
+            }
+            let watching = fs_watch::spawn(
+                root.clone(),
+                move || walker_version.is_wrong(),
+                move |change| {
+                    // the walk's candidates no longer match the disk
+                    visitor
+                        .candidates
+                        .lock()
+                        .expect(crate::LOCK_SHOULD_BE_OK)
+                        .complete = None;
+                    match change {
+                        Change::Removed(rel) => {
+                            visitor.out.remove_under(rel.as_os_str().as_bytes())
+                        }
+                        Change::Added(rel) => {
+                            if visitor.created(&root, &rel, excludes_file.as_deref())
+                                && let Some(signals) = &signals
+                            {
+                                let _ = signals.send(Signal::Refresh);
+                            }
+                        }
+                        Change::Lost(detail) => {
+                            visitor.out.warning("fs-watch", detail);
+                            if let Some(signals) = &signals {
+                                let _ = signals.send(Signal::Refresh);
+                            }
+                        }
+                    }
+                },
+            );
+            if let Err(err) = watching {
+                out.warning("fs-watch", err.to_string());
+            }
+        }
+    }
+
+    /// Tell the client the walk is done then, unless it was killed, run the walk-done hook.
+    fn walk_done(&self) -> impl FnOnce() + Send + 'static + use<> {
+        let out = self.visitor.out.clone();
+        let walker_version = self.visitor.walker_version.clone();
+        let hooks = self.hooks.clone();
+        let failed = self.visitor.failed.clone();
+        let progress = self.visitor.progress.clone();
+        let stats = self.progress_period.map(|_| Instant::now());
+        let count = self.visitor.count;
+        let path = self
+            .path
+            .components()
+            .as_path()
+            .to_string_lossy()
+            .into_owned();
+        move || {
+            let failed = failed.lock().expect(crate::LOCK_SHOULD_BE_OK).len();
+            if failed > 0 && !walker_version.is_wrong() {
+                out.warning(
+                    "walk-errors",
+                    format!("{failed} paths could not be read; retry-errors walks them again"),
+                );
+            }
+            if count
+                && !walker_version.is_wrong()
+                && let Some(matched) = progress.uncounted()
+            {
+                out.count(matched);
+            }
+            if progress.is_truncated() && !walker_version.is_wrong() {
+                out.truncated(&walker_version);
+            } else {
+                out.done(&walker_version);
+            }
+            if !walker_version.is_wrong() {
+                if let Some(started) = stats {
+                    out.walk_stats(progress.scanned(), progress.matched(), started.elapsed());
+                }
+                hooks.run(Event::WalkDone, &path, progress.matched());
+            }
         }
     }
 
@@ -362,16 +2855,28 @@ impl Walker {
             let (tx, rx) = mpsc::channel();
             self.match_sender = Some(tx);
             self.visitor.walker_version.start();
+            self.visitor
+                .walker_version
+                .set_request(self.visitor.out.request());
             let walker_version = self.visitor.walker_version.clone();
             let builder = self.visitor.clone();
             self.match_thread = Some(thread::spawn(move || {
-                for msg in rx.iter() {
-                    builder.out.add(msg, 0, &walker_version);
+                if let Err(err) = panic::catch_unwind(AssertUnwindSafe(|| {
+                    for msg in rx.iter() {
+                        builder.out.add(msg, 0, &walker_version);
+                    }
+                })) {
+                    builder.out.fatal(super::panic_message(err.as_ref()));
                 }
             }))
         }
 
         if !self.ignore_pattern.any_matches(arg.as_bytes())
+            && self
+                .visitor
+                .ignore_dirs
+                .excluding(Path::new(arg), false)
+                .is_none()
             && let Some(tx) = &self.match_sender
             && tx.send(Bytes::copy_from_slice(arg.as_bytes())).is_err()
         {
@@ -381,6 +2886,30 @@ impl Walker {
     }
 }
 
+/// Find the global git excludes file for `dir` the way git does: `core.excludesFile` from any
+/// config scope (including includes and the repo's own config), falling back to
+/// `$XDG_CONFIG_HOME/git/ignore`. Only applies when `dir` is inside a git work tree.
+fn git_excludes_file(dir: &Path) -> Option<PathBuf> {
+    let dir = fs::canonicalize(dir).ok()?;
+    if !dir.ancestors().any(|d| d.join(".git").exists()) {
+        return None;
+    }
+    let path = process::Command::new("git")
+        .arg("-C")
+        .arg(&dir)
+        .args(["config", "--type=path", "--get", "core.excludesFile"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|p| p.trim_end().to_string())
+        .filter(|p| !p.is_empty())
+        .map(|p| dir.join(p))
+        .or_else(ignore::gitignore::gitconfig_excludes_path)?;
+    path.is_file().then_some(path)
+}
+
 #[cfg(test)]
 #[path = "walker_test.rs"]
 mod test;
@@ -1,8 +1,14 @@
-use std::{sync::mpsc, time::Duration};
+use std::{
+    collections::BTreeSet,
+    env,
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
 
 use pretty_assertions::assert_matches;
 
 use super::*;
+use crate::test_util::TempDir;
 
 const WT: Duration = Duration::from_millis(200);
 
@@ -142,6 +148,51 @@ fn window_size() {
     assert_eq!(win.size(), 3);
 }
 
+#[test]
+fn quotas() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win.clone());
+
+    walker.set_quotas(Quotas {
+        window_size: Some(4),
+        watchers: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(win.size(), 4);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Warning {
+            kind: "quota".to_string(),
+            detail: "window_size 5 is over the quota of 4".to_string()
+        }
+    );
+
+    walker.command("window_size", "3").unwrap();
+    assert_eq!(win.size(), 3);
+    walker.command("window_size", "9").unwrap();
+    assert_eq!(win.size(), 4);
+    assert_matches!(rx.recv_timeout(WT).unwrap(), Msg::Warning { kind, .. } if kind == "quota");
+
+    walker.command("root-watch", "50").unwrap();
+    walker.command("git-watch", "50").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Warning {
+            kind: "quota".to_string(),
+            detail: "watchers 2 is over the quota of 1".to_string()
+        }
+    );
+    assert_eq!(walker.head_watch, None);
+
+    // changing or stopping a running watcher is always allowed
+    walker.command("root-watch", "60").unwrap();
+    walker.command("root-watch", "off").unwrap();
+    walker.command("git-watch", "50").unwrap();
+    assert_matches!(walker.head_watch, Some(_));
+    assert_matches!(rx.try_recv(), Err(_));
+}
+
 #[test]
 fn remove() {
     let (tx, mut rx) = mpsc::sync_channel(5);
@@ -193,14 +244,106 @@ fn stop() {
 
     walker.command("ignore", "foo").unwrap();
     assert_eq!(walker.visitor.ignore_pattern.clone_text(), "foo");
-    assert_eq!(walker.visitor.pattern.clone_text(), ">2.txt a/1");
+    assert_eq!(walker.visitor.out.pattern().clone_text(), ">2.txt a/1");
 
     walker.command("stop", "").unwrap();
 
-    assert_eq!(walker.visitor.pattern.clone_text(), "");
+    assert_eq!(walker.visitor.out.pattern().clone_text(), "");
     assert_eq!(walker.visitor.ignore_pattern.clone_text(), "");
 }
 
+#[test]
+fn widen_from_candidates() {
+    let dir = TempDir::new("walk_candidates");
+    for file in ["apple.txt", "apricot.txt", "banana.txt"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker
+        .command("switch", &format!("{} apple", dir.display()))
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+apple.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // not walked again, so not found
+    fs::write(dir.join("apex.txt"), "").unwrap();
+    walker.command("rm", "3").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+apricot.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("ignore", "*.log").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("rm", "1").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(
+        to_raf(&mut rx, 4),
+        "+apex.txt +apple.txt +apricot.txt +banana.txt"
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn index() {
+    let dir = TempDir::new("walk_index");
+    let file = env::temp_dir().join(format!("koru_find_walk_index_{}.idx", process::id()));
+    for file in ["kept.txt", "gone.txt", "other.rs"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let _ = fs::remove_file(&file);
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("index", file.to_str().unwrap()).unwrap();
+    walker
+        .command("switch", &format!("{} txt", dir.display()))
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+gone.txt +kept.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    wait_running(&mut walker, WT);
+    assert!(file.exists());
+
+    fs::remove_file(dir.join("gone.txt")).unwrap();
+    fs::write(dir.join("new.txt"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("index", file.to_str().unwrap()).unwrap();
+    walker
+        .command("switch", &format!("{} txt", dir.display()))
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_matches!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(m) if m.starts_with("index 3 paths saved ")
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    // the indexed path is offered first, then the walk finds what is new
+    assert_eq!(to_raf(&mut rx, 1), "+kept.txt");
+    assert_eq!(to_raf(&mut rx, 1), "+new.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    wait_running(&mut walker, WT);
+    assert_eq!(
+        index::load(&file, &walker.path)
+            .unwrap()
+            .unwrap()
+            .paths
+            .len(),
+        3
+    );
+
+    let _ = fs::remove_file(&file);
+}
+
 #[test]
 fn redraw() {
     let (tx, mut rx) = mpsc::sync_channel(5);
@@ -331,3 +474,2166 @@ fn ignore_pattern() {
     assert_eq!(to_raf(&mut rx, 1), "+a/1/3.txt");
     assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
 }
+
+#[test]
+fn git_excludes_file() {
+    let dir = TempDir::new("excludes");
+    let repo = dir.join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(dir.join("excludes"), "*.log\n").unwrap();
+    fs::write(repo.join("keep.txt"), "").unwrap();
+    fs::write(repo.join("junk.log"), "").unwrap();
+    let git = |args: &[&str]| {
+        process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(args)
+            .status()
+            .unwrap()
+    };
+    git(&["init", "-q"]);
+    git(&["config", "core.excludesFile", "../excludes"]);
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", repo.to_str().unwrap()).unwrap();
+    walker.command("add", "k").unwrap();
+
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+keep.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn ignore_files() {
+    let dir = TempDir::new("walk_ignore_files");
+    fs::create_dir_all(dir.join(".git/info")).unwrap();
+    fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(dir.join(".git/info/exclude"), "*.tmp\n").unwrap();
+    for file in ["keep.txt", "junk.log", "x.tmp"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let sorted = |rx: &mut mpsc::Receiver<Msg>, n| {
+        let mut found: Vec<_> = (0..n).map(|_| to_raf(rx, 1)).collect();
+        found.sort();
+        found.join(" ")
+    };
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+keep.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("ignore-files", "gitignore off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(sorted(&mut rx, 2), "+junk.log +keep.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    assert_eq!(
+        walker.why_ignored("x.tmp").split(": ").last(),
+        Some("*.tmp")
+    );
+
+    walker.command("ignore-files", "exclude off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(sorted(&mut rx, 3), "+junk.log +keep.txt +x.tmp");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn sort() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    assert_matches!(
+        walker.command("sort", "size"),
+        Err(Error::InvalidArgument(_))
+    );
+    walker.command("sort", "path").unwrap();
+    assert_matches!(rx.try_recv(), Err(_));
+
+    walker.command("match", "x10").unwrap();
+    walker.command("match", "x9").unwrap();
+    assert_eq!(to_raf(&mut rx, 2), "+x10 +x9");
+
+    walker.command("sort", "natural").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Resync);
+}
+
+#[test]
+fn sort_mtime() {
+    let dir = TempDir::new("walk_sort_mtime");
+    for (name, secs) in [("b", 1_000), ("a", 3_000), ("c", 2_000)] {
+        let file = fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    walker.command("sort", "mtime").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    // to_raf sorts, so check the order they were sent in
+    let sent: Vec<_> = (0..3).map(|_| rx.recv_timeout(WT).unwrap()).collect();
+    assert_eq!(
+        sent,
+        ["a", "c", "b"].map(|p| Msg::AddFile(Bytes::from_static(p.as_bytes())))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // found by path even though the file, and so its key, has gone
+    fs::remove_file(dir.join("c")).unwrap();
+    walker.visitor.out.remove_under(b"c");
+    assert_eq!(to_raf(&mut rx, 1), "-c");
+    let version = walker.visitor.out.version();
+    fs::remove_file(dir.join("a")).unwrap();
+    walker.visitor.out.remove("a", version).unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "-a");
+    assert_eq!(walker.visitor.out.paths(), ["b"]);
+}
+
+#[test]
+fn sort_score() {
+    let dir = TempDir::new("walk_sort_score");
+    for file in ["a", "ab", "zzzzzzax"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(2, tx);
+    let mut walker = Walker::new(win);
+    walker.command("sort", "score").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker
+        .command("switch", &format!("{} a", dir.display()))
+        .unwrap();
+    while rx.recv_timeout(WT).is_ok_and(|msg| msg != Msg::WalkDone) {}
+    // the walk never waits for room, keeping the best matches
+    assert_eq!(walker.visitor.out.paths(), ["a", "ab"]);
+
+    // a narrower query matches what was evicted
+    walker.command("add", "x").unwrap();
+    while rx.recv_timeout(WT).is_ok_and(|msg| msg != Msg::WalkDone) {}
+    assert_eq!(walker.visitor.out.paths(), ["zzzzzzax"]);
+}
+
+#[test]
+fn sort_keys() {
+    let dir = TempDir::new("walk_sort_keys");
+    for file in ["ccc", "dd", "a", "bb"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(3, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    walker.command("sort", "len,path").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    while rx.recv_timeout(WT).is_ok_and(|msg| msg != Msg::WalkDone) {}
+    assert_eq!(walker.visitor.out.paths(), ["a", "bb", "dd"]);
+}
+
+#[test]
+fn scroll() {
+    let dir = TempDir::new("walk_scroll");
+    for file in ["a", "b", "c", "d", "e"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, rx) = mpsc::sync_channel(20);
+    let win = Window::new(2, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    let mut shown = BTreeSet::new();
+    let mut apply = |rx: &mpsc::Receiver<Msg>| {
+        while let Ok(msg) = rx.recv_timeout(WT) {
+            match msg {
+                Msg::AddFile(path) => assert!(shown.insert(path)),
+                Msg::RmFile(path) => assert!(shown.remove(&path)),
+                Msg::WalkDone => break,
+                _ => {}
+            }
+        }
+        shown
+            .iter()
+            .map(|p| str::from_utf8(p).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(apply(&rx), ["a", "b"]);
+
+    // the window is refilled to hold the entries before the page too
+    walker.command("scroll", "2").unwrap();
+    assert_eq!(apply(&rx), ["c", "d"]);
+    assert_eq!(walker.visitor.out.paths(), ["a", "b", "c", "d"]);
+
+    walker.command("scroll", "4").unwrap();
+    assert_eq!(apply(&rx), ["e"]);
+
+    walker.command("scroll", "0").unwrap();
+    assert_eq!(apply(&rx), ["a", "b"]);
+}
+
+#[test]
+fn deterministic() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    assert_matches!(
+        walker.command("deterministic", "yes"),
+        Err(Error::InvalidArgument(_))
+    );
+    walker.command("deterministic", "on").unwrap();
+    walker.command("window_size", "1").unwrap();
+
+    walker.command("walk", "test").unwrap();
+    walker.command("add", "a").unwrap();
+
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"a/1/2.txt"))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("stop", "").unwrap();
+    walker.command("window_size", "5").unwrap();
+    walker.command("walk", "test").unwrap();
+    walker.command("add", "txt").unwrap();
+
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"a/1/2.txt"))
+    );
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"a/1/3.txt"))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn watchdog_command() {
+    let (tx, _rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("watchdog", "5000 cancel").unwrap();
+    assert_eq!(
+        walker.watchdog,
+        Some(Watchdog {
+            period: Duration::from_secs(5),
+            cancel: true
+        })
+    );
+    walker.command("watchdog", "250").unwrap();
+    assert_eq!(
+        walker.watchdog,
+        Some(Watchdog {
+            period: Duration::from_millis(250),
+            cancel: false
+        })
+    );
+    assert_matches!(
+        walker.command("watchdog", "250 kill"),
+        Err(Error::InvalidArgument(_))
+    );
+    walker.command("watchdog", "off").unwrap();
+    assert_eq!(walker.watchdog, None);
+}
+
+#[test]
+fn msg_parse() {
+    for msg in [
+        Msg::Clear,
+        Msg::WalkDone,
+        Msg::WalkTruncated,
+        Msg::WalkStarted,
+        Msg::Resync,
+        Msg::AddFile(Bytes::from_static(b"a/b c")),
+        Msg::RmFile(Bytes::from_static(b"-x")),
+        Msg::Message("hello world".to_string()),
+        Msg::Fatal("oops".to_string()),
+        Msg::SnapshotBegin(3),
+        Msg::SnapshotEnd(3),
+        Msg::Warning {
+            kind: "complex-query".to_string(),
+            detail: "too many terms".to_string(),
+        },
+        Msg::Protocol(2),
+        Msg::Progress {
+            scanned: 12000,
+            matched: 43,
+        },
+        Msg::WalkStats {
+            scanned: 12000,
+            matched: 43,
+            elapsed: Duration::from_millis(812),
+        },
+        Msg::Count(43),
+        Msg::Extension {
+            name: "git-status".to_string(),
+            payload: Bytes::from_static(b"M a b"),
+        },
+        Msg::Extension {
+            name: "ping".to_string(),
+            payload: Bytes::new(),
+        },
+        Msg::Named {
+            name: "buffers".to_string(),
+            msg: Box::new(Msg::AddFile(Bytes::from_static(b"a b"))),
+        },
+        Msg::Request {
+            id: "42".to_string(),
+            msg: Box::new(Msg::WalkDone),
+        },
+    ] {
+        let mut out = vec![];
+        msg.write(&mut out).unwrap();
+        assert_eq!(Msg::parse(&out[..out.len() - 1]), Some(msg));
+    }
+    assert_eq!(Msg::parse(b"bogus"), None);
+}
+
+#[test]
+fn request_ids() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    let request = |id: &str, msg| Msg::Request {
+        id: id.to_string(),
+        msg: Box::new(msg),
+    };
+
+    // untagged until a request ID is given
+    walker.command("walk", "test/a/1").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    for _ in 0..2 {
+        assert_matches!(rx.recv_timeout(WT).unwrap(), Msg::AddFile(_));
+    }
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    walker.request_command(Some("7"), "stop", "").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), request("7", Msg::Clear));
+    walker
+        .request_command(Some("8"), "walk", "test/a/1")
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), request("8", Msg::WalkStarted));
+    for _ in 0..2 {
+        assert_matches!(
+            rx.recv_timeout(WT).unwrap(),
+            Msg::Request { id, msg } if id == "8" && matches!(*msg, Msg::AddFile(_))
+        );
+    }
+    assert_eq!(rx.recv_timeout(WT).unwrap(), request("8", Msg::WalkDone));
+
+    walker.request_command(Some("9"), "stop", "").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), request("9", Msg::Clear));
+    // the text of a query is never an ID
+    walker.command("add", "#12").unwrap();
+    assert_eq!(walker.visitor.out.pattern().clone_text(), "#12");
+
+    // replies are not tagged
+    walker.request_command(Some("10"), "protocol", "2").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Protocol(2));
+}
+
+#[test]
+fn request_id_of_walk() {
+    let (tx, _rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.request_command(Some("1"), "walk", "test").unwrap();
+    let first = walker.visitor.walker_version.clone();
+    assert_eq!(first.request().as_deref(), Some("1"));
+
+    // refining the walk retags it
+    walker.request_command(Some("2"), "add", "a").unwrap();
+    assert_eq!(first.request().as_deref(), Some("2"));
+
+    // a walk stopped by the request keeps its own ID
+    walker.request_command(Some("3"), "walk", "test/a").unwrap();
+    assert_eq!(first.request().as_deref(), Some("2"));
+    assert_eq!(
+        walker.visitor.walker_version.request().as_deref(),
+        Some("3")
+    );
+}
+
+#[test]
+fn msg_write_version() {
+    let mut out = vec![];
+    Msg::SnapshotBegin(1).write_version(&mut out, 1).unwrap();
+    Msg::WalkDone.write_version(&mut out, 1).unwrap();
+    Msg::WalkTruncated.write_version(&mut out, 1).unwrap();
+    Msg::SnapshotBegin(1).write_version(&mut out, 2).unwrap();
+    assert_eq!(
+        out,
+        b"message snapshot-begin 1\x00done\x00done\x00snapshot-begin 1\x00"
+    );
+}
+
+#[test]
+fn progress() {
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("progress", "60000").unwrap();
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkDone {}
+    assert_matches!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::WalkStats { scanned, matched: 2, .. } if scanned >= 5
+    );
+
+    let mut out = vec![];
+    Msg::Progress {
+        scanned: 12000,
+        matched: 43,
+    }
+    .write(&mut out)
+    .unwrap();
+    assert_eq!(out, b"progress scanned=12000 matched=43\x00");
+
+    walker.command("progress", "off").unwrap();
+    assert_eq!(walker.progress_period, None);
+}
+
+#[test]
+fn count() {
+    let dir = TempDir::new("walk_count");
+    for i in 0..20 {
+        fs::write(dir.join(format!("f{i:02}")), "").unwrap();
+    }
+
+    let (tx, rx) = mpsc::sync_channel(50);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.set_quotas(Quotas {
+        walker_threads: Some(1),
+        ..Default::default()
+    });
+    walker.command("sort", "score").unwrap();
+    walker.command("count", "on").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    let mut counts = vec![];
+    loop {
+        match rx.recv_timeout(WT).unwrap() {
+            Msg::Count(n) => counts.push(n),
+            Msg::WalkDone => break,
+            _ => {}
+        }
+    }
+    // more matches than fit the window, sent as the total grows by an eighth
+    assert_eq!(
+        counts,
+        [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 18, 20
+        ]
+    );
+
+    // the final count is always sent
+    walker.command("add", "zz").unwrap();
+    let mut counts = vec![];
+    loop {
+        match rx.recv_timeout(WT).unwrap() {
+            Msg::Count(n) => counts.push(n),
+            Msg::WalkDone => break,
+            _ => {}
+        }
+    }
+    assert_eq!(counts, [0]);
+
+    let mut out = vec![];
+    Msg::Count(20).write(&mut out).unwrap();
+    assert_eq!(out, b"count 20\x00");
+}
+
+#[test]
+fn protocol_command() {
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("protocol", "1").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Protocol(1));
+    assert_eq!(
+        walker
+            .visitor
+            .out
+            .protocol()
+            .load(atomic::Ordering::Relaxed),
+        1
+    );
+
+    walker.command("protocol", "99").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Protocol(PROTOCOL_VERSION)
+    );
+}
+
+#[test]
+fn profile() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("profile", "start").unwrap();
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkDone {}
+
+    walker.command("profile", "dump").unwrap();
+    let Msg::Message(report) = rx.recv_timeout(WT).unwrap() else {
+        panic!("expected a report");
+    };
+    assert!(report.starts_with("profile walk "), "{report}");
+    // two files checked against the ignore pattern and the pattern
+    assert!(report.contains("ms/2 match "), "{report}");
+    assert!(report.contains(" lock "), "{report}");
+
+    walker.command("profile", "stop").unwrap();
+    assert_matches!(
+        walker.command("profile", "reset"),
+        Err(Error::InvalidArgument(_))
+    );
+}
+
+#[test]
+fn external_matcher() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("matcher", "grep -F {}").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("add", "3").unwrap();
+    walker.command("walk", "test").unwrap();
+
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // grep's fixed string would not match fuzzily
+    walker.command("set", "0 2t").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("matcher", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a/1/2.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("matcher", "/nonexistent/matcher").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_matches!(rx.recv_timeout(WT).unwrap(), Msg::Message(m) if m.starts_with("matcher failed"));
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    assert_matches!(
+        walker.command("matcher", " "),
+        Err(Error::InvalidArgument(_))
+    );
+}
+
+#[test]
+fn hooks() {
+    let out = env::temp_dir().join(format!("koru_find_walk_hooks_{}", process::id()));
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    let mut hooks = Hooks::default();
+    hooks.set(
+        Event::WalkDone,
+        format!("echo {{path}} {{count}} >> {}", out.display()),
+    );
+    hooks.set(
+        Event::SelectionAccepted,
+        format!("echo accepted {{path}} >> {}", out.display()),
+    );
+    walker.set_hooks(hooks);
+
+    // hooks run concurrently so may finish in any order
+    let lines = || {
+        let mut lines: Vec<_> = fs::read_to_string(&out)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    };
+    let wait_for = |expected: &str| {
+        let deadline = std::time::Instant::now() + WT * 25;
+        while lines() != expected && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(lines(), expected);
+    };
+
+    walker.command("add", "3").unwrap();
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    wait_for("test 1");
+
+    wait_running(&mut walker, WT);
+    // accepting reorders the results so the walk is redone
+    walker.command("accepted", "a/1/3.txt").unwrap();
+    wait_for("accepted a/1/3.txt\ntest 1\ntest 1");
+    let _ = fs::remove_file(&out);
+}
+
+#[test]
+fn walk_done_count() {
+    let out = env::temp_dir().join(format!("koru_find_walk_done_count_{}", process::id()));
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(1, tx);
+    let mut walker = Walker::new(win);
+    let mut hooks = Hooks::default();
+    hooks.set(
+        Event::WalkDone,
+        format!("echo {{count}} > {}", out.display()),
+    );
+    walker.set_hooks(hooks);
+
+    // the score order keeps the best one rather than waiting for room
+    walker.command("sort", "score").unwrap();
+    walker.command("add", "txt").unwrap();
+    walker.command("walk", "test").unwrap();
+    let deadline = std::time::Instant::now() + WT * 25;
+    while fs::read_to_string(&out).unwrap_or_default().is_empty()
+        && std::time::Instant::now() < deadline
+    {
+        let _ = rx.try_iter().count();
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(fs::read_to_string(&out).unwrap(), "2\n");
+    let _ = fs::remove_file(&out);
+}
+
+#[test]
+fn export() {
+    let dest = env::temp_dir().join(format!("koru_find_walk_export_{}", process::id()));
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(1, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", "test").unwrap();
+    walker.command("add", "txt").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    // the walk is blocked on the full window
+    assert!(to_raf(&mut rx, 1).starts_with("+a/1/"));
+
+    walker
+        .command("export", &format!("{}\x1fnul", dest.display()))
+        .unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT * 5).unwrap(),
+        Msg::Message(format!("exported 2 to {}", dest.display()))
+    );
+    assert_eq!(fs::read(&dest).unwrap(), b"a/1/2.txt\0a/1/3.txt\0");
+    let _ = fs::remove_file(&dest);
+}
+
+#[test]
+fn classify() {
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.set_options(WalkOptions {
+        kinds: Some(Kinds {
+            file: true,
+            dir: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    walker.command("deterministic", "on").unwrap();
+    walker.command("classify", "on").unwrap();
+    // changing the options clears the window
+    while rx.try_recv().is_ok() {}
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(
+        to_raf(&mut rx, 4),
+        "+a\x1f/ +a/1\x1f/ +a/1/2.txt\x1f +a/1/3.txt\x1f"
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn metadata() {
+    let dir = TempDir::new("walk_metadata");
+    fs::write(dir.join("a.txt"), "hello").unwrap();
+    let mtime = fs::metadata(dir.join("a.txt")).unwrap().mtime();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("metadata", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), format!("+a.txt\x1f5\x1f{mtime}"));
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // fields keep their places when the classify character is empty
+    walker.command("classify", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), format!("+a.txt\x1f\x1f5\x1f{mtime}"));
+}
+
+#[test]
+fn file_types() {
+    let dir = TempDir::new("walk_types");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    for file in ["src/main.rs", "src/notes.md", "build.rs", "page.html"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.command("type", "rust").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+build.rs +src/main.rs");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    assert_eq!(
+        walker.why_ignored("page.html"),
+        "not of a type being walked"
+    );
+
+    walker.command("type", "no-such-type").unwrap();
+    assert_matches!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Warning { kind, .. } if kind == "type"
+    );
+
+    walker.command("type", "off").unwrap();
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkDone {}
+    walker.command("type-add", "page:*.html").unwrap();
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkDone {}
+
+    walker.command("type-not", "page").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 3), "+build.rs +src/main.rs +src/notes.md");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // files created since the walk are filtered the same way
+    for file in ["new.html", "new.rs"] {
+        fs::write(dir.join(file), "").unwrap();
+        assert!(
+            !walker
+                .visitor
+                .visitor()
+                .created(&dir, Path::new(file), None)
+        );
+    }
+    assert_eq!(to_raf(&mut rx, 1), "+new.rs");
+    assert_matches!(rx.try_recv(), Err(_));
+}
+
+#[test]
+fn executable() {
+    let dir = TempDir::new("walk_exe");
+    fs::create_dir_all(dir.join("bin")).unwrap();
+    fs::write(dir.join("bin/run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(dir.join("bin/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("notes.txt"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("executable", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+bin/run.sh");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("executable", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+bin/run.sh +notes.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // only the executable kind is added and removed
+    walker.set_options(WalkOptions {
+        kinds: Some(Kinds {
+            dir: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    walker.command("deterministic", "on").unwrap();
+    wait_running(&mut walker, WT);
+    while rx.try_recv().is_ok() {}
+
+    walker.command("executable", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+bin +bin/run.sh");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("executable", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+bin");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn max_depth() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a/1/2.txt +a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("max-depth", "2").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("max-depth", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a/1/2.txt +a/1/3.txt");
+}
+
+#[test]
+fn hidden() {
+    let dir = TempDir::new("walk_hidden");
+    fs::create_dir_all(dir.join(".github/workflows")).unwrap();
+    fs::write(dir.join(".github/workflows/ci.yml"), "").unwrap();
+    fs::write(dir.join("a.yml"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a.yml");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("hidden", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+.github/workflows/ci.yml +a.yml");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn follow_symlinks() {
+    let dir = TempDir::new("walk_follow");
+    fs::create_dir_all(dir.join("ws")).unwrap();
+    fs::create_dir_all(dir.join("pkg/src")).unwrap();
+    fs::write(dir.join("pkg/src/lib.rs"), "").unwrap();
+    std::os::unix::fs::symlink("../pkg", dir.join("ws/pkg")).unwrap();
+    std::os::unix::fs::symlink(".", dir.join("ws/loop")).unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    let ws = dir.join("ws");
+    walker
+        .command("switch", &format!("{} lib", ws.display()))
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("follow-symlinks", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+pkg/src/lib.rs");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn same_fs() {
+    let dir = TempDir::new("walk_same_fs");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/file"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+sub/file");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("same-fs", "on").unwrap();
+    assert!(walker.options.same_file_system);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+sub/file");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn walk_errors() {
+    let dir = TempDir::new("walk_errors");
+    fs::write(dir.join("file"), "").unwrap();
+    std::os::unix::fs::symlink("missing", dir.join("bad")).unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("follow-symlinks", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+file");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("walk-errors", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    let mut msgs: Vec<_> = (0..2).map(|_| rx.recv_timeout(WT).unwrap()).collect();
+    msgs.sort_by_key(|m| matches!(m, Msg::AddFile(_)));
+    assert_matches!(
+        &msgs[0],
+        Msg::Warning { kind, detail } if kind == "walk-error" && detail.starts_with("bad: ")
+    );
+    assert_eq!(msgs[1], Msg::AddFile(Bytes::from_static(b"file")));
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn git_backend() {
+    let dir = TempDir::new("walk_git_backend");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let status = process::Command::new("git")
+        .arg("-C")
+        .arg(&*dir)
+        .args(["init", "-q"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+    fs::write(dir.join("src/a.rs"), "").unwrap();
+    fs::write(dir.join("b.rs"), "").unwrap();
+    fs::write(dir.join("c.log"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("backend", "git").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker
+        .command("switch", &format!("{} a.rs", dir.display()))
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+src/a.rs");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // matched again from what git listed
+    walker.command("rm", "4").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+b.rs");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    let plain = dir.join("src");
+    fs::remove_dir_all(dir.join(".git")).unwrap();
+    walker.command("walk", plain.to_str().unwrap()).unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Warning {
+            kind: "backend".to_string(),
+            detail: format!(
+                "git: {}/ is not in a worktree so is walked",
+                plain.display()
+            )
+        }
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a.rs");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn newer_than_and_max_size() {
+    let dir = TempDir::new("walk_limits");
+    fs::write(dir.join("new"), "").unwrap();
+    fs::write(dir.join("big"), "0123456789").unwrap();
+    let old = fs::File::create(dir.join("old")).unwrap();
+    old.set_modified(SystemTime::now() - Duration::from_secs(3 * 86_400))
+        .unwrap();
+    drop(old);
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("newer-than", "1d").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+big +new");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("why-ignored", "old").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("why-ignored old: older than newer-than".to_string())
+    );
+
+    walker.command("max-size", "5").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+new");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("newer-than", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+new +old");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[cfg(feature = "archives")]
+#[test]
+fn archives() {
+    let dir = TempDir::new("walk_archives");
+    fs::write(dir.join("maid.txt"), "").unwrap();
+    let mut tar = tar::Builder::new(fs::File::create(dir.join("a.tar")).unwrap());
+    for name in ["src/main.rs", "src/mail.rs"] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(0);
+        tar.append_data(&mut header, name, std::io::empty())
+            .unwrap();
+    }
+    tar.finish().unwrap();
+    drop(tar);
+    fs::write(dir.join("bad.zip"), "not a zip").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("archives", "on").unwrap();
+    walker.command("walk-errors", "on").unwrap();
+    while rx.try_recv().is_ok() {}
+    walker
+        .command("switch", &format!("{} main.rs", dir.display()))
+        .unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    let mut msgs: Vec<_> = (0..2).map(|_| rx.recv_timeout(WT).unwrap()).collect();
+    msgs.sort_by_key(|m| matches!(m, Msg::AddFile(_)));
+    assert_matches!(
+        &msgs[0],
+        Msg::Warning { kind, detail } if kind == "walk-error" && detail.starts_with("bad.zip: ")
+    );
+    assert_eq!(
+        msgs[1],
+        Msg::AddFile(Bytes::from_static(b"a.tar!src/main.rs"))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // matched again from the kept entries
+    walker.command("rm", "4").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a.tar!src/mail.rs +maid.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[cfg(not(feature = "archives"))]
+#[test]
+fn archives() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("archives", "on").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("archives: built without the archives feature".to_string())
+    );
+}
+
+#[test]
+fn broken_links() {
+    let dir = TempDir::new("walk_links");
+    fs::write(dir.join("file"), "").unwrap();
+    let _ = std::os::unix::fs::symlink("file", dir.join("good"));
+    let _ = std::os::unix::fs::symlink("missing", dir.join("bad"));
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("broken-links", "only").unwrap();
+    walker.command("classify", "on").unwrap();
+    while rx.try_recv().is_ok() {}
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+bad\x1f!");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("broken-links", "hide").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+file\x1f +good\x1f@");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn context_refills_window() {
+    let dir = TempDir::new("walk_context");
+    for d in ["a", "b"] {
+        fs::create_dir_all(dir.join(d)).unwrap();
+        fs::write(dir.join(d).join("1"), "").unwrap();
+        fs::write(dir.join(d).join("2"), "").unwrap();
+    }
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(2, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    while rx.try_recv().is_ok() {}
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a/1 +a/2");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // b/ was not in the window, so only walking again finds what now ranks first
+    walker.command("accepted", "b/2").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+b/1 +b/2");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn dedup_hardlinks() {
+    let dir = TempDir::new("walk_hardlinks");
+    fs::create_dir_all(dir.join("backup/old")).unwrap();
+    fs::write(dir.join("a.txt"), "a").unwrap();
+    fs::write(dir.join("b.txt"), "b").unwrap();
+    let _ = fs::hard_link(dir.join("a.txt"), dir.join("backup/a.txt"));
+    let _ = fs::hard_link(dir.join("a.txt"), dir.join("backup/old/a.txt"));
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    walker.command("dedup-hardlinks", "on").unwrap();
+    while rx.try_recv().is_ok() {}
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a.txt +b.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn dedup_files() {
+    let dir = TempDir::new("walk_dedup_files");
+    fs::create_dir_all(dir.join("d")).unwrap();
+    fs::write(dir.join("a"), "a").unwrap();
+    fs::write(dir.join("d/x"), "x").unwrap();
+    let _ = std::os::unix::fs::symlink("d", dir.join("l"));
+    let _ = std::os::unix::fs::symlink("a", dir.join("s"));
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    walker.command("follow-symlinks", "on").unwrap();
+    while rx.try_recv().is_ok() {}
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 4), "+a +d/x +l/x +s");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.command("deterministic", "on").unwrap();
+    walker.command("follow-symlinks", "on").unwrap();
+    walker.command("dedup-hardlinks", "files").unwrap();
+    while rx.try_recv().is_ok() {}
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a +d/x");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn sparse() {
+    let dir = TempDir::new("walk_sparse");
+    fs::create_dir_all(dir.join(".git/info")).unwrap();
+    fs::create_dir_all(dir.join("kept")).unwrap();
+    fs::create_dir_all(dir.join("gone")).unwrap();
+    fs::write(dir.join(".git/config"), "[core]\n\tsparseCheckout = true\n").unwrap();
+    fs::write(dir.join(".git/info/sparse-checkout"), "/*\n!/*/\n/kept/\n").unwrap();
+    fs::write(dir.join("kept/a"), "").unwrap();
+    fs::write(dir.join("gone/b"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.set_options(WalkOptions {
+        hidden: true,
+        sparse: true,
+        ..Default::default()
+    });
+    while rx.try_recv().is_ok() {}
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+kept/a unexpected WalkDone");
+
+    walker.command("sparse", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+gone/b +kept/a");
+}
+
+#[test]
+fn submodules() {
+    let dir = TempDir::new("walk_submodules");
+    fs::create_dir_all(dir.join("lib/dep")).unwrap();
+    fs::write(dir.join("lib/dep/.git"), "gitdir: ../../.git/modules/dep\n").unwrap();
+    fs::write(dir.join("lib/dep/a"), "").unwrap();
+    fs::write(dir.join("lib/b"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.command("submodules", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+lib/b unexpected WalkDone");
+
+    walker.command("submodules", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+lib/b +lib/dep/a");
+}
+
+#[test]
+fn refine() {
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("add", "txt").unwrap();
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a/1/2.txt +a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    wait_running(&mut walker, WT);
+
+    walker.command("refine", "3").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "-a/1/2.txt");
+    assert_eq!(walker.pattern.clone_text(), "txt");
+
+    walker.command("refine-clear", "").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a/1/2.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn refresh() {
+    let dir = TempDir::new("walk_refresh");
+    fs::write(dir.join("a.txt"), "").unwrap();
+    fs::write(dir.join("b.txt"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a.txt +b.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    fs::remove_file(dir.join("a.txt")).unwrap();
+    fs::write(dir.join("c.txt"), "").unwrap();
+    walker.refresh();
+    assert_eq!(to_raf(&mut rx, 1), "-a.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+c.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn fs_watch() {
+    let dir = TempDir::new("walk_fs_watch");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), "").unwrap();
+    fs::write(dir.join("sub/b.txt"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("fs-watch", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a.txt +sub/b.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    wait_running(&mut walker, WT);
+
+    fs::write(dir.join(".hidden"), "").unwrap();
+    fs::write(dir.join("c.txt"), "").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+c.txt");
+    fs::remove_file(dir.join("a.txt")).unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "-a.txt");
+    fs::rename(dir.join("c.txt"), dir.join("sub/d.txt")).unwrap();
+    let mut moved = [to_raf(&mut rx, 1), to_raf(&mut rx, 1)];
+    moved.sort();
+    assert_eq!(moved, ["+sub/d.txt", "-c.txt"]);
+    fs::remove_dir_all(dir.join("sub")).unwrap();
+    let mut removed = [to_raf(&mut rx, 1), to_raf(&mut rx, 1)];
+    removed.sort();
+    assert_eq!(removed, ["-sub/b.txt", "-sub/d.txt"]);
+
+    walker.visitor.kill();
+}
+
+#[test]
+fn watch_limit() {
+    let dir = TempDir::new("walk_watch_limit");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("watch-limit", "1").unwrap();
+    walker.command("fs-watch", "on").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Warning {
+            kind: "watch-limit".to_string(),
+            detail: format!(
+                "{}/ has over 1 directories to watch; fs-watch is off",
+                dir.display()
+            ),
+        }
+    );
+
+    walker.command("watch-limit", "2").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+    wait_running(&mut walker, WT);
+    fs::write(dir.join("b.txt"), "").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+b.txt");
+
+    walker.visitor.kill();
+}
+
+#[test]
+fn walk_add() {
+    let dir = TempDir::new("walk_add");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/2.txt"), "").unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk-add", dir.to_str().unwrap()).unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(format!("walk-add {} failed: not walking", dir.display()))
+    );
+
+    walker.command("switch", "test 2").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from_static(b"a/1/2.txt"))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("walk-add", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::AddFile(Bytes::from(format!("{}/src/2.txt", dir.display())))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker
+        .command("walk-add", dir.join("src").to_str().unwrap())
+        .unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(format!(
+            "walk-add {0}/src failed: overlaps {0}/",
+            dir.display()
+        ))
+    );
+
+    walker.command("walk", "test").unwrap();
+    assert!(walker.roots.is_empty());
+}
+
+#[test]
+fn retry_errors() {
+    let dir = TempDir::new("walk_retry");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/a.txt"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+sub/a.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    wait_running(&mut walker, WT);
+    // as if reading sub had failed with EMFILE
+    walker.visitor.failed.lock().unwrap().push(dir.join("sub"));
+    fs::write(dir.join("sub/b.txt"), "").unwrap();
+    fs::write(dir.join("c.txt"), "").unwrap();
+    walker.command("retry-errors", "").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+sub/b.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("retry-errors", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("retry-errors: nothing to retry".to_string())
+    );
+}
+
+#[test]
+fn transient_failure() {
+    let err = |code| ignore::Error::WithPath {
+        path: "a/b".into(),
+        err: Box::new(ignore::Error::Io(io::Error::from_raw_os_error(code))),
+    };
+    assert_eq!(
+        super::transient_failure(&err(libc::EMFILE)),
+        Some(Path::new("a/b"))
+    );
+    assert_eq!(
+        super::transient_failure(&ignore::Error::WithDepth {
+            depth: 2,
+            err: Box::new(err(libc::EAGAIN)),
+        }),
+        Some(Path::new("a/b"))
+    );
+    assert_eq!(super::transient_failure(&err(libc::EACCES)), None);
+}
+
+#[test]
+fn fd_limit() {
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("fd-limit", "2").unwrap();
+    walker.command("walk", "test").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    let budget = FdBudget::new(Some(2));
+    assert_eq!(budget.threads, 1);
+    if let Some(detail) = budget.warning() {
+        assert_eq!(
+            rx.recv_timeout(WT).unwrap(),
+            Msg::Warning {
+                kind: "fd-limit".to_string(),
+                detail
+            }
+        );
+    }
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a/1/2.txt +a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn why_ignored() {
+    let dir = TempDir::new("walk_why");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(dir.join(".git/config"), "").unwrap();
+    fs::write(dir.join(".ignore"), "*.log\n").unwrap();
+    for file in ["a.log", ".hidden", "src/main.rs", "src/lib.rs", "b.txt"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.walk(dir.to_str().unwrap()).unwrap();
+    walker.ignore_pattern.set(0, "*lib");
+    walker.pattern.add("rs");
+
+    let why = |path| walker.why_ignored(path);
+    assert_eq!(
+        why("a.log"),
+        format!("ignored by {}:1: *.log", dir.join(".ignore").display())
+    );
+    assert_eq!(why(".hidden"), "hidden by .hidden");
+    assert_eq!(why(".git/config"), "git's own files are never walked");
+    assert_eq!(why("missing"), "does not exist");
+    assert_eq!(why("src/lib.rs"), "matches ignore *lib");
+    assert_eq!(why("src"), "not a kind of entry being reported");
+    assert_eq!(why("b.txt"), "not ignored, but does not match the query");
+    assert_eq!(why("src/main.rs"), "not ignored");
+    assert_eq!(why("./src/main.rs"), "not ignored");
+    assert_eq!(why("src/../src/main.rs"), "not ignored");
+    let absolute = dir.join("src/main.rs");
+    assert_eq!(why(absolute.to_str().unwrap()), "not ignored");
+
+    walker.command("why-ignored", "a.log").unwrap();
+    assert_matches!(rx.recv_timeout(WT), Ok(Msg::Message(m)) if m.starts_with("why-ignored a.log: ignored by "));
+}
+
+#[test]
+fn switch() {
+    let (tx, mut rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("walk", "src").unwrap();
+    walker.command("add", "walker").unwrap();
+    walker.command("ignore", "3").unwrap();
+    wait_running(&mut walker, WT);
+    while rx.try_recv().is_ok() {}
+
+    walker.command("switch", "test 1/ txt").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 2), "+a/1/2.txt +a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("switch", "missing x").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_matches!(rx.recv_timeout(WT).unwrap(), Msg::Message(m) if m.starts_with("walk missing failed"));
+}
+
+#[test]
+fn walk_expands_path() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("add", "2.txt").unwrap();
+
+    // cargo sets it for the tests it runs
+    walker
+        .command("walk", "${CARGO_MANIFEST_DIR}/test/a")
+        .unwrap();
+    let root = fs::canonicalize("test/a").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(format!("walk-root {}/", root.display()))
+    );
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+1/2.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker
+        .command("walk", "$KORU_FIND_UNSET_VARIABLE/test")
+        .unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(
+            "walk $KORU_FIND_UNSET_VARIABLE/test failed: KORU_FIND_UNSET_VARIABLE is not set"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn ignore_dirs() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("ignore", "x 1/").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(walker.ignore_pattern.clone_text(), "x");
+
+    walker.command("walk", "test/").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("why-ignored", "a/1/2.txt").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("why-ignored a/1/2.txt: in a directory ignored by 1/".to_string())
+    );
+    walker.command("save", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("saved 0,0,0,0,1:/,0:0,0,0,0,1:/,4:x 1/".to_string())
+    );
+
+    walker.command("ignore", "a[/").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Warning {
+            kind: "ignore".to_string(),
+            detail: "a[/: unclosed character class; missing ']'".to_string()
+        }
+    );
+}
+
+#[test]
+fn save_load() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("add", "txt").unwrap();
+    walker.command("ignore", "2").unwrap();
+    walker.command("skip-prefix", "1").unwrap();
+    while rx.try_recv().is_ok() {}
+    walker.command("save", "").unwrap();
+    let state = "1,0,0,0,1:/,3:txt1,0,0,0,1:/,1:2";
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(format!("saved {state}"))
+    );
+
+    walker.command("stop", "").unwrap();
+    walker.command("walk", "test").unwrap();
+    wait_running(&mut walker, WT);
+    while rx.try_recv().is_ok() {}
+
+    walker.command("load", state).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("load", "1,0,0,0,1:/,3:txt").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("load 1,0,0,0,1:/,3:txt: invalid state".to_string())
+    );
+    assert_eq!(walker.pattern.clone_text(), "txt");
+}
+
+#[test]
+fn doctor() {
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("doctor", "").unwrap();
+    let findings: Vec<_> = rx.try_iter().collect();
+    assert_eq!(findings.len(), 5);
+    assert_matches!(&findings[2], Msg::Message(m) | Msg::Warning { detail: m, .. } if m.contains("git"));
+    assert_matches!(&findings[4], Msg::Message(m) if m.starts_with("doctor: ") && m.ends_with(" problems"));
+}
+
+#[test]
+fn run_macro() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    let mut macros = Macros::default();
+    macros.set("txt", "walk {1}\nadd :txt {2}");
+    macros.set("bad", "stop\nrun txt\nadd x");
+    walker.set_macros(macros);
+
+    walker.command("run", "txt test 3").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 1), "+a/1/3.txt");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    walker.command("run", "txt").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("run txt: missing parameter 1".to_string())
+    );
+
+    walker.command("run", "bad").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Clear);
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("run bad: a macro can't run another".to_string())
+    );
+    assert_eq!(walker.pattern.clone_text(), "");
+}
+
+#[test]
+fn basename_command() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("basename", "on").unwrap();
+    walker.command("add", "a").unwrap();
+    walker.command("match", "a/1.txt").unwrap();
+    walker.command("match", "b/a.txt").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+b/a.txt");
+
+    walker.command("basename", "off").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Resync);
+    walker.command("match", "a/2.txt").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+a/2.txt");
+}
+
+#[test]
+fn state() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("state", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(
+            r#"state {"ignore":"","offset":0,"pattern":"","paused":false,"root":null,"skip_prefix":0,"skip_suffix":0,"window_size":5}"#
+                .to_string()
+        )
+    );
+
+    walker.command("ignore", "x target/").unwrap();
+    walker.command("skip-prefix", "2").unwrap();
+    walker.command("add", "a b").unwrap();
+    walker.command("window_size", "7").unwrap();
+    walker.command("walk", "test").unwrap();
+    wait_running(&mut walker, WT);
+    while rx.try_recv().is_ok() {}
+    walker.command("state", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(
+            r#"state {"ignore":"x target/","offset":0,"pattern":"a b","paused":false,"root":"test/","skip_prefix":2,"skip_suffix":0,"window_size":7}"#
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn pause_resume() {
+    let dir = TempDir::new("walk_pause");
+    fs::write(dir.join("file"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("pause", "").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_matches!(rx.recv_timeout(Duration::from_millis(100)), Err(_));
+
+    walker.command("state", "").unwrap();
+    assert_matches!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(state) if state.contains(r#""paused":true"#)
+    );
+    walker.command("resume", "").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+file");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // stopping a paused walk does not wait for it to be resumed
+    walker.command("pause", "").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    walker.command("stop", "").unwrap();
+    wait_running(&mut walker, WT);
+}
+
+#[test]
+fn walk_budget() {
+    let dir = TempDir::new("walk_budget");
+    fs::write(dir.join("file"), "").unwrap();
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    walker.command("walk-budget", "20").unwrap();
+    // time paused is not spent
+    walker.command("pause", "").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    thread::sleep(Duration::from_millis(60));
+    walker.command("resume", "").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+file");
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    for i in 0..20 {
+        let sub = dir.join(i.to_string());
+        fs::create_dir(&sub).unwrap();
+        for j in 0..20 {
+            fs::write(sub.join(j.to_string()), "").unwrap();
+        }
+    }
+    // slowed so it can't finish within the budget
+    walker.command("throttle", "5").unwrap();
+    walker.command("walk-budget", "1").unwrap();
+    walker.command("add", "no-such-file").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkStarted {}
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkTruncated);
+
+    walker.command("walk-budget", "off").unwrap();
+    walker.command("throttle", "off").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    while rx.recv_timeout(WT).unwrap() != Msg::WalkStarted {}
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn max_scan() {
+    let dir = TempDir::new("walk_max_scan");
+    for file in ["a", "b", "c"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    // the root and one file
+    walker.command("max-scan", "2").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    let mut msgs: Vec<_> = (0..2).map(|_| rx.recv_timeout(WT).unwrap()).collect();
+    msgs.sort_by_key(|m| matches!(m, Msg::AddFile(_)));
+    assert_eq!(
+        msgs[0],
+        Msg::Warning {
+            kind: "max-scan".to_string(),
+            detail: "walk stopped after 2 entries".to_string()
+        }
+    );
+    let Msg::AddFile(first) = &msgs[1] else {
+        panic!("expected a file; got {:?}", msgs[1]);
+    };
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkTruncated);
+
+    // the rest are added by walking again
+    walker.command("max-scan", "off").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    let rest: Vec<_> = ["+a", "+b", "+c"]
+        .into_iter()
+        .filter(|f| f.as_bytes()[1..] != first[..])
+        .collect();
+    assert_eq!(to_raf(&mut rx, 2), rest.join(" "));
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn throttle() {
+    let dir = TempDir::new("walk_throttle");
+    // more than a batch, so the walk sleeps at least once
+    for i in 0..100 {
+        fs::write(dir.join(format!("f{i:03}")), "").unwrap();
+    }
+
+    let (tx, mut rx) = mpsc::sync_channel(200);
+    let win = Window::new(100, tx);
+    let mut walker = Walker::new(win);
+    walker.command("throttle", "50").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    assert_eq!(to_raf(&mut rx, 100).matches('+').count(), 100);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+}
+
+#[test]
+fn shallow_first() {
+    let dir = TempDir::new("walk_shallow");
+    fs::create_dir_all(dir.join("a/b/c")).unwrap();
+    fs::create_dir_all(dir.join("d/e")).unwrap();
+    for file in ["a/b/c/f4", "d/e/f3", "a/f2", "d/f2", "f1", "z1"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let (tx, rx) = mpsc::sync_channel(10);
+    let win = Window::new(10, tx);
+    let mut walker = Walker::new(win);
+    walker.set_quotas(Quotas {
+        walker_threads: Some(1),
+        ..Default::default()
+    });
+    walker.command("shallow-first", "on").unwrap();
+    walker.command("walk", dir.to_str().unwrap()).unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkStarted);
+    let depths: Vec<_> = (0..6)
+        .map(|_| match rx.recv_timeout(WT).unwrap() {
+            Msg::AddFile(path) => path.iter().filter(|&&c| c == b'/').count(),
+            msg => panic!("expected a file; got {msg:?}"),
+        })
+        .collect();
+    assert_eq!(depths, [0, 0, 1, 1, 2, 3]);
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::WalkDone);
+
+    // a wider query matches the kept candidates again, shallowest first
+    walker.command("add", "z").unwrap();
+    for _ in 0..5 {
+        assert_matches!(rx.recv_timeout(WT).unwrap(), Msg::RmFile(_));
+    }
+    walker.command("rm", "1").unwrap();
+    let mut depths = vec![];
+    while let Ok(msg) = rx.recv_timeout(WT) {
+        if let Msg::AddFile(path) = msg {
+            depths.push(path.iter().filter(|&&c| c == b'/').count());
+        }
+    }
+    assert_eq!(depths.len(), 5, "{depths:?}");
+    assert!(depths.is_sorted(), "{depths:?}");
+}
+
+#[test]
+fn undo_redo() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("undo", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("undo: nothing to undo".to_string())
+    );
+    walker.command("add", "a").unwrap();
+    walker.command("add", "b").unwrap();
+    walker.command("undo", "").unwrap();
+    assert_eq!(walker.pattern.clone_text(), "a");
+    walker.command("redo", "").unwrap();
+    assert_eq!(walker.pattern.clone_text(), "ab");
+    walker.command("redo", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("redo: nothing to redo".to_string())
+    );
+
+    walker.command("match", "xab").unwrap();
+    walker.command("match", "xa").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+xab");
+    walker.command("undo", "").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Resync);
+}
+
+#[test]
+fn pattern_terms() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("add", "<src *a[ |").unwrap();
+    walker.command("pattern?", "").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(
+            r#"pattern? [{"kind":"starts-with","literal":false,"ok":true,"text":"<src"},{"kind":"regex","literal":false,"ok":false,"text":"*a["},{"kind":"or","literal":false,"ok":true,"text":"|"}]"#
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn named_patterns() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("session", "create buffers").unwrap();
+    walker.command("session", "create files").unwrap();
+    walker.command("add", "@buffers ma").unwrap();
+    walker.command("add", "x").unwrap();
+    walker.command("match", "@buffers main.rs").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Named {
+            name: "buffers".to_string(),
+            msg: Box::new(Msg::AddFile(Bytes::from_static(b"main.rs"))),
+        }
+    );
+    walker.command("match", "x.rs").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+x.rs");
+    assert_eq!(walker.pattern.clone_text(), "x");
+    assert_eq!(walker.named["buffers"].pattern.clone_text(), "ma");
+
+    walker.command("walk", "@files test").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Named {
+            name: "files".to_string(),
+            msg: Box::new(Msg::WalkStarted),
+        }
+    );
+    assert_matches!(
+        walker.command("rm", "@buffers x"),
+        Err(Error::InvalidArgument(_))
+    );
+    // an empty name is not a named pattern
+    walker.command("add", "@ y").unwrap();
+    assert_eq!(walker.pattern.clone_text(), "x@ y");
+    // nor is one without a session
+    walker.command("add", "@types/node").unwrap();
+    assert_eq!(walker.pattern.clone_text(), "x@ y@types/node");
+    assert!(!walker.named.contains_key("types"));
+    // `@@` is an escaped `@`
+    walker.command("add", "@@buffers").unwrap();
+    assert_eq!(walker.pattern.clone_text(), "x@ y@types/node@buffers");
+    assert_eq!(walker.named["buffers"].pattern.clone_text(), "ma");
+}
+
+#[test]
+fn sessions() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+    let named = |name: &str, msg| Msg::Named {
+        name: name.to_string(),
+        msg: Box::new(msg),
+    };
+
+    walker.command("session", "create files").unwrap();
+    walker.command("session", "create grep").unwrap();
+    walker.command("session", "create files").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("session create files: already exists".to_string())
+    );
+    walker.command("session", "list").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(r#"sessions ["files","grep"]"#.to_string())
+    );
+
+    // each session has its own pattern and window
+    walker.command("add", "@files a").unwrap();
+    walker.command("match", "@files a.rs").unwrap();
+    walker.command("match", "@grep a.rs").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        named("files", Msg::AddFile(Bytes::from_static(b"a.rs")))
+    );
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        named("grep", Msg::AddFile(Bytes::from_static(b"a.rs")))
+    );
+
+    walker.command("session", "close files").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), named("files", Msg::Clear));
+    walker.command("session", "close files").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("session close files: no such session".to_string())
+    );
+    walker.command("session", "list").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message(r#"sessions ["grep"]"#.to_string())
+    );
+}
+
+#[test]
+fn separators_command() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("separators", "::").unwrap();
+    walker.command("add", "fb").unwrap();
+    walker.command("match", "foo::bar").unwrap();
+    walker.command("match", "fb::x").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+fb::x");
+
+    walker.command("separators", "/").unwrap();
+    assert_eq!(rx.recv_timeout(WT).unwrap(), Msg::Resync);
+    walker.command("match", "foo::bar").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+foo::bar");
+}
+
+#[cfg(feature = "nfc")]
+#[test]
+fn nfc_command() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("add", "=caf\u{e9}").unwrap();
+    walker.command("nfc", "on").unwrap();
+    walker.command("match", "cafe\u{301}").unwrap();
+    walker.command("match", "cafe").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+cafe\u{301}");
+}
+
+#[cfg(not(feature = "nfc"))]
+#[test]
+fn nfc_command() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("nfc", "on").unwrap();
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("nfc: built without the nfc feature".to_string())
+    );
+}
+
+#[test]
+fn skip_suffix_command() {
+    let (tx, mut rx) = mpsc::sync_channel(5);
+    let win = Window::new(5, tx);
+    let mut walker = Walker::new(win);
+
+    walker.command("skip-suffix", "4").unwrap();
+    walker.command("add", ">rs").unwrap();
+    walker.command("match", "a.rs").unwrap();
+    walker.command("match", "b.rs [x]").unwrap();
+    walker.command("match", "c.rs").unwrap();
+    assert_eq!(to_raf(&mut rx, 1), "+b.rs [x]");
+}
@@ -0,0 +1,308 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicUsize},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{walker::WalkerVersion, window::Window};
+
+/// How often a paused visitor checks whether its walk has been killed.
+const KILL_CHECK_PERIOD: Duration = Duration::from_millis(50);
+/// How often the time budget checks whether the walk is paused or waiting for room.
+const BUDGET_CHECK_PERIOD: Duration = Duration::from_millis(10);
+/// How many units of work a `Throttle` counts between measuring how long they took.
+const THROTTLE_BATCH: u32 = 64;
+
+/// Shared between the visitors of a walk so the watchdog can tell if the walk is moving.
+#[derive(Debug, Default)]
+pub struct Progress {
+    scanned: AtomicUsize,
+    matched: AtomicUsize,
+    /// The match count last sent, with `count` on.
+    counted: AtomicUsize,
+    dir: Mutex<PathBuf>,
+    /// Set when the walk's time budget or `max-scan` ran out, so visitors stop.
+    truncated: AtomicBool,
+    paused: AtomicBool,
+    pause_lock: Mutex<()>,
+    resumed: Condvar,
+}
+impl Progress {
+    pub fn reset(&self) {
+        self.scanned.store(0, std::sync::atomic::Ordering::Relaxed);
+        self.matched.store(0, std::sync::atomic::Ordering::Relaxed);
+        self.counted.store(0, std::sync::atomic::Ordering::Relaxed);
+        self.dir().clear();
+        self.truncated
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn truncate(&self) {
+        self.truncated
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// The entries found matching the query so far, whether or not they fit the window.
+    #[inline(always)]
+    pub fn matched(&self) -> usize {
+        self.matched.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Count an entry matched, returning how many have been.
+    #[inline(always)]
+    pub fn found(&self) -> usize {
+        self.matched
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1
+    }
+
+    /// Whether `matched` has grown by at least an eighth since the count last sent, in which case
+    /// it is taken as sent.
+    pub fn count_due(&self, matched: usize) -> bool {
+        let counted = self.counted.load(std::sync::atomic::Ordering::Relaxed);
+        matched >= counted + (counted / 8).max(1)
+            && self
+                .counted
+                .compare_exchange(
+                    counted,
+                    matched,
+                    std::sync::atomic::Ordering::Relaxed,
+                    std::sync::atomic::Ordering::Relaxed,
+                )
+                .is_ok()
+    }
+
+    /// The final match count, unless it is the one last sent; a walk matching nothing reports 0.
+    pub fn uncounted(&self) -> Option<usize> {
+        let matched = self.matched();
+        let counted = self
+            .counted
+            .swap(matched, std::sync::atomic::Ordering::Relaxed);
+        (counted != matched || matched == 0).then_some(matched)
+    }
+
+    #[inline(always)]
+    pub fn scanned(&self) -> usize {
+        self.scanned.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Count an entry visited, returning how many have been.
+    #[inline(always)]
+    pub fn visited(&self) -> usize {
+        self.scanned
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1
+    }
+
+    pub fn enter_dir(&self, dir: &Path) {
+        let mut current = self.dir();
+        current.clear();
+        current.push(dir);
+    }
+
+    /// Halt the visitors at their next entry until `resume` is called.
+    pub fn pause(&self) {
+        let _lock = self.pause_lock.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        self.paused
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        let _lock = self.pause_lock.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        self.paused
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        self.resumed.notify_all();
+    }
+
+    #[inline(always)]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Block while paused, until resumed or `walker_version` is killed.
+    #[inline(always)]
+    pub fn wait_while_paused(&self, walker_version: &WalkerVersion) {
+        if self.is_paused() {
+            self.wait_for_resume(walker_version);
+        }
+    }
+
+    /// Sleep for `period`, or until `walker_version` is killed.
+    pub fn sleep(&self, period: Duration, walker_version: &WalkerVersion) {
+        let deadline = Instant::now() + period;
+        loop {
+            let now = Instant::now();
+            if now >= deadline || walker_version.is_wrong() {
+                return;
+            }
+            // killing the walk does not notify, so check for it now and then
+            thread::sleep((deadline - now).min(KILL_CHECK_PERIOD));
+        }
+    }
+
+    fn wait_for_resume(&self, walker_version: &WalkerVersion) {
+        let mut lock = self.pause_lock.lock().expect(crate::LOCK_SHOULD_BE_OK);
+        while self.is_paused() && !walker_version.is_wrong() {
+            // killing the walk does not notify, so check for it now and then
+            lock = self
+                .resumed
+                .wait_timeout(lock, KILL_CHECK_PERIOD)
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .0;
+        }
+    }
+
+    fn dir(&self) -> MutexGuard<'_, PathBuf> {
+        self.dir.lock().expect(crate::LOCK_SHOULD_BE_OK)
+    }
+}
+
+/// Keeps a thread busy for only a percentage of the time, by pausing in proportion to the time
+/// spent on each batch of work.
+#[derive(Debug)]
+pub struct Throttle {
+    percent: u8,
+    count: u32,
+    busy: Duration,
+}
+impl Throttle {
+    /// `percent` is clamped to 1..=100.
+    pub fn new(percent: u8) -> Self {
+        Self {
+            percent: percent.clamp(1, 100),
+            count: 0,
+            busy: Duration::ZERO,
+        }
+    }
+
+    /// Count a unit of work that took `busy`, returning true when a batch is done and `pause`
+    /// should be called.
+    #[inline(always)]
+    pub fn visited(&mut self, busy: Duration) -> bool {
+        self.count += 1;
+        self.busy += busy;
+        self.count >= THROTTLE_BATCH
+    }
+
+    /// How long to pause for the batch just done to have taken `percent` of the time.
+    pub fn pause(&mut self) -> Duration {
+        let pause = self
+            .busy
+            .mul_f64(f64::from(100 - self.percent) / f64::from(self.percent));
+        self.count = 0;
+        self.busy = Duration::ZERO;
+        pause
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchdog {
+    pub period: Duration,
+    pub cancel: bool,
+}
+impl Watchdog {
+    /// Watch a walk until `done` is disconnected. If no entries are visited for `period`, while
+    /// the window has room and the walk is not paused, warn the client with the directory last
+    /// entered and, if `cancel` is set, kill the walk.
+    pub fn spawn(
+        self,
+        done: mpsc::Receiver<()>,
+        progress: Arc<Progress>,
+        out: Window,
+        walker_version: WalkerVersion,
+    ) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut last = progress.scanned();
+            let mut warned = false;
+            while let Err(mpsc::RecvTimeoutError::Timeout) = done.recv_timeout(self.period) {
+                if walker_version.is_wrong() {
+                    break;
+                }
+                let scanned = progress.scanned();
+                if scanned != last || out.is_full() || progress.is_paused() {
+                    last = scanned;
+                    warned = false;
+                    continue;
+                }
+                if !warned {
+                    warned = true;
+                    out.message(format!(
+                        "walk stalled for {}ms in {}",
+                        self.period.as_millis(),
+                        progress.dir().display()
+                    ));
+                }
+                if self.cancel {
+                    walker_version.kill();
+                    out.killed();
+                    out.message("walk cancelled".to_string());
+                    break;
+                }
+            }
+        })
+    }
+}
+
+/// Send the client a `progress` message every `period` until `done` is disconnected.
+pub fn report(
+    period: Duration,
+    done: mpsc::Receiver<()>,
+    progress: Arc<Progress>,
+    out: Window,
+    walker_version: WalkerVersion,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = done.recv_timeout(period) {
+            if walker_version.is_wrong() {
+                break;
+            }
+            out.progress(progress.scanned(), progress.matched());
+        }
+    })
+}
+
+/// Truncate the walk if `done` is not disconnected within `budget` of walking; time paused or
+/// waiting for room in the window is not counted.
+pub fn budget(
+    budget: Duration,
+    done: mpsc::Receiver<()>,
+    progress: Arc<Progress>,
+    out: Window,
+    walker_version: WalkerVersion,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut left = budget;
+        loop {
+            let start = Instant::now();
+            if !matches!(
+                done.recv_timeout(left.min(BUDGET_CHECK_PERIOD)),
+                Err(mpsc::RecvTimeoutError::Timeout)
+            ) || walker_version.is_wrong()
+            {
+                break;
+            }
+            if progress.is_paused() || out.is_full() {
+                continue;
+            }
+            left = left.saturating_sub(start.elapsed());
+            if left.is_zero() {
+                progress.truncate();
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+#[path = "watchdog_test.rs"]
+mod test;
@@ -0,0 +1,239 @@
+use pretty_assertions::assert_matches;
+
+use super::*;
+use crate::server::walker::Msg;
+
+const WT: Duration = Duration::from_millis(200);
+
+#[test]
+fn stalled_cancel() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let progress = Arc::new(Progress::default());
+    let mut wv = WalkerVersion::default();
+    wv.start();
+    let (_done_tx, done_rx) = mpsc::channel();
+
+    progress.visited();
+    progress.enter_dir(Path::new("/mnt/nfs/slow"));
+
+    let watchdog = Watchdog {
+        period: Duration::from_millis(10),
+        cancel: true,
+    };
+    let t = watchdog.spawn(done_rx, progress, out, wv.clone());
+
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("walk stalled for 10ms in /mnt/nfs/slow".to_string())
+    );
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Message("walk cancelled".to_string())
+    );
+    t.join().unwrap();
+    assert!(wv.is_wrong());
+}
+
+#[test]
+fn progressing() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let progress = Arc::new(Progress::default());
+    let wv = WalkerVersion::default();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let watchdog = Watchdog {
+        period: Duration::from_millis(20),
+        cancel: true,
+    };
+    let t = watchdog.spawn(done_rx, progress.clone(), out, wv.clone());
+
+    for _ in 0..5 {
+        progress.visited();
+        thread::sleep(Duration::from_millis(5));
+    }
+    drop(done_tx);
+    t.join().unwrap();
+
+    assert_matches!(rx.try_recv(), Err(_));
+    assert!(!wv.is_wrong());
+}
+
+#[test]
+fn paused() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let progress = Arc::new(Progress::default());
+    let wv = WalkerVersion::default();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    progress.pause();
+    let watchdog = Watchdog {
+        period: Duration::from_millis(10),
+        cancel: true,
+    };
+    let t = watchdog.spawn(done_rx, progress.clone(), out, wv.clone());
+    thread::sleep(Duration::from_millis(50));
+    drop(done_tx);
+    t.join().unwrap();
+
+    assert_matches!(rx.try_recv(), Err(_));
+    assert!(!wv.is_wrong());
+}
+
+#[test]
+fn wait_while_paused() {
+    let progress = Arc::new(Progress::default());
+    let mut wv = WalkerVersion::default();
+    wv.start();
+    progress.wait_while_paused(&wv);
+
+    progress.pause();
+    let waiter = {
+        let (progress, wv) = (progress.clone(), wv.clone());
+        thread::spawn(move || progress.wait_while_paused(&wv))
+    };
+    thread::sleep(Duration::from_millis(20));
+    assert!(!waiter.is_finished());
+    progress.resume();
+    waiter.join().unwrap();
+
+    // a killed walk stops waiting even while paused
+    progress.pause();
+    let waiter = {
+        let (progress, wv) = (progress.clone(), wv.clone());
+        thread::spawn(move || progress.wait_while_paused(&wv))
+    };
+    wv.kill();
+    waiter.join().unwrap();
+    assert!(progress.is_paused());
+}
+
+#[test]
+fn sleep() {
+    let progress = Progress::default();
+    let mut wv = WalkerVersion::default();
+    wv.start();
+    let start = Instant::now();
+    progress.sleep(Duration::from_millis(20), &wv);
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    // a killed walk stops sleeping
+    let sleeper = {
+        let wv = wv.clone();
+        thread::spawn(move || progress.sleep(Duration::from_secs(60), &wv))
+    };
+    wv.kill();
+    sleeper.join().unwrap();
+}
+
+#[test]
+fn throttle() {
+    let mut throttle = Throttle::new(25);
+    for _ in 1..THROTTLE_BATCH {
+        assert!(!throttle.visited(Duration::ZERO));
+    }
+    // busy for 10ms so idle for 30ms
+    assert!(throttle.visited(Duration::from_millis(10)));
+    assert_eq!(throttle.pause(), Duration::from_millis(30));
+    assert!(!throttle.visited(Duration::from_millis(20)));
+
+    // only the time visited since the last pause counts
+    assert_eq!(throttle.pause(), Duration::from_millis(60));
+    assert_eq!(throttle.pause(), Duration::ZERO);
+
+    let mut throttle = Throttle::new(100);
+    throttle.visited(Duration::from_secs(1));
+    assert_eq!(throttle.pause(), Duration::ZERO);
+}
+
+#[test]
+fn budget() {
+    let (tx, _rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let progress = Arc::new(Progress::default());
+    let wv = WalkerVersion::default();
+
+    let (_done_tx, done_rx) = mpsc::channel();
+    let t = super::budget(
+        Duration::from_millis(10),
+        done_rx,
+        progress.clone(),
+        out.clone(),
+        wv.clone(),
+    );
+    t.join().unwrap();
+    assert!(progress.is_truncated());
+
+    progress.reset();
+    let (done_tx, done_rx) = mpsc::channel();
+    let t = super::budget(WT, done_rx, progress.clone(), out.clone(), wv.clone());
+    drop(done_tx);
+    t.join().unwrap();
+    assert!(!progress.is_truncated());
+
+    // time paused is not spent
+    progress.pause();
+    let (done_tx, done_rx) = mpsc::channel();
+    let t = super::budget(
+        Duration::from_millis(20),
+        done_rx,
+        progress.clone(),
+        out,
+        wv,
+    );
+    thread::sleep(Duration::from_millis(60));
+    assert!(!progress.is_truncated());
+    progress.resume();
+    thread::sleep(WT);
+    assert!(progress.is_truncated());
+    drop(done_tx);
+    t.join().unwrap();
+}
+
+#[test]
+fn count_due() {
+    let progress = Progress::default();
+    let sent: Vec<_> = (1..=40)
+        .filter(|_| {
+            let matched = progress.found();
+            progress.count_due(matched)
+        })
+        .collect();
+    assert_eq!(
+        sent,
+        [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 18, 20, 22, 24, 27, 30, 33, 37
+        ]
+    );
+    assert_eq!(progress.uncounted(), Some(40));
+    assert_eq!(progress.uncounted(), None);
+
+    progress.reset();
+    assert_eq!(progress.uncounted(), Some(0));
+}
+
+#[test]
+fn report_progress() {
+    let (tx, rx) = mpsc::sync_channel(5);
+    let out = Window::new(5, tx);
+    let progress = Arc::new(Progress::default());
+    let wv = WalkerVersion::default();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    progress.visited();
+    progress.visited();
+    progress.found();
+    let t = report(Duration::from_millis(10), done_rx, progress, out, wv);
+
+    assert_eq!(
+        rx.recv_timeout(WT).unwrap(),
+        Msg::Progress {
+            scanned: 2,
+            matched: 1
+        }
+    );
+    drop(done_tx);
+    t.join().unwrap();
+}
@@ -1,27 +1,137 @@
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeSet, HashSet},
+    ffi::OsStr,
+    fs, io,
+    ops::Bound,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
     sync::{
-        Arc, Condvar, Mutex, MutexGuard,
-        atomic::AtomicUsize,
-        mpsc::{SendError, SyncSender},
+        Arc, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard,
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, AtomicUsize},
+        mpsc::{self, SendError, SyncSender},
     },
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use bytes::Bytes;
 
-use crate::pattern::Pattern;
+use crate::{
+    pattern::Pattern,
+    scorer::{FuzzyScorer, Scorer, Weights},
+};
+
+use super::{
+    frecency::Frecency,
+    order::{Entry, Order, SortKey},
+    profile::{Profile, Stage},
+    rate::RateLimit,
+    root_watch::RootChange,
+    walker::{Msg, PROTOCOL_VERSION, WalkerVersion},
+};
+
+const CONTEXT_BOOST: f64 = 2.0;
+/// The number of recent context directories boosted; each older one gets the square root of the
+/// boost of the one after it.
+const CONTEXT_DEPTH: usize = 4;
+
+/// The page of a scrolled window: the paths sent, and the entries bounding it so a change to one
+/// entry is sent without counting those before it.
+#[derive(Default)]
+struct Shown {
+    paths: HashSet<Bytes>,
+    /// The entry just before the page; `None` while there are fewer than `offset` entries.
+    before: Option<Entry>,
+    /// The last entry of the page; `None` while it is empty.
+    last: Option<Entry>,
+}
+impl Shown {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
 
-use super::walker::{Msg, WalkerVersion};
+    fn contains(&self, entry: &Entry) -> bool {
+        self.before.as_ref().is_some_and(|before| entry > before)
+            && self.last.as_ref().is_some_and(|last| entry <= last)
+    }
+}
 
 struct Inner {
     pattern: Pattern,
+    /// A second query applied on top of `pattern`.
+    refine: Pattern,
+    scorer: Arc<dyn Scorer>,
+    /// Matches scoring less are rejected; `i64::MIN` when there is no threshold.
+    min_score: AtomicI64,
+    /// Bumped whenever the minimum score or the weights change, as either may change what
+    /// matches.
+    scoring: AtomicUsize,
     size: AtomicUsize,
-    content: Mutex<BTreeSet<Bytes>>,
+    /// The entries before this in order are held but not sent, so the client sees the page of
+    /// `size` entries from here.
+    offset: AtomicUsize,
+    /// While scrolled: what has been sent of the page, so the changes to it can be. Always locked
+    /// after `pending`.
+    shown: Mutex<Shown>,
+    order: RwLock<Order>,
+    /// The directory the paths are relative to, for orders that look at the files and frecency.
+    root: RwLock<PathBuf>,
+    /// The directories of the last accepted results, most recent first.
+    context: RwLock<Vec<Bytes>>,
+    weights: RwLock<Weights>,
+    /// Shared with the named windows.
+    frecency: Arc<RwLock<Frecency>>,
+    content: Mutex<BTreeSet<Entry>>,
+    deterministic: AtomicBool,
+    /// The number of snapshots sent.
+    snapshots: AtomicU64,
+    /// While a deterministic walk is running: the entries added to `content` which have not been
+    /// sent yet. Always locked after `content`.
+    pending: Mutex<Option<HashSet<Bytes>>>,
     lock: Mutex<()>,
     cvar: Condvar,
     out: SyncSender<Msg>,
+    /// The ID the client gave its latest request, echoed on the messages sent for the walk and
+    /// the window.
+    request: RwLock<Option<Arc<str>>>,
+    profile: Arc<Profile>,
+    rate_limit: Arc<RateLimit>,
+    /// The protocol version the client asked for.
+    protocol: Arc<AtomicU32>,
 }
 impl Inner {
+    fn matches(&self, value: &[u8]) -> bool {
+        if !(self.refine.all_matches(value) && self.pattern.all_matches(value)) {
+            return false;
+        }
+        let min_score = self.min_score.load(std::sync::atomic::Ordering::Relaxed);
+        min_score == i64::MIN || self.score(value).is_some_and(|s| s >= min_score)
+    }
+
+    /// The scorer's score of `path` weighted by `weights`.
+    fn score(&self, path: &[u8]) -> Option<i64> {
+        let score = self.scorer.score(&self.pattern, path)?.score;
+        Some(
+            self.weights
+                .read()
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .apply(score, path),
+        )
+    }
+
+    /// Changes whenever either pattern or the scoring does.
+    fn version(&self) -> usize {
+        self.pattern
+            .version()
+            .wrapping_add(self.refine.version())
+            .wrapping_add(self.scoring.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    fn scoring_changed(&self) {
+        self.scoring
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
     fn size(&self) -> usize {
         self.size.load(std::sync::atomic::Ordering::Relaxed)
     }
@@ -29,57 +139,408 @@ impl Inner {
     fn set_size(&self, value: usize) {
         self.size.store(value, std::sync::atomic::Ordering::Relaxed);
         let mut content = self.content();
-        while value < content.len() {
+        while self.capacity() < content.len() {
             content.pop_last();
         }
+        if self.scrolled() {
+            self.sync_shown(&content, &self.pending());
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.offset.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn scrolled(&self) -> bool {
+        self.offset() > 0
+    }
+
+    /// The most entries held: those scrolled past as well as the page sent.
+    fn capacity(&self) -> usize {
+        self.size() + self.offset()
+    }
+
+    /// Show the page of entries from `offset`, sending what changed. Returns `true` if the window
+    /// may now hold entries it has not kept, in which case it should be refilled.
+    fn scroll(&self, offset: usize) -> bool {
+        let mut content = self.content();
+        let mut pending = self.pending();
+        let old = self
+            .offset
+            .swap(offset, std::sync::atomic::Ordering::Relaxed);
+        if old == offset {
+            return false;
+        }
+        if old == 0 {
+            // everything held has been sent
+            self.shown().paths = content
+                .iter()
+                .filter(|e| !pending.as_ref().is_some_and(|p| p.contains(&e.path)))
+                .map(|e| e.path.clone())
+                .collect();
+        }
+        while self.capacity() < content.len()
+            && let Some(last) = content.pop_last()
+        {
+            if let Some(pending) = pending.as_mut() {
+                pending.remove(&last.path);
+            }
+        }
+        self.sync_shown(&content, &pending);
+        if offset == 0 {
+            self.shown().clear();
+        }
+        self.cvar.notify_all();
+        offset > old
+    }
+
+    /// Send the changes to the page of `content` the client sees while scrolled; entries still
+    /// `pending` are not sent yet.
+    fn sync_shown(&self, content: &BTreeSet<Entry>, pending: &Option<HashSet<Bytes>>) {
+        self.sync_shown_as(content, pending, &self.request());
+    }
+
+    /// As `sync_shown`, tagging the changes with the `request` ID.
+    fn sync_shown_as(
+        &self,
+        content: &BTreeSet<Entry>,
+        pending: &Option<HashSet<Bytes>>,
+        request: &Option<Arc<str>>,
+    ) {
+        let mut entries = content.iter();
+        let before = match self.offset() {
+            0 => None,
+            offset => entries.nth(offset - 1),
+        };
+        let page: Vec<&Entry> = entries.take(self.size()).collect();
+        let mut shown = self.shown();
+        shown.before = before.cloned();
+        shown.last = page.last().copied().cloned();
+        let page: Vec<&Entry> = page
+            .into_iter()
+            .filter(|e| !pending.as_ref().is_some_and(|p| p.contains(&e.path)))
+            .collect();
+        let paths: HashSet<&Bytes> = page.iter().map(|e| &e.path).collect();
+        shown.paths.retain(|path| {
+            let kept = paths.contains(path);
+            if !kept {
+                let _ = self.send_as(Msg::RmFile(path.clone()), request);
+            }
+            kept
+        });
+        for entry in page {
+            if shown.paths.insert(entry.path.clone()) {
+                let _ = self.send_as(Msg::AddFile(entry.to_bytes()), request);
+            }
+        }
+    }
+
+    /// Move the bounds of the page for `entry` just inserted into `content`, noting in `changed`
+    /// the entries that may have moved into or out of it.
+    fn page_inserted(
+        &self,
+        content: &BTreeSet<Entry>,
+        shown: &mut Shown,
+        entry: &Entry,
+        changed: &mut Vec<Entry>,
+    ) {
+        let full = content.len() > self.capacity();
+        let Some(before) = shown.before.clone() else {
+            if content.len() == self.offset() {
+                shown.before = content.last().cloned();
+            }
+            return;
+        };
+        if self.size() == 0 {
+            return;
+        }
+        if *entry < before {
+            // the entry before the page moves into it
+            shown.before = content.range(..&before).next_back().cloned();
+            if shown.last.is_none() {
+                shown.last = Some(before.clone());
+            }
+            changed.push(before);
+        } else if !full || shown.last.as_ref().is_some_and(|last| entry < last) {
+            if shown.last.as_ref().is_none_or(|last| entry > last) {
+                shown.last = Some(entry.clone());
+            }
+            changed.push(entry.clone());
+        } else {
+            return;
+        }
+        if full && let Some(last) = shown.last.take() {
+            // so the last entry of the page moves out
+            shown.last = content.range(..&last).next_back().cloned();
+            changed.push(last);
+        }
+    }
+
+    /// Move the bounds of the page for `entry` just removed from `content`, noting in `changed`
+    /// the entries that may have moved into or out of it.
+    fn page_removed(
+        &self,
+        content: &BTreeSet<Entry>,
+        shown: &mut Shown,
+        entry: &Entry,
+        changed: &mut Vec<Entry>,
+    ) {
+        let Some(before) = shown.before.clone() else {
+            return;
+        };
+        let Some(last) = shown.last.clone() else {
+            if *entry <= before {
+                shown.before = None;
+            }
+            return;
+        };
+        if *entry <= before {
+            // the first entry of the page moves before it
+            shown.before = content
+                .range((Bound::Excluded(&before), Bound::Unbounded))
+                .next()
+                .cloned();
+            changed.extend(shown.before.clone());
+        } else if *entry <= last {
+            changed.push(entry.clone());
+        } else {
+            return;
+        }
+        // so the entry after the page moves into it
+        match content
+            .range((Bound::Excluded(&last), Bound::Unbounded))
+            .next()
+        {
+            Some(next) => {
+                shown.last = Some(next.clone());
+                changed.push(next.clone());
+            }
+            None => {
+                let last = content.range(..=&last).next_back();
+                shown.last = last
+                    .filter(|last| shown.before.as_ref().is_some_and(|b| *last > b))
+                    .cloned();
+            }
+        }
+    }
+
+    /// Send the `changed` entries that moved into or out of the page.
+    fn send_page_changes(
+        &self,
+        content: &BTreeSet<Entry>,
+        pending: &Option<HashSet<Bytes>>,
+        shown: &mut Shown,
+        changed: Vec<Entry>,
+        request: &Option<Arc<str>>,
+    ) {
+        let (entered, left): (Vec<Entry>, Vec<Entry>) = changed.into_iter().partition(|e| {
+            shown.contains(e)
+                && content.contains(e)
+                && !pending.as_ref().is_some_and(|p| p.contains(&e.path))
+        });
+        for entry in left {
+            // an entry moved within the page is still shown
+            if !entered.iter().any(|e| e.path == entry.path) && shown.paths.remove(&entry.path) {
+                let _ = self.send_as(Msg::RmFile(entry.path), request);
+            }
+        }
+        for entry in entered {
+            if shown.paths.insert(entry.path.clone()) {
+                let _ = self.send_as(Msg::AddFile(entry.to_bytes()), request);
+            }
+        }
     }
 
     fn add(
         &self,
         value: impl Into<Bytes>,
+        fields: Bytes,
         pattern_version: usize,
         walker_version: &WalkerVersion,
     ) -> Option<()> {
-        let mut content = self.content_add(walker_version)?;
+        let mut content = self
+            .profile
+            .time(Stage::Lock, || self.content_add(walker_version))?;
 
+        // tagged with the request of the walk that found it
+        let request = walker_version.request();
         let value: Bytes = value.into();
         // need to recheck; pattern has changed since our last check
-        if (pattern_version == self.pattern.version() || self.pattern.all_matches(value.as_ref()))
-            && content.insert(value.clone())
-            && self.out.send(Msg::AddFile(value)).is_err()
-        {
-            None
-        } else {
-            Some(())
+        if pattern_version == self.version() || self.matches(value.as_ref()) {
+            let mut entry = self.entry(value.clone());
+            entry.fields = fields;
+            let data = entry.to_bytes();
+            // found again: now verified, and no longer stale
+            let old = self.take_held(&mut content, &entry);
+            if self.scrolled() {
+                let mut pending = self.pending();
+                if old.is_none()
+                    && let Some(pending) = pending.as_mut()
+                {
+                    pending.insert(value.clone());
+                }
+                let mut shown = self.shown();
+                let mut changed = vec![];
+                if let Some(old) = &old {
+                    self.page_removed(&content, &mut shown, old, &mut changed);
+                }
+                content.insert(entry.clone());
+                self.page_inserted(&content, &mut shown, &entry, &mut changed);
+                while self.capacity() < content.len()
+                    && let Some(last) = content.pop_last()
+                {
+                    if let Some(pending) = pending.as_mut() {
+                        pending.remove(&last.path);
+                    }
+                }
+                if old.is_some_and(|old| old.stale) && shown.paths.contains(&value) {
+                    self.send_as(Msg::AddFile(data), &request).ok()?;
+                }
+                self.send_page_changes(&content, &pending, &mut shown, changed, &request);
+                return Some(());
+            }
+            content.insert(entry);
+            if let Some(old) = old {
+                if old.stale && !self.pending().as_ref().is_some_and(|p| p.contains(&value)) {
+                    return self.send_as(Msg::AddFile(data), &request).ok();
+                }
+                return Some(());
+            }
+            let mut pending = self.pending();
+            if let Some(pending) = pending.as_mut() {
+                pending.insert(value);
+                if content.len() > self.size()
+                    && let Some(last) = content.pop_last()
+                    && !pending.remove(&last.path)
+                {
+                    return self.send_as(Msg::RmFile(last.path), &request).ok();
+                }
+            } else if content.len() > self.size()
+                && let Some(last) = content.pop_last()
+            {
+                // the worst is evicted, which may be the entry just added
+                if last.path != value {
+                    self.send_as(Msg::RmFile(last.path), &request).ok()?;
+                    return self.send_as(Msg::AddFile(data), &request).ok();
+                }
+            } else {
+                return self.send_as(Msg::AddFile(data), &request).ok();
+            }
         }
+        Some(())
     }
 
     fn remove(&self, value: impl Into<Bytes>, version: usize) -> Result<(), SendError<Msg>> {
         let mut content = self.content();
 
-        let value = value.into();
-        if (version == self.pattern.version() || !self.pattern.all_matches(value.as_ref()))
-            && content.remove(value.as_ref())
-            && content.len() < self.size()
+        let value: Bytes = value.into();
+        if (version == self.version() || !self.matches(value.as_ref()))
+            && let Some(old) = self.take_held(&mut content, &self.entry(value.clone()))
         {
-            self.cvar.notify_all();
+            let mut pending = self.pending();
+            let sent = !pending
+                .as_mut()
+                .is_some_and(|pending| pending.remove(&value));
+            if content.len() < self.capacity() {
+                self.cvar.notify_all();
+            }
+            if self.scrolled() {
+                let mut shown = self.shown();
+                let mut changed = vec![];
+                self.page_removed(&content, &mut shown, &old, &mut changed);
+                self.send_page_changes(&content, &pending, &mut shown, changed, &self.request());
+            } else if sent {
+                return self.send(Msg::RmFile(value));
+            }
         }
         Ok(())
     }
 
     fn clear(&self) {
-        let _ = self.out.send(Msg::Clear);
+        let _ = self.send(Msg::Clear);
         let mut content = self.content();
         content.clear();
+        if let Some(pending) = self.pending().as_mut() {
+            pending.clear();
+        }
+        self.shown().clear();
         self.cvar.notify_all();
     }
 
     fn redraw(&self) {
-        let _ = self.out.send(Msg::Clear);
+        let _ = self.send(Msg::Clear);
         let content = self.content();
+        let pending = self.pending();
+        if self.scrolled() {
+            self.shown().clear();
+            self.sync_shown(&content, &pending);
+            return;
+        }
         for entry in content.iter() {
-            let _ = self.out.send(Msg::AddFile(entry.to_owned()));
+            if !pending.as_ref().is_some_and(|p| p.contains(&entry.path)) {
+                let _ = self.send(Msg::AddFile(entry.to_bytes()));
+            }
+        }
+    }
+
+    fn snapshot(&self) {
+        let content = self.content();
+        let pending = self.pending();
+        let seq = self
+            .snapshots
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1;
+        let _ = self.out.send(Msg::SnapshotBegin(seq));
+        let page = if self.scrolled() {
+            self.size()
+        } else {
+            usize::MAX
+        };
+        for entry in content.iter().skip(self.offset()).take(page) {
+            if !pending.as_ref().is_some_and(|p| p.contains(&entry.path)) {
+                let _ = self.out.send(Msg::AddFile(entry.to_bytes()));
+            }
+        }
+        let _ = self.out.send(Msg::SnapshotEnd(seq));
+    }
+
+    fn started(&self) {
+        let _content = self.content();
+        if self
+            .deterministic
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            *self.pending() = Some(HashSet::new());
+        }
+        let _ = self.send(Msg::WalkStarted);
+    }
+
+    fn done(&self, walker_version: &WalkerVersion) {
+        self.done_with(Msg::WalkDone, walker_version.request());
+    }
+
+    fn done_with(&self, msg: Msg, request: Option<Arc<str>>) {
+        {
+            let content = self.content();
+            let mut pending = self.pending();
+            if let Some(held) = pending.take() {
+                if self.scrolled() {
+                    self.sync_shown_as(&content, &pending, &request);
+                } else {
+                    for entry in content.iter() {
+                        if held.contains(&entry.path) {
+                            let _ = self.send_as(Msg::AddFile(entry.to_bytes()), &request);
+                        }
+                    }
+                }
+            }
         }
+        let _ = self.send_as(msg, &request);
+    }
+
+    /// As `done`, for a walk stopped by its time budget.
+    fn truncated(&self, walker_version: &WalkerVersion) {
+        self.done_with(Msg::WalkTruncated, walker_version.request());
     }
 
     fn killed(&self) {
@@ -87,14 +548,30 @@ impl Inner {
         self.cvar.notify_all();
     }
 
+    /// Remove the entries the query no longer matches; the score order ranks the rest again.
     fn remove_unmatched(&self) {
+        self.remove_if(|path| !self.matches(path));
+        if self.order().has(SortKey::Score) {
+            let mut content = self.content();
+            self.rekey(&mut content);
+            if self.scrolled() {
+                self.sync_shown(&content, &self.pending());
+            }
+        }
+    }
+
+    /// Remove the entries for which `f` is true, telling the client about those already sent.
+    fn remove_if(&self, f: impl Fn(&[u8]) -> bool) {
         let mut content = self.content();
         let len = content.len();
-        let pattern = self.pattern.clone();
+        let mut pending = self.pending();
+        let scrolled = self.scrolled();
 
         content.retain(|k| {
-            if !pattern.all_matches(k) {
-                let _ = self.out.send(Msg::RmFile(k.clone()));
+            if f(&k.path) {
+                if !pending.as_mut().is_some_and(|p| p.remove(&k.path)) && !scrolled {
+                    let _ = self.send(Msg::RmFile(k.path.clone()));
+                }
                 false
             } else {
                 true
@@ -102,19 +579,220 @@ impl Inner {
         });
 
         if len > content.len() {
+            if scrolled {
+                self.sync_shown(&content, &pending);
+            }
+            self.cvar.notify_all();
+        }
+    }
+
+    /// Handle the entries no walk has found for `age`: with `verify` those missing from `root`
+    /// are removed and the rest kept as fresh; otherwise they are marked stale and resent.
+    fn check_stale(&self, root: &Path, age: Duration, verify: bool) {
+        let mut content = self.content();
+        let mut pending = self.pending();
+        let old: Vec<Entry> = content
+            .iter()
+            .filter(|e| !e.stale && e.verified.elapsed() >= age)
+            .cloned()
+            .collect();
+        let len = content.len();
+        let scrolled = self.scrolled();
+        for mut entry in old {
+            let sent = !pending.as_ref().is_some_and(|p| p.contains(&entry.path))
+                && (!scrolled || self.shown().paths.contains(&entry.path));
+            if !verify {
+                entry.stale = true;
+                if sent {
+                    let _ = self.send(Msg::AddFile(entry.to_bytes()));
+                }
+            } else if !is_on_disk(root, &entry.path) {
+                content.remove(&entry);
+                if let Some(pending) = pending.as_mut() {
+                    pending.remove(&entry.path);
+                }
+                if sent && !scrolled {
+                    let _ = self.send(Msg::RmFile(entry.path));
+                }
+                continue;
+            } else {
+                entry.verified = Instant::now();
+            }
+            content.replace(entry);
+        }
+        if len > content.len() {
+            if scrolled {
+                self.sync_shown(&content, &pending);
+            }
             self.cvar.notify_all();
         }
     }
 
+    /// Remove and return the entry held for `entry`'s path. Its key is rebuilt from the path
+    /// unless the order has an mtime or score key, or frecency ranks it, where the file, query or
+    /// time may have changed since it was added.
+    fn take_held(&self, content: &mut BTreeSet<Entry>, entry: &Entry) -> Option<Entry> {
+        if self.order().is_by_path()
+            && self
+                .frecency
+                .read()
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .is_empty()
+        {
+            return content.take(entry);
+        }
+        let held = content.iter().find(|e| e.path == entry.path)?.clone();
+        content.take(&held)
+    }
+
+    fn order(&self) -> RwLockReadGuard<'_, Order> {
+        self.order.read().expect(crate::LOCK_SHOULD_BE_OK)
+    }
+
+    fn set_order(&self, value: Order) -> bool {
+        let mut content = self.content();
+        {
+            let mut order = self.order.write().expect(crate::LOCK_SHOULD_BE_OK);
+            if *order == value {
+                return false;
+            }
+            *order = value;
+        }
+        self.rekey(&mut content);
+        true
+    }
+
+    fn set_context(&self, dir: Option<Bytes>) -> bool {
+        let mut content = self.content();
+        {
+            let mut context = self.context.write().expect(crate::LOCK_SHOULD_BE_OK);
+            let before = context.clone();
+            match dir {
+                Some(dir) => {
+                    context.retain(|d| *d != dir);
+                    context.insert(0, dir);
+                    context.truncate(CONTEXT_DEPTH);
+                }
+                None => context.clear(),
+            }
+            if *context == before {
+                return false;
+            }
+        }
+        self.rekey(&mut content);
+        true
+    }
+
+    fn visited(&self, path: PathBuf) {
+        let mut content = self.content();
+        self.frecency
+            .write()
+            .expect(crate::LOCK_SHOULD_BE_OK)
+            .visit(path, SystemTime::now());
+        self.rekey(&mut content);
+    }
+
+    fn set_weight(&self, pattern: &str, multiplier: f64) -> Result<bool, ignore::Error> {
+        let mut content = self.content();
+        {
+            let mut weights = self.weights.write().expect(crate::LOCK_SHOULD_BE_OK);
+            let before = weights.clone();
+            weights.set(pattern, multiplier)?;
+            if *weights == before {
+                return Ok(false);
+            }
+        }
+        self.scoring_changed();
+        self.rekey(&mut content);
+        Ok(true)
+    }
+
+    /// Build the entry for `path`, ranked by its frecency at `now`; those below the latest
+    /// context directory count double, and less for each later one.
+    fn entry(&self, path: Bytes) -> Entry {
+        self.entry_at(path, SystemTime::now())
+    }
+
+    fn entry_at(&self, path: Bytes, now: SystemTime) -> Entry {
+        let root = self.root.read().expect(crate::LOCK_SHOULD_BE_OK);
+        let order = self.order();
+        let mut entry = order.entry(path, &root);
+        if order.has(SortKey::Score) {
+            let score = self.score(&entry.path);
+            order.set_score(&mut entry, score);
+        }
+        let mut weight = 1.0;
+        let frecency = self.frecency.read().expect(crate::LOCK_SHOULD_BE_OK);
+        if !frecency.is_empty() {
+            weight *= frecency.boost(&root.join(OsStr::from_bytes(&entry.path)), now);
+        }
+        if let Some(age) = self
+            .context
+            .read()
+            .expect(crate::LOCK_SHOULD_BE_OK)
+            .iter()
+            .position(|dir| entry.path.starts_with(dir))
+        {
+            weight *= CONTEXT_BOOST.powf(0.5f64.powi(age as i32));
+        }
+        entry.rank = ((1.0 - weight) * 1000.0).round() as i64;
+        entry
+    }
+
+    fn rekey(&self, content: &mut BTreeSet<Entry>) {
+        // the same time for every entry, so their frecency is compared fairly
+        let now = SystemTime::now();
+        *content = std::mem::take(content)
+            .into_iter()
+            .map(|e| {
+                let mut entry = self.entry_at(e.path, now);
+                entry.fields = e.fields;
+                entry.verified = e.verified;
+                entry.stale = e.stale;
+                entry
+            })
+            .collect();
+    }
+
+    fn request(&self) -> Option<Arc<str>> {
+        self.request.read().expect(crate::LOCK_SHOULD_BE_OK).clone()
+    }
+
+    /// Send `msg`, tagged with the ID of the client's latest request if it gave one.
+    fn send(&self, msg: Msg) -> Result<(), SendError<Msg>> {
+        self.send_as(msg, &self.request())
+    }
+
+    /// Send `msg`, tagged with the `request` ID if there is one.
+    fn send_as(&self, msg: Msg, request: &Option<Arc<str>>) -> Result<(), SendError<Msg>> {
+        match request {
+            Some(id) => self.out.send(Msg::Request {
+                id: id.to_string(),
+                msg: Box::new(msg),
+            }),
+            None => self.out.send(msg),
+        }
+    }
+
     #[inline(always)]
-    fn content(&self) -> MutexGuard<'_, BTreeSet<Bytes>> {
+    fn content(&self) -> MutexGuard<'_, BTreeSet<Entry>> {
         self.content.lock().expect(crate::LOCK_SHOULD_BE_OK)
     }
 
+    #[inline(always)]
+    fn pending(&self) -> MutexGuard<'_, Option<HashSet<Bytes>>> {
+        self.pending.lock().expect(crate::LOCK_SHOULD_BE_OK)
+    }
+
+    #[inline(always)]
+    fn shown(&self) -> MutexGuard<'_, Shown> {
+        self.shown.lock().expect(crate::LOCK_SHOULD_BE_OK)
+    }
+
     fn content_add(
         &self,
         walker_version: &WalkerVersion,
-    ) -> Option<MutexGuard<'_, BTreeSet<Bytes>>> {
+    ) -> Option<MutexGuard<'_, BTreeSet<Entry>>> {
         let mut al = self.lock.lock().expect(crate::LOCK_SHOULD_BE_OK);
 
         loop {
@@ -123,7 +801,12 @@ impl Inner {
                 if walker_version.is_wrong() {
                     return None;
                 }
-                if content.len() < self.size() {
+                // a deterministic walk or the score order keeps the best `size` entries so never
+                // waits
+                if content.len() < self.capacity()
+                    || self.pending().is_some()
+                    || self.order().has(SortKey::Score)
+                {
                     return Some(content);
                 }
             }
@@ -138,25 +821,109 @@ pub struct Window {
 }
 impl Window {
     pub fn new(size: usize, out: SyncSender<Msg>) -> Self {
+        Self::with_scorer(size, out, Arc::new(FuzzyScorer))
+    }
+
+    pub fn with_scorer(size: usize, out: SyncSender<Msg>, scorer: Arc<dyn Scorer>) -> Self {
+        Self::build(
+            size,
+            out,
+            scorer,
+            Default::default(),
+            Default::default(),
+            Arc::new(PROTOCOL_VERSION.into()),
+            Default::default(),
+        )
+    }
+
+    fn build(
+        size: usize,
+        out: SyncSender<Msg>,
+        scorer: Arc<dyn Scorer>,
+        profile: Arc<Profile>,
+        rate_limit: Arc<RateLimit>,
+        protocol: Arc<AtomicU32>,
+        frecency: Arc<RwLock<Frecency>>,
+    ) -> Self {
         Self {
             inner: Arc::new(Inner {
                 size: size.into(),
+                offset: Default::default(),
+                shown: Default::default(),
                 out,
+                request: Default::default(),
                 pattern: Default::default(),
+                refine: Default::default(),
+                scorer,
+                min_score: i64::MIN.into(),
+                scoring: Default::default(),
+                order: Default::default(),
+                root: RwLock::new("./".into()),
+                context: Default::default(),
+                weights: Default::default(),
+                frecency,
                 content: Default::default(),
+                deterministic: Default::default(),
+                snapshots: Default::default(),
+                pending: Default::default(),
                 cvar: Default::default(),
                 lock: Default::default(),
+                profile,
+                rate_limit,
+                protocol,
             }),
         }
     }
 
+    /// A new window for the pattern named `name`, with this one's size, scorer, weights and
+    /// frecency. Its messages are sent as `Msg::Named`, except a `Msg::Fatal` which ends the
+    /// server.
+    pub fn named(&self, name: &str) -> Self {
+        let size = self.size();
+        let (tx, rx) = mpsc::sync_channel(size * 2);
+        let out = self.inner.out.clone();
+        let name = name.to_string();
+        thread::spawn(move || {
+            for msg in rx {
+                let msg = match msg {
+                    Msg::Fatal(_) => msg,
+                    msg => Msg::Named {
+                        name: name.clone(),
+                        msg: Box::new(msg),
+                    },
+                };
+                if out.send(msg).is_err() {
+                    return;
+                }
+            }
+        });
+        let inner = &self.inner;
+        let window = Self::build(
+            size,
+            tx,
+            inner.scorer.clone(),
+            inner.profile.clone(),
+            inner.rate_limit.clone(),
+            inner.protocol.clone(),
+            inner.frecency.clone(),
+        );
+        window.set_weights(
+            inner
+                .weights
+                .read()
+                .expect(crate::LOCK_SHOULD_BE_OK)
+                .clone(),
+        );
+        window
+    }
+
     #[inline(always)]
     pub fn size(&self) -> usize {
         self.inner.size()
     }
 
-    /// Add `value` to this window.  It is expected `version` is from the `pattern` used to match
-    /// `value`.  If the pattern version has changed the test will be redone.
+    /// Add `value` to this window. `version` is the window's `version` from before `value` was
+    /// matched; if it has changed since, `value` is matched again.
     #[inline(always)]
     pub fn add(
         &self,
@@ -164,29 +931,188 @@ impl Window {
         pattern_version: usize,
         walker_version: &WalkerVersion,
     ) -> Option<()> {
-        self.inner.add(value, pattern_version, walker_version)
+        self.inner
+            .add(value, Bytes::new(), pattern_version, walker_version)
+    }
+
+    /// Like `add` but the entry carries metadata `fields`, sent after its path.
+    #[inline(always)]
+    pub fn add_with_fields(
+        &self,
+        value: impl Into<Bytes>,
+        fields: Bytes,
+        pattern_version: usize,
+        walker_version: &WalkerVersion,
+    ) -> Option<()> {
+        self.inner
+            .add(value, fields, pattern_version, walker_version)
     }
 
-    /// Remove `value` from this window.  It is expected `version` is from the `pattern` used to
-    /// test `value` does not match.  If the pattern version has changed the test will be redone.
+    /// Remove `value` from this window. `version` is the window's `version` from before `value`
+    /// was found not to match; if it has changed since, `value` is matched again.
     #[inline(always)]
     pub fn remove(&self, value: impl Into<Bytes>, version: usize) -> Result<(), SendError<Msg>> {
         self.inner.remove(value, version)
     }
 
+    /// The number of entries held, which includes those a deterministic walk has not sent yet.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.inner.content().len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entries held, in window order.
+    pub fn paths(&self) -> Vec<Bytes> {
+        self.inner
+            .content()
+            .iter()
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    /// The entry `path` would have in this window, for sorting paths into window order.
+    #[inline(always)]
+    pub fn entry(&self, path: Bytes) -> Entry {
+        self.inner.entry(path)
+    }
+
+    #[inline(always)]
+    pub fn order(&self) -> Order {
+        self.inner.order().clone()
+    }
+
+    /// True if adding to the window would wait for room.
+    #[inline(always)]
+    pub fn is_full(&self) -> bool {
+        self.inner.content().len() >= self.inner.capacity()
+    }
+
     #[inline(always)]
     pub fn set_size(&self, value: usize) {
         self.inner.set_size(value);
     }
 
     #[inline(always)]
-    pub fn done(&self) {
-        let _ = self.inner.out.send(Msg::WalkDone);
+    pub fn offset(&self) -> usize {
+        self.inner.offset()
+    }
+
+    /// Show the client the `size` entries from `offset` in order, rather than the first. Returns
+    /// `true` if this scrolled forward, in which case the window should be refilled.
+    #[inline(always)]
+    pub fn scroll(&self, offset: usize) -> bool {
+        self.inner.scroll(offset)
+    }
+
+    /// Change how entries are ordered. Returns `true` if the order changed, in which case the
+    /// window no longer holds the first `size` matches and should be refilled.
+    #[inline(always)]
+    pub fn set_order(&self, value: Order) -> bool {
+        self.inner.set_order(value)
+    }
+
+    /// Set the directory paths are relative to. It matters to the mtime order, which stats the
+    /// files, and to frecency, which is kept by whole path.
+    pub fn set_root(&self, root: &Path) {
+        *self.inner.root.write().expect(crate::LOCK_SHOULD_BE_OK) = root.to_path_buf();
+    }
+
+    /// Rank entries within `dir`, a path prefix ending in `/`, ahead of the rest, and those within
+    /// the previous directories less so; `None` forgets them all. Returns `true` if this changed
+    /// the order, in which case the window should be redrawn.
+    #[inline(always)]
+    pub fn set_context(&self, dir: Option<Bytes>) -> bool {
+        self.inner.set_context(dir)
+    }
+
+    /// Weight the entries matching `pattern`; see `Weights::set`. Returns `true` if this changed
+    /// the weights, in which case the window should be refilled.
+    #[inline(always)]
+    pub fn set_weight(&self, pattern: &str, multiplier: f64) -> Result<bool, ignore::Error> {
+        self.inner.set_weight(pattern, multiplier)
+    }
+
+    /// Count a visit to the whole `path`, boosting its rank.
+    #[inline(always)]
+    pub fn visited(&self, path: PathBuf) {
+        self.inner.visited(path);
+    }
+
+    pub fn set_frecency(&self, frecency: Frecency) {
+        let mut content = self.inner.content();
+        *self.inner.frecency.write().expect(crate::LOCK_SHOULD_BE_OK) = frecency;
+        self.inner.rekey(&mut content);
+    }
+
+    pub fn save_frecency(&self, file: &Path) -> io::Result<()> {
+        self.inner
+            .frecency
+            .read()
+            .expect(crate::LOCK_SHOULD_BE_OK)
+            .save(file)
+    }
+
+    #[inline(always)]
+    pub fn set_weights(&self, weights: Weights) {
+        let mut content = self.inner.content();
+        *self.inner.weights.write().expect(crate::LOCK_SHOULD_BE_OK) = weights;
+        self.inner.scoring_changed();
+        self.inner.rekey(&mut content);
+    }
+
+    /// Reject matches scoring below `value`. Returns the previous threshold.
+    #[inline(always)]
+    pub fn set_min_score(&self, value: Option<i64>) -> Option<i64> {
+        let value = value.unwrap_or(i64::MIN);
+        let old = self
+            .inner
+            .min_score
+            .swap(value, std::sync::atomic::Ordering::Relaxed);
+        if old != value {
+            self.inner.scoring_changed();
+        }
+        (old != i64::MIN).then_some(old)
+    }
+
+    /// When `value` is true, walks started from now on hold back their results until `done` and
+    /// then send them in window order.
+    #[inline(always)]
+    pub fn set_deterministic(&self, value: bool) {
+        self.inner
+            .deterministic
+            .store(value, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// The walk of `walker_version` is done; sent tagged with its request ID.
+    #[inline(always)]
+    pub fn done(&self, walker_version: &WalkerVersion) {
+        self.inner.done(walker_version);
+    }
+
+    /// Tag the messages sent from now on, other than those of a walk, with the client's request
+    /// `id`.
+    pub fn set_request(&self, id: &str) {
+        *self.inner.request.write().expect(crate::LOCK_SHOULD_BE_OK) = Some(id.into());
+    }
+
+    /// The ID of the client's latest request, if it gave one.
+    pub fn request(&self) -> Option<Arc<str>> {
+        self.inner.request()
+    }
+
+    #[inline(always)]
+    pub fn truncated(&self, walker_version: &WalkerVersion) {
+        self.inner.truncated(walker_version);
     }
 
     #[inline(always)]
     pub fn started(&self) {
-        let _ = self.inner.out.send(Msg::WalkStarted);
+        self.inner.started();
     }
 
     #[inline(always)]
@@ -199,6 +1125,12 @@ impl Window {
         self.inner.redraw();
     }
 
+    /// Resend the whole window between snapshot markers, without clearing it first.
+    #[inline(always)]
+    pub fn snapshot(&self) {
+        self.inner.snapshot();
+    }
+
     #[inline(always)]
     pub fn killed(&self) {
         self.inner.killed();
@@ -209,22 +1141,135 @@ impl Window {
         &self.inner.pattern
     }
 
+    #[inline(always)]
+    pub fn refine(&self) -> &Pattern {
+        &self.inner.refine
+    }
+
+    /// The version of the query as a whole, to pass to `add` and `remove`.
+    #[inline(always)]
+    pub fn version(&self) -> usize {
+        self.inner.version()
+    }
+
+    /// True if `value` matches the pattern and the refinement, and scores at least the minimum
+    /// score if there is one.
+    #[inline(always)]
+    pub fn matches(&self, value: &[u8]) -> bool {
+        self.inner.matches(value)
+    }
+
+    #[inline(always)]
+    pub fn profile(&self) -> &Arc<Profile> {
+        &self.inner.profile
+    }
+
+    pub fn rate_limit(&self) -> &Arc<RateLimit> {
+        &self.inner.rate_limit
+    }
+
+    pub fn protocol(&self) -> &Arc<AtomicU32> {
+        &self.inner.protocol
+    }
+
+    pub fn set_protocol(&self, version: u32) {
+        self.inner
+            .protocol
+            .store(version, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn command_done(&self, number: u64) {
+        if self.inner.profile.is_timing() {
+            let _ = self.inner.out.send(Msg::CommandDone(number));
+        }
+    }
+
+    #[inline(always)]
+    pub fn protocol_version(&self, version: u32) {
+        let _ = self.inner.out.send(Msg::Protocol(version));
+    }
+
     #[inline(always)]
     pub fn remove_unmatched(&self) {
         self.inner.remove_unmatched();
     }
 
+    #[inline(always)]
+    pub fn check_stale(&self, root: &Path, age: Duration, verify: bool) {
+        self.inner.check_stale(root, age, verify);
+    }
+
+    /// Remove the entries no longer found under `root`.
+    pub fn remove_missing(&self, root: &Path) {
+        self.inner.remove_if(|path| !is_on_disk(root, path));
+    }
+
+    /// Remove the entry `rel` and everything under it, as when the directory `rel` is deleted.
+    pub fn remove_under(&self, rel: &[u8]) {
+        self.inner.remove_if(|path| {
+            path.strip_prefix(rel)
+                .is_some_and(|rest| rest.is_empty() || rest[0] == b'/')
+        });
+    }
+
     #[inline(always)]
     pub fn message(&self, msg: String) {
         let _ = self.inner.out.send(Msg::Message(msg));
     }
 
+    #[inline(always)]
+    pub fn warning(&self, kind: &str, detail: String) {
+        let _ = self.inner.out.send(Msg::Warning {
+            kind: kind.to_string(),
+            detail,
+        });
+    }
+
+    /// Report an unrecoverable error; this is the last message the client will receive.
+    #[inline(always)]
+    pub fn fatal(&self, msg: String) {
+        let _ = self.inner.out.send(Msg::Fatal(msg));
+    }
+
+    #[inline(always)]
+    pub fn root_changed(&self, change: RootChange) {
+        let _ = self.inner.out.send(Msg::RootChanged(change));
+    }
+
+    #[inline(always)]
+    pub fn progress(&self, scanned: usize, matched: usize) {
+        let _ = self.inner.out.send(Msg::Progress { scanned, matched });
+    }
+
+    #[inline(always)]
+    pub fn count(&self, matched: usize) {
+        let _ = self.inner.out.send(Msg::Count(matched));
+    }
+
+    #[inline(always)]
+    pub fn walk_stats(&self, scanned: usize, matched: usize, elapsed: Duration) {
+        let _ = self.inner.out.send(Msg::WalkStats {
+            scanned,
+            matched,
+            elapsed,
+        });
+    }
+
     #[inline(always)]
     pub fn request_resync(&self) {
         let _ = self.inner.out.send(Msg::Resync);
     }
 }
 
+/// Whether the file `path`, under `root`, is still there; for a file within an archive, whether
+/// the archive is.
+fn is_on_disk(root: &Path, path: &[u8]) -> bool {
+    #[cfg(feature = "archives")]
+    let path = super::archive::archive_of(path).unwrap_or(path);
+    fs::symlink_metadata(root.join(OsStr::from_bytes(path))).is_ok()
+}
+
 #[cfg(test)]
 #[path = "window_test.rs"]
 mod test;
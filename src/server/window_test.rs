@@ -10,7 +10,7 @@ fn content_to_string(w: &Window) -> String {
     let guard = w.inner.content();
     let r: Vec<String> = guard
         .iter()
-        .map(|s| String::from_utf8_lossy(s).to_string())
+        .map(|s| String::from_utf8_lossy(&s.path).to_string())
         .collect();
     r.join(" ")
 }
@@ -72,6 +72,35 @@ fn redraw() {
     assert_matches!(rx.recv_timeout(WT).unwrap(), Msg::AddFile(x) if x.as_ref() == b"world");
 }
 
+#[test]
+fn snapshot() {
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(3, tx);
+    let wv = WalkerVersion::default();
+
+    w.add("b", 0, &wv).unwrap();
+    w.add("a", 0, &wv).unwrap();
+    let _ = rx.try_iter().count();
+
+    w.snapshot();
+    w.snapshot();
+
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [
+            Msg::SnapshotBegin(1),
+            Msg::AddFile(Bytes::from_static(b"a")),
+            Msg::AddFile(Bytes::from_static(b"b")),
+            Msg::SnapshotEnd(1),
+            Msg::SnapshotBegin(2),
+            Msg::AddFile(Bytes::from_static(b"a")),
+            Msg::AddFile(Bytes::from_static(b"b")),
+            Msg::SnapshotEnd(2),
+        ]
+    );
+    assert_eq!(content_to_string(&w), "a b");
+}
+
 #[test]
 fn window_size() {
     let (tx, _rx) = mpsc::sync_channel(50);
@@ -124,3 +153,375 @@ fn window_size() {
     w.remove("2o", 0).unwrap(); // wrong version retested
     assert_eq!(content_to_string(&w), "2o 3o");
 }
+
+#[test]
+fn set_order() {
+    let (tx, _rx) = mpsc::sync_channel(50);
+    let w = Window::new(3, tx);
+
+    let wv = WalkerVersion::default();
+    let add = |t, n| w.add(t, n, &wv).unwrap();
+
+    add("f10", 0);
+    add("f9", 0);
+    add("f1", 0);
+    assert_eq!(content_to_string(&w), "f1 f10 f9");
+
+    assert!(w.set_order(SortKey::Natural.into()));
+    assert!(!w.set_order(SortKey::Natural.into()));
+    assert_eq!(content_to_string(&w), "f1 f9 f10");
+
+    w.remove("f9", 0).unwrap();
+    add("f2", 0);
+    assert_eq!(content_to_string(&w), "f1 f2 f10");
+
+    w.set_size(2);
+    assert_eq!(content_to_string(&w), "f1 f2");
+}
+
+#[test]
+fn score_order() {
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(2, tx);
+    w.set_order(SortKey::Score.into());
+    w.inner.pattern.add("ab");
+
+    let wv = WalkerVersion::default();
+    let add = |t| w.add(t, 0, &wv).unwrap();
+
+    add("zzazzb");
+    add("zab");
+    // a full window evicts its worst match for a better one
+    add("ab");
+    assert_eq!(content_to_string(&w), "ab zab");
+    add("zzzazzzb");
+    assert_eq!(content_to_string(&w), "ab zab");
+
+    let msgs: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        msgs,
+        [
+            Msg::AddFile("zzazzb".into()),
+            Msg::AddFile("zab".into()),
+            Msg::RmFile("zzazzb".into()),
+            Msg::AddFile("ab".into()),
+        ]
+    );
+
+    // found by path though the query, and so its key, has changed
+    w.inner.pattern.rm(1);
+    w.remove("zab", w.version()).unwrap();
+    assert_eq!(content_to_string(&w), "ab");
+}
+
+#[test]
+fn scroll() {
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(2, tx);
+    assert!(w.scroll(2));
+    assert!(!w.scroll(2));
+
+    let wv = WalkerVersion::default();
+    let add = |t| w.add(t, 0, &wv).unwrap();
+
+    add("d");
+    add("b");
+    add("a");
+    add("c");
+    assert_eq!(content_to_string(&w), "a b c d");
+    w.remove("a", 0).unwrap();
+    assert!(!w.scroll(1));
+    w.redraw();
+    assert!(!w.scroll(0));
+    assert_eq!(content_to_string(&w), "b c");
+
+    let msgs: Vec<_> = rx.try_iter().collect();
+    let file = |p: &'static str| Bytes::from_static(p.as_bytes());
+    assert_eq!(
+        msgs,
+        [
+            Msg::AddFile(file("d")),
+            Msg::AddFile(file("c")),
+            // a page of the entries from 2 when one before it goes
+            Msg::RmFile(file("c")),
+            Msg::AddFile(file("c")),
+            Msg::Clear,
+            Msg::AddFile(file("c")),
+            Msg::AddFile(file("d")),
+            Msg::RmFile(file("d")),
+            Msg::AddFile(file("b")),
+        ]
+    );
+}
+
+#[test]
+fn scroll_sends_page_changes() {
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(3, tx);
+    // so adding never waits for room
+    w.set_order(SortKey::Score.into());
+    w.scroll(4);
+    let wv = WalkerVersion::default();
+    let mut seen = HashSet::new();
+    let mut next = 7u32;
+    for i in 0..200 {
+        next = next.wrapping_mul(1103515245).wrapping_add(12345);
+        let path = format!("{:02}", (next >> 16) % 20);
+        if i % 3 == 2 {
+            w.remove(path, 0).unwrap();
+        } else {
+            w.add(path, 0, &wv).unwrap();
+        }
+        for msg in rx.try_iter() {
+            match msg {
+                Msg::AddFile(path) => assert!(seen.insert(path)),
+                Msg::RmFile(path) => assert!(seen.remove(&path)),
+                msg => panic!("unexpected {msg:?}"),
+            }
+        }
+        let page: HashSet<Bytes> = w
+            .inner
+            .content()
+            .iter()
+            .skip(4)
+            .take(3)
+            .map(|e| e.path.clone())
+            .collect();
+        assert_eq!(seen, page);
+    }
+}
+
+#[test]
+fn deterministic() {
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(2, tx);
+    w.set_deterministic(true);
+
+    let wv = WalkerVersion::default();
+    let add = |t| w.add(t, 0, &wv).unwrap();
+
+    add("c");
+    w.started();
+    add("d");
+    add("b");
+    add("a");
+    assert_eq!(content_to_string(&w), "a b");
+    w.done(&wv);
+
+    let msgs: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        msgs,
+        [
+            Msg::AddFile(Bytes::from_static(b"c")),
+            Msg::WalkStarted,
+            Msg::RmFile(Bytes::from_static(b"c")),
+            Msg::AddFile(Bytes::from_static(b"a")),
+            Msg::AddFile(Bytes::from_static(b"b")),
+            Msg::WalkDone,
+        ]
+    );
+}
+
+#[test]
+fn remove_sends_rm() {
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(3, tx);
+    let wv = WalkerVersion::default();
+
+    w.add("a", 0, &wv).unwrap();
+    w.remove("a", 0).unwrap();
+    w.remove("b", 0).unwrap();
+
+    let msgs: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        msgs,
+        [
+            Msg::AddFile(Bytes::from_static(b"a")),
+            Msg::RmFile(Bytes::from_static(b"a"))
+        ]
+    );
+}
+
+#[test]
+fn set_context() {
+    let (tx, _rx) = mpsc::sync_channel(50);
+    let w = Window::new(4, tx);
+
+    let wv = WalkerVersion::default();
+    let add = |t, n| w.add(t, n, &wv).unwrap();
+
+    add("a/1", 0);
+    add("b/2", 0);
+    add("c", 0);
+    assert!(w.set_context(Some(Bytes::from_static(b"b/"))));
+    assert!(!w.set_context(Some(Bytes::from_static(b"b/"))));
+    assert_eq!(content_to_string(&w), "b/2 a/1 c");
+
+    add("b/1", 0);
+    assert_eq!(content_to_string(&w), "b/1 b/2 a/1 c");
+
+    assert!(w.set_context(None));
+    assert_eq!(content_to_string(&w), "a/1 b/1 b/2 c");
+
+    // the boost of older contexts decays until they are forgotten
+    assert!(w.set_context(Some(Bytes::from_static(b"a/"))));
+    assert!(w.set_context(Some(Bytes::from_static(b"b/"))));
+    assert_eq!(content_to_string(&w), "b/1 b/2 a/1 c");
+    for dir in ["d/", "e/", "f/"] {
+        assert!(w.set_context(Some(Bytes::from(dir))));
+    }
+    assert_eq!(content_to_string(&w), "b/1 b/2 a/1 c");
+    assert!(w.set_context(Some(Bytes::from_static(b"g/"))));
+    assert_eq!(content_to_string(&w), "a/1 b/1 b/2 c");
+}
+
+#[test]
+fn set_weight() {
+    let (tx, _rx) = mpsc::sync_channel(50);
+    let w = Window::new(4, tx);
+
+    let wv = WalkerVersion::default();
+    let add = |t, n| w.add(t, n, &wv).unwrap();
+
+    // the weights multiply the score so only change the score order
+    add("a.lock", 0);
+    add("b.rs", 0);
+    add("c", 0);
+    assert!(w.set_weight("lock", 0.1).unwrap());
+    assert!(!w.set_weight("lock", 0.1).unwrap());
+    assert!(w.set_weight("*.rs", 3.0).unwrap());
+    assert_eq!(content_to_string(&w), "a.lock b.rs c");
+    assert!(w.set_order(SortKey::Score.into()));
+    assert_eq!(content_to_string(&w), "b.rs c a.lock");
+
+    assert!(w.set_context(Some(Bytes::from_static(b"d/"))));
+    add("d/e", 0);
+    assert_eq!(content_to_string(&w), "d/e b.rs c a.lock");
+
+    w.set_min_score(Some(-5));
+    w.remove_unmatched();
+    assert_eq!(content_to_string(&w), "d/e b.rs c");
+}
+
+#[test]
+fn visited() {
+    let (tx, _rx) = mpsc::sync_channel(50);
+    let w = Window::new(4, tx);
+    w.set_root(Path::new("/root"));
+
+    let wv = WalkerVersion::default();
+    let add = |t, n| w.add(t, n, &wv).unwrap();
+
+    add("a", 0);
+    add("b", 0);
+    w.visited("/root/b".into());
+    assert_eq!(content_to_string(&w), "b a");
+    add("c", 0);
+    w.visited("/root/c".into());
+    w.visited("/root/c".into());
+    assert_eq!(content_to_string(&w), "c b a");
+
+    w.set_frecency(Frecency::default());
+    assert_eq!(content_to_string(&w), "a b c");
+
+    // the boost drops as the visit ages past an hour, after `b` was added
+    let mut frecency = Frecency::default();
+    let visit = SystemTime::now() - Duration::from_secs(3599);
+    frecency.visit("/root/d".into(), visit);
+    w.set_frecency(frecency);
+    add("d", 0);
+    assert_eq!(content_to_string(&w), "d a b c");
+    thread::sleep(Duration::from_millis(1100));
+    w.remove("d", w.version()).unwrap();
+    assert_eq!(content_to_string(&w), "a b c");
+}
+
+#[test]
+fn min_score() {
+    let (tx, _rx) = mpsc::sync_channel(50);
+    let w = Window::new(4, tx);
+    w.inner.pattern.add("a");
+
+    let wv = WalkerVersion::default();
+    let add = |t, n| w.add(t, n, &wv).unwrap();
+
+    let version = w.version();
+    assert_eq!(w.set_min_score(Some(10)), None);
+    assert_ne!(w.version(), version);
+    add("a", 0);
+    // matched before the threshold was set so checked again
+    add("xxxxxxxxa", version);
+    assert_eq!(content_to_string(&w), "a");
+
+    assert_eq!(w.set_min_score(None), Some(10));
+    add("xxxxxxxxa", 0);
+    assert_eq!(content_to_string(&w), "a xxxxxxxxa");
+
+    w.set_min_score(Some(10));
+    w.remove_unmatched();
+    assert_eq!(content_to_string(&w), "a");
+}
+
+#[test]
+fn check_stale() {
+    let dir = std::env::temp_dir().join(format!("koru_find_window_stale_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a"), "").unwrap();
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(4, tx);
+    let wv = WalkerVersion::default();
+
+    w.add("a", 0, &wv).unwrap();
+    w.add("b", 0, &wv).unwrap();
+    let _: Vec<_> = rx.try_iter().collect();
+
+    w.check_stale(&dir, Duration::from_secs(60), false);
+    assert_matches!(rx.try_recv(), Err(_));
+
+    w.check_stale(&dir, Duration::ZERO, true);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [Msg::RmFile(Bytes::from_static(b"b"))]
+    );
+
+    w.check_stale(&dir, Duration::ZERO, false);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [Msg::AddFile(Bytes::from_static(b"a\x1fstale"))]
+    );
+    w.check_stale(&dir, Duration::ZERO, false);
+    assert_matches!(rx.try_recv(), Err(_));
+
+    // found again by a walk
+    w.add("a", 0, &wv).unwrap();
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [Msg::AddFile(Bytes::from_static(b"a"))]
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(feature = "archives")]
+#[test]
+fn missing_archived() {
+    let dir = std::env::temp_dir().join(format!("koru_find_window_zip_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.zip"), "").unwrap();
+    let (tx, rx) = mpsc::sync_channel(50);
+    let w = Window::new(4, tx);
+    let wv = WalkerVersion::default();
+
+    w.add("a.zip!x", 0, &wv).unwrap();
+    w.add("b.zip!y", 0, &wv).unwrap();
+    let _: Vec<_> = rx.try_iter().collect();
+
+    // only files within archives that are gone are missing
+    w.check_stale(&dir, Duration::ZERO, true);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [Msg::RmFile(Bytes::from_static(b"b.zip!y"))]
+    );
+    w.remove_missing(&dir);
+    assert_eq!(content_to_string(&w), "a.zip!x");
+    let _ = fs::remove_dir_all(&dir);
+}
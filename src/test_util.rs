@@ -0,0 +1,163 @@
+//! Helpers for testing clients against a real server running in-process. Enabled with the
+//! `test-util` feature.
+//!
+//! ```
+//! use koru_find::{server::walker::Msg, test_util::TestServer};
+//!
+//! let mut server = TestServer::start(4);
+//! server.send("walk", "test");
+//! server.expect(Msg::WalkStarted);
+//! assert_eq!(server.files_until_done(), ["a/1/2.txt", "a/1/3.txt"]);
+//! ```
+
+use std::{
+    env, fs,
+    io::{self, PipeWriter, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use crate::server::{self, walker::Msg};
+
+pub use crate::server::FrameReader;
+
+/// A server running `server::run` on its own threads, talking over pipes.
+pub struct TestServer {
+    input: PipeWriter,
+    msgs: mpsc::Receiver<Msg>,
+    timeout: Duration,
+}
+impl TestServer {
+    pub fn start(threads: usize) -> Self {
+        let (in_reader, input) = io::pipe().expect("pipe should open");
+        let (out_reader, out_writer) = io::pipe().expect("pipe should open");
+        let (tx, msgs) = mpsc::channel();
+
+        thread::spawn(move || server::run(threads, in_reader, out_writer));
+        thread::spawn(move || {
+            let mut reader = FrameReader::new(out_reader);
+            while let Ok(Some(frame)) = reader.next_frame() {
+                if let Some(msg) = Msg::parse(frame)
+                    && tx.send(msg).is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            input,
+            msgs,
+            timeout: Duration::from_millis(500),
+        }
+    }
+
+    /// How long to wait for each expected message; defaults to 500ms.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn send(&mut self, cmd: &str, arg: &str) {
+        let mut data = Vec::with_capacity(cmd.len() + arg.len() + 2);
+        data.extend_from_slice(cmd.as_bytes());
+        if !arg.is_empty() {
+            data.push(b' ');
+            data.extend_from_slice(arg.as_bytes());
+        }
+        data.push(0);
+        self.input
+            .write_all(&data)
+            .expect("server should be reading");
+    }
+
+    /// The next message or `None` if nothing arrives within the timeout.
+    pub fn recv(&self) -> Option<Msg> {
+        self.msgs.recv_timeout(self.timeout).ok()
+    }
+
+    /// Panic unless the next message is `expected`.
+    #[track_caller]
+    pub fn expect(&self, expected: Msg) {
+        match self.recv() {
+            Some(msg) => assert_eq!(msg, expected),
+            None => panic!("timed out waiting for {expected:?}"),
+        }
+    }
+
+    /// Collect messages up to and excluding the next `Msg::WalkDone`.
+    #[track_caller]
+    pub fn until_done(&self) -> Vec<Msg> {
+        let mut result = vec![];
+        loop {
+            match self.recv() {
+                Some(Msg::WalkDone) => return result,
+                Some(msg) => result.push(msg),
+                None => panic!("timed out waiting for done; got {result:?}"),
+            }
+        }
+    }
+
+    /// The sorted paths added before the next `Msg::WalkDone`. Panics if any other message
+    /// arrives first.
+    #[track_caller]
+    pub fn files_until_done(&self) -> Vec<String> {
+        let mut files: Vec<_> = self
+            .until_done()
+            .into_iter()
+            .map(|msg| match msg {
+                Msg::AddFile(path) => String::from_utf8_lossy(&path).to_string(),
+                msg => panic!("expected a file; got {msg:?}"),
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Panic if any message arrives within `wait`.
+    #[track_caller]
+    pub fn assert_quiet(&self, wait: Duration) {
+        if let Ok(msg) = self.msgs.recv_timeout(wait) {
+            panic!("expected no messages; got {msg:?}");
+        }
+    }
+}
+
+/// An empty directory under the system's temp directory, removed with its contents when
+/// dropped.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+impl TempDir {
+    /// `koru_find_{name}_{pid}`, emptied of anything an earlier run left behind.
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("koru_find_{name}_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("temp dir should be created");
+        Self(dir)
+    }
+}
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+#[path = "test_util_test.rs"]
+mod test;
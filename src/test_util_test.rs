@@ -0,0 +1,50 @@
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+#[test]
+fn frame_reader() {
+    let data: &[u8] = b"started\x00+a\x00\x00message hello world\x00partial";
+    let mut reader = FrameReader::new(data);
+    assert_eq!(reader.next_frame().unwrap(), Some(&b"started"[..]));
+    assert_eq!(reader.next_frame().unwrap(), Some(&b"+a"[..]));
+    assert_eq!(reader.next_frame().unwrap(), Some(&b""[..]));
+    assert_eq!(
+        reader.next_frame().unwrap(),
+        Some(&b"message hello world"[..])
+    );
+    assert_eq!(reader.next_frame().unwrap(), None);
+}
+
+#[test]
+fn test_server() {
+    let mut server = TestServer::start(4);
+    server.send("walk", "test");
+    server.send("add", "3");
+
+    server.expect(Msg::WalkStarted);
+    assert_eq!(server.files_until_done(), ["a/1/3.txt"]);
+    server.assert_quiet(Duration::from_millis(10));
+
+    server.send("rm", "x");
+    assert_eq!(
+        server.recv(),
+        Some(Msg::Message(
+            r#"invalid argument: rm amount: expected a number; got "x""#.to_string()
+        ))
+    );
+}
+
+#[test]
+fn temp_dir() {
+    let dir = TempDir::new("test_util_temp_dir");
+    std::fs::write(dir.join("a"), "").unwrap();
+    let path = dir.to_path_buf();
+    drop(dir);
+    assert!(!path.exists());
+
+    // what an earlier run left is removed
+    std::fs::create_dir_all(path.join("old")).unwrap();
+    let dir = TempDir::new("test_util_temp_dir");
+    assert!(dir.is_dir() && !dir.join("old").exists());
+}